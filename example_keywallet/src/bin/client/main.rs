@@ -2,57 +2,40 @@
 //! Note though that this is not meant as a real secret-service you should use, it will likely be very insecure. This is just to have a realworld
 //! usecase to validate the existing codebase and new ideas
 
-use rustbus::connection::get_session_bus_path;
-use rustbus::connection::ll_conn::DuplexConn;
-fn main() {
-    let mut con = DuplexConn::connect_to_bus(get_session_bus_path().unwrap(), false).unwrap();
-
-    con.send_hello(rustbus::connection::Timeout::Infinite)
-        .unwrap();
-
-    let resp = con
-        .recv
-        .get_next_message(rustbus::connection::Timeout::Infinite)
-        .unwrap();
-
-    println!("Unique name: {}", resp.body.parser().get::<&str>().unwrap());
+use rustbus::connection::rpc_conn::CallError;
+use rustbus::connection::Timeout;
+use rustbus::wire::ObjectPath;
+use rustbus::{dbus_proxy, RpcConn};
+use std::collections::HashMap;
 
-    let mut rpc_conn = rustbus::connection::rpc_conn::RpcConn::new(con);
-    let mut msg = rustbus::message_builder::MessageBuilder::new()
-        .call("SearchItems")
-        .on("/org/freedesktop/secrets")
-        .with_interface("org.freedesktop.Secret.Service")
-        .at("io.killingspark.secrets")
-        .build();
+/// The parts of org.freedesktop.Secret.Service this client uses
+#[dbus_proxy(
+    interface = "org.freedesktop.Secret.Service",
+    default_path = "/org/freedesktop/secrets",
+    default_service = "io.killingspark.secrets"
+)]
+trait SecretService {
+    /// Returns the unlocked and the locked items that match the attributes
+    fn search_items(
+        &self,
+        attributes: &HashMap<String, String>,
+    ) -> (Vec<ObjectPath<String>>, Vec<ObjectPath<String>>);
+}
 
-    let attrs = std::collections::HashMap::<String, String>::new();
-    msg.body.push_param(&attrs).unwrap();
+fn main() {
+    let mut con = RpcConn::session_conn(Timeout::Infinite).unwrap();
+    println!("Unique name: {}", con.unique_name().unwrap());
 
-    let serial = rpc_conn
-        .send_message(&mut msg)
-        .unwrap()
-        .write_all()
-        .unwrap();
-    let resp = rpc_conn
-        .wait_response(serial, rustbus::connection::Timeout::Infinite)
-        .unwrap();
-    println!("Header: {:?}", resp.dynheader);
-    match resp.typ {
-        rustbus::message_builder::MessageType::Error => {
-            println!(
-                "Error name: {}",
-                resp.dynheader.error_name.as_ref().unwrap()
-            );
-            println!("Error: {}", resp.body.parser().get::<&str>().unwrap());
-        }
-        _ => {
-            let (unlocked, locked) = resp
-                .body
-                .parser()
-                .get2::<Vec<rustbus::wire::ObjectPath<&str>>, Vec<rustbus::wire::ObjectPath<&str>>>(
-                )
-                .unwrap();
+    let mut service = SecretServiceProxy::with_defaults(&mut con);
+    let attrs = HashMap::new();
+    match service.search_items(&attrs) {
+        Ok((unlocked, locked)) => {
             println!("Items found: (unlocked){:?} (locked){:?}", unlocked, locked);
         }
-    };
+        Err(CallError::ErrorReply(error)) => {
+            println!("Error name: {}", error.name);
+            println!("Error: {}", error.message.unwrap_or_default());
+        }
+        Err(error) => panic!("{}", error),
+    }
 }
@@ -3,7 +3,6 @@ use std::collections::HashMap;
 use rustbus::connection::dispatch_conn::HandleResult;
 use rustbus::connection::dispatch_conn::Matches;
 use rustbus::message_builder::MarshalledMessage;
-use rustbus::wire::unmarshal::traits::Variant;
 use rustbus::wire::ObjectPath;
 
 use super::service;
@@ -78,7 +77,7 @@ pub fn handle_collection_interface(
             Ok(Some(resp))
         }
         "CreateItem" => {
-            let (props, secret, replace): (HashMap<String, Variant>, messages::Secret, bool) =
+            let (props, secret, replace): (messages::ItemProperties, messages::Secret, bool) =
                 msg.body.parser().get3().expect("Types did not match");
 
             println!("Create item with props: {:?}", props);
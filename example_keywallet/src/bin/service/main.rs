@@ -35,7 +35,11 @@ fn default_handler(
 
 enum ObjectType<'a> {
     Collection(&'a str),
-    Item { col: &'a str, item: &'a str },
+    Item {
+        col: &'a str,
+        item: &'a str,
+    },
+    #[allow(dead_code)]
     Session(&'a str),
 }
 
@@ -200,7 +204,12 @@ fn session_handler(
 }
 
 fn main() {
-    let mut con = DuplexConn::connect_to_bus(get_session_bus_path().unwrap(), false).unwrap();
+    let mut con = DuplexConn::connect_to_bus(
+        get_session_bus_path().unwrap(),
+        false,
+        rustbus::connection::Timeout::Infinite,
+    )
+    .unwrap();
 
     let unique_name = con
         .send_hello(rustbus::connection::Timeout::Infinite)
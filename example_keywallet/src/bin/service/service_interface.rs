@@ -39,7 +39,7 @@ pub fn handle_service_interface(
             Ok(Some(resp))
         }
         "CreateCollection" => {
-            let (props, alias): (HashMap<&str, Variant>, &str) =
+            let (props, alias): (messages::CollectionProperties, &str) =
                 msg.body.parser().get2().expect("Types did not match!");
             println!(
                 "Create collection with props: {:?} and alias: {}",
@@ -12,3 +12,21 @@ pub struct Secret {
     pub value: Vec<u8>,
     pub content_type: String,
 }
+
+/// The properties passed to CreateCollection
+#[derive(Marshal, Unmarshal, Signature, Debug, Default)]
+#[rustbus(dict)]
+pub struct CollectionProperties {
+    #[rustbus(rename = "org.freedesktop.Secret.Collection.Label")]
+    pub label: Option<String>,
+}
+
+/// The properties passed to CreateItem
+#[derive(Marshal, Unmarshal, Signature, Debug, Default)]
+#[rustbus(dict)]
+pub struct ItemProperties {
+    #[rustbus(rename = "org.freedesktop.Secret.Item.Label")]
+    pub label: Option<String>,
+    #[rustbus(rename = "org.freedesktop.Secret.Item.Attributes", default)]
+    pub attributes: std::collections::HashMap<String, String>,
+}
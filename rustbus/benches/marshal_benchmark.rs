@@ -12,12 +12,13 @@ fn marsh(msg: &rustbus::message_builder::MarshalledMessage, buf: &mut Vec<u8>) {
 }
 
 fn unmarshal(buf: &[u8]) {
-    let (hdrbytes, header) = unmarshal_header(&buf, 0).unwrap();
-    let (dynhdrbytes, dynheader) = unmarshal_dynamic_header(&header, &buf, hdrbytes).unwrap();
+    let (hdrbytes, header) = unmarshal_header(buf, 0).unwrap();
+    let (dynhdrbytes, dynheader) = unmarshal_dynamic_header(&header, buf, hdrbytes).unwrap();
     let (_, _unmarshed_msg) =
-        unmarshal_next_message(&header, dynheader, &buf, hdrbytes + dynhdrbytes).unwrap();
+        unmarshal_next_message(&header, dynheader, buf, hdrbytes + dynhdrbytes).unwrap();
 }
 
+#[allow(clippy::mutable_key_type)]
 fn criterion_benchmark(c: &mut Criterion) {
     let mut params: Vec<Param> = Vec::new();
 
@@ -4,7 +4,7 @@ use rustbus::{
 
 fn main() -> Result<(), rustbus::connection::Error> {
     let session_path = get_session_bus_path()?;
-    let con = DuplexConn::connect_to_bus(session_path, true)?;
+    let con = DuplexConn::connect_to_bus(session_path, true, Timeout::Infinite)?;
     let mut rpc_con = RpcConn::new(con);
 
     rpc_con.set_filter(Box::new(|msg| match msg.typ {
@@ -35,10 +35,7 @@ fn main() -> Result<(), rustbus::connection::Error> {
     println!("\n");
 
     let reqname_serial = rpc_con
-        .send_message(&mut standard_messages::request_name(
-            "io.killing.spark".into(),
-            0,
-        ))?
+        .send_message(&mut standard_messages::request_name("io.killing.spark", 0))?
         .write_all()
         .unwrap();
 
@@ -73,7 +70,7 @@ fn main() -> Result<(), rustbus::connection::Error> {
     println!("\n");
     println!("\n");
 
-    let mut sig_listen_msg = standard_messages::add_match("type='signal'".into());
+    let mut sig_listen_msg = standard_messages::add_match("type='signal'");
 
     //println!("Send message: {:?}", sig_listen_msg);
     rpc_con
@@ -14,13 +14,13 @@ fn main() -> Result<(), rustbus::connection::Error> {
         send_fd()?;
     } else {
         let session_path = get_session_bus_path()?;
-        let con = DuplexConn::connect_to_bus(session_path, true)?;
+        let con = DuplexConn::connect_to_bus(session_path, true, Timeout::Infinite)?;
         let mut con = RpcConn::new(con);
         con.send_message(&mut standard_messages::hello())?
             .write_all()
             .unwrap();
 
-        con.send_message(&mut standard_messages::add_match("type='signal'".into()))?
+        con.send_message(&mut standard_messages::add_match("type='signal'"))?
             .write_all()
             .unwrap();
 
@@ -31,10 +31,9 @@ fn main() -> Result<(), rustbus::connection::Error> {
                 .dynheader
                 .interface
                 .eq(&Some("io.killing.spark".to_owned()))
+                && signal.dynheader.member.eq(&Some("TestSignal".to_owned()))
             {
-                if signal.dynheader.member.eq(&Some("TestSignal".to_owned())) {
-                    break signal;
-                }
+                break signal;
             }
         };
 
@@ -56,7 +55,7 @@ fn main() -> Result<(), rustbus::connection::Error> {
 
 fn send_fd() -> Result<(), rustbus::connection::Error> {
     let session_path = rustbus::connection::get_session_bus_path()?;
-    let mut con = rustbus::DuplexConn::connect_to_bus(session_path, true)?;
+    let mut con = rustbus::DuplexConn::connect_to_bus(session_path, true, Timeout::Infinite)?;
     con.send_hello(Timeout::Infinite).unwrap();
     let mut sig = MessageBuilder::new()
         .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
@@ -66,12 +65,12 @@ fn send_fd() -> Result<(), rustbus::connection::Error> {
     let stdin_fd = std::io::stdin();
     sig.body.push_param((&stdin_fd) as &dyn AsRawFd).unwrap();
     sig.dynheader.num_fds = Some(1);
-    con.send.send_message(&mut sig)?.write_all().unwrap();
+    con.send.send_message(&sig)?.write_all().unwrap();
 
-    let mut sig = MessageBuilder::new()
+    let sig = MessageBuilder::new()
         .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
         .build();
-    con.send.send_message(&mut sig)?.write_all().unwrap();
+    con.send.send_message(&sig)?.write_all().unwrap();
 
     println!("Printing stuff from stdin. The following is input from the other process!");
     let mut line = String::new();
@@ -2,7 +2,7 @@ use rustbus::{connection::Timeout, get_session_bus_path, DuplexConn, MessageBuil
 
 fn main() -> Result<(), rustbus::connection::Error> {
     let session_path = get_session_bus_path()?;
-    let mut con = DuplexConn::connect_to_bus(session_path, true)?;
+    let mut con = DuplexConn::connect_to_bus(session_path, true, Timeout::Infinite)?;
     con.send_hello(Timeout::Infinite)?;
 
     let mut sig = MessageBuilder::new()
@@ -33,9 +33,9 @@ fn main() -> Result<(), rustbus::connection::Error> {
 
     println!("{:?}", sig);
 
-    con.send.send_message(&mut sig)?.write_all().unwrap();
+    con.send.send_message(&sig)?.write_all().unwrap();
     std::thread::sleep(std::time::Duration::from_secs(1));
-    con.send.send_message(&mut sig)?.write_all().unwrap();
+    con.send.send_message(&sig)?.write_all().unwrap();
 
     Ok(())
 }
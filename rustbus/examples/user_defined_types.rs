@@ -139,7 +139,7 @@ use rustbus::{connection::Timeout, get_session_bus_path, DuplexConn, MessageBuil
 // Just to have a main here we will send a message containing two MyType structs
 fn main() -> Result<(), rustbus::connection::Error> {
     let session_path = get_session_bus_path()?;
-    let mut con = DuplexConn::connect_to_bus(session_path, true)?;
+    let mut con = DuplexConn::connect_to_bus(session_path, true, Timeout::Infinite)?;
     con.send_hello(Timeout::Infinite)?;
 
     let mut sig = MessageBuilder::new()
@@ -166,7 +166,7 @@ fn main() -> Result<(), rustbus::connection::Error> {
     sig.body.push_param(MyVar::Int32(100))?;
     sig.body.push_param(MyVar::Int64(-100))?;
 
-    con.send.send_message(&mut sig)?.write_all().unwrap();
+    con.send.send_message(&sig)?.write_all().unwrap();
 
     Ok(())
 }
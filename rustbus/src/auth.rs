@@ -1,10 +1,91 @@
 //! Deals with authentication to the other side. You probably do not need this.
 
-use nix::unistd::getuid;
+use crate::connection::Timeout;
 use std::io::{Read, Write};
-use std::os::unix::net::UnixStream;
+use std::time;
+use thiserror::Error;
 
-fn write_message(msg: &str, stream: &mut UnixStream) -> std::io::Result<()> {
+pub mod mechanisms;
+pub use mechanisms::Mechanism;
+
+/// Errors that can occur while authenticating to the other side
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("An io error occured during authentication: {0}")]
+    IoError(std::io::Error),
+    #[error("Timeout occured during authentication")]
+    TimedOut,
+    #[error("The connection was closed during authentication")]
+    ConnectionClosed,
+    #[error("Received a line that is not valid utf8")]
+    InvalidUtf8,
+    #[error("Received a line that is longer than {MAX_LINE_LENGTH} bytes")]
+    LineTooLong,
+    #[error("The server rejected all mechanisms. It supports: {0:?}")]
+    Rejected(Vec<String>),
+    #[error("Unexpected response during authentication: {0}")]
+    UnexpectedResponse(String),
+    #[error("The server has the guid {received} but the address named {expected}")]
+    GuidMismatch { expected: String, received: String },
+}
+
+impl std::convert::From<std::io::Error> for AuthError {
+    fn from(e: std::io::Error) -> AuthError {
+        match e.kind() {
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => AuthError::TimedOut,
+            std::io::ErrorKind::UnexpectedEof => AuthError::ConnectionClosed,
+            _ => AuthError::IoError(e),
+        }
+    }
+}
+
+/// The commands are short, no sane peer sends lines this long
+pub const MAX_LINE_LENGTH: usize = 16 * 1024;
+
+/// A stream the authentication can run over. The timeout is applied to all following reads and writes.
+pub trait AuthStream: Read + Write {
+    fn set_timeout(&self, timeout: Timeout) -> std::io::Result<()>;
+}
+
+macro_rules! impl_auth_stream {
+    ($stream:ty) => {
+        impl AuthStream for $stream {
+            fn set_timeout(&self, timeout: Timeout) -> std::io::Result<()> {
+                match timeout {
+                    Timeout::Infinite => {
+                        self.set_nonblocking(false)?;
+                        self.set_read_timeout(None)?;
+                        self.set_write_timeout(None)
+                    }
+                    Timeout::Duration(d) => {
+                        self.set_nonblocking(false)?;
+                        self.set_read_timeout(Some(d))?;
+                        self.set_write_timeout(Some(d))
+                    }
+                    Timeout::Nonblock => self.set_nonblocking(true),
+                }
+            }
+        }
+    };
+}
+
+impl_auth_stream!(std::os::unix::net::UnixStream);
+impl_auth_stream!(std::net::TcpStream);
+
+fn time_left(start_time: &time::Instant, timeout: Timeout) -> Result<Timeout, AuthError> {
+    match timeout {
+        Timeout::Duration(timeout) => {
+            let elapsed = start_time.elapsed();
+            if elapsed >= timeout {
+                return Err(AuthError::TimedOut);
+            }
+            Ok(Timeout::Duration(timeout - elapsed))
+        }
+        other => Ok(other),
+    }
+}
+
+fn write_message<S: Write>(msg: &str, stream: &mut S) -> std::io::Result<()> {
     let mut buf = Vec::new();
     buf.extend(msg.bytes());
     buf.push(b'\r');
@@ -13,98 +94,619 @@ fn write_message(msg: &str, stream: &mut UnixStream) -> std::io::Result<()> {
     Ok(())
 }
 
-fn has_line_ending(buf: &[u8]) -> bool {
-    for idx in 1..buf.len() {
-        if buf[idx - 1] == b'\r' && buf[idx] == b'\n' {
-            return true;
+/// Reads a line one byte at a time. Each side may send the first message right after BEGIN,
+/// so nothing beyond the end of the line may be read.
+fn read_line_exact<S: Read>(stream: &mut S) -> Result<Vec<u8>, AuthError> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    while !line.ends_with(b"\r\n") {
+        if line.len() > MAX_LINE_LENGTH {
+            return Err(AuthError::LineTooLong);
+        }
+        match stream.read(&mut byte) {
+            Ok(0) => return Err(AuthError::ConnectionClosed),
+            Ok(_) => line.push(byte[0]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
         }
     }
-    false
+    line.truncate(line.len() - 2);
+    Ok(line)
+}
+
+/// Applies what is left of the timeout before every read and write
+struct TimedStream<'a, S: AuthStream> {
+    stream: &'a mut S,
+    start_time: time::Instant,
+    timeout: Timeout,
 }
 
-fn find_line_ending(buf: &[u8]) -> Option<usize> {
-    for idx in 1..buf.len() {
-        if buf[idx - 1] == b'\r' && buf[idx] == b'\n' {
-            return Some(idx - 1);
+impl<'a, S: AuthStream> TimedStream<'a, S> {
+    fn new(stream: &'a mut S, timeout: Timeout) -> Self {
+        TimedStream {
+            stream,
+            start_time: time::Instant::now(),
+            timeout,
         }
     }
-    None
-}
 
-fn read_message(stream: &mut UnixStream, buf: &mut Vec<u8>) -> std::io::Result<String> {
-    let mut tmpbuf = [0u8; 512];
-    while !has_line_ending(buf) {
-        let bytes = stream.read(&mut tmpbuf[..])?;
-        buf.extend(&tmpbuf[..bytes])
+    fn apply_timeout(&mut self) -> Result<(), AuthError> {
+        let timeout = time_left(&self.start_time, self.timeout)?;
+        self.stream.set_timeout(timeout)?;
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), AuthError> {
+        self.apply_timeout()?;
+        self.stream.write_all(bytes)?;
+        Ok(())
+    }
+
+    fn write_line(&mut self, msg: &str) -> Result<(), AuthError> {
+        self.apply_timeout()?;
+        write_message(msg, self.stream)?;
+        Ok(())
     }
-    let idx = find_line_ending(buf).unwrap();
-    let line = buf.drain(0..idx).collect::<Vec<_>>();
-    Ok(String::from_utf8(line).unwrap())
-}
 
-fn get_uid_as_hex() -> String {
-    let uid = getuid();
-    let mut tmp = uid.as_raw();
-    let mut numbers = Vec::new();
-    if tmp == 0 {
-        return "30".to_owned();
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<(), AuthError> {
+        self.apply_timeout()?;
+        self.stream.read_exact(buf)?;
+        Ok(())
     }
-    while tmp > 0 {
-        numbers.push(tmp % 10);
-        tmp /= 10;
+
+    fn read_raw_line(&mut self) -> Result<Vec<u8>, AuthError> {
+        self.apply_timeout()?;
+        read_line_exact(self.stream)
     }
-    let mut hex = String::new();
-    for idx in 0..numbers.len() {
-        hex.push_str(match numbers[numbers.len() - 1 - idx] {
-            0 => "30",
-            1 => "31",
-            2 => "32",
-            3 => "33",
-            4 => "34",
-            5 => "35",
-            6 => "36",
-            7 => "37",
-            8 => "38",
-            9 => "39",
-            _ => unreachable!(),
-        })
+
+    fn read_line(&mut self) -> Result<String, AuthError> {
+        let line = self.read_raw_line()?;
+        String::from_utf8(line).map_err(|_| AuthError::InvalidUtf8)
     }
+}
 
-    hex
+/// Splits a line into the command and the rest of the line
+fn split_command(line: &str) -> (&str, &str) {
+    match line.split_once(' ') {
+        Some((command, rest)) => (command, rest.trim()),
+        None => (line, ""),
+    }
 }
 
-pub enum AuthResult {
+/// The states of the client side as described in the specification
+enum WaitingFor {
+    /// Waiting for OK or more challenges from the server
+    Data,
+    /// The initial response was sent, OK or REJECTED are expected
     Ok,
-    Rejected,
+    /// We sent CANCEL and wait for the server to reject the current mechanism
+    Reject,
+}
+
+/// Authenticate with the default mechanisms: EXTERNAL, DBUS_COOKIE_SHA1 and ANONYMOUS.
+///
+/// Returns the guid the server sent in the OK response.
+pub fn do_auth<S: AuthStream>(stream: &mut S, timeout: Timeout) -> Result<String, AuthError> {
+    do_auth_with_mechanisms(stream, &mut mechanisms::default_mechanisms(), timeout)
 }
 
-pub fn do_auth(stream: &mut UnixStream) -> std::io::Result<AuthResult> {
+/// Authenticate with the given mechanisms. They are tried in order, but after the first rejection only the mechanisms
+/// the server lists as supported in the `REJECTED` response are tried.
+///
+/// Returns the guid the server sent in the OK response.
+pub fn do_auth_with_mechanisms<S: AuthStream>(
+    stream: &mut S,
+    mechanisms: &mut [Box<dyn Mechanism>],
+    timeout: Timeout,
+) -> Result<String, AuthError> {
+    let mut stream = TimedStream::new(stream, timeout);
     // send a null byte as the first thing
-    stream.write_all(&[0])?;
-    write_message(&format!("AUTH EXTERNAL {}", get_uid_as_hex()), stream)?;
+    stream.write_bytes(&[0])?;
+
+    // None until the server told us which mechanisms it supports
+    let mut supported: Option<Vec<String>> = None;
+    let mut next_mech = 0;
+    let mut unexpected_commands = 0;
+
+    'mechanisms: loop {
+        let mech = loop {
+            let mech = match mechanisms.get_mut(next_mech) {
+                Some(mech) => mech,
+                None => return Err(AuthError::Rejected(supported.unwrap_or_default())),
+            };
+            next_mech += 1;
+            let is_supported = match &supported {
+                Some(supported) => supported.iter().any(|name| name == mech.name()),
+                None => true,
+            };
+            if is_supported {
+                break mech;
+            }
+        };
 
-    let mut read_buf = Vec::new();
-    let msg = read_message(stream, &mut read_buf)?;
-    if msg.starts_with("OK") {
-        Ok(AuthResult::Ok)
-    } else {
-        Ok(AuthResult::Rejected)
+        let mut state = match mech.initial_response() {
+            Some(resp) => {
+                stream.write_line(&format!(
+                    "AUTH {} {}",
+                    mech.name(),
+                    mechanisms::hex_encode(&resp)
+                ))?;
+                WaitingFor::Ok
+            }
+            None => {
+                stream.write_line(&format!("AUTH {}", mech.name()))?;
+                WaitingFor::Data
+            }
+        };
+
+        loop {
+            let line = stream.read_line()?;
+            let (command, args) = split_command(&line);
+
+            state = match (state, command) {
+                (_, "REJECTED") => {
+                    supported = Some(
+                        args.split(' ')
+                            .filter(|w| !w.is_empty())
+                            .map(str::to_owned)
+                            .collect(),
+                    );
+                    continue 'mechanisms;
+                }
+                (WaitingFor::Reject, _) => {
+                    return Err(AuthError::UnexpectedResponse(line));
+                }
+                (_, "OK") => {
+                    if args.is_empty() {
+                        return Err(AuthError::UnexpectedResponse(line));
+                    }
+                    return Ok(args.to_owned());
+                }
+                (_, "DATA") => {
+                    let response = mechanisms::hex_decode(args.as_bytes())
+                        .ok_or_else(|| {
+                            std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid hex")
+                        })
+                        .and_then(|challenge| mech.handle_data(&challenge));
+                    match response {
+                        Ok(response) if response.is_empty() => {
+                            stream.write_line("DATA")?;
+                            WaitingFor::Data
+                        }
+                        Ok(response) => {
+                            stream.write_line(&format!(
+                                "DATA {}",
+                                mechanisms::hex_encode(&response)
+                            ))?;
+                            WaitingFor::Data
+                        }
+                        // the server answers a CANCEL with REJECTED which moves on to the next mechanism
+                        Err(_) => {
+                            stream.write_line("CANCEL")?;
+                            WaitingFor::Reject
+                        }
+                    }
+                }
+                (_, "ERROR") => {
+                    stream.write_line("CANCEL")?;
+                    WaitingFor::Reject
+                }
+                (state, _) => {
+                    unexpected_commands += 1;
+                    if unexpected_commands >= MAX_FAILED_COMMANDS {
+                        return Err(AuthError::UnexpectedResponse(line));
+                    }
+                    stream.write_line("ERROR \"Unexpected command\"")?;
+                    state
+                }
+            };
+        }
     }
 }
 
-pub fn negotiate_unix_fds(stream: &mut UnixStream) -> std::io::Result<AuthResult> {
-    write_message("NEGOTIATE_UNIX_FD", stream)?;
+/// Ask the server whether unix fds can be passed over this connection. Must be done after the authentication succeeded.
+///
+/// Returns false if the server does not support unix fd passing, the connection can still be used without it.
+pub fn negotiate_unix_fds<S: AuthStream>(
+    stream: &mut S,
+    timeout: Timeout,
+) -> Result<bool, AuthError> {
+    let mut stream = TimedStream::new(stream, timeout);
+    stream.write_line("NEGOTIATE_UNIX_FD")?;
 
-    let mut read_buf = Vec::new();
-    let msg = read_message(stream, &mut read_buf)?;
-    if msg.starts_with("AGREE_UNIX_FD") {
-        Ok(AuthResult::Ok)
-    } else {
-        Ok(AuthResult::Rejected)
+    let line = stream.read_line()?;
+    match split_command(&line).0 {
+        "AGREE_UNIX_FD" => Ok(true),
+        "ERROR" => Ok(false),
+        _ => Err(AuthError::UnexpectedResponse(line)),
     }
 }
 
-pub fn send_begin(stream: &mut UnixStream) -> std::io::Result<()> {
-    write_message("BEGIN", stream)?;
-    Ok(())
+/// End the authentication. After this the stream carries dbus messages.
+pub fn send_begin<S: AuthStream>(stream: &mut S, timeout: Timeout) -> Result<(), AuthError> {
+    TimedStream::new(stream, timeout).write_line("BEGIN")
+}
+
+/// Result of the server side of the authentication
+pub enum ServerAuthResult {
+    /// The client authenticated and sent BEGIN. `unix_fd` tells whether unix fd passing was agreed upon.
+    Ok { unix_fd: bool },
+    /// The client did not manage to authenticate and the connection should be dropped
+    Rejected,
+}
+
+// Clients that send this many commands without successfully authenticating get disconnected. The client side gives up
+// after this many unexpected commands from the server.
+const MAX_FAILED_COMMANDS: usize = 16;
+
+/// EXTERNAL sends the uid as the hex encoding of its ascii decimal representation
+fn parse_external_uid(hex: &[u8]) -> Option<u32> {
+    let decoded = mechanisms::hex_decode(hex)?;
+    std::str::from_utf8(&decoded).ok()?.parse().ok()
+}
+
+/// Run the server side of the authentication. Only the EXTERNAL mechanism is offered. The uid the client claims
+/// has to match `peer_uid` (as reported by the socket credentials) and has to be accepted by `allow_uid`.
+///
+/// `guid` is sent to the client in the OK response. Unix fd passing is agreed to if `can_pass_fds` is true.
+/// Clients that do not finish the authentication within `timeout` get an `AuthError::TimedOut`.
+pub fn do_server_auth<S: AuthStream>(
+    stream: &mut S,
+    peer_uid: Option<u32>,
+    allow_uid: &dyn Fn(u32) -> bool,
+    guid: &str,
+    can_pass_fds: bool,
+    timeout: Timeout,
+) -> Result<ServerAuthResult, AuthError> {
+    let mut stream = TimedStream::new(stream, timeout);
+
+    // the client sends a null byte as the first thing
+    let mut null = [1u8; 1];
+    stream.read_bytes(&mut null)?;
+    if null[0] != 0 {
+        return Ok(ServerAuthResult::Rejected);
+    }
+
+    let check_uid = |claimed: Option<u32>| -> bool {
+        match (peer_uid, claimed) {
+            // an empty response means "use whatever the socket credentials say"
+            (Some(peer), None) => allow_uid(peer),
+            (Some(peer), Some(claimed)) => peer == claimed && allow_uid(peer),
+            (None, _) => false,
+        }
+    };
+
+    let mut authenticated = false;
+    let mut waiting_for_data = false;
+    let mut unix_fd = false;
+    let mut failed_commands = 0;
+
+    loop {
+        if failed_commands >= MAX_FAILED_COMMANDS {
+            return Ok(ServerAuthResult::Rejected);
+        }
+        let line = stream.read_raw_line()?;
+        let mut words = line.split(|b| *b == b' ').filter(|w| !w.is_empty());
+        let command = words.next().unwrap_or(b"");
+        let arg1 = words.next();
+
+        if authenticated {
+            match command {
+                b"BEGIN" => return Ok(ServerAuthResult::Ok { unix_fd }),
+                b"NEGOTIATE_UNIX_FD" => {
+                    if can_pass_fds {
+                        unix_fd = true;
+                        stream.write_line("AGREE_UNIX_FD")?;
+                    } else {
+                        stream.write_line("ERROR \"Unix fd passing is not supported\"")?;
+                    }
+                }
+                b"CANCEL" | b"ERROR" => {
+                    authenticated = false;
+                    unix_fd = false;
+                    failed_commands += 1;
+                    stream.write_line("REJECTED EXTERNAL")?;
+                }
+                _ => {
+                    failed_commands += 1;
+                    stream.write_line("ERROR \"Unknown command\"")?;
+                }
+            }
+            continue;
+        }
+
+        let accepted = match (command, waiting_for_data) {
+            (b"AUTH", _) => match arg1 {
+                Some(b"EXTERNAL") => match words.next() {
+                    Some(hex) => {
+                        Some(parse_external_uid(hex).is_some_and(|uid| check_uid(Some(uid))))
+                    }
+                    None => {
+                        waiting_for_data = true;
+                        stream.write_line("DATA")?;
+                        continue;
+                    }
+                },
+                _ => Some(false),
+            },
+            (b"DATA", true) => {
+                waiting_for_data = false;
+                match arg1 {
+                    Some(hex) => {
+                        Some(parse_external_uid(hex).is_some_and(|uid| check_uid(Some(uid))))
+                    }
+                    None => Some(check_uid(None)),
+                }
+            }
+            (b"CANCEL", _) | (b"ERROR", _) => {
+                waiting_for_data = false;
+                Some(false)
+            }
+            // BEGIN or NEGOTIATE_UNIX_FD before authenticating, unexpected DATA or unknown commands
+            _ => None,
+        };
+
+        match accepted {
+            Some(true) => {
+                authenticated = true;
+                stream.write_line(&format!("OK {}", guid))?;
+            }
+            Some(false) => {
+                failed_commands += 1;
+                stream.write_line("REJECTED EXTERNAL")?;
+            }
+            None => {
+                failed_commands += 1;
+                stream.write_line("ERROR \"Unexpected command\"")?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+
+    fn run_server(
+        peer_uid: Option<u32>,
+        can_pass_fds: bool,
+    ) -> (UnixStream, std::thread::JoinHandle<ServerAuthResult>) {
+        let (client, mut server) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            do_server_auth(
+                &mut server,
+                peer_uid,
+                &|uid| uid == 1000,
+                "0123456789abcdef0123456789abcdef",
+                can_pass_fds,
+                Timeout::Infinite,
+            )
+            .unwrap()
+        });
+        (client, handle)
+    }
+
+    fn read_reply(stream: &mut UnixStream) -> String {
+        String::from_utf8(read_line_exact(stream).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn server_auth_external() {
+        let (mut client, server) = run_server(Some(1000), true);
+        client.write_all(&[0]).unwrap();
+        // claiming another uid than the socket reports
+        write_message("AUTH EXTERNAL 30", &mut client).unwrap();
+        assert_eq!(read_reply(&mut client), "REJECTED EXTERNAL");
+        // unknown mechanism
+        write_message("AUTH ANONYMOUS", &mut client).unwrap();
+        assert_eq!(read_reply(&mut client), "REJECTED EXTERNAL");
+        // the correct uid ("1000" hex encoded)
+        write_message("AUTH EXTERNAL 31303030", &mut client).unwrap();
+        assert_eq!(
+            read_reply(&mut client),
+            "OK 0123456789abcdef0123456789abcdef"
+        );
+        write_message("NEGOTIATE_UNIX_FD", &mut client).unwrap();
+        assert_eq!(read_reply(&mut client), "AGREE_UNIX_FD");
+        write_message("BEGIN", &mut client).unwrap();
+        assert!(matches!(
+            server.join().unwrap(),
+            ServerAuthResult::Ok { unix_fd: true }
+        ));
+    }
+
+    #[test]
+    fn server_auth_external_with_data() {
+        let (mut client, server) = run_server(Some(1000), false);
+        // commands may be pipelined by the client
+        client
+            .write_all(b"\0AUTH EXTERNAL\r\nDATA\r\nNEGOTIATE_UNIX_FD\r\nBEGIN\r\n")
+            .unwrap();
+        assert_eq!(read_reply(&mut client), "DATA");
+        assert_eq!(
+            read_reply(&mut client),
+            "OK 0123456789abcdef0123456789abcdef"
+        );
+        assert!(read_reply(&mut client).starts_with("ERROR"));
+        assert!(matches!(
+            server.join().unwrap(),
+            ServerAuthResult::Ok { unix_fd: false }
+        ));
+    }
+
+    #[test]
+    fn server_auth_rejects_disallowed_uid() {
+        let (mut client, server) = run_server(Some(0), false);
+        client.write_all(&[0]).unwrap();
+        for _ in 0..MAX_FAILED_COMMANDS {
+            write_message("AUTH EXTERNAL 30", &mut client).unwrap();
+            assert_eq!(read_reply(&mut client), "REJECTED EXTERNAL");
+        }
+        assert!(matches!(server.join().unwrap(), ServerAuthResult::Rejected));
+    }
+
+    #[test]
+    fn server_auth_times_out() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        client.write_all(&[0]).unwrap();
+        // the client never finishes its command
+        client.write_all(b"AUTH EXTERNAL").unwrap();
+        let res = do_server_auth(
+            &mut server,
+            Some(1000),
+            &|_| true,
+            "0123456789abcdef0123456789abcdef",
+            false,
+            Timeout::Duration(std::time::Duration::from_millis(50)),
+        );
+        assert!(matches!(res, Err(AuthError::TimedOut)));
+    }
+
+    #[test]
+    fn client_walks_rejected_list() {
+        let dir =
+            std::env::temp_dir().join(format!("rustbus-auth-keyrings-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("org_freedesktop_general"), "7 1600000000 secret\n").unwrap();
+
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut null = [1u8; 1];
+            server.read_exact(&mut null).unwrap();
+            assert_eq!(null[0], 0);
+            assert!(read_reply(&mut server).starts_with("AUTH EXTERNAL "));
+            write_message("REJECTED DBUS_COOKIE_SHA1 ANONYMOUS", &mut server).unwrap();
+
+            let uid = mechanisms::hex_encode(nix::unistd::getuid().as_raw().to_string().as_bytes());
+            assert_eq!(
+                read_reply(&mut server),
+                format!("AUTH DBUS_COOKIE_SHA1 {}", uid)
+            );
+            let challenge = mechanisms::hex_encode(b"org_freedesktop_general 7 serverside");
+            write_message(&format!("DATA {}", challenge), &mut server).unwrap();
+
+            let data = read_reply(&mut server);
+            let response =
+                mechanisms::hex_decode(data.strip_prefix("DATA ").unwrap().as_bytes()).unwrap();
+            let response = String::from_utf8(response).unwrap();
+            let (client_challenge, digest) = response.split_once(' ').unwrap();
+            let expected = sha1_smol::Sha1::from(format!("serverside:{}:secret", client_challenge))
+                .digest()
+                .to_string();
+            assert_eq!(digest, expected);
+            write_message("OK 0123456789abcdef0123456789abcdef", &mut server).unwrap();
+        });
+
+        let mut mechs: Vec<Box<dyn Mechanism>> = vec![
+            Box::new(mechanisms::External),
+            Box::new(mechanisms::CookieSha1::with_keyring_dir(dir.clone())),
+            Box::new(mechanisms::Anonymous::default()),
+        ];
+        assert_eq!(
+            do_auth_with_mechanisms(&mut client, &mut mechs, Timeout::Infinite).unwrap(),
+            "0123456789abcdef0123456789abcdef"
+        );
+        server.join().unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn client_falls_back_to_anonymous() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut null = [1u8; 1];
+            server.read_exact(&mut null).unwrap();
+            assert!(read_reply(&mut server).starts_with("AUTH EXTERNAL "));
+            // only ANONYMOUS is supported so the cookie mechanism has to be skipped
+            write_message("REJECTED ANONYMOUS", &mut server).unwrap();
+            assert!(read_reply(&mut server).starts_with("AUTH ANONYMOUS "));
+            write_message("REJECTED", &mut server).unwrap();
+        });
+        assert!(matches!(
+            do_auth(&mut client, Timeout::Infinite),
+            Err(AuthError::Rejected(supported)) if supported.is_empty()
+        ));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn client_cancels_on_error() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut null = [1u8; 1];
+            server.read_exact(&mut null).unwrap();
+            assert!(read_reply(&mut server).starts_with("AUTH EXTERNAL "));
+            write_message("ERROR \"try again\"", &mut server).unwrap();
+            assert_eq!(read_reply(&mut server), "CANCEL");
+            write_message("REJECTED EXTERNAL", &mut server).unwrap();
+            // EXTERNAL was already tried, the client gives up and closes the connection
+            assert!(matches!(
+                read_line_exact(&mut server),
+                Err(AuthError::ConnectionClosed)
+            ));
+        });
+        assert!(matches!(
+            do_auth(&mut client, Timeout::Infinite),
+            Err(AuthError::Rejected(_))
+        ));
+        drop(client);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn client_auth_misbehaving_server() {
+        // the server never answers
+        let (mut client, _server) = UnixStream::pair().unwrap();
+        assert!(matches!(
+            do_auth(
+                &mut client,
+                Timeout::Duration(std::time::Duration::from_millis(50))
+            ),
+            Err(AuthError::TimedOut)
+        ));
+
+        // the server closes the connection
+        let (mut client, server) = UnixStream::pair().unwrap();
+        drop(server);
+        assert!(matches!(
+            do_auth(&mut client, Timeout::Infinite),
+            Err(AuthError::ConnectionClosed) | Err(AuthError::IoError(_))
+        ));
+
+        // the server sends garbage
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        server.write_all(b"OK \xff\xfe\r\n").unwrap();
+        assert!(matches!(
+            do_auth(&mut client, Timeout::Infinite),
+            Err(AuthError::InvalidUtf8)
+        ));
+
+        // the server keeps sending commands that make no sense
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        for _ in 0..MAX_FAILED_COMMANDS {
+            write_message("GARBAGE", &mut server).unwrap();
+        }
+        assert!(matches!(
+            do_auth(&mut client, Timeout::Infinite),
+            Err(AuthError::UnexpectedResponse(line)) if line == "GARBAGE"
+        ));
+    }
+
+    #[test]
+    fn client_unix_fd_negotiation() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        write_message("ERROR \"not supported\"", &mut server).unwrap();
+        assert!(!negotiate_unix_fds(&mut client, Timeout::Infinite).unwrap());
+        assert_eq!(read_reply(&mut server), "NEGOTIATE_UNIX_FD");
+
+        write_message("AGREE_UNIX_FD", &mut server).unwrap();
+        assert!(negotiate_unix_fds(&mut client, Timeout::Infinite).unwrap());
+
+        write_message("DATA", &mut server).unwrap();
+        assert!(matches!(
+            negotiate_unix_fds(&mut client, Timeout::Infinite),
+            Err(AuthError::UnexpectedResponse(_))
+        ));
+    }
 }
@@ -0,0 +1,234 @@
+//! The client side of the authentication mechanisms defined by the dbus specification
+//!
+//! * External proves the identity with the credentials of the unix socket
+//! * CookieSha1 proves the identity by reading a secret cookie from the users home directory
+//! * Anonymous does not prove anything and is only accepted by servers that explicitly allow it
+
+use nix::unistd::getuid;
+use std::io::{BufRead, Read};
+use std::path::PathBuf;
+
+/// A client side authentication mechanism. The data is exchanged in raw form, the hex encoding is taken care of
+/// by the authentication routine.
+pub trait Mechanism {
+    /// The name of the mechanism that is sent in the AUTH command
+    fn name(&self) -> &str;
+    /// The initial response that is sent along with the AUTH command. None if the mechanism has no initial response.
+    fn initial_response(&mut self) -> Option<Vec<u8>>;
+    /// Answer a challenge the server sent in a DATA command. An error cancels this mechanism.
+    fn handle_data(&mut self, challenge: &[u8]) -> std::io::Result<Vec<u8>>;
+}
+
+/// The default list of mechanisms in the order they are tried: EXTERNAL, DBUS_COOKIE_SHA1, ANONYMOUS
+pub fn default_mechanisms() -> Vec<Box<dyn Mechanism>> {
+    vec![
+        Box::new(External),
+        Box::new(CookieSha1::new()),
+        Box::new(Anonymous::default()),
+    ]
+}
+
+pub(crate) fn hex_encode(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn hex_decode(hex: &[u8]) -> Option<Vec<u8>> {
+    // from_str_radix would also accept a sign
+    if hex.len() % 2 != 0 || !hex.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    hex.chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).ok()?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
+
+fn invalid_data(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+pub(crate) fn random_bytes(count: usize) -> std::io::Result<Vec<u8>> {
+    let mut rand = vec![0u8; count];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut rand)?;
+    Ok(rand)
+}
+
+/// The EXTERNAL mechanism. The uid of this process is sent and the server checks it against the socket credentials.
+pub struct External;
+
+impl Mechanism for External {
+    fn name(&self) -> &str {
+        "EXTERNAL"
+    }
+    fn initial_response(&mut self) -> Option<Vec<u8>> {
+        Some(getuid().as_raw().to_string().into_bytes())
+    }
+    fn handle_data(&mut self, _challenge: &[u8]) -> std::io::Result<Vec<u8>> {
+        // the identity was already sent with the initial response, there is nothing more to say
+        Ok(Vec::new())
+    }
+}
+
+/// The ANONYMOUS mechanism. The trace string is purely informational for the server.
+pub struct Anonymous {
+    pub trace: String,
+}
+
+impl Default for Anonymous {
+    fn default() -> Self {
+        Anonymous {
+            trace: "rustbus".to_owned(),
+        }
+    }
+}
+
+impl Mechanism for Anonymous {
+    fn name(&self) -> &str {
+        "ANONYMOUS"
+    }
+    fn initial_response(&mut self) -> Option<Vec<u8>> {
+        Some(self.trace.as_bytes().to_vec())
+    }
+    fn handle_data(&mut self, _challenge: &[u8]) -> std::io::Result<Vec<u8>> {
+        Ok(Vec::new())
+    }
+}
+
+/// The DBUS_COOKIE_SHA1 mechanism. The server names a cookie from a keyring in `~/.dbus-keyrings`, and the client proves
+/// it can read that keyring by hashing the cookie together with challenges from both sides.
+pub struct CookieSha1 {
+    keyring_dir: Option<PathBuf>,
+}
+
+impl Default for CookieSha1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CookieSha1 {
+    /// Use the keyrings in `$HOME/.dbus-keyrings`
+    pub fn new() -> Self {
+        CookieSha1 {
+            keyring_dir: std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".dbus-keyrings")),
+        }
+    }
+
+    /// Use the keyrings in another directory
+    pub fn with_keyring_dir(dir: PathBuf) -> Self {
+        CookieSha1 {
+            keyring_dir: Some(dir),
+        }
+    }
+
+    fn find_cookie(&self, context: &str, cookie_id: &str) -> std::io::Result<String> {
+        // the context names a file in the keyring directory, it must not be able to escape it
+        if context.is_empty()
+            || context.contains(|c: char| c == '/' || c == '\\' || c == '.' || c.is_whitespace())
+        {
+            return Err(invalid_data("Invalid cookie context"));
+        }
+        let dir = self
+            .keyring_dir
+            .as_ref()
+            .ok_or_else(|| invalid_data("No keyring directory known"))?;
+        let keyring = std::fs::File::open(dir.join(context))?;
+
+        // every line is: <id> <creation time> <cookie>
+        for line in std::io::BufReader::new(keyring).lines() {
+            let line = line?;
+            let mut parts = line.split(' ');
+            if let (Some(id), Some(_time), Some(cookie)) =
+                (parts.next(), parts.next(), parts.next())
+            {
+                if id == cookie_id {
+                    return Ok(cookie.to_owned());
+                }
+            }
+        }
+        Err(invalid_data("Cookie not found in keyring"))
+    }
+}
+
+impl Mechanism for CookieSha1 {
+    fn name(&self) -> &str {
+        "DBUS_COOKIE_SHA1"
+    }
+    fn initial_response(&mut self) -> Option<Vec<u8>> {
+        Some(getuid().as_raw().to_string().into_bytes())
+    }
+    fn handle_data(&mut self, challenge: &[u8]) -> std::io::Result<Vec<u8>> {
+        // the challenge is: <context> <cookie id> <server challenge>
+        let challenge =
+            std::str::from_utf8(challenge).map_err(|_| invalid_data("Challenge is not utf8"))?;
+        let mut parts = challenge.split(' ');
+        let (context, cookie_id, server_challenge) =
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(context), Some(id), Some(server_challenge)) => {
+                    (context, id, server_challenge)
+                }
+                _ => return Err(invalid_data("Malformed DBUS_COOKIE_SHA1 challenge")),
+            };
+
+        let cookie = self.find_cookie(context, cookie_id)?;
+        let client_challenge = hex_encode(&random_bytes(16)?);
+
+        let digest = sha1_smol::Sha1::from(format!(
+            "{}:{}:{}",
+            server_challenge, client_challenge, cookie
+        ))
+        .digest()
+        .to_string();
+
+        Ok(format!("{} {}", client_challenge, digest).into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cookie_sha1_response() {
+        let dir = std::env::temp_dir().join(format!("rustbus-keyrings-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("org_freedesktop_general"),
+            "1 1600000000 0000\n42 1600000000 cafebabe\n",
+        )
+        .unwrap();
+
+        let mut mech = CookieSha1::with_keyring_dir(dir.clone());
+        let response = mech
+            .handle_data(b"org_freedesktop_general 42 serverchallenge")
+            .unwrap();
+        let response = String::from_utf8(response).unwrap();
+        let (client_challenge, digest) = response.split_once(' ').unwrap();
+        let expected =
+            sha1_smol::Sha1::from(format!("serverchallenge:{}:cafebabe", client_challenge))
+                .digest()
+                .to_string();
+        assert_eq!(digest, expected);
+
+        // unknown cookies and contexts that try to leave the keyring dir are errors
+        assert!(mech
+            .handle_data(b"org_freedesktop_general 43 serverchallenge")
+            .is_err());
+        assert!(mech.handle_data(b"../secrets 42 serverchallenge").is_err());
+        assert!(mech.handle_data(b"missingparts").is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(hex_encode(b"1000"), "31303030");
+        assert_eq!(hex_decode(b"31303030").unwrap(), b"1000");
+        assert!(hex_decode(b"3").is_none());
+        assert!(hex_decode(b"zz").is_none());
+        assert!(hex_decode(b"+1").is_none());
+    }
+}
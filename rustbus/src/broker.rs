@@ -0,0 +1,304 @@
+//! A small bus daemon. It accepts connections on a unix socket, assigns unique names and routes messages between the
+//! connected clients.
+//!
+//! This is meant for tests and embedded setups where running a dbus-daemon is not an option. Only the core methods of
+//! `org.freedesktop.DBus` are implemented: Hello, RequestName, ReleaseName, AddMatch, RemoveMatch, GetNameOwner,
+//! NameHasOwner, ListNames, ListQueuedOwners and GetId. There is no activation and no security policy besides the
+//! uids that are allowed to connect.
+//!
+//! ```rust,no_run
+//! use rustbus::broker::Broker;
+//! use rustbus::connection::DBusAddress;
+//! use rustbus::RpcConn;
+//!
+//! fn main() -> Result<(), rustbus::connection::Error> {
+//!     let broker = Broker::bind(&DBusAddress::parse("unix:tmpdir=/tmp")?)?;
+//!     let handle = broker.spawn();
+//!
+//!     let mut con = RpcConn::connect_to_path([handle.address().clone()], rustbus::connection::Timeout::Infinite)?;
+//!     // ... use the connection like any other bus connection
+//!
+//!     // stops the broker and disconnects all clients
+//!     drop(handle);
+//!     Ok(())
+//! }
+//! ```
+
+mod bus;
+
+use crate::connection::address::{Transport, UnixAddress};
+use crate::connection::dispatch_conn::DispatchConn;
+use crate::connection::listener::DBusListener;
+use crate::connection::ll_conn::{DuplexConn, RecvConn, SendConn};
+use crate::connection::{DBusAddress, Error, Timeout};
+use crate::message_builder::{MarshalledMessage, MessageType};
+use crate::standard_messages;
+use crate::DBusError;
+
+use bus::{BusState, Client, BUS_NAME};
+
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+
+use nix::sys::socket;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Clients that take longer than this to authenticate are disconnected
+const AUTH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// A bus daemon listening on a unix socket. Use `run` to serve clients on the current thread or `spawn` to serve
+/// them in the background.
+pub struct Broker {
+    listener: DBusListener,
+    state: Arc<Mutex<BusState>>,
+    stop: Arc<AtomicBool>,
+}
+
+/// Handle to a broker that runs in the background. Dropping it stops the broker and disconnects all clients.
+pub struct BrokerHandle {
+    address: DBusAddress,
+    state: Arc<Mutex<BusState>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<std::thread::JoinHandle<Result<()>>>,
+}
+
+impl Broker {
+    /// Bind to a unix address, see `DBusListener::bind` for the supported addresses.
+    pub fn bind(addr: &DBusAddress) -> Result<Self> {
+        let listener = DBusListener::bind(addr)?;
+        let state = BusState::new(listener.guid().to_owned());
+        Ok(Broker {
+            listener,
+            state: Arc::new(Mutex::new(state)),
+            stop: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Bind to a new socket in the temporary directory of the system, e.g. to give every test its own bus
+    pub fn bind_tmpdir() -> Result<Self> {
+        Self::bind(&DBusAddress {
+            transport: Transport::Unix(UnixAddress::TmpDir(std::env::temp_dir())),
+            guid: None,
+        })
+    }
+
+    /// The address clients can use to connect to this broker
+    pub fn address(&self) -> &DBusAddress {
+        self.listener.address()
+    }
+
+    /// Restrict which users may connect. `None` accepts every client that proves its identity.
+    pub fn set_allowed_uids(&mut self, uids: Option<Vec<u32>>) {
+        self.listener.set_allowed_uids(uids);
+    }
+
+    /// Accept clients until an error occurs. Every client is authenticated and served by its own threads.
+    ///
+    /// Clients that fail to authenticate or take longer than a few seconds to do so are ignored.
+    pub fn run(&self) -> Result<()> {
+        loop {
+            let res = self.listener.accept_unauthenticated(Timeout::Infinite);
+            if self.stop.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            let stream = res?;
+            let auth = self.listener.server_auth().clone();
+            let state = self.state.clone();
+            let stop = self.stop.clone();
+            std::thread::Builder::new()
+                .name("rustbus-broker-auth".to_owned())
+                .spawn(move || {
+                    // clients that fail to authenticate are simply dropped
+                    if let Ok(conn) = auth.authenticate(stream, Timeout::Duration(AUTH_TIMEOUT)) {
+                        let _ = serve(conn, state, stop);
+                    }
+                })?;
+        }
+    }
+
+    /// Run the broker on a new thread
+    pub fn spawn(self) -> BrokerHandle {
+        let address = self.address().clone();
+        let state = self.state.clone();
+        let stop = self.stop.clone();
+        let thread = std::thread::spawn(move || self.run());
+        BrokerHandle {
+            address,
+            state,
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+fn serve(conn: DuplexConn, state: Arc<Mutex<BusState>>, stop: Arc<AtomicBool>) -> Result<()> {
+    let DuplexConn { send, recv, .. } = conn;
+    let (outgoing, to_write) = channel();
+    let client = Client {
+        outgoing,
+        unix_fd: send.can_pass_unix_fds(),
+        // the recv half lives until the client has been removed from the bus
+        fd: recv.as_raw_fd(),
+        match_rules: Vec::new(),
+    };
+
+    std::thread::Builder::new()
+        .name("rustbus-broker-writer".to_owned())
+        .spawn(move || write_messages(send, to_write))?;
+    std::thread::Builder::new()
+        .name("rustbus-broker-reader".to_owned())
+        .spawn(move || read_messages(recv, client, state, stop))?;
+    Ok(())
+}
+
+fn shutdown(fd: RawFd) {
+    let _ = socket::shutdown(fd, socket::Shutdown::Both);
+}
+
+/// Writes everything the bus routes to this client. Ends when the client is removed from the bus.
+fn write_messages(mut send: SendConn, to_write: Receiver<Arc<MarshalledMessage>>) {
+    for msg in to_write {
+        if send.send_message_write_all(&msg).is_err() {
+            // makes the reader notice the broken connection and remove the client
+            shutdown(send.as_raw_fd());
+            return;
+        }
+    }
+}
+
+fn is_hello(msg: &MarshalledMessage) -> bool {
+    msg.typ == MessageType::Call
+        && msg.dynheader.destination.as_deref() == Some(BUS_NAME)
+        && msg.dynheader.interface.as_deref() == Some(BUS_NAME)
+        && msg.dynheader.member.as_deref() == Some("Hello")
+}
+
+/// Reads the messages of one client and hands them to the bus. The first message has to be the Hello call.
+fn read_messages(
+    mut recv: RecvConn,
+    client: Client,
+    state: Arc<Mutex<BusState>>,
+    stop: Arc<AtomicBool>,
+) {
+    let hello = match recv.get_next_message(Timeout::Infinite) {
+        Ok(msg) if is_hello(&msg) => msg,
+        _ => {
+            shutdown(client.fd);
+            return;
+        }
+    };
+    let unique_name = {
+        let mut state = state.lock().unwrap();
+        // the broker stopped while this client was connecting, nobody would disconnect it anymore
+        if stop.load(Ordering::SeqCst) {
+            shutdown(client.fd);
+            return;
+        }
+        state.add_client(client, &hello)
+    };
+
+    while let Ok(msg) = recv.get_next_message(Timeout::Infinite) {
+        state.lock().unwrap().handle_message(&unique_name, msg);
+    }
+    state.lock().unwrap().remove_client(&unique_name);
+}
+
+impl BrokerHandle {
+    /// The address clients can use to connect to this broker
+    pub fn address(&self) -> &DBusAddress {
+        &self.address
+    }
+
+    /// Connect a service to this broker and run it on a new thread. Returns the unique name of the service.
+    ///
+    /// The well-known `names` are requested before this returns, so the service can be called by them right away.
+    /// A `DispatchConn` can not be sent to another thread, so `build` makes it from the connection on the new thread.
+    /// The service runs until the broker is stopped.
+    pub fn spawn_service<U, E, F>(&self, names: &[&str], build: F) -> Result<String>
+    where
+        F: FnOnce(DuplexConn) -> DispatchConn<U, E> + Send + 'static,
+        E: std::fmt::Debug,
+    {
+        let mut conn = DuplexConn::connect_to_bus(
+            std::slice::from_ref(&self.address),
+            false,
+            Timeout::Infinite,
+        )?;
+        let unique_name = conn.send_hello(Timeout::Infinite)?;
+        for name in names {
+            let request = standard_messages::request_name(
+                name,
+                standard_messages::DBUS_NAME_FLAG_DO_NOT_QUEUE,
+            );
+            let serial = conn.send.send_message_write_all(&request)?;
+            let reply = loop {
+                let msg = conn.recv.get_next_message(Timeout::Infinite)?;
+                if msg.dynheader.response_serial == Some(serial) {
+                    break msg;
+                }
+            };
+            if let Some(error) = DBusError::from_message(&reply) {
+                return Err(Error::ErrorReply(error));
+            }
+            if reply.body.parser().get::<u32>()?
+                != standard_messages::DBUS_REQUEST_NAME_REPLY_PRIMARY_OWNER
+            {
+                return Err(Error::NameTaken);
+            }
+        }
+        std::thread::Builder::new()
+            .name("rustbus-broker-service".to_owned())
+            .spawn(move || {
+                let mut service = build(conn);
+                let _ = service.run();
+            })?;
+        Ok(unique_name)
+    }
+
+    /// Stop accepting clients, disconnect all connected clients and wait for the broker to finish
+    pub fn stop(mut self) -> Result<()> {
+        self.stop_and_join()
+    }
+
+    fn stop_and_join(&mut self) -> Result<()> {
+        let thread = match self.thread.take() {
+            Some(thread) => thread,
+            None => return Ok(()),
+        };
+        self.stop.store(true, Ordering::SeqCst);
+
+        // wake up the accept call by connecting to the broker
+        if let Transport::Unix(unix) = &self.address.transport {
+            let addr = crate::connection::unix_sockaddr(unix)?;
+            let sock = socket::socket(
+                socket::AddressFamily::Unix,
+                socket::SockType::Stream,
+                socket::SockFlag::empty(),
+                None,
+            )?;
+            let res = socket::connect(sock, &addr);
+            let _ = nix::unistd::close(sock);
+            res?;
+        }
+        let res = thread.join().map_err(|_| {
+            Error::IoError(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "The broker thread panicked",
+            ))
+        })?;
+
+        for client in self.state.lock().unwrap().clients.values() {
+            shutdown(client.fd);
+        }
+        res
+    }
+}
+
+impl Drop for BrokerHandle {
+    fn drop(&mut self) {
+        let _ = self.stop_and_join();
+    }
+}
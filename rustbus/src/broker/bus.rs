@@ -0,0 +1,416 @@
+//! The state of the bus: connected clients, owned names and match rules. Also implements the org.freedesktop.DBus
+//! methods the bus itself offers.
+
+use crate::dbus_error::{DBusError, ErrorName};
+use crate::match_rule::MatchRule;
+use crate::message_builder::{HeaderFlags, MarshalledMessage, MessageBuilder, MessageType};
+use crate::standard_messages::*;
+
+use std::collections::{HashMap, VecDeque};
+use std::os::unix::io::RawFd;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+pub(super) const BUS_NAME: &str = "org.freedesktop.DBus";
+const BUS_PATH: &str = "/org/freedesktop/DBus";
+
+/// A connected client that said Hello
+pub(super) struct Client {
+    /// Messages put in here are written to the client by its writer thread
+    pub outgoing: Sender<Arc<MarshalledMessage>>,
+    pub unix_fd: bool,
+    /// Used to shut down the socket when the broker stops
+    pub fd: RawFd,
+    pub match_rules: Vec<MatchRule>,
+}
+
+struct NameEntry {
+    owner: String,
+    owner_flags: u32,
+    /// Clients waiting to get the name, with the flags they requested it with
+    queue: VecDeque<(String, u32)>,
+}
+
+pub(super) struct BusState {
+    guid: String,
+    next_id: u64,
+    pub clients: HashMap<String, Client>,
+    names: HashMap<String, NameEntry>,
+}
+
+fn bus_signal(member: &str) -> MarshalledMessage {
+    let mut sig = MessageBuilder::new()
+        .signal(BUS_NAME, member, BUS_PATH)
+        .build();
+    sig.dynheader.sender = Some(BUS_NAME.to_owned());
+    sig
+}
+
+fn error_reply(call: &MarshalledMessage, name: ErrorName, text: String) -> MarshalledMessage {
+    DBusError::new(name, text).to_message(&call.dynheader)
+}
+
+impl BusState {
+    pub fn new(guid: String) -> Self {
+        BusState {
+            guid,
+            next_id: 1,
+            clients: HashMap::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    /// Register a new client that sent the Hello call. The reply and the NameAcquired/NameOwnerChanged signals are sent.
+    pub fn add_client(&mut self, client: Client, hello: &MarshalledMessage) -> String {
+        let unique_name = format!(":1.{}", self.next_id);
+        self.next_id += 1;
+        self.clients.insert(unique_name.clone(), client);
+
+        let mut hello = hello.dynheader.clone();
+        hello.sender = Some(unique_name.clone());
+        let mut reply = hello.make_response();
+        reply.body.push_param(unique_name.as_str()).unwrap();
+        self.send_from_bus(reply);
+
+        self.name_owner_changed(&unique_name, "", &unique_name);
+        self.name_acquired(&unique_name, &unique_name);
+        unique_name
+    }
+
+    /// Forget about a client that disconnected. All names it owned are released.
+    pub fn remove_client(&mut self, unique_name: &str) {
+        if self.clients.remove(unique_name).is_none() {
+            return;
+        }
+        let names: Vec<String> = self.names.keys().cloned().collect();
+        for name in names {
+            self.release_name(unique_name, &name);
+        }
+        self.name_owner_changed(unique_name, unique_name, "");
+    }
+
+    /// Route a message that was received from a client
+    pub fn handle_message(&mut self, sender: &str, mut msg: MarshalledMessage) {
+        msg.dynheader.sender = Some(sender.to_owned());
+        if msg.dynheader.destination.as_deref() == Some(BUS_NAME) {
+            if msg.typ == MessageType::Call {
+                let reply = self.handle_bus_call(&msg);
+                if !HeaderFlags::NoReplyExpected.is_set(msg.flags) {
+                    self.send_from_bus(reply);
+                }
+            }
+            return;
+        }
+        self.route(msg);
+    }
+
+    /// Find the unique name of the connection that owns the name
+    fn resolve(&self, name: &str) -> Option<&str> {
+        if name.starts_with(':') {
+            self.clients
+                .get_key_value(name)
+                .map(|(name, _)| name.as_str())
+        } else if name == BUS_NAME {
+            Some(BUS_NAME)
+        } else {
+            self.names.get(name).map(|entry| entry.owner.as_str())
+        }
+    }
+
+    fn rule_matches(&self, rule: &MatchRule, msg: &MarshalledMessage) -> bool {
+        match &rule.sender {
+            // rules may name the well-known name while the message carries the unique name
+            Some(sender) if !sender.starts_with(':') && sender != BUS_NAME => {
+                self.resolve(sender).is_some()
+                    && msg.dynheader.sender.as_deref() == self.resolve(sender)
+                    && rule.matches_ignoring_sender(msg)
+            }
+            _ => rule.matches(msg),
+        }
+    }
+
+    /// Deliver a message to its destination or to everyone with a matching rule if it has no destination
+    fn route(&mut self, msg: MarshalledMessage) {
+        let has_fds = !msg.body.raw_fds.is_empty();
+        let expects_reply =
+            msg.typ == MessageType::Call && !HeaderFlags::NoReplyExpected.is_set(msg.flags);
+
+        match &msg.dynheader.destination {
+            Some(destination) => {
+                let client = self
+                    .resolve(destination)
+                    .and_then(|owner| self.clients.get(owner));
+                let error = match client {
+                    Some(client) if has_fds && !client.unix_fd => error_reply(
+                        &msg,
+                        ErrorName::NotSupported,
+                        format!("{} can not receive unix fds", destination),
+                    ),
+                    Some(client) => {
+                        let _ = client.outgoing.send(Arc::new(msg));
+                        return;
+                    }
+                    None => error_reply(
+                        &msg,
+                        ErrorName::ServiceUnknown,
+                        format!("The name {} is not owned by any connection", destination),
+                    ),
+                };
+                if expects_reply {
+                    self.send_from_bus(error);
+                }
+            }
+            None => {
+                let msg = Arc::new(msg);
+                for client in self.clients.values() {
+                    if has_fds && !client.unix_fd {
+                        continue;
+                    }
+                    if client
+                        .match_rules
+                        .iter()
+                        .any(|rule| self.rule_matches(rule, &msg))
+                    {
+                        let _ = client.outgoing.send(msg.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    fn send_from_bus(&mut self, mut msg: MarshalledMessage) {
+        msg.dynheader.sender = Some(BUS_NAME.to_owned());
+        self.route(msg);
+    }
+
+    fn name_owner_changed(&mut self, name: &str, old_owner: &str, new_owner: &str) {
+        let mut sig = bus_signal("NameOwnerChanged");
+        sig.body.push_param3(name, old_owner, new_owner).unwrap();
+        self.route(sig);
+    }
+
+    fn name_acquired(&mut self, client: &str, name: &str) {
+        let mut sig = bus_signal("NameAcquired");
+        sig.dynheader.destination = Some(client.to_owned());
+        sig.body.push_param(name).unwrap();
+        self.route(sig);
+    }
+
+    fn name_lost(&mut self, client: &str, name: &str) {
+        let mut sig = bus_signal("NameLost");
+        sig.dynheader.destination = Some(client.to_owned());
+        sig.body.push_param(name).unwrap();
+        self.route(sig);
+    }
+
+    fn request_name(&mut self, client: &str, name: &str, flags: u32) -> u32 {
+        let entry = match self.names.get_mut(name) {
+            Some(entry) => entry,
+            None => {
+                self.names.insert(
+                    name.to_owned(),
+                    NameEntry {
+                        owner: client.to_owned(),
+                        owner_flags: flags,
+                        queue: VecDeque::new(),
+                    },
+                );
+                self.name_owner_changed(name, "", client);
+                self.name_acquired(client, name);
+                return DBUS_REQUEST_NAME_REPLY_PRIMARY_OWNER;
+            }
+        };
+
+        if entry.owner == client {
+            entry.owner_flags = flags;
+            return DBUS_REQUEST_NAME_REPLY_ALREADY_OWNER;
+        }
+
+        entry.queue.retain(|(queued, _)| queued != client);
+        let may_replace = entry.owner_flags & DBUS_NAME_FLAG_ALLOW_REPLACEMENT != 0
+            && flags & DBUS_NAME_FLAG_REPLACE_EXISTING != 0;
+
+        if may_replace {
+            let old_owner = std::mem::replace(&mut entry.owner, client.to_owned());
+            let old_flags = std::mem::replace(&mut entry.owner_flags, flags);
+            if old_flags & DBUS_NAME_FLAG_DO_NOT_QUEUE == 0 {
+                entry.queue.push_front((old_owner.clone(), old_flags));
+            }
+            self.name_lost(&old_owner, name);
+            self.name_owner_changed(name, &old_owner, client);
+            self.name_acquired(client, name);
+            DBUS_REQUEST_NAME_REPLY_PRIMARY_OWNER
+        } else if flags & DBUS_NAME_FLAG_DO_NOT_QUEUE != 0 {
+            DBUS_REQUEST_NAME_REPLY_EXISTS
+        } else {
+            entry.queue.push_back((client.to_owned(), flags));
+            DBUS_REQUEST_NAME_REPLY_IN_QUEUE
+        }
+    }
+
+    fn release_name(&mut self, client: &str, name: &str) -> u32 {
+        let entry = match self.names.get_mut(name) {
+            Some(entry) => entry,
+            None => return DBUS_RELEASE_NAME_REPLY_NON_EXISTENT,
+        };
+
+        if entry.owner != client {
+            let queued = entry.queue.len();
+            entry.queue.retain(|(queued, _)| queued != client);
+            return if entry.queue.len() != queued {
+                DBUS_RELEASE_NAME_REPLY_RELEASED
+            } else {
+                DBUS_RELEASE_NAME_REPLY_NOT_OWNER
+            };
+        }
+
+        match entry.queue.pop_front() {
+            Some((next, flags)) => {
+                entry.owner = next.clone();
+                entry.owner_flags = flags;
+                self.name_lost(client, name);
+                self.name_owner_changed(name, client, &next);
+                self.name_acquired(&next, name);
+            }
+            None => {
+                self.names.remove(name);
+                self.name_lost(client, name);
+                self.name_owner_changed(name, client, "");
+            }
+        }
+        DBUS_RELEASE_NAME_REPLY_RELEASED
+    }
+
+    /// Handle a call to the bus itself. Returns the reply.
+    fn handle_bus_call(&mut self, call: &MarshalledMessage) -> MarshalledMessage {
+        let sender = call.dynheader.sender.clone().unwrap_or_default();
+        let member = call.dynheader.member.as_deref().unwrap_or("");
+        let mut reply = call.dynheader.make_response();
+        let invalid_args = || invalid_args(&call.dynheader, None);
+
+        if call.dynheader.interface.as_deref() == Some("org.freedesktop.DBus.Peer") {
+            return match member {
+                "Ping" => reply,
+                _ => unknown_method(&call.dynheader),
+            };
+        }
+
+        match member {
+            "Hello" => error_reply(
+                call,
+                ErrorName::Failed,
+                "Already handled an Hello message".to_owned(),
+            ),
+            "RequestName" => match call.body.parser().get2::<&str, u32>() {
+                Ok((name, _))
+                    if name.starts_with(':')
+                        || name == BUS_NAME
+                        || crate::params::validate_busname(name).is_err() =>
+                {
+                    error_reply(
+                        call,
+                        ErrorName::InvalidArgs,
+                        format!("Can not request the name {}", name),
+                    )
+                }
+                Ok((name, flags)) => {
+                    let result = self.request_name(&sender, name, flags);
+                    reply.body.push_param(result).unwrap();
+                    reply
+                }
+                Err(_) => invalid_args(),
+            },
+            "ReleaseName" => match call.body.parser().get::<&str>() {
+                Ok(name) if name.starts_with(':') || name == BUS_NAME => error_reply(
+                    call,
+                    ErrorName::InvalidArgs,
+                    format!("Can not release the name {}", name),
+                ),
+                Ok(name) => {
+                    let result = self.release_name(&sender, name);
+                    reply.body.push_param(result).unwrap();
+                    reply
+                }
+                Err(_) => invalid_args(),
+            },
+            "AddMatch" | "RemoveMatch" => {
+                let rule = match call.body.parser().get::<&str>() {
+                    Ok(rule) => rule,
+                    Err(_) => return invalid_args(),
+                };
+                let rule = match MatchRule::parse(rule) {
+                    Ok(rule) => rule,
+                    Err(e) => return error_reply(call, ErrorName::MatchRuleInvalid, e.to_string()),
+                };
+                let rules = match self.clients.get_mut(&sender) {
+                    Some(client) => &mut client.match_rules,
+                    None => return reply,
+                };
+                if member == "AddMatch" {
+                    rules.push(rule);
+                    return reply;
+                }
+                match rules.iter().position(|r| *r == rule) {
+                    Some(idx) => {
+                        rules.remove(idx);
+                        reply
+                    }
+                    None => error_reply(
+                        call,
+                        ErrorName::MatchRuleNotFound,
+                        "The match rule was not added before".to_owned(),
+                    ),
+                }
+            }
+            "GetNameOwner" => match call.body.parser().get::<&str>() {
+                Ok(name) => match self.resolve(name) {
+                    Some(owner) => {
+                        reply.body.push_param(owner).unwrap();
+                        reply
+                    }
+                    None => error_reply(
+                        call,
+                        ErrorName::NameHasNoOwner,
+                        format!("The name {} does not have an owner", name),
+                    ),
+                },
+                Err(_) => invalid_args(),
+            },
+            "NameHasOwner" => match call.body.parser().get::<&str>() {
+                Ok(name) => {
+                    reply.body.push_param(self.resolve(name).is_some()).unwrap();
+                    reply
+                }
+                Err(_) => invalid_args(),
+            },
+            "ListNames" => {
+                let mut names = vec![BUS_NAME.to_owned()];
+                names.extend(self.clients.keys().cloned());
+                names.extend(self.names.keys().cloned());
+                reply.body.push_param(names).unwrap();
+                reply
+            }
+            "ListQueuedOwners" => match call.body.parser().get::<&str>() {
+                Ok(name) => match self.names.get(name) {
+                    Some(entry) => {
+                        let mut owners = vec![entry.owner.clone()];
+                        owners.extend(entry.queue.iter().map(|(queued, _)| queued.clone()));
+                        reply.body.push_param(owners).unwrap();
+                        reply
+                    }
+                    None => error_reply(
+                        call,
+                        ErrorName::NameHasNoOwner,
+                        format!("The name {} does not have an owner", name),
+                    ),
+                },
+                Err(_) => invalid_args(),
+            },
+            "GetId" => {
+                reply.body.push_param(self.guid.as_str()).unwrap();
+                reply
+            }
+            _ => unknown_method(&call.dynheader),
+        }
+    }
+}
@@ -0,0 +1,335 @@
+//! Typed access to the `org.freedesktop.DBus` interface of the bus daemon
+//!
+//! ```rust,no_run
+//! use rustbus::bus::{BusProxy, NameFlags, RequestNameReply};
+//! use rustbus::{connection::Timeout, RpcConn};
+//!
+//! fn main() -> Result<(), rustbus::connection::rpc_conn::CallError> {
+//!     let mut con = RpcConn::session_conn(Timeout::Infinite)?;
+//!     let mut bus = BusProxy::new(&mut con);
+//!
+//!     match bus.request_name("io.killing.spark", NameFlags::default().do_not_queue())? {
+//!         RequestNameReply::PrimaryOwner => println!("Got the name"),
+//!         other => println!("Did not get the name: {:?}", other),
+//!     }
+//!     println!("Names on the bus: {:?}", bus.list_names()?);
+//!     Ok(())
+//! }
+//! ```
+
+use crate::connection::rpc_conn::{CallError, RpcConn};
+use crate::connection::Timeout;
+use crate::match_rule::MatchRule;
+use crate::message_builder::{ArgList, MarshalledMessage, MessageBuilder};
+use crate::standard_messages::*;
+use crate::wire::errors::UnmarshalError;
+use crate::wire::{OwnedVariant, VariantMap};
+
+use std::collections::HashMap;
+
+mod name_owner;
+pub use name_owner::{NameOwner, NameStatus, NameStatusCallback};
+
+pub const BUS_NAME: &str = "org.freedesktop.DBus";
+pub const BUS_PATH: &str = "/org/freedesktop/DBus";
+pub const BUS_INTERFACE: &str = "org.freedesktop.DBus";
+
+type Result<T> = std::result::Result<T, CallError>;
+
+/// The flags for RequestName
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NameFlags {
+    /// Other connections may take the name over with `replace_existing`
+    pub allow_replacement: bool,
+    /// Take the name from the current owner if it allows replacement
+    pub replace_existing: bool,
+    /// Do not wait in the queue if the name can not be acquired right away
+    pub do_not_queue: bool,
+}
+
+impl NameFlags {
+    pub fn allow_replacement(mut self) -> Self {
+        self.allow_replacement = true;
+        self
+    }
+    pub fn replace_existing(mut self) -> Self {
+        self.replace_existing = true;
+        self
+    }
+    pub fn do_not_queue(mut self) -> Self {
+        self.do_not_queue = true;
+        self
+    }
+
+    pub fn into_raw(self) -> u32 {
+        let mut flags = 0;
+        if self.allow_replacement {
+            flags |= DBUS_NAME_FLAG_ALLOW_REPLACEMENT;
+        }
+        if self.replace_existing {
+            flags |= DBUS_NAME_FLAG_REPLACE_EXISTING;
+        }
+        if self.do_not_queue {
+            flags |= DBUS_NAME_FLAG_DO_NOT_QUEUE;
+        }
+        flags
+    }
+
+    pub fn from_raw(flags: u32) -> Self {
+        NameFlags {
+            allow_replacement: flags & DBUS_NAME_FLAG_ALLOW_REPLACEMENT != 0,
+            replace_existing: flags & DBUS_NAME_FLAG_REPLACE_EXISTING != 0,
+            do_not_queue: flags & DBUS_NAME_FLAG_DO_NOT_QUEUE != 0,
+        }
+    }
+}
+
+/// The result of RequestName
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestNameReply {
+    /// This connection owns the name now
+    PrimaryOwner,
+    /// The name is owned by another connection, this connection was put into the queue
+    InQueue,
+    /// The name is owned by another connection and this connection did not want to be queued
+    Exists,
+    /// This connection already owned the name
+    AlreadyOwner,
+}
+
+impl RequestNameReply {
+    pub fn from_raw(reply: u32) -> Option<Self> {
+        match reply {
+            DBUS_REQUEST_NAME_REPLY_PRIMARY_OWNER => Some(RequestNameReply::PrimaryOwner),
+            DBUS_REQUEST_NAME_REPLY_IN_QUEUE => Some(RequestNameReply::InQueue),
+            DBUS_REQUEST_NAME_REPLY_EXISTS => Some(RequestNameReply::Exists),
+            DBUS_REQUEST_NAME_REPLY_ALREADY_OWNER => Some(RequestNameReply::AlreadyOwner),
+            _ => None,
+        }
+    }
+}
+
+/// The result of ReleaseName
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReleaseNameReply {
+    /// The name was released or this connection was removed from the queue
+    Released,
+    /// Nobody owned the name
+    NonExistent,
+    /// This connection neither owned the name nor waited for it
+    NotOwner,
+}
+
+impl ReleaseNameReply {
+    pub fn from_raw(reply: u32) -> Option<Self> {
+        match reply {
+            DBUS_RELEASE_NAME_REPLY_RELEASED => Some(ReleaseNameReply::Released),
+            DBUS_RELEASE_NAME_REPLY_NON_EXISTENT => Some(ReleaseNameReply::NonExistent),
+            DBUS_RELEASE_NAME_REPLY_NOT_OWNER => Some(ReleaseNameReply::NotOwner),
+            _ => None,
+        }
+    }
+}
+
+/// The result of StartServiceByName
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartServiceReply {
+    Success,
+    AlreadyRunning,
+}
+
+/// The result of GetConnectionCredentials. Credentials the bus does not know about are None.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionCredentials {
+    pub unix_user_id: Option<u32>,
+    pub unix_group_ids: Option<Vec<u32>>,
+    pub process_id: Option<u32>,
+    pub linux_security_label: Option<Vec<u8>>,
+}
+
+/// Calls the methods of the bus over an RpcConn. Messages that arrive while waiting for the replies are put into the
+/// queues of the RpcConn as usual.
+pub struct BusProxy<'a> {
+    con: &'a mut RpcConn,
+    timeout: Timeout,
+}
+
+fn bus_call(member: &str) -> MarshalledMessage {
+    MessageBuilder::new()
+        .call(member)
+        .with_interface(BUS_INTERFACE)
+        .on(BUS_PATH)
+        .at(BUS_NAME)
+        .build()
+}
+
+/// The bus replied with a number that has no meaning in the enum of the reply
+fn unexpected_reply() -> CallError {
+    CallError::InvalidReply(UnmarshalError::NoMatchingVariantFound)
+}
+
+impl<'a> BusProxy<'a> {
+    /// Wait for the replies without a timeout
+    pub fn new(con: &'a mut RpcConn) -> Self {
+        Self::with_timeout(con, Timeout::Infinite)
+    }
+
+    /// Wait at most `timeout` for each reply
+    pub fn with_timeout(con: &'a mut RpcConn, timeout: Timeout) -> Self {
+        BusProxy { con, timeout }
+    }
+
+    /// Call a method of the bus interface that returns one value
+    fn call_method<A, R>(&mut self, member: &str, args: A) -> Result<R>
+    where
+        A: ArgList,
+        R: for<'r> crate::Unmarshal<'r, 'r>,
+    {
+        let (ret,) = self.con.call(
+            BUS_NAME,
+            BUS_PATH,
+            BUS_INTERFACE,
+            member,
+            args,
+            self.timeout,
+        )?;
+        Ok(ret)
+    }
+
+    pub fn request_name(&mut self, name: &str, flags: NameFlags) -> Result<RequestNameReply> {
+        let reply = self.call_method("RequestName", (name, flags.into_raw()))?;
+        RequestNameReply::from_raw(reply).ok_or_else(unexpected_reply)
+    }
+
+    pub fn release_name(&mut self, name: &str) -> Result<ReleaseNameReply> {
+        let reply = self.call_method("ReleaseName", (name,))?;
+        ReleaseNameReply::from_raw(reply).ok_or_else(unexpected_reply)
+    }
+
+    /// All names that currently have an owner, unique names included
+    pub fn list_names(&mut self) -> Result<Vec<String>> {
+        self.call_method("ListNames", ())
+    }
+
+    /// All names that can be started by the bus
+    pub fn list_activatable_names(&mut self) -> Result<Vec<String>> {
+        self.call_method("ListActivatableNames", ())
+    }
+
+    pub fn name_has_owner(&mut self, name: &str) -> Result<bool> {
+        self.call_method("NameHasOwner", (name,))
+    }
+
+    /// The unique name of the owner. Fails with NameHasNoOwner if there is none.
+    pub fn get_name_owner(&mut self, name: &str) -> Result<String> {
+        self.call_method("GetNameOwner", (name,))
+    }
+
+    /// The unique names of the owner and all connections waiting in the queue for the name
+    pub fn list_queued_owners(&mut self, name: &str) -> Result<Vec<String>> {
+        self.call_method("ListQueuedOwners", (name,))
+    }
+
+    pub fn start_service_by_name(&mut self, name: &str) -> Result<StartServiceReply> {
+        // the flags are currently unused by the bus
+        match self.call_method::<_, u32>("StartServiceByName", (name, 0u32))? {
+            1 => Ok(StartServiceReply::Success),
+            2 => Ok(StartServiceReply::AlreadyRunning),
+            _ => Err(unexpected_reply()),
+        }
+    }
+
+    pub fn get_connection_unix_user(&mut self, name: &str) -> Result<u32> {
+        self.call_method("GetConnectionUnixUser", (name,))
+    }
+
+    pub fn get_connection_unix_process_id(&mut self, name: &str) -> Result<u32> {
+        self.call_method("GetConnectionUnixProcessID", (name,))
+    }
+
+    pub fn get_connection_credentials(&mut self, name: &str) -> Result<ConnectionCredentials> {
+        let creds: VariantMap = self.call_method("GetConnectionCredentials", (name,))?;
+
+        let mut result = ConnectionCredentials::default();
+        for (key, value) in creds {
+            match key.as_str() {
+                "UnixUserID" => result.unix_user_id = Some(value.get()?),
+                "UnixGroupIDs" => result.unix_group_ids = Some(value.get()?),
+                "ProcessID" => result.process_id = Some(value.get()?),
+                "LinuxSecurityLabel" => result.linux_security_label = Some(value.get()?),
+                // the spec allows new keys to be added
+                _ => {}
+            }
+        }
+        Ok(result)
+    }
+
+    /// The guid of the bus
+    pub fn get_id(&mut self) -> Result<String> {
+        self.call_method("GetId", ())
+    }
+
+    /// Add variables to the environment of services the bus starts
+    pub fn update_activation_environment(&mut self, env: &HashMap<String, String>) -> Result<()> {
+        self.con.call(
+            BUS_NAME,
+            BUS_PATH,
+            BUS_INTERFACE,
+            "UpdateActivationEnvironment",
+            (env,),
+            self.timeout,
+        )
+    }
+
+    /// Same as `RpcConn::add_match`, so the connection keeps the signals matching the rule
+    pub fn add_match(&mut self, rule: &MatchRule) -> Result<()> {
+        self.con.add_match(rule.clone(), self.timeout)?;
+        Ok(())
+    }
+
+    /// Same as `RpcConn::remove_match`
+    pub fn remove_match(&mut self, rule: &MatchRule) -> Result<()> {
+        self.con.remove_match(rule, self.timeout)?;
+        Ok(())
+    }
+
+    /// Turn this connection into a monitor that receives all messages matching any of the rules (or all messages if
+    /// there are none). A monitor can not send messages anymore, the bus disconnects it if it tries.
+    pub fn become_monitor(&mut self, rules: &[MatchRule]) -> Result<()> {
+        let rules: Vec<String> = rules.iter().map(|rule| rule.to_string()).collect();
+        self.con.call(
+            BUS_NAME,
+            BUS_PATH,
+            "org.freedesktop.DBus.Monitoring",
+            "BecomeMonitor",
+            (rules, 0u32),
+            self.timeout,
+        )
+    }
+
+    fn get_property<R>(&mut self, property: &str) -> Result<R>
+    where
+        R: for<'r> crate::Unmarshal<'r, 'r>,
+    {
+        let (value,): (OwnedVariant,) = self.con.call(
+            BUS_NAME,
+            BUS_PATH,
+            "org.freedesktop.DBus.Properties",
+            "Get",
+            (BUS_INTERFACE, property),
+            self.timeout,
+        )?;
+        let value = value.get()?;
+        Ok(value)
+    }
+
+    /// Optional features the bus supports, e.g. "SystemdActivation"
+    pub fn features(&mut self) -> Result<Vec<String>> {
+        self.get_property("Features")
+    }
+
+    /// Extra interfaces the bus object implements besides org.freedesktop.DBus, e.g. "org.freedesktop.DBus.Monitoring"
+    pub fn interfaces(&mut self) -> Result<Vec<String>> {
+        self.get_property("Interfaces")
+    }
+}
@@ -0,0 +1,215 @@
+//! Owning a well-known name and keeping track of whether it is still owned
+
+use super::{BusProxy, NameFlags, ReleaseNameReply, RequestNameReply, BUS_INTERFACE, BUS_NAME};
+use crate::connection::rpc_conn::{CallError, RpcConn, SubscriptionId};
+use crate::connection::Timeout;
+use crate::dbus_error::ErrorName;
+use crate::match_rule::MatchRule;
+use crate::message_builder::MessageType;
+
+use std::borrow::BorrowMut;
+use std::sync::{Arc, Mutex};
+
+/// Whether a connection currently owns the name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameStatus {
+    /// This connection is the primary owner
+    Owner,
+    /// Another connection owns the name, this connection waits in the queue
+    InQueue,
+    /// This connection does not own the name and is not in the queue
+    NotOwner,
+}
+
+/// Called with the new status when the bus signals that the name was acquired or lost
+pub type NameStatusCallback = Box<dyn FnMut(NameStatus) + Sync + Send>;
+
+struct SharedState {
+    status: NameStatus,
+    callback: Option<NameStatusCallback>,
+}
+
+/// Requests a name on creation and releases it when dropped. The NameAcquired and NameLost signals of the bus are
+/// followed to know whether the name is still owned, e.g. after another connection took it over with `replace_existing`.
+///
+/// The signals are only processed while the connection receives messages, so the status is only as current as the
+/// last io on the RpcConn. The connection can be owned by the NameOwner or borrowed, and is reachable with
+/// `conn`/`conn_mut` either way.
+///
+/// ```rust,no_run
+/// use rustbus::bus::{NameFlags, NameOwner, NameStatus};
+/// use rustbus::{connection::Timeout, RpcConn};
+///
+/// fn main() -> Result<(), rustbus::connection::rpc_conn::CallError> {
+///     let con = RpcConn::session_conn(Timeout::Infinite)?;
+///     let flags = NameFlags::default().allow_replacement();
+///     let mut owner = NameOwner::request(con, "io.killing.spark", flags, Timeout::Infinite)?;
+///     owner.on_change(Box::new(|status| {
+///         if status != NameStatus::Owner {
+///             println!("We have been replaced");
+///         }
+///     }));
+///
+///     loop {
+///         let call = owner.conn_mut().wait_call(Timeout::Infinite)?;
+///         // handle the call
+///     }
+/// }
+/// ```
+pub struct NameOwner<C: BorrowMut<RpcConn>> {
+    con: C,
+    name: String,
+    flags: NameFlags,
+    timeout: Timeout,
+    state: Arc<Mutex<SharedState>>,
+    subscriptions: Vec<SubscriptionId>,
+    released: bool,
+}
+
+impl<C: BorrowMut<RpcConn>> NameOwner<C> {
+    /// Request the name. The handle is also returned if the name could not be acquired right away, check `status` for that.
+    ///
+    /// The timeout is used for all calls to the bus, including the ones made when the handle is released.
+    pub fn request(
+        mut con: C,
+        name: &str,
+        flags: NameFlags,
+        timeout: Timeout,
+    ) -> Result<Self, CallError> {
+        let state = Arc::new(Mutex::new(SharedState {
+            status: NameStatus::NotOwner,
+            callback: None,
+        }));
+
+        // subscribe before requesting the name so no signal is missed
+        let mut subscriptions = Vec::new();
+        let status_after_loss = if flags.do_not_queue {
+            NameStatus::NotOwner
+        } else {
+            // the bus puts the previous owner into the queue unless it asked not to be queued
+            NameStatus::InQueue
+        };
+        for (member, new_status) in [
+            ("NameAcquired", NameStatus::Owner),
+            ("NameLost", status_after_loss),
+        ]
+        .iter()
+        {
+            let rule = MatchRule::new()
+                .with_type(MessageType::Signal)
+                .with_sender(BUS_NAME)
+                .with_interface(BUS_INTERFACE)
+                .with_member(*member)
+                .with_arg(0, name);
+            let state = state.clone();
+            let new_status = *new_status;
+            let callback = Box::new(move |_: &_| {
+                let mut state = state.lock().unwrap();
+                if state.status != new_status {
+                    state.status = new_status;
+                    if let Some(callback) = &mut state.callback {
+                        callback(new_status);
+                    }
+                }
+            });
+            subscriptions.push(
+                con.borrow_mut()
+                    .subscribe_with_callback(rule, callback, timeout)?,
+            );
+        }
+
+        let mut owner = NameOwner {
+            con,
+            name: name.to_owned(),
+            flags,
+            timeout,
+            state,
+            subscriptions,
+            released: false,
+        };
+        let reply =
+            BusProxy::with_timeout(owner.con.borrow_mut(), timeout).request_name(name, flags)?;
+        owner.state.lock().unwrap().status = match reply {
+            RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner => NameStatus::Owner,
+            RequestNameReply::InQueue => NameStatus::InQueue,
+            RequestNameReply::Exists => NameStatus::NotOwner,
+        };
+        Ok(owner)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn flags(&self) -> NameFlags {
+        self.flags
+    }
+
+    /// The status as of the last message the connection received
+    pub fn status(&self) -> NameStatus {
+        self.state.lock().unwrap().status
+    }
+
+    /// Call the callback whenever the status changes because of a signal from the bus. Replaces the previous callback.
+    pub fn on_change(&mut self, callback: NameStatusCallback) {
+        self.state.lock().unwrap().callback = Some(callback);
+    }
+
+    /// Ask the bus for the position of this connection in the queue of the name. 0 means this connection is the
+    /// owner, None that it is not in the queue at all.
+    pub fn queue_position(&mut self) -> Result<Option<usize>, CallError> {
+        let mut call = super::bus_call("ListQueuedOwners");
+        call.body.push_param(self.name.as_str())?;
+        let reply = match self.con.borrow_mut().call_message(call, self.timeout) {
+            Ok(reply) => reply,
+            Err(CallError::ErrorReply(e)) if e.name == ErrorName::NameHasNoOwner => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        };
+        let queue = reply.body.parser().get::<Vec<&str>>()?;
+        // the reply is addressed to the unique name of this connection
+        let unique_name = reply.dynheader.destination.as_deref();
+        Ok(queue.iter().position(|queued| Some(*queued) == unique_name))
+    }
+
+    pub fn conn(&self) -> &RpcConn {
+        self.con.borrow()
+    }
+
+    pub fn conn_mut(&mut self) -> &mut RpcConn {
+        self.con.borrow_mut()
+    }
+
+    /// Release the name and end following the signals. Dropping the NameOwner does the same but ignores errors and
+    /// waits at most a second for each call to the bus.
+    pub fn release(mut self) -> Result<ReleaseNameReply, CallError> {
+        let timeout = self.timeout;
+        self.release_name(timeout)
+    }
+
+    /// The name is released even if ending a subscription fails, the first error is returned after that
+    fn release_name(&mut self, timeout: Timeout) -> Result<ReleaseNameReply, CallError> {
+        self.released = true;
+        let con = self.con.borrow_mut();
+        let mut unsubscribed = Ok(());
+        for id in self.subscriptions.drain(..) {
+            let res = con.unsubscribe(id, timeout);
+            if unsubscribed.is_ok() {
+                unsubscribed = res;
+            }
+        }
+        self.state.lock().unwrap().status = NameStatus::NotOwner;
+        let reply = BusProxy::with_timeout(con, timeout).release_name(&self.name);
+        unsubscribed?;
+        reply
+    }
+}
+
+impl<C: BorrowMut<RpcConn>> Drop for NameOwner<C> {
+    fn drop(&mut self) {
+        if !self.released {
+            let _ = self.release_name(crate::connection::drop_timeout(self.timeout));
+        }
+    }
+}
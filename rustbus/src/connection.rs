@@ -1,13 +1,24 @@
 //! Different connection types you will need to talk to the bus
 //!
-//! * ll_conn is the basic send and recive primitives used to build the other connection types
+//! * address parses the dbus address strings that describe where a bus can be reached
+//! * async_conn has async versions of the DuplexConn and RpcConn for tokio (needs the `tokio` feature)
+//! * ll_conn is the basic send and recive primitives used to build the other connection types. With the `mio` feature
+//!   the connections can be registered with a mio poller.
 //! * dispatch_conn is meant for services that need to dispatch calls to different handlers
+//! * listener accepts connections from peers that want to talk to you directly without a bus
 //! * rpc_conn is meant for clients that make calls to services on the bus
 
+pub mod address;
+#[cfg(feature = "tokio")]
+pub mod async_conn;
 pub mod dispatch_conn;
+pub mod listener;
 pub mod ll_conn;
 pub mod rpc_conn;
 
+pub use address::DBusAddress;
+use address::{TcpAddress, TcpFamily, Transport, UnixAddress};
+
 use std::path::PathBuf;
 use std::time;
 
@@ -35,8 +46,12 @@ pub enum Error {
     MarshalError(crate::wire::errors::MarshalError),
     #[error("Authentication failed")]
     AuthFailed,
+    #[error("An error occured while authenticating: {0}")]
+    AuthError(crate::auth::AuthError),
     #[error("Negotiating unix fd usage failed")]
     UnixFdNegotiationFailed,
+    #[error("Unix fds can not be passed over this connection")]
+    UnixFdNotSupported,
     #[error("The name is already taken")]
     NameTaken,
     #[error("The address type {0} is not yet supportd by this lib")]
@@ -45,8 +60,12 @@ pub enum Error {
     PathDoesNotExist(String),
     #[error("Address not found")]
     NoAddressFound,
+    #[error("The address is invalid: {0}")]
+    InvalidAddress(String),
     #[error("Unexpected message type received")]
     UnexpectedMessageTypeReceived,
+    #[error("The call was answered with the error {0}")]
+    ErrorReply(crate::dbus_error::DBusError),
     #[error("Timeout occured")]
     TimedOut,
     #[error("Connection has been closed by the other side")]
@@ -59,6 +78,15 @@ impl std::convert::From<std::io::Error> for Error {
     }
 }
 
+impl std::convert::From<crate::auth::AuthError> for Error {
+    fn from(e: crate::auth::AuthError) -> Error {
+        match e {
+            crate::auth::AuthError::TimedOut => Error::TimedOut,
+            e => Error::AuthError(e),
+        }
+    }
+}
+
 impl std::convert::From<crate::wire::errors::UnmarshalError> for Error {
     fn from(e: crate::wire::errors::UnmarshalError) -> Error {
         Error::UnmarshalError(e)
@@ -79,51 +107,72 @@ impl std::convert::From<crate::wire::errors::MarshalError> for Error {
 
 type Result<T> = std::result::Result<T, Error>;
 
-fn parse_dbus_addr_str(addr: &str) -> Result<UnixAddr> {
-    let addr_parts: Vec<&str> = addr.split(',').collect();
-    let addr = addr_parts.get(0).unwrap_or(&addr);
-
-    if addr.starts_with("unix:path=") {
-        let ps = addr.trim_start_matches("unix:path=");
-        let p = PathBuf::from(&ps);
+/// Convenience function that returns the addresses of the session bus according to the env
+/// var $DBUS_SESSION_BUS_ADDRESS. If that is not set, the socket at $XDG_RUNTIME_DIR/bus is used if it exists.
+///
+/// The addresses should be tried in order, which is what `DuplexConn::connect_to_bus` does.
+pub fn get_session_bus_path() -> Result<Vec<DBusAddress>> {
+    if let Ok(envvar) = std::env::var("DBUS_SESSION_BUS_ADDRESS") {
+        DBusAddress::parse_list(&envvar)
+    } else if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        let p = PathBuf::from(runtime_dir).join("bus");
         if p.exists() {
-            Ok(UnixAddr::new(&p)?)
+            Ok(vec![DBusAddress {
+                transport: Transport::Unix(UnixAddress::Path(p)),
+                guid: None,
+            }])
         } else {
-            Err(Error::PathDoesNotExist(ps.to_owned()))
-        }
-    } else if addr.starts_with("unix:abstract=") {
-        #[cfg(not(target_os = "linux"))]
-        {
-            Err(Error::AddressTypeNotSupported(addr.to_owned()))
-        }
-        #[cfg(target_os = "linux")]
-        {
-            let ps = addr.trim_start_matches("unix:abstract=");
-            Ok(UnixAddr::new_abstract(ps.as_bytes())?)
+            Err(Error::NoAddressFound)
         }
     } else {
-        Err(Error::AddressTypeNotSupported(addr.to_owned()))
+        Err(Error::NoAddressFound)
     }
 }
 
-/// Convenience function that returns the UnixAddr of the session bus according to the env
-/// var $DBUS_SESSION_BUS_ADDRESS.
-pub fn get_session_bus_path() -> Result<UnixAddr> {
-    if let Ok(envvar) = std::env::var("DBUS_SESSION_BUS_ADDRESS") {
-        parse_dbus_addr_str(&envvar)
+/// Convenience function that returns the addresses of the system bus according to the env
+/// var $DBUS_SYSTEM_BUS_ADDRESS, defaulting to /run/dbus/system_bus_socket
+pub fn get_system_bus_path() -> Result<Vec<DBusAddress>> {
+    if let Ok(envvar) = std::env::var("DBUS_SYSTEM_BUS_ADDRESS") {
+        DBusAddress::parse_list(&envvar)
     } else {
-        Err(Error::NoAddressFound)
+        Ok(vec![DBusAddress {
+            transport: Transport::Unix(UnixAddress::Path(PathBuf::from(
+                "/run/dbus/system_bus_socket",
+            ))),
+            guid: None,
+        }])
     }
 }
 
-/// Convenience function that returns a path to the system bus at /run/dbus/systemd_bus_socket
-pub fn get_system_bus_path() -> Result<UnixAddr> {
-    let ps = "/run/dbus/system_bus_socket";
-    let p = PathBuf::from(&ps);
-    if p.exists() {
-        Ok(UnixAddr::new(&p)?)
-    } else {
-        Err(Error::PathDoesNotExist(ps.to_owned()))
+/// Resolve a unix address to a socket address that can be connected to
+pub(crate) fn unix_sockaddr(addr: &UnixAddress) -> Result<UnixAddr> {
+    match addr {
+        UnixAddress::Path(p) => {
+            if p.exists() {
+                Ok(UnixAddr::new(p)?)
+            } else {
+                Err(Error::PathDoesNotExist(p.to_string_lossy().into_owned()))
+            }
+        }
+        UnixAddress::Abstract(name) => {
+            #[cfg(not(target_os = "linux"))]
+            {
+                let _ = name;
+                Err(Error::AddressTypeNotSupported("unix:abstract".to_owned()))
+            }
+            #[cfg(target_os = "linux")]
+            {
+                Ok(UnixAddr::new_abstract(name)?)
+            }
+        }
+        UnixAddress::Runtime => match std::env::var_os("XDG_RUNTIME_DIR") {
+            Some(runtime_dir) => {
+                unix_sockaddr(&UnixAddress::Path(PathBuf::from(runtime_dir).join("bus")))
+            }
+            None => Err(Error::NoAddressFound),
+        },
+        UnixAddress::Dir(_) => Err(Error::AddressTypeNotSupported("unix:dir".to_owned())),
+        UnixAddress::TmpDir(_) => Err(Error::AddressTypeNotSupported("unix:tmpdir".to_owned())),
     }
 }
 
@@ -141,44 +190,94 @@ pub(crate) fn calc_timeout_left(start_time: &time::Instant, timeout: Timeout) ->
     }
 }
 
+/// Drop impls that talk to the bus wait at most this long, so a stalled bus can not block dropping a value forever
+const DROP_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+/// The timeout to use for calls in a Drop impl: `timeout`, but not longer than `DROP_TIMEOUT`
+pub(crate) fn drop_timeout(timeout: Timeout) -> Timeout {
+    match timeout {
+        Timeout::Duration(timeout) if timeout < DROP_TIMEOUT => Timeout::Duration(timeout),
+        Timeout::Nonblock => Timeout::Nonblock,
+        _ => Timeout::Duration(DROP_TIMEOUT),
+    }
+}
+
+/// Connect to the first socket address the host and port of a tcp address resolve to, honoring the requested family
+pub(crate) fn tcp_connect(addr: &TcpAddress, timeout: Timeout) -> Result<std::net::TcpStream> {
+    use std::net::ToSocketAddrs;
+
+    let host = addr.host.as_deref().unwrap_or("localhost");
+    let port = match addr.port {
+        Some(port) if port != 0 => port,
+        _ => {
+            return Err(Error::InvalidAddress(
+                "tcp addresses need a non-zero port to connect to".to_owned(),
+            ))
+        }
+    };
+
+    let mut last_err = Error::NoAddressFound;
+    for sockaddr in (host, port).to_socket_addrs()? {
+        let family_matches = match addr.family {
+            Some(TcpFamily::Ipv4) => sockaddr.is_ipv4(),
+            Some(TcpFamily::Ipv6) => sockaddr.is_ipv6(),
+            None => true,
+        };
+        if !family_matches {
+            continue;
+        }
+        let stream = match timeout {
+            Timeout::Duration(d) => std::net::TcpStream::connect_timeout(&sockaddr, d),
+            _ => std::net::TcpStream::connect(sockaddr),
+        };
+        match stream {
+            Ok(stream) => {
+                // dbus messages are often small and latency sensitive
+                stream.set_nodelay(true)?;
+                return Ok(stream);
+            }
+            Err(e) => last_err = e.into(),
+        }
+    }
+    Err(last_err)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use nix::sys::socket::UnixAddr;
 
-    #[cfg(target_os = "linux")]
     #[test]
-    fn test_get_session_bus_path() {
-        let path = "unix:path=/tmp/dbus-test-not-exist";
-        let path_with_keys = "unix:path=/tmp/dbus-test-not-exist,guid=aaaaa,test=bbbbbbbb";
-        let abstract_path = "unix:abstract=/tmp/dbus-test";
-        let abstract_path_with_keys = "unix:abstract=/tmp/dbus-test,guid=aaaaaaaa,test=bbbbbbbb";
-
-        let addr = parse_dbus_addr_str(path);
-        assert!(addr.is_err());
-
-        let addr = parse_dbus_addr_str(path_with_keys);
-        match addr {
-            Err(Error::PathDoesNotExist(path)) => {
-                // The assertion here ensures that DBus session keys are
-                // stripped from the session bus' determined path.
-                assert_eq!("/tmp/dbus-test-not-exist", path);
-            }
-            _ => assert!(false, "expected Error::PathDoesNotExist"),
+    fn test_unix_sockaddr() {
+        let path = DBusAddress::parse(
+            "unix:path=/tmp/dbus-test-not-exist,guid=0123456789abcdef0123456789abcdef,test=bbbbbbbb",
+        )
+        .unwrap();
+        match path.transport {
+            Transport::Unix(unix) => match unix_sockaddr(&unix) {
+                Err(Error::PathDoesNotExist(path)) => {
+                    assert_eq!("/tmp/dbus-test-not-exist", path);
+                }
+                _ => panic!("expected Error::PathDoesNotExist"),
+            },
+            _ => panic!("expected a unix address"),
         }
 
-        let addr = parse_dbus_addr_str(abstract_path).unwrap();
-        assert_eq!(addr, UnixAddr::new_abstract(b"/tmp/dbus-test").unwrap());
-
-        let addr = parse_dbus_addr_str(abstract_path_with_keys).unwrap();
-        assert_eq!(addr, UnixAddr::new_abstract(b"/tmp/dbus-test").unwrap());
+        let addr = unix_sockaddr(&UnixAddress::Dir("/tmp".into()));
+        assert!(matches!(addr, Err(Error::AddressTypeNotSupported(_))));
     }
-    #[cfg(not(target_os = "linux"))]
-    #[test]
-    fn test_get_session_bus_path() {
-        let path = "unix:path=/tmp/dbus-test-not-exist";
 
-        let addr = parse_dbus_addr_str(path);
-        assert!(addr.is_err());
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_unix_sockaddr_abstract() {
+        let abstract_path_with_keys =
+            DBusAddress::parse("unix:abstract=/tmp/dbus-test,test=bbbbbbbb").unwrap();
+        match abstract_path_with_keys.transport {
+            Transport::Unix(unix) => {
+                let addr = unix_sockaddr(&unix).unwrap();
+                assert_eq!(addr, UnixAddr::new_abstract(b"/tmp/dbus-test").unwrap());
+            }
+            _ => panic!("expected a unix address"),
+        }
     }
 }
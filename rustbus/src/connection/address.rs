@@ -0,0 +1,477 @@
+//! Parsing and formatting of dbus server addresses
+//!
+//! A dbus address string is a `;` separated list of addresses. Each address is a transport name followed by `:`
+//! and a `,` separated list of `key=value` pairs. Values may contain `%xx` escapes for arbitrary bytes.
+//!
+//! e.g. `unix:path=/run/user/1000/bus,guid=0123456789abcdef0123456789abcdef;tcp:host=localhost,port=4242`
+
+use super::Error;
+use super::Result;
+
+use std::ffi::OsString;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::PathBuf;
+
+/// The different flavours of unix socket addresses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnixAddress {
+    /// A socket at a path in the filesystem (`unix:path=...`)
+    Path(PathBuf),
+    /// A socket in the abstract namespace (`unix:abstract=...`). Only supported on linux.
+    Abstract(Vec<u8>),
+    /// Only meaningful for listening. The server creates a socket with a random name in this directory (`unix:dir=...`)
+    Dir(PathBuf),
+    /// Only meaningful for listening. Like `Dir` but may use the abstract namespace (`unix:tmpdir=...`)
+    TmpDir(PathBuf),
+    /// The socket at `$XDG_RUNTIME_DIR/bus` (`unix:runtime=yes`)
+    Runtime,
+}
+
+/// Address family that should be used for tcp transports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcpFamily {
+    Ipv4,
+    Ipv6,
+}
+
+/// The parameters of a `tcp:` or `nonce-tcp:` address
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TcpAddress {
+    pub host: Option<String>,
+    pub bind: Option<String>,
+    pub port: Option<u16>,
+    pub family: Option<TcpFamily>,
+}
+
+/// The transport part of an address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transport {
+    Unix(UnixAddress),
+    Tcp(TcpAddress),
+    NonceTcp {
+        tcp: TcpAddress,
+        noncefile: Option<PathBuf>,
+    },
+    /// Any transport this lib does not know about (e.g. `launchd:` or `autolaunch:`). The key/value pairs
+    /// are kept (already unescaped) so the caller can deal with them.
+    Other {
+        name: String,
+        params: Vec<(String, Vec<u8>)>,
+    },
+}
+
+/// One address out of a dbus address string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DBusAddress {
+    pub transport: Transport,
+    /// The guid of the server, if the address specified one
+    pub guid: Option<String>,
+}
+
+impl DBusAddress {
+    /// Parse a complete address string which may contain multiple `;` separated addresses.
+    /// The order of the addresses is kept, connecting should try them in that order.
+    pub fn parse_list(addrs: &str) -> Result<Vec<DBusAddress>> {
+        addrs
+            .split(';')
+            .filter(|addr| !addr.is_empty())
+            .map(DBusAddress::parse)
+            .collect()
+    }
+
+    /// Parse a single address (it must not contain a `;`)
+    pub fn parse(addr: &str) -> Result<DBusAddress> {
+        let (name, params) = match addr.find(':') {
+            Some(idx) => (&addr[..idx], &addr[idx + 1..]),
+            None => return Err(invalid(addr, "missing ':' after the transport name")),
+        };
+        if name.is_empty() {
+            return Err(invalid(addr, "empty transport name"));
+        }
+
+        let mut pairs: Vec<(String, Vec<u8>)> = Vec::new();
+        for pair in params.split(',').filter(|pair| !pair.is_empty()) {
+            let (key, value) = match pair.find('=') {
+                Some(idx) => (&pair[..idx], &pair[idx + 1..]),
+                None => return Err(invalid(addr, "key without a value")),
+            };
+            if key.is_empty() {
+                return Err(invalid(addr, "empty key"));
+            }
+            if pairs.iter().any(|(k, _)| k == key) {
+                return Err(invalid(addr, "duplicated key"));
+            }
+            let value = unescape(value).ok_or_else(|| invalid(addr, "invalid escape sequence"))?;
+            pairs.push((key.to_owned(), value));
+        }
+
+        let guid = match take_param(&mut pairs, "guid") {
+            Some(guid) => {
+                let guid =
+                    String::from_utf8(guid).map_err(|_| invalid(addr, "guid is not valid utf8"))?;
+                if guid.len() != 32 || !guid.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return Err(invalid(addr, "guid must be 32 hex digits"));
+                }
+                Some(guid)
+            }
+            None => None,
+        };
+
+        let transport = match name {
+            "unix" => Transport::Unix(parse_unix(addr, &mut pairs)?),
+            "tcp" => Transport::Tcp(parse_tcp(addr, &mut pairs)?),
+            "nonce-tcp" => {
+                let noncefile = take_param(&mut pairs, "noncefile")
+                    .map(|f| PathBuf::from(OsString::from_vec(f)));
+                Transport::NonceTcp {
+                    tcp: parse_tcp(addr, &mut pairs)?,
+                    noncefile,
+                }
+            }
+            other => Transport::Other {
+                name: other.to_owned(),
+                params: pairs,
+            },
+        };
+
+        Ok(DBusAddress { transport, guid })
+    }
+}
+
+impl std::str::FromStr for DBusAddress {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        DBusAddress::parse(s)
+    }
+}
+
+fn invalid(addr: &str, reason: &str) -> Error {
+    Error::InvalidAddress(format!("{}: {}", addr, reason))
+}
+
+fn take_param(pairs: &mut Vec<(String, Vec<u8>)>, key: &str) -> Option<Vec<u8>> {
+    let idx = pairs.iter().position(|(k, _)| k == key)?;
+    Some(pairs.remove(idx).1)
+}
+
+fn take_string_param(
+    addr: &str,
+    pairs: &mut Vec<(String, Vec<u8>)>,
+    key: &str,
+) -> Result<Option<String>> {
+    match take_param(pairs, key) {
+        Some(value) => String::from_utf8(value)
+            .map(Some)
+            .map_err(|_| invalid(addr, "value is not valid utf8")),
+        None => Ok(None),
+    }
+}
+
+fn parse_unix(addr: &str, pairs: &mut Vec<(String, Vec<u8>)>) -> Result<UnixAddress> {
+    let mut kinds = Vec::new();
+    if let Some(path) = take_param(pairs, "path") {
+        kinds.push(UnixAddress::Path(PathBuf::from(OsString::from_vec(path))));
+    }
+    if let Some(name) = take_param(pairs, "abstract") {
+        kinds.push(UnixAddress::Abstract(name));
+    }
+    if let Some(dir) = take_param(pairs, "dir") {
+        kinds.push(UnixAddress::Dir(PathBuf::from(OsString::from_vec(dir))));
+    }
+    if let Some(dir) = take_param(pairs, "tmpdir") {
+        kinds.push(UnixAddress::TmpDir(PathBuf::from(OsString::from_vec(dir))));
+    }
+    if let Some(runtime) = take_param(pairs, "runtime") {
+        if runtime != b"yes" {
+            return Err(invalid(addr, "the only valid value for runtime is 'yes'"));
+        }
+        kinds.push(UnixAddress::Runtime);
+    }
+
+    if kinds.len() > 1 {
+        return Err(invalid(
+            addr,
+            "only one of path, abstract, dir, tmpdir or runtime may be specified",
+        ));
+    }
+    kinds.pop().ok_or_else(|| {
+        invalid(
+            addr,
+            "one of path, abstract, dir, tmpdir or runtime is required",
+        )
+    })
+}
+
+fn parse_tcp(addr: &str, pairs: &mut Vec<(String, Vec<u8>)>) -> Result<TcpAddress> {
+    let host = take_string_param(addr, pairs, "host")?;
+    let bind = take_string_param(addr, pairs, "bind")?;
+    let port = match take_string_param(addr, pairs, "port")? {
+        Some(port) => Some(
+            port.parse::<u16>()
+                .map_err(|_| invalid(addr, "port is not a valid port number"))?,
+        ),
+        None => None,
+    };
+    let family = match take_string_param(addr, pairs, "family")?.as_deref() {
+        Some("ipv4") => Some(TcpFamily::Ipv4),
+        Some("ipv6") => Some(TcpFamily::Ipv6),
+        Some(_) => return Err(invalid(addr, "family must be either ipv4 or ipv6")),
+        None => None,
+    };
+    Ok(TcpAddress {
+        host,
+        bind,
+        port,
+        family,
+    })
+}
+
+fn unescape(value: &str) -> Option<Vec<u8>> {
+    let bytes = value.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'%' {
+            let hex = bytes.get(idx + 1..idx + 3)?;
+            // from_str_radix would also accept a sign
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            let hex = std::str::from_utf8(hex).ok()?;
+            unescaped.push(u8::from_str_radix(hex, 16).ok()?);
+            idx += 3;
+        } else {
+            unescaped.push(bytes[idx]);
+            idx += 1;
+        }
+    }
+    Some(unescaped)
+}
+
+fn is_optionally_escaped(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'/' | b'.' | b'\\' | b'*')
+}
+
+fn write_escaped(f: &mut std::fmt::Formatter<'_>, value: &[u8]) -> std::fmt::Result {
+    for b in value {
+        if is_optionally_escaped(*b) {
+            write!(f, "{}", *b as char)?;
+        } else {
+            write!(f, "%{:02x}", b)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_tcp(
+    f: &mut std::fmt::Formatter<'_>,
+    tcp: &TcpAddress,
+) -> std::result::Result<bool, std::fmt::Error> {
+    let mut first = true;
+    let mut write_pair = |f: &mut std::fmt::Formatter<'_>, key: &str, value: &[u8]| {
+        if !first {
+            write!(f, ",")?;
+        }
+        first = false;
+        write!(f, "{}=", key)?;
+        write_escaped(f, value)
+    };
+    if let Some(host) = &tcp.host {
+        write_pair(f, "host", host.as_bytes())?;
+    }
+    if let Some(bind) = &tcp.bind {
+        write_pair(f, "bind", bind.as_bytes())?;
+    }
+    if let Some(port) = &tcp.port {
+        write_pair(f, "port", port.to_string().as_bytes())?;
+    }
+    match tcp.family {
+        Some(TcpFamily::Ipv4) => write_pair(f, "family", b"ipv4")?,
+        Some(TcpFamily::Ipv6) => write_pair(f, "family", b"ipv6")?,
+        None => {}
+    }
+    Ok(!first)
+}
+
+impl std::fmt::Display for DBusAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let has_params = match &self.transport {
+            Transport::Unix(unix) => {
+                write!(f, "unix:")?;
+                match unix {
+                    UnixAddress::Path(p) => {
+                        write!(f, "path=")?;
+                        write_escaped(f, p.as_os_str().as_bytes())?;
+                    }
+                    UnixAddress::Abstract(name) => {
+                        write!(f, "abstract=")?;
+                        write_escaped(f, name)?;
+                    }
+                    UnixAddress::Dir(p) => {
+                        write!(f, "dir=")?;
+                        write_escaped(f, p.as_os_str().as_bytes())?;
+                    }
+                    UnixAddress::TmpDir(p) => {
+                        write!(f, "tmpdir=")?;
+                        write_escaped(f, p.as_os_str().as_bytes())?;
+                    }
+                    UnixAddress::Runtime => write!(f, "runtime=yes")?,
+                }
+                true
+            }
+            Transport::Tcp(tcp) => {
+                write!(f, "tcp:")?;
+                write_tcp(f, tcp)?
+            }
+            Transport::NonceTcp { tcp, noncefile } => {
+                write!(f, "nonce-tcp:")?;
+                let mut has_params = write_tcp(f, tcp)?;
+                if let Some(noncefile) = noncefile {
+                    if has_params {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "noncefile=")?;
+                    write_escaped(f, noncefile.as_os_str().as_bytes())?;
+                    has_params = true;
+                }
+                has_params
+            }
+            Transport::Other { name, params } => {
+                write!(f, "{}:", name)?;
+                for (idx, (key, value)) in params.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}=", key)?;
+                    write_escaped(f, value)?;
+                }
+                !params.is_empty()
+            }
+        };
+        if let Some(guid) = &self.guid {
+            if has_params {
+                write!(f, ",")?;
+            }
+            write!(f, "guid={}", guid)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_unix_addresses() {
+        let addr = DBusAddress::parse(
+            "unix:path=/tmp/dbus-test-not-exist,guid=0123456789abcdef0123456789abcdef,test=bbbbbbbb",
+        )
+        .unwrap();
+        // DBus session keys are stripped from the path.
+        assert_eq!(
+            addr.transport,
+            Transport::Unix(UnixAddress::Path("/tmp/dbus-test-not-exist".into()))
+        );
+        assert_eq!(
+            addr.guid.as_deref(),
+            Some("0123456789abcdef0123456789abcdef")
+        );
+
+        let addr = DBusAddress::parse("unix:abstract=/tmp/dbus-test").unwrap();
+        assert_eq!(
+            addr.transport,
+            Transport::Unix(UnixAddress::Abstract(b"/tmp/dbus-test".to_vec()))
+        );
+
+        let addr = DBusAddress::parse("unix:runtime=yes").unwrap();
+        assert_eq!(addr.transport, Transport::Unix(UnixAddress::Runtime));
+        let addr = DBusAddress::parse("unix:tmpdir=/tmp").unwrap();
+        assert_eq!(
+            addr.transport,
+            Transport::Unix(UnixAddress::TmpDir("/tmp".into()))
+        );
+        let addr = DBusAddress::parse("unix:dir=/tmp").unwrap();
+        assert_eq!(
+            addr.transport,
+            Transport::Unix(UnixAddress::Dir("/tmp".into()))
+        );
+
+        assert!(DBusAddress::parse("unix:runtime=no").is_err());
+        assert!(DBusAddress::parse("unix:path=/a,abstract=b").is_err());
+        assert!(DBusAddress::parse("unix:guid=0123456789abcdef0123456789abcdef").is_err());
+        assert!(DBusAddress::parse("unix:path=/a,guid=nothex").is_err());
+        assert!(DBusAddress::parse("unix:path=/a,path=/b").is_err());
+        assert!(DBusAddress::parse("unix").is_err());
+        assert!(DBusAddress::parse(":path=/a").is_err());
+    }
+
+    #[test]
+    fn parse_escapes() {
+        let addr = DBusAddress::parse("unix:path=/tmp/with%20space%2cand%3bmore").unwrap();
+        assert_eq!(
+            addr.transport,
+            Transport::Unix(UnixAddress::Path("/tmp/with space,and;more".into()))
+        );
+        assert_eq!(addr.to_string(), "unix:path=/tmp/with%20space%2cand%3bmore");
+
+        let addr = DBusAddress::parse("unix:abstract=%00%ff").unwrap();
+        assert_eq!(
+            addr.transport,
+            Transport::Unix(UnixAddress::Abstract(vec![0, 0xff]))
+        );
+
+        assert!(DBusAddress::parse("unix:path=/tmp/%2").is_err());
+        assert!(DBusAddress::parse("unix:path=/tmp/%zz").is_err());
+        assert!(DBusAddress::parse("unix:path=/tmp/%+f").is_err());
+    }
+
+    #[test]
+    fn parse_lists_and_other_transports() {
+        let addrs = DBusAddress::parse_list(
+            "unix:path=/a;tcp:host=localhost,port=4242,family=ipv4;nonce-tcp:host=127.0.0.1,port=1,noncefile=/tmp/nonce;autolaunch:scope=foo;",
+        )
+        .unwrap();
+        assert_eq!(addrs.len(), 4);
+        assert_eq!(
+            addrs[0].transport,
+            Transport::Unix(UnixAddress::Path("/a".into()))
+        );
+        assert_eq!(
+            addrs[1].transport,
+            Transport::Tcp(TcpAddress {
+                host: Some("localhost".into()),
+                bind: None,
+                port: Some(4242),
+                family: Some(TcpFamily::Ipv4),
+            })
+        );
+        assert_eq!(
+            addrs[2].transport,
+            Transport::NonceTcp {
+                tcp: TcpAddress {
+                    host: Some("127.0.0.1".into()),
+                    bind: None,
+                    port: Some(1),
+                    family: None,
+                },
+                noncefile: Some("/tmp/nonce".into()),
+            }
+        );
+        assert_eq!(
+            addrs[3].transport,
+            Transport::Other {
+                name: "autolaunch".into(),
+                params: vec![("scope".into(), b"foo".to_vec())],
+            }
+        );
+
+        assert!(DBusAddress::parse("tcp:port=notaport").is_err());
+        assert!(DBusAddress::parse("tcp:family=ipx").is_err());
+
+        // formatting and parsing again yields the same address
+        for addr in addrs {
+            assert_eq!(DBusAddress::parse(&addr.to_string()).unwrap(), addr);
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! Async versions of the DuplexConn and RpcConn for use with tokio. Only available with the `tokio` feature.
+//!
+//! The io is done with the same machinery as the blocking connections, just with `Timeout::Nonblock`. Waiting for the
+//! socket to become readable/writable is left to the tokio reactor.
+//!
+//! ```rust,no_run
+//! use rustbus::connection::async_conn::AsyncRpcConn;
+//! use rustbus::MessageBuilder;
+//!
+//! async fn call() -> Result<(), rustbus::connection::Error> {
+//!     let mut con = AsyncRpcConn::session_conn().await?;
+//!     let call = MessageBuilder::new()
+//!         .call("ListNames")
+//!         .with_interface("org.freedesktop.DBus")
+//!         .on("/org/freedesktop/DBus")
+//!         .at("org.freedesktop.DBus")
+//!         .build();
+//!     let serial = con.send_message(&call).await?;
+//!     let reply = con.wait_response(serial).await?;
+//!     println!("{:?}", reply.body.parser().get::<Vec<String>>()?);
+//!     Ok(())
+//! }
+//! ```
+
+use super::ll_conn::{DuplexConn, RecvConn, SendConn, SendMessageContext};
+use super::*;
+use crate::message_builder::{MarshalledMessage, MessageType};
+
+use std::collections::{HashMap, VecDeque};
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+
+/// The sending half of an AsyncDuplexConn
+pub struct AsyncSendConn {
+    // declared before the conn so it is deregistered before the fd is closed
+    fd: AsyncFd<RawFd>,
+    conn: SendConn,
+}
+
+/// The receiving half of an AsyncDuplexConn
+pub struct AsyncRecvConn {
+    // declared before the conn so it is deregistered before the fd is closed
+    fd: AsyncFd<RawFd>,
+    conn: RecvConn,
+}
+
+/// Async version of the DuplexConn. The send and recv halves can be used independently, e.g. from different tasks.
+pub struct AsyncDuplexConn {
+    pub send: AsyncSendConn,
+    pub recv: AsyncRecvConn,
+    guid: String,
+}
+
+impl AsyncSendConn {
+    /// Wrap a SendConn. Must be called from within a tokio runtime.
+    pub fn new(conn: SendConn) -> Result<Self> {
+        Ok(AsyncSendConn {
+            fd: AsyncFd::with_interest(conn.as_raw_fd(), Interest::WRITABLE)?,
+            conn,
+        })
+    }
+
+    pub fn conn(&self) -> &SendConn {
+        &self.conn
+    }
+
+    /// get the next new serial
+    pub fn alloc_serial(&mut self) -> u32 {
+        self.conn.alloc_serial()
+    }
+
+    /// Send a message and wait until all bytes have been written. Returns the serial of the message to match the response.
+    ///
+    /// If this future is dropped after the message was partially written the connection is left in an ill defined state
+    /// and should not be used anymore.
+    pub async fn send_message(&mut self, msg: &MarshalledMessage) -> Result<u32> {
+        let ctx = self.conn.send_message(msg)?;
+        let serial = ctx.serial();
+        // only the progress is kept across the awaits so dropping this future never panics
+        let mut progress = ctx.into_progress();
+
+        loop {
+            let mut guard = self.fd.writable().await?;
+
+            let mut ctx = SendMessageContext::resume(&mut self.conn, msg, progress);
+            let res = ctx.write_once(Timeout::Nonblock);
+            let done = ctx.all_bytes_written();
+            progress = ctx.into_progress();
+
+            match res {
+                Ok(_) if done => return Ok(serial),
+                Ok(_) => {}
+                Err(Error::TimedOut) => guard.clear_ready(),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl AsyncRecvConn {
+    /// Wrap a RecvConn. Must be called from within a tokio runtime.
+    pub fn new(conn: RecvConn) -> Result<Self> {
+        Ok(AsyncRecvConn {
+            fd: AsyncFd::with_interest(conn.as_raw_fd(), Interest::READABLE)?,
+            conn,
+        })
+    }
+
+    pub fn conn(&self) -> &RecvConn {
+        &self.conn
+    }
+
+    /// Wait until the next message has been read from the connection.
+    ///
+    /// This is cancel safe. Partially read messages are kept in the buffer and finished by the next call.
+    pub async fn get_next_message(&mut self) -> Result<MarshalledMessage> {
+        loop {
+            if self.conn.buffer_contains_whole_message()? {
+                return self.conn.get_next_message(Timeout::Nonblock);
+            }
+
+            let mut guard = self.fd.readable().await?;
+            match self.conn.read_once(Timeout::Nonblock) {
+                Ok(()) => {}
+                Err(Error::TimedOut) => guard.clear_ready(),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl AsyncDuplexConn {
+    /// Wrap an established connection. Must be called from within a tokio runtime.
+    pub fn new(conn: DuplexConn) -> Result<Self> {
+        let guid = conn.server_guid().to_owned();
+        let DuplexConn { send, recv, .. } = conn;
+        Ok(AsyncDuplexConn {
+            send: AsyncSendConn::new(send)?,
+            recv: AsyncRecvConn::new(recv)?,
+            guid,
+        })
+    }
+
+    /// Connect to the first of the addresses that can be connected to, like `DuplexConn::connect_to_bus`.
+    ///
+    /// Connecting and authenticating is done on the blocking thread pool of the runtime.
+    ///
+    /// Remember to send the mandatory hello message before doing anything else with the connection!
+    /// You can use the `send_hello` function for this.
+    pub async fn connect_to_bus<A: AsRef<[DBusAddress]>>(
+        addrs: A,
+        with_unix_fd: bool,
+        timeout: Timeout,
+    ) -> Result<Self> {
+        let addrs = addrs.as_ref().to_vec();
+        let conn = tokio::task::spawn_blocking(move || {
+            DuplexConn::connect_to_bus(addrs, with_unix_fd, timeout)
+        })
+        .await
+        .map_err(|e| Error::IoError(std::io::Error::new(std::io::ErrorKind::Other, e)))??;
+        Self::new(conn)
+    }
+
+    /// The guid of the server, as sent during the authentication
+    pub fn server_guid(&self) -> &str {
+        &self.guid
+    }
+
+    /// Sends the obligatory hello message and returns the unique id the daemon assigned this connection
+    pub async fn send_hello(&mut self) -> Result<String> {
+        let hello = crate::standard_messages::hello();
+        let serial = self.send.send_message(&hello).await?;
+        let resp = self.recv.get_next_message().await?;
+        if resp.dynheader.response_serial != Some(serial) {
+            return Err(Error::AuthFailed);
+        }
+        let unique_name = resp.body.parser().get::<String>()?;
+        Ok(unique_name)
+    }
+}
+
+/// Async version of the RpcConn. Messages are sorted into queues for calls, signals and responses while waiting for
+/// the one that was asked for.
+pub struct AsyncRpcConn {
+    signals: VecDeque<MarshalledMessage>,
+    calls: VecDeque<MarshalledMessage>,
+    responses: HashMap<u32, MarshalledMessage>,
+    conn: AsyncDuplexConn,
+    filter: rpc_conn::MessageFilter,
+}
+
+impl AsyncRpcConn {
+    pub fn new(conn: AsyncDuplexConn) -> Self {
+        AsyncRpcConn {
+            signals: VecDeque::new(),
+            calls: VecDeque::new(),
+            responses: HashMap::new(),
+            conn,
+            filter: Box::new(|_| true),
+        }
+    }
+    pub fn conn(&self) -> &AsyncDuplexConn {
+        &self.conn
+    }
+    pub fn conn_mut(&mut self) -> &mut AsyncDuplexConn {
+        &mut self.conn
+    }
+
+    /// get the next new serial
+    pub fn alloc_serial(&mut self) -> u32 {
+        self.conn.send.alloc_serial()
+    }
+
+    pub async fn session_conn() -> Result<Self> {
+        Self::connect_to_path(get_session_bus_path()?).await
+    }
+
+    pub async fn system_conn() -> Result<Self> {
+        Self::connect_to_path(get_system_bus_path()?).await
+    }
+
+    /// Connect to the bus and send the hello message
+    pub async fn connect_to_path<A: AsRef<[DBusAddress]>>(addrs: A) -> Result<Self> {
+        let mut conn = AsyncDuplexConn::connect_to_bus(addrs, true, Timeout::Infinite).await?;
+        conn.send_hello().await?;
+        Ok(Self::new(conn))
+    }
+
+    pub fn set_filter(&mut self, filter: rpc_conn::MessageFilter) {
+        self.filter = filter;
+    }
+
+    /// Send a message to the bus and wait until it has been written. Returns the serial of the message.
+    pub async fn send_message(&mut self, msg: &MarshalledMessage) -> Result<u32> {
+        self.conn.send.send_message(msg).await
+    }
+
+    /// Return a response if one is there but dont wait
+    pub fn try_get_response(&mut self, serial: u32) -> Option<MarshalledMessage> {
+        self.responses.remove(&serial)
+    }
+
+    /// Return a response if one is there or wait until it arrives
+    pub async fn wait_response(&mut self, serial: u32) -> Result<MarshalledMessage> {
+        loop {
+            if let Some(msg) = self.try_get_response(serial) {
+                return Ok(msg);
+            }
+            self.refill_once().await?;
+        }
+    }
+
+    /// Return a signal if one is there but dont wait
+    pub fn try_get_signal(&mut self) -> Option<MarshalledMessage> {
+        self.signals.pop_front()
+    }
+
+    /// Return a signal if one is there or wait until it arrives
+    pub async fn wait_signal(&mut self) -> Result<MarshalledMessage> {
+        loop {
+            if let Some(msg) = self.try_get_signal() {
+                return Ok(msg);
+            }
+            self.refill_once().await?;
+        }
+    }
+
+    /// Return a call if one is there but dont wait
+    pub fn try_get_call(&mut self) -> Option<MarshalledMessage> {
+        self.calls.pop_front()
+    }
+
+    /// Return a call if one is there or wait until it arrives
+    pub async fn wait_call(&mut self) -> Result<MarshalledMessage> {
+        loop {
+            if let Some(msg) = self.try_get_call() {
+                return Ok(msg);
+            }
+            self.refill_once().await?;
+        }
+    }
+
+    /// Wait for the next message and place it into the appropriate queue. The result tells you which
+    /// kind of message has been received.
+    ///
+    /// If a call is received that should be filtered out an error message is sent automatically
+    pub async fn refill_once(&mut self) -> Result<MessageType> {
+        let msg = self.conn.recv.get_next_message().await?;
+        let typ = msg.typ;
+
+        if self.filter.as_ref()(&msg) {
+            match msg.typ {
+                MessageType::Call => self.calls.push_back(msg),
+                MessageType::Invalid => return Err(Error::UnexpectedMessageTypeReceived),
+                MessageType::Error | MessageType::Reply => {
+                    self.responses
+                        .insert(msg.dynheader.response_serial.unwrap(), msg);
+                }
+                MessageType::Signal => self.signals.push_back(msg),
+            }
+        } else {
+            match msg.typ {
+                MessageType::Call => {
+                    let reply = crate::standard_messages::unknown_method(&msg.dynheader);
+                    self.conn.send.send_message(&reply).await?;
+                }
+                MessageType::Invalid => return Err(Error::UnexpectedMessageTypeReceived),
+                // just drop everything else
+                MessageType::Error | MessageType::Reply | MessageType::Signal => {}
+            }
+        }
+        Ok(typ)
+    }
+}
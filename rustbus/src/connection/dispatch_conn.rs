@@ -16,7 +16,15 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::Mutex;
 
-#[derive(Eq, PartialEq, Hash)]
+mod introspection;
+mod object;
+mod properties;
+use introspection::InterfaceRegistry;
+pub use object::DBusInterface;
+use properties::PropertyRegistry;
+pub use properties::{Property, PropertyAccess, PROPERTIES_INTERFACE};
+
+#[derive(Eq, PartialEq, Hash, PartialOrd, Ord)]
 enum PathPart {
     MatchExact(String),
     MatchAs(String),
@@ -27,9 +35,17 @@ impl PathPart {
     fn is_accept_all(&self) -> bool {
         matches!(self, PathPart::AcceptAll)
     }
+
+    fn specificity(&self) -> u8 {
+        match self {
+            PathPart::MatchExact(_) => 2,
+            PathPart::MatchAs(_) => 1,
+            PathPart::AcceptAll => 0,
+        }
+    }
 }
 
-#[derive(Eq, PartialEq, Hash)]
+#[derive(Eq, PartialEq, Hash, PartialOrd, Ord)]
 struct ObjectPathPattern(Vec<PathPart>);
 #[derive(Default)]
 pub struct Matches {
@@ -50,24 +66,66 @@ impl ObjectPathPattern {
         Self(parts.collect())
     }
 
+    /// The name of the child of `path` if objects matching this pattern are below it. Only parts that match exactly
+    /// can name a child.
+    fn child_of<'a>(&'a self, path: &str) -> Option<&'a str> {
+        let parts = if path == "/" {
+            vec![""]
+        } else {
+            path.split('/').collect::<Vec<_>>()
+        };
+        if self.0.len() <= parts.len() {
+            return None;
+        }
+        let prefix_matches = self
+            .0
+            .iter()
+            .zip(parts.iter())
+            .all(|(pattern, part)| match pattern {
+                PathPart::MatchExact(exact) => exact == part,
+                PathPart::MatchAs(_) | PathPart::AcceptAll => true,
+            });
+        match &self.0[parts.len()] {
+            PathPart::MatchExact(child) if prefix_matches && !child.is_empty() => Some(child),
+            _ => None,
+        }
+    }
+
+    /// Of several patterns matching the same path the greatest is the most specific one: compared part by part, exact
+    /// parts beat captures and captures beat wildcards. Patterns that are equally specific are ordered by their parts,
+    /// so the choice does not depend on the order they are stored in.
+    fn specificity(&self) -> (Vec<u8>, &Self) {
+        (self.0.iter().map(PathPart::specificity).collect(), self)
+    }
+
+    /// The most specific of the patterns that match the path, together with its value
+    fn most_specific<'a, V>(
+        patterns: impl Iterator<Item = (&'a ObjectPathPattern, V)>,
+        path: &str,
+    ) -> Option<(Matches, V)> {
+        let (pattern, value) = patterns
+            .filter(|(pattern, _)| pattern.matches(path).is_some())
+            .max_by(|(a, _), (b, _)| a.specificity().cmp(&b.specificity()))?;
+        Some((pattern.matches(path)?, value))
+    }
+
     pub fn matches(&self, query: &str) -> Option<Matches> {
         let parts = query.split('/').collect::<Vec<_>>();
         if parts.len() < self.0.len() {
             None
         } else {
-            parts
-                .into_iter()
-                .enumerate()
-                .fold(Some(Matches::default()), |matches, (idx, part)| {
+            parts.into_iter().enumerate().try_fold(
+                Matches::default(),
+                |mut matches, (idx, part)| {
                     if idx >= self.0.len() {
                         // The path is too long. If the last member of the patter is a wildcard
                         // this is acceptable.
                         if self.0.last().unwrap().is_accept_all() {
-                            matches
+                            Some(matches)
                         } else {
                             None
                         }
-                    } else if let Some(mut matches) = matches {
+                    } else {
                         match &self.0[idx] {
                             PathPart::AcceptAll => {
                                 // Nothing to do :)
@@ -85,10 +143,9 @@ impl ObjectPathPattern {
                                 Some(matches)
                             }
                         }
-                    } else {
-                        None
                     }
-                })
+                },
+            )
         }
     }
 }
@@ -164,6 +221,33 @@ impl<UserError: std::fmt::Debug> From<crate::connection::Error> for HandleError<
 pub struct HandleEnvironment<UserData, UserError: std::fmt::Debug> {
     pub conn: Arc<Mutex<SendConn>>,
     pub new_dispatches: PathMatcher<UserData, UserError>,
+    changed_properties: Vec<ChangedProperties>,
+}
+
+struct ChangedProperties {
+    path: String,
+    interface: String,
+    changed: Vec<String>,
+    invalidated: Vec<String>,
+}
+
+impl<UserData, UserError: std::fmt::Debug> HandleEnvironment<UserData, UserError> {
+    /// Emit a PropertiesChanged signal for the object once the handler returned successfully. The values of the
+    /// changed properties are read with the getters registered with `DispatchConn::add_property`.
+    pub fn properties_changed(
+        &mut self,
+        path: &str,
+        interface: &str,
+        changed: &[&str],
+        invalidated: &[&str],
+    ) {
+        self.changed_properties.push(ChangedProperties {
+            path: path.to_owned(),
+            interface: interface.to_owned(),
+            changed: changed.iter().map(|name| name.to_string()).collect(),
+            invalidated: invalidated.iter().map(|name| name.to_string()).collect(),
+        });
+    }
 }
 pub type HandleResult<UserError> =
     std::result::Result<Option<MarshalledMessage>, HandleError<UserError>>;
@@ -178,6 +262,8 @@ pub struct DispatchConn<HandlerCtx, HandlerError: std::fmt::Debug> {
     recv: RecvConn,
     send: Arc<Mutex<SendConn>>,
     objects: PathMatcher<HandlerCtx, HandlerError>,
+    properties: PropertyRegistry<HandlerCtx>,
+    interfaces: InterfaceRegistry,
     default_handler: Box<HandleFn<HandlerCtx, HandlerError>>,
     ctx: HandlerCtx,
 }
@@ -192,6 +278,8 @@ impl<UserData, UserError: std::fmt::Debug> DispatchConn<UserData, UserError> {
             recv: conn.recv,
             send: Arc::new(Mutex::new(conn.send)),
             objects: PathMatcher::new(),
+            properties: PropertyRegistry::new(),
+            interfaces: InterfaceRegistry::new(),
             default_handler,
             ctx,
         }
@@ -201,6 +289,84 @@ impl<UserData, UserError: std::fmt::Debug> DispatchConn<UserData, UserError> {
         self.objects.insert(path, handler);
     }
 
+    /// Declare an interface of the objects matching the path pattern. Calls to
+    /// `org.freedesktop.DBus.Introspectable.Introspect` are answered by `run` from the declared interfaces, together
+    /// with the registered properties and the children that are known from the path patterns of the handlers,
+    /// interfaces and properties. Declaring an interface with the same name again replaces it.
+    ///
+    /// The calls to the methods of the interface still have to be handled by a handler.
+    pub fn add_interface(&mut self, path: &str, interface: crate::introspect::Interface) {
+        self.interfaces.insert(path, interface);
+    }
+
+    /// Serve the object at the path pattern: its interface is declared for Introspect and a handler that owns the
+    /// object answers the method calls. Calls to other interfaces are answered with an UnknownMethod error. This
+    /// replaces the handler for the path pattern, objects with more than one interface need a handler of their own
+    /// that calls `DBusInterface::handle_call` for each of them.
+    pub fn add_object<O: DBusInterface + 'static>(&mut self, path: &str, mut object: O) {
+        self.add_interface(path, O::introspect());
+        self.add_handler(
+            path,
+            Box::new(move |_, _, msg, _| match object.handle_call(msg) {
+                Some(reply) => reply.map(Some).map_err(HandleError::Marshal),
+                None => Ok(Some(crate::standard_messages::unknown_method(
+                    &msg.dynheader,
+                ))),
+            }),
+        );
+    }
+
+    /// Register a property of an interface of the objects matching the path pattern. Calls to
+    /// `org.freedesktop.DBus.Properties` on objects with registered properties are answered by `run` without calling
+    /// the handlers. Registering a property with the same name again replaces it.
+    pub fn add_property(
+        &mut self,
+        path: &str,
+        interface: &str,
+        name: &str,
+        property: Property<UserData>,
+    ) {
+        self.properties.insert(path, interface, name, property);
+    }
+
+    /// Send a PropertiesChanged signal for the object. The values of the changed properties are read with their getters,
+    /// properties without a getter are sent as invalidated. Use `HandleEnvironment::properties_changed` from within handlers.
+    pub fn emit_properties_changed(
+        &mut self,
+        path: &str,
+        interface: &str,
+        changed: &[&str],
+        invalidated: &[&str],
+    ) -> std::result::Result<(), HandleError<UserError>> {
+        let changed = ChangedProperties {
+            path: path.to_owned(),
+            interface: interface.to_owned(),
+            changed: changed.iter().map(|name| name.to_string()).collect(),
+            invalidated: invalidated.iter().map(|name| name.to_string()).collect(),
+        };
+        self.send_properties_changed(&changed)
+    }
+
+    fn send_properties_changed(
+        &mut self,
+        changed: &ChangedProperties,
+    ) -> std::result::Result<(), HandleError<UserError>> {
+        let signal = self.properties.changed_signal(
+            &mut self.ctx,
+            &changed.path,
+            &changed.interface,
+            &changed.changed,
+            &changed.invalidated,
+        )?;
+        self.send
+            .lock()
+            .unwrap()
+            .send_message(&signal)?
+            .write_all()
+            .map_err(ll_conn::force_finish_on_error)?;
+        Ok(())
+    }
+
     /// Endless loop that takes messages and dispatches them to the setup
     /// handlers. If any errors occur they will be returned. Depending on the error you may
     /// choose to just call this function again. Note that you are expected to send a meaningful
@@ -218,27 +384,25 @@ impl<UserData, UserError: std::fmt::Debug> DispatchConn<UserData, UserError> {
                     let mut env = HandleEnvironment {
                         conn: self.send.clone(),
                         new_dispatches: PathMatcher::new(),
+                        changed_properties: Vec::new(),
                     };
-                    let result = {
-                        if let Some(obj) = &msg.dynheader.object {
-                            if let Some((matches, handler)) = self.objects.get_match(obj) {
-                                handler(&mut self.ctx, matches, &msg, &mut env)
-                            } else {
-                                (self.default_handler)(
-                                    &mut self.ctx,
-                                    Matches::default(),
-                                    &msg,
-                                    &mut env,
-                                )
-                            }
-                        } else {
-                            (self.default_handler)(
-                                &mut self.ctx,
-                                Matches::default(),
-                                &msg,
-                                &mut env,
-                            )
-                        }
+                    let objects = &mut self.objects;
+                    let result = if let Some(reply) =
+                        self.interfaces
+                            .handle_call(&msg, &self.properties, objects.pathes.keys())
+                    {
+                        reply.map(Some).map_err(HandleError::from)
+                    } else if let Some(reply) = self.properties.handle_call(&mut self.ctx, &msg) {
+                        reply.map(Some).map_err(HandleError::from)
+                    } else if let Some((matches, handler)) = msg
+                        .dynheader
+                        .object
+                        .as_ref()
+                        .and_then(|obj| objects.get_match(obj))
+                    {
+                        handler(&mut self.ctx, matches, &msg, &mut env)
+                    } else {
+                        (self.default_handler)(&mut self.ctx, Matches::default(), &msg, &mut env)
                     };
 
                     if result.is_ok() {
@@ -256,9 +420,10 @@ impl<UserData, UserError: std::fmt::Debug> DispatchConn<UserData, UserError> {
                                 Ok(ctx) => ctx,
                                 Err(e) => return Err((Some(msg), e.into())),
                             };
-                            ctx.write_all()
-                                .map_err(|(ctx, e)| ll_conn::force_finish_on_error((ctx, e)))
-                                .map_err(|e| (Some(msg), e.into()))?
+                            if let Err(e) = ctx.write_all().map_err(ll_conn::force_finish_on_error)
+                            {
+                                return Err((Some(msg), e.into()));
+                            }
                         }
 
                         Ok(None) => {
@@ -267,12 +432,21 @@ impl<UserData, UserError: std::fmt::Debug> DispatchConn<UserData, UserError> {
                                 Ok(ctx) => ctx,
                                 Err(e) => return Err((Some(msg), e.into())),
                             };
-                            ctx.write_all()
-                                .map_err(|(ctx, e)| ll_conn::force_finish_on_error((ctx, e)))
-                                .map_err(|e| (Some(msg), e.into()))?
+                            if let Err(e) = ctx.write_all().map_err(ll_conn::force_finish_on_error)
+                            {
+                                return Err((Some(msg), e.into()));
+                            }
                         }
                         Err(error) => return Err((Some(msg), error)),
                     };
+                    drop(send_conn);
+
+                    // the signals for properties changed by the handler are sent after its reply
+                    for changed in &env.changed_properties {
+                        if let Err(e) = self.send_properties_changed(changed) {
+                            return Err((Some(msg), e));
+                        }
+                    }
                 }
                 Err(error) => return Err((None, HandleError::Connection(error))),
             }
@@ -311,3 +485,37 @@ fn test_path_matcher() {
     // Multiple in the middle are not fine
     assert!(pattern.matches("/ABCD/TOO/WILD/A/B/C/DEF").is_none());
 }
+
+#[test]
+fn test_path_children() {
+    let pattern = ObjectPathPattern::new("/ABCD/:1/DEF/*");
+    assert_eq!(pattern.child_of("/"), Some("ABCD"));
+    assert_eq!(pattern.child_of("/ABCD"), None);
+    assert_eq!(pattern.child_of("/ABCD/A"), Some("DEF"));
+    assert_eq!(pattern.child_of("/ABCD/A/DEF"), None);
+    assert_eq!(pattern.child_of("/XYZ/A"), None);
+    assert_eq!(pattern.child_of("/ABCD/A/DEF/GHI"), None);
+}
+
+#[test]
+fn test_most_specific_path() {
+    let patterns = ["/items/*", "/items/:id", "/items/special", "/:any/special"]
+        .iter()
+        .map(|pattern| (ObjectPathPattern::new(pattern), *pattern))
+        .collect::<Vec<_>>();
+    let most_specific = |path: &str| {
+        // the result must not depend on the order of the patterns
+        let forward = ObjectPathPattern::most_specific(patterns.iter().map(|(p, v)| (p, *v)), path);
+        let backward =
+            ObjectPathPattern::most_specific(patterns.iter().rev().map(|(p, v)| (p, *v)), path);
+        let forward = forward.map(|(_, v)| v);
+        assert_eq!(forward, backward.map(|(_, v)| v));
+        forward
+    };
+
+    assert_eq!(most_specific("/items/special"), Some("/items/special"));
+    assert_eq!(most_specific("/items/other"), Some("/items/:id"));
+    assert_eq!(most_specific("/items/other/deeper"), Some("/items/*"));
+    assert_eq!(most_specific("/things/special"), Some("/:any/special"));
+    assert_eq!(most_specific("/things"), None);
+}
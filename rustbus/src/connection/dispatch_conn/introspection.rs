@@ -0,0 +1,149 @@
+//! Answering `org.freedesktop.DBus.Introspectable.Introspect` from the interfaces declared for the objects, the
+//! registered properties and the known path patterns.
+
+use super::properties::PropertyRegistry;
+use super::ObjectPathPattern;
+use crate::introspect::{Interface, Node, INTROSPECTABLE_INTERFACE};
+use crate::message_builder::{MarshalledMessage, MessageType};
+use crate::properties::PROPERTIES_INTERFACE;
+use crate::wire::errors::MarshalError;
+
+use std::collections::{BTreeSet, HashMap};
+
+/// The declared interfaces of all objects, keyed by the path pattern they were declared with
+pub(super) struct InterfaceRegistry {
+    objects: HashMap<ObjectPathPattern, Vec<Interface>>,
+}
+
+impl InterfaceRegistry {
+    pub fn new() -> Self {
+        InterfaceRegistry {
+            objects: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, path_pattern: &str, interface: Interface) {
+        let interfaces = self
+            .objects
+            .entry(ObjectPathPattern::new(path_pattern))
+            .or_default();
+        match interfaces
+            .iter_mut()
+            .find(|iface| iface.name == interface.name)
+        {
+            Some(iface) => *iface = interface,
+            None => interfaces.push(interface),
+        }
+    }
+
+    /// Answer the call if it is a call to Introspect on a path this registry knows about: a path with declared
+    /// interfaces or registered properties, or a path without a handler that has such objects below it.
+    ///
+    /// Calls to all other paths are left to the handlers, which may answer Introspect themselves.
+    pub fn handle_call<'a, UserData>(
+        &'a self,
+        msg: &MarshalledMessage,
+        properties: &'a PropertyRegistry<UserData>,
+        handler_patterns: impl Iterator<Item = &'a ObjectPathPattern>,
+    ) -> Option<Result<MarshalledMessage, MarshalError>> {
+        if msg.typ != MessageType::Call
+            || msg.dynheader.interface.as_deref() != Some(INTROSPECTABLE_INTERFACE)
+        {
+            return None;
+        }
+        let path = msg.dynheader.object.as_deref()?;
+        let handler_patterns: Vec<&ObjectPathPattern> = handler_patterns.collect();
+        if !self.knows_path(path, properties, &handler_patterns) {
+            return None;
+        }
+        if msg.dynheader.member.as_deref() != Some("Introspect") {
+            return Some(Ok(crate::standard_messages::unknown_method(&msg.dynheader)));
+        }
+        let node = self.node(path, properties, handler_patterns.into_iter());
+
+        let mut reply = msg.dynheader.make_response();
+        Some(reply.body.push_param(node.to_xml()).map(|_| reply))
+    }
+
+    fn knows_path<UserData>(
+        &self,
+        path: &str,
+        properties: &PropertyRegistry<UserData>,
+        handler_patterns: &[&ObjectPathPattern],
+    ) -> bool {
+        let mut declared = self.objects.keys().chain(properties.patterns());
+        if declared.any(|pattern| pattern.matches(path).is_some()) {
+            return true;
+        }
+        // intermediate nodes, so the declared objects can be found by walking the tree
+        let has_handler = handler_patterns
+            .iter()
+            .any(|pattern| pattern.matches(path).is_some());
+        !has_handler
+            && self
+                .objects
+                .keys()
+                .chain(properties.patterns())
+                .any(|pattern| pattern.child_of(path).is_some())
+    }
+
+    fn node<'a, UserData>(
+        &'a self,
+        path: &str,
+        properties: &'a PropertyRegistry<UserData>,
+        handler_patterns: impl Iterator<Item = &'a ObjectPathPattern>,
+    ) -> Node {
+        let mut node = Node::new();
+        for (pattern, interfaces) in &self.objects {
+            if pattern.matches(path).is_none() {
+                continue;
+            }
+            for interface in interfaces {
+                if node.interface(&interface.name).is_none() {
+                    node.interfaces.push(interface.clone());
+                }
+            }
+        }
+
+        // the registered properties are reported even if their interface was not declared
+        for registered in properties.introspect(path) {
+            match node
+                .interfaces
+                .iter_mut()
+                .find(|iface| iface.name == registered.name)
+            {
+                Some(iface) => {
+                    for property in registered.properties {
+                        if iface.property(&property.name).is_none() {
+                            iface.properties.push(property);
+                        }
+                    }
+                }
+                None => node.interfaces.push(registered),
+            }
+        }
+
+        let has_properties = node
+            .interfaces
+            .iter()
+            .any(|iface| !iface.properties.is_empty());
+        if has_properties && node.interface(PROPERTIES_INTERFACE).is_none() {
+            node.interfaces.push(Interface::properties());
+        }
+        if node.interface(INTROSPECTABLE_INTERFACE).is_none() {
+            node.interfaces.push(Interface::introspectable());
+        }
+
+        let children: BTreeSet<&str> = self
+            .objects
+            .keys()
+            .chain(properties.patterns())
+            .chain(handler_patterns)
+            .filter_map(|pattern| pattern.child_of(path))
+            .collect();
+        for child in children {
+            node = node.with_child(child);
+        }
+        node
+    }
+}
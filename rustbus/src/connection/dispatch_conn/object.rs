@@ -0,0 +1,62 @@
+//! Objects that implement a whole interface, usually by the `#[dbus_interface]` attribute on an impl block
+
+use crate::message_builder::MarshalledMessage;
+use crate::wire::errors::MarshalError;
+
+/// An interface that is implemented by a rust type. The `#[dbus_interface(name = "...")]` attribute implements this
+/// for the type of an impl block, every method with a `self` receiver becomes a method of the interface.
+///
+/// ```rust
+/// use rustbus::connection::dispatch_conn::DBusInterface;
+/// use rustbus::{dbus_interface, DBusError, MessageBuilder};
+///
+/// struct Counter {
+///     count: u32,
+/// }
+///
+/// #[dbus_interface(name = "io.killing.spark.Counter")]
+/// impl Counter {
+///     fn add(&mut self, amount: u32) -> u32 {
+///         self.count += amount;
+///         self.count
+///     }
+///
+///     fn describe(&self, verbose: bool) -> Result<(String, u32), DBusError> {
+///         Ok((if verbose { "counted" } else { "" }.to_owned(), self.count))
+///     }
+///
+///     #[dbus_method(name = "Clear")]
+///     fn reset(&mut self) {
+///         self.count = 0;
+///     }
+/// }
+///
+/// let introspected = Counter::introspect();
+/// assert!(introspected.method("Add").is_some());
+/// assert!(introspected.method("Clear").is_some());
+///
+/// let mut counter = Counter { count: 0 };
+/// let mut call = MessageBuilder::new()
+///     .call("Add")
+///     .with_interface(Counter::INTERFACE)
+///     .on("/counter")
+///     .build();
+/// call.body.push_param(5u32).unwrap();
+/// let reply = counter.handle_call(&call).unwrap().unwrap();
+/// assert_eq!(reply.body.parser().get::<u32>().unwrap(), 5);
+/// ```
+pub trait DBusInterface {
+    /// The name of the interface
+    const INTERFACE: &'static str;
+
+    /// The description of the interface for `org.freedesktop.DBus.Introspectable.Introspect`
+    fn introspect() -> crate::introspect::Interface;
+
+    /// Call the method the message is addressed to. Returns None if the message is not a call to this interface.
+    /// Calls to unknown methods are answered with an UnknownMethod error, calls with arguments that do not match the
+    /// signature of the method with an InvalidArgs error.
+    fn handle_call(
+        &mut self,
+        msg: &MarshalledMessage,
+    ) -> Option<Result<MarshalledMessage, MarshalError>>;
+}
@@ -0,0 +1,504 @@
+//! Answering calls to `org.freedesktop.DBus.Properties` from getters and setters that are registered per object and interface.
+//!
+//! ```rust,no_run
+//! use rustbus::connection::dispatch_conn::{DispatchConn, Property};
+//! use rustbus::connection::{get_session_bus_path, ll_conn::DuplexConn, Timeout};
+//! use rustbus::dbus_error::{DBusError, ErrorName};
+//!
+//! struct Counter {
+//!     count: u32,
+//!     label: String,
+//! }
+//!
+//! let mut con =
+//!     DuplexConn::connect_to_bus(get_session_bus_path().unwrap(), false, Timeout::Infinite).unwrap();
+//! con.send_hello(Timeout::Infinite).unwrap();
+//! let counter = Counter { count: 0, label: "clicks".to_owned() };
+//! let mut con = DispatchConn::new(con, counter, Box::new(|_, _, _, _| Ok(None)));
+//!
+//! con.add_property(
+//!     "/io/killing/spark",
+//!     "io.killing.spark.Counter",
+//!     "Count",
+//!     Property::read_only(|counter: &mut Counter, _| Ok(counter.count)),
+//! );
+//! con.add_property(
+//!     "/io/killing/spark",
+//!     "io.killing.spark.Counter",
+//!     "Label",
+//!     Property::read_write(
+//!         |counter: &mut Counter, _| Ok(counter.label.clone()),
+//!         |counter: &mut Counter, _, label: String| {
+//!             if label.is_empty() {
+//!                 return Err(DBusError::new(ErrorName::InvalidArgs, "The label can not be empty"));
+//!             }
+//!             counter.label = label;
+//!             Ok(())
+//!         },
+//!     ),
+//! );
+//! # let _: Result<(), (_, rustbus::connection::dispatch_conn::HandleError<()>)> = con.run();
+//! ```
+
+use super::{Matches, ObjectPathPattern};
+use crate::dbus_error::{DBusError, ErrorName};
+use crate::introspect;
+use crate::message_builder::{MarshalledMessage, MessageBuilder, MessageType};
+use crate::wire::errors::MarshalError;
+use crate::wire::marshal::traits::SignatureBuffer;
+use crate::wire::marshal::MarshalContext;
+use crate::wire::unmarshal::traits::Variant;
+use crate::{Marshal, Signature, Unmarshal};
+
+use std::collections::{BTreeMap, HashMap};
+
+pub use crate::properties::PROPERTIES_INTERFACE;
+
+/// How a property can be accessed by other connections
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertyAccess {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl PropertyAccess {
+    pub fn readable(self) -> bool {
+        matches!(self, PropertyAccess::Read | PropertyAccess::ReadWrite)
+    }
+
+    pub fn writable(self) -> bool {
+        matches!(self, PropertyAccess::Write | PropertyAccess::ReadWrite)
+    }
+
+    /// The name used for the access attribute in introspection data
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PropertyAccess::Read => "read",
+            PropertyAccess::Write => "write",
+            PropertyAccess::ReadWrite => "readwrite",
+        }
+    }
+}
+
+/// A value of a type that is only known at runtime, marshalled as a variant
+trait VariantValue {
+    fn marshal_variant(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError>;
+}
+
+impl<T: Marshal> VariantValue for T {
+    fn marshal_variant(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
+        self.marshal_as_variant(ctx)
+    }
+}
+
+struct AsVariant<'a>(&'a dyn VariantValue);
+
+impl Signature for AsVariant<'_> {
+    fn signature() -> crate::signature::Type {
+        crate::signature::Type::Container(crate::signature::Container::Variant)
+    }
+    fn alignment() -> usize {
+        1
+    }
+    fn sig_str(s_buf: &mut SignatureBuffer) {
+        s_buf.push_static("v")
+    }
+    fn has_sig(sig: &str) -> bool {
+        sig.starts_with('v')
+    }
+}
+
+impl Marshal for AsVariant<'_> {
+    fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
+        self.0.marshal_variant(ctx)
+    }
+}
+
+/// Property names and values as they are sent in GetAll replies and PropertiesChanged signals
+struct PropertyValues(Vec<(String, Box<dyn VariantValue>)>);
+
+impl Signature for PropertyValues {
+    fn signature() -> crate::signature::Type {
+        crate::signature::Type::Container(crate::signature::Container::Dict(
+            crate::signature::Base::String,
+            Box::new(AsVariant::signature()),
+        ))
+    }
+    fn alignment() -> usize {
+        4
+    }
+    fn sig_str(s_buf: &mut SignatureBuffer) {
+        s_buf.push_static("a{sv}")
+    }
+    fn has_sig(sig: &str) -> bool {
+        sig.starts_with("a{sv}")
+    }
+}
+
+impl Marshal for PropertyValues {
+    fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
+        // same layout as the HashMap impl, the values are written by the boxed values themselves
+        ctx.align_to(4);
+        let size_pos = ctx.buf.len();
+        ctx.buf.extend_from_slice(&[0, 0, 0, 0]);
+        ctx.align_to(8);
+
+        let size_before = ctx.buf.len();
+        for (name, value) in &self.0 {
+            ctx.align_to(8);
+            name.marshal(ctx)?;
+            value.marshal_variant(ctx)?;
+        }
+        let size_of_content = ctx.buf.len() - size_before;
+        crate::wire::util::insert_u32(
+            ctx.byteorder,
+            size_of_content as u32,
+            &mut ctx.buf[size_pos..size_pos + 4],
+        );
+        Ok(())
+    }
+}
+
+type GetterFn<UserData> =
+    dyn FnMut(&mut UserData, &Matches) -> Result<Box<dyn VariantValue>, DBusError>;
+type SetterFn<UserData> =
+    dyn for<'a> FnMut(&mut UserData, &Matches, &Variant<'a, 'a>) -> Result<(), DBusError>;
+
+/// A property with the closures to read and/or write it. Getters and setters get the same arguments as the handlers,
+/// the user data and the matches of the path pattern the property was registered with. Errors they return are sent
+/// to the caller as error reply.
+pub struct Property<UserData> {
+    access: PropertyAccess,
+    signature: String,
+    getter: Option<Box<GetterFn<UserData>>>,
+    setter: Option<Box<SetterFn<UserData>>>,
+}
+
+fn signature_of<T: Signature>() -> String {
+    let mut sig = SignatureBuffer::new();
+    T::sig_str(&mut sig);
+    sig.as_str().to_owned()
+}
+
+fn boxed_getter<UserData, T, G>(mut getter: G) -> Box<GetterFn<UserData>>
+where
+    T: Marshal + 'static,
+    G: FnMut(&mut UserData, &Matches) -> Result<T, DBusError> + 'static,
+{
+    Box::new(move |ctx, matches| {
+        getter(ctx, matches).map(|value| Box::new(value) as Box<dyn VariantValue>)
+    })
+}
+
+fn boxed_setter<UserData, T, S>(mut setter: S) -> Box<SetterFn<UserData>>
+where
+    T: for<'a> Unmarshal<'a, 'a>,
+    S: FnMut(&mut UserData, &Matches, T) -> Result<(), DBusError> + 'static,
+{
+    Box::new(move |ctx, matches, value| {
+        let value = value.get::<T>().map_err(|_| {
+            DBusError::new(
+                ErrorName::InvalidArgs,
+                format!(
+                    "Expected a value of type {} but got {:?}",
+                    signature_of::<T>(),
+                    value.get_value_sig()
+                ),
+            )
+        })?;
+        setter(ctx, matches, value)
+    })
+}
+
+impl<UserData> Property<UserData> {
+    pub fn read_only<T, G>(getter: G) -> Self
+    where
+        T: Marshal + 'static,
+        G: FnMut(&mut UserData, &Matches) -> Result<T, DBusError> + 'static,
+    {
+        Property {
+            access: PropertyAccess::Read,
+            signature: signature_of::<T>(),
+            getter: Some(boxed_getter(getter)),
+            setter: None,
+        }
+    }
+
+    pub fn write_only<T, S>(setter: S) -> Self
+    where
+        T: for<'a> Unmarshal<'a, 'a>,
+        S: FnMut(&mut UserData, &Matches, T) -> Result<(), DBusError> + 'static,
+    {
+        Property {
+            access: PropertyAccess::Write,
+            signature: signature_of::<T>(),
+            getter: None,
+            setter: Some(boxed_setter(setter)),
+        }
+    }
+
+    /// The setter is only called with values of type T, other values are rejected with InvalidArgs
+    pub fn read_write<T, G, S>(getter: G, setter: S) -> Self
+    where
+        T: Marshal + for<'a> Unmarshal<'a, 'a> + 'static,
+        G: FnMut(&mut UserData, &Matches) -> Result<T, DBusError> + 'static,
+        S: FnMut(&mut UserData, &Matches, T) -> Result<(), DBusError> + 'static,
+    {
+        Property {
+            access: PropertyAccess::ReadWrite,
+            signature: signature_of::<T>(),
+            getter: Some(boxed_getter(getter)),
+            setter: Some(boxed_setter(setter)),
+        }
+    }
+
+    pub fn access(&self) -> PropertyAccess {
+        self.access
+    }
+
+    /// The signature of the value
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    fn introspect(&self, name: &str) -> introspect::Property {
+        let mut types = crate::signature::Type::parse_description(&self.signature)
+            .expect("The signature was built from a Signature impl");
+        introspect::Property {
+            name: name.to_owned(),
+            typ: types.remove(0),
+            access: self.access,
+            annotations: Vec::new(),
+        }
+    }
+}
+
+type Interfaces<UserData> = BTreeMap<String, BTreeMap<String, Property<UserData>>>;
+
+/// The properties of all objects, keyed by the path pattern they were registered with and the interface
+pub(super) struct PropertyRegistry<UserData> {
+    objects: HashMap<ObjectPathPattern, Interfaces<UserData>>,
+}
+
+impl<UserData> PropertyRegistry<UserData> {
+    pub fn new() -> Self {
+        PropertyRegistry {
+            objects: HashMap::new(),
+        }
+    }
+
+    pub fn insert(
+        &mut self,
+        path_pattern: &str,
+        interface: &str,
+        name: &str,
+        property: Property<UserData>,
+    ) {
+        self.objects
+            .entry(ObjectPathPattern::new(path_pattern))
+            .or_default()
+            .entry(interface.to_owned())
+            .or_default()
+            .insert(name.to_owned(), property);
+    }
+
+    /// The properties of the most specific pattern matching the path
+    fn get_match(&mut self, path: &str) -> Option<(Matches, &mut Interfaces<UserData>)> {
+        ObjectPathPattern::most_specific(self.objects.iter_mut(), path)
+    }
+
+    /// The patterns properties were registered with, used to find the children of objects
+    pub fn patterns(&self) -> impl Iterator<Item = &ObjectPathPattern> {
+        self.objects.keys()
+    }
+
+    /// The interfaces with the registered properties of the object, as they are reported by Introspect
+    pub fn introspect(&self, path: &str) -> Vec<introspect::Interface> {
+        let interfaces = ObjectPathPattern::most_specific(self.objects.iter(), path)
+            .map(|(_, interfaces)| interfaces);
+        interfaces
+            .into_iter()
+            .flatten()
+            .map(|(interface, properties)| introspect::Interface {
+                properties: properties
+                    .iter()
+                    .map(|(name, property)| property.introspect(name))
+                    .collect(),
+                ..introspect::Interface::new(interface.as_str())
+            })
+            .collect()
+    }
+
+    /// Answer the call if it is a call to the properties interface of an object with registered properties
+    pub fn handle_call(
+        &mut self,
+        ctx: &mut UserData,
+        msg: &MarshalledMessage,
+    ) -> Option<Result<MarshalledMessage, MarshalError>> {
+        if msg.typ != MessageType::Call
+            || msg.dynheader.interface.as_deref() != Some(PROPERTIES_INTERFACE)
+        {
+            return None;
+        }
+        let (matches, interfaces) = self.get_match(msg.dynheader.object.as_deref()?)?;
+
+        let result = match msg.dynheader.member.as_deref() {
+            Some("Get") => get(ctx, &matches, interfaces, msg),
+            Some("Set") => set(ctx, &matches, interfaces, msg),
+            Some("GetAll") => get_all(ctx, &matches, interfaces, msg),
+            _ => return Some(Ok(crate::standard_messages::unknown_method(&msg.dynheader))),
+        };
+        Some(match result {
+            Ok(Ok(reply)) => Ok(reply),
+            Ok(Err(error)) => Ok(error.to_message(&msg.dynheader)),
+            Err(error) => Err(error),
+        })
+    }
+
+    /// Build the PropertiesChanged signal. The values of the changed properties are read with their getters,
+    /// properties without a getter or whose getter fails are sent as invalidated.
+    pub fn changed_signal(
+        &mut self,
+        ctx: &mut UserData,
+        path: &str,
+        interface: &str,
+        changed: &[String],
+        invalidated: &[String],
+    ) -> Result<MarshalledMessage, MarshalError> {
+        let mut values = Vec::new();
+        let mut invalidated = invalidated.to_vec();
+        let mut properties = self
+            .get_match(path)
+            .and_then(|(matches, interfaces)| Some((matches, interfaces.get_mut(interface)?)));
+        for name in changed {
+            let value = properties.as_mut().and_then(|(matches, properties)| {
+                let getter = properties.get_mut(name.as_str())?.getter.as_mut()?;
+                getter(ctx, matches).ok()
+            });
+            match value {
+                Some(value) => values.push((name.clone(), value)),
+                None => invalidated.push(name.clone()),
+            }
+        }
+        let mut signal = MessageBuilder::new()
+            .signal(PROPERTIES_INTERFACE, "PropertiesChanged", path)
+            .build();
+        signal
+            .body
+            .push_param3(interface, PropertyValues(values), invalidated.as_slice())?;
+        Ok(signal)
+    }
+}
+
+type CallResult = Result<Result<MarshalledMessage, DBusError>, MarshalError>;
+
+fn unknown_property(interface: &str, name: &str) -> DBusError {
+    DBusError::new(
+        ErrorName::UnknownProperty,
+        format!("No property {} in interface {}", name, interface),
+    )
+}
+
+fn invalid_args(msg: &MarshalledMessage) -> DBusError {
+    DBusError::new(
+        ErrorName::InvalidArgs,
+        format!(
+            "Invalid arguments with signature {} for {}",
+            msg.get_sig(),
+            msg.dynheader.member.as_deref().unwrap_or("")
+        ),
+    )
+}
+
+fn get<UserData>(
+    ctx: &mut UserData,
+    matches: &Matches,
+    interfaces: &mut Interfaces<UserData>,
+    msg: &MarshalledMessage,
+) -> CallResult {
+    let (interface, name) = match msg.body.parser().get2::<&str, &str>() {
+        Ok(args) => args,
+        Err(_) => return Ok(Err(invalid_args(msg))),
+    };
+    let property = match interfaces
+        .get_mut(interface)
+        .and_then(|properties| properties.get_mut(name))
+    {
+        Some(property) => property,
+        None => return Ok(Err(unknown_property(interface, name))),
+    };
+    let getter = match &mut property.getter {
+        Some(getter) => getter,
+        None => {
+            return Ok(Err(DBusError::new(
+                ErrorName::AccessDenied,
+                format!("Property {} of interface {} is write-only", name, interface),
+            )))
+        }
+    };
+    let value = match getter(ctx, matches) {
+        Ok(value) => value,
+        Err(error) => return Ok(Err(error)),
+    };
+    let mut reply = msg.dynheader.make_response();
+    reply.body.push_param(AsVariant(value.as_ref()))?;
+    Ok(Ok(reply))
+}
+
+fn set<UserData>(
+    ctx: &mut UserData,
+    matches: &Matches,
+    interfaces: &mut Interfaces<UserData>,
+    msg: &MarshalledMessage,
+) -> CallResult {
+    let mut parser = msg.body.parser();
+    let (interface, name, value) = match parser.get3::<&str, &str, Variant>() {
+        Ok(args) => args,
+        Err(_) => return Ok(Err(invalid_args(msg))),
+    };
+    let property = match interfaces
+        .get_mut(interface)
+        .and_then(|properties| properties.get_mut(name))
+    {
+        Some(property) => property,
+        None => return Ok(Err(unknown_property(interface, name))),
+    };
+    let setter = match &mut property.setter {
+        Some(setter) => setter,
+        None => {
+            return Ok(Err(DBusError::new(
+                ErrorName::PropertyReadOnly,
+                format!("Property {} of interface {} is read-only", name, interface),
+            )))
+        }
+    };
+    Ok(setter(ctx, matches, &value).map(|()| msg.dynheader.make_response()))
+}
+
+fn get_all<UserData>(
+    ctx: &mut UserData,
+    matches: &Matches,
+    interfaces: &mut Interfaces<UserData>,
+    msg: &MarshalledMessage,
+) -> CallResult {
+    let interface = match msg.body.parser().get::<&str>() {
+        Ok(interface) => interface,
+        Err(_) => return Ok(Err(invalid_args(msg))),
+    };
+    let mut values = Vec::new();
+    // objects can implement interfaces without properties, those get an empty dict
+    if let Some(properties) = interfaces.get_mut(interface) {
+        for (name, property) in properties.iter_mut() {
+            if let Some(getter) = &mut property.getter {
+                match getter(ctx, matches) {
+                    Ok(value) => values.push((name.clone(), value)),
+                    Err(error) => return Ok(Err(error)),
+                }
+            }
+        }
+    }
+    let mut reply = msg.dynheader.make_response();
+    reply.body.push_param(PropertyValues(values))?;
+    Ok(Ok(reply))
+}
@@ -0,0 +1,318 @@
+//! Accept connections from other processes to talk dbus peer-to-peer without a bus daemon in between.
+//!
+//! ```rust,no_run
+//! use rustbus::connection::{listener::DBusListener, DBusAddress, Timeout};
+//!
+//! fn main() -> Result<(), rustbus::connection::Error> {
+//!     let addr = DBusAddress::parse("unix:tmpdir=/tmp")?;
+//!     let listener = DBusListener::bind(&addr)?;
+//!     // Tell the clients where to find you. This includes the guid of this server.
+//!     println!("Listening on: {}", listener.address());
+//!
+//!     loop {
+//!         // There is no bus, so there is no hello message to be sent. The connection can be used right away.
+//!         let mut con = listener.accept(Timeout::Infinite)?;
+//!         let msg = con.recv.get_next_message(Timeout::Infinite)?;
+//!         con.send.send_message_write_all(&msg.dynheader.make_response())?;
+//!     }
+//! }
+//! ```
+
+use super::address::{Transport, UnixAddress};
+use super::ll_conn::{DuplexConn, Stream};
+use super::{calc_timeout_left, DBusAddress, Error, Result, Timeout};
+use crate::auth::{self, AuthStream};
+
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::time;
+
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::socket;
+
+/// Listens on a unix socket and runs the server side of the authentication for every client
+pub struct DBusListener {
+    listener: UnixListener,
+    auth: ServerAuth,
+    address: DBusAddress,
+    // socket file that is removed again when the listener is dropped
+    socket_path: Option<PathBuf>,
+}
+
+/// What is needed to authenticate clients. Can be cloned to authenticate clients away from the listener.
+#[derive(Clone)]
+pub(crate) struct ServerAuth {
+    guid: String,
+    allowed_uids: Option<Vec<u32>>,
+}
+
+fn random_hex(bytes: usize) -> Result<String> {
+    Ok(auth::mechanisms::hex_encode(
+        &auth::mechanisms::random_bytes(bytes)?,
+    ))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_uid(stream: &UnixStream) -> Option<u32> {
+    socket::getsockopt(stream.as_raw_fd(), socket::sockopt::PeerCredentials)
+        .ok()
+        .map(|creds| creds.uid())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_uid(_stream: &UnixStream) -> Option<u32> {
+    None
+}
+
+impl DBusListener {
+    /// Bind to a unix address. `path`, `abstract`, `dir`, `tmpdir` and `runtime` addresses are supported.
+    ///
+    /// A new guid is generated for this server. By default only clients running as the same user
+    /// as this process are accepted, see `set_allowed_uids`.
+    pub fn bind(addr: &DBusAddress) -> Result<Self> {
+        let unix = match &addr.transport {
+            Transport::Unix(unix) => unix,
+            _ => return Err(Error::AddressTypeNotSupported(addr.to_string())),
+        };
+
+        let guid = random_hex(16)?;
+        let (listener, bound, socket_path) = match unix {
+            UnixAddress::Abstract(name) => (Self::bind_abstract(name)?, unix.clone(), None),
+            UnixAddress::Path(path) => {
+                let listener = UnixListener::bind(path)?;
+                (listener, unix.clone(), Some(path.clone()))
+            }
+            UnixAddress::Dir(dir) | UnixAddress::TmpDir(dir) => {
+                let path = dir.join(format!("dbus-{}", random_hex(8)?));
+                let listener = UnixListener::bind(&path)?;
+                (listener, UnixAddress::Path(path.clone()), Some(path))
+            }
+            UnixAddress::Runtime => {
+                let runtime_dir =
+                    std::env::var_os("XDG_RUNTIME_DIR").ok_or(Error::NoAddressFound)?;
+                let path = PathBuf::from(runtime_dir).join("bus");
+                let listener = UnixListener::bind(&path)?;
+                (listener, UnixAddress::Path(path.clone()), Some(path))
+            }
+        };
+
+        Ok(DBusListener {
+            listener,
+            address: DBusAddress {
+                transport: Transport::Unix(bound),
+                guid: Some(guid.clone()),
+            },
+            auth: ServerAuth {
+                guid,
+                allowed_uids: Some(vec![nix::unistd::getuid().as_raw()]),
+            },
+            socket_path,
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn bind_abstract(name: &[u8]) -> Result<UnixListener> {
+        let addr = socket::UnixAddr::new_abstract(name)?;
+        let sock = socket::socket(
+            socket::AddressFamily::Unix,
+            socket::SockType::Stream,
+            socket::SockFlag::empty(),
+            None,
+        )?;
+        let listener = unsafe { UnixListener::from_raw_fd(sock) };
+        socket::bind(sock, &addr)?;
+        socket::listen(sock, 128)?;
+        Ok(listener)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn bind_abstract(_name: &[u8]) -> Result<UnixListener> {
+        Err(Error::AddressTypeNotSupported("unix:abstract".to_owned()))
+    }
+
+    /// The guid that is sent to clients during the authentication
+    pub fn guid(&self) -> &str {
+        &self.auth.guid
+    }
+
+    /// The address clients can use to connect to this listener, including the guid
+    pub fn address(&self) -> &DBusAddress {
+        &self.address
+    }
+
+    /// Restrict which users may connect. `None` accepts every client that proves its identity.
+    pub fn set_allowed_uids(&mut self, uids: Option<Vec<u32>>) {
+        self.auth.allowed_uids = uids;
+    }
+
+    /// Block until a client connects and finishes the authentication.
+    ///
+    /// `timeout` covers both waiting for a client and its authentication. If it runs out `Error::TimedOut` is returned,
+    /// a client that was accepted but did not finish the authentication in time is dropped.
+    ///
+    /// The returned connection is ready to be used. There is no bus so there is no need (or possibility) to send a hello message.
+    pub fn accept(&self, timeout: Timeout) -> Result<DuplexConn> {
+        let start_time = time::Instant::now();
+        let stream = self.accept_unauthenticated(timeout)?;
+        self.auth
+            .authenticate(stream, calc_timeout_left(&start_time, timeout)?)
+    }
+
+    /// Accept the next client without authenticating it. Use the `server_auth` to do that, e.g. on another thread.
+    pub(crate) fn accept_unauthenticated(&self, timeout: Timeout) -> Result<UnixStream> {
+        self.wait_for_client(timeout)?;
+        let (stream, _) = self.listener.accept()?;
+        Ok(stream)
+    }
+
+    pub(crate) fn server_auth(&self) -> &ServerAuth {
+        &self.auth
+    }
+
+    fn wait_for_client(&self, timeout: Timeout) -> Result<()> {
+        let millis = match timeout {
+            Timeout::Infinite => return Ok(()),
+            Timeout::Nonblock => 0,
+            Timeout::Duration(d) => d.as_millis().min(i32::MAX as u128) as i32,
+        };
+        let mut fds = [PollFd::new(self.listener.as_raw_fd(), PollFlags::POLLIN)];
+        loop {
+            match poll(&mut fds, millis) {
+                Ok(0) => return Err(Error::TimedOut),
+                Ok(_) => return Ok(()),
+                Err(nix::errno::Errno::EINTR) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl ServerAuth {
+    /// Run the server side of the authentication on a freshly accepted client
+    pub(crate) fn authenticate(&self, stream: UnixStream, timeout: Timeout) -> Result<DuplexConn> {
+        let peer_uid = peer_uid(&stream);
+        let mut stream = Stream::Unix(stream);
+
+        let allowed_uids = &self.allowed_uids;
+        let allow_uid = |uid: u32| match allowed_uids {
+            Some(uids) => uids.contains(&uid),
+            None => true,
+        };
+        match auth::do_server_auth(&mut stream, peer_uid, &allow_uid, &self.guid, true, timeout)? {
+            auth::ServerAuthResult::Ok { unix_fd } => {
+                // the connection sets its own timeouts on each operation from here on
+                stream.set_timeout(Timeout::Infinite)?;
+                DuplexConn::from_authenticated_stream(stream, unix_fd, self.guid.clone())
+            }
+            auth::ServerAuthResult::Rejected => Err(Error::AuthFailed),
+        }
+    }
+}
+
+impl AsRawFd for DBusListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+}
+
+impl Drop for DBusListener {
+    fn drop(&mut self) {
+        if let Some(path) = &self.socket_path {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::Timeout;
+    use crate::message_builder::MessageBuilder;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn peer_to_peer_connection() {
+        let addr = DBusAddress {
+            transport: Transport::Unix(UnixAddress::TmpDir(std::env::temp_dir())),
+            guid: None,
+        };
+        let listener = DBusListener::bind(&addr).unwrap();
+        let client_addr = listener.address().clone();
+        assert_eq!(client_addr.guid.as_deref(), Some(listener.guid()));
+
+        let client = std::thread::spawn(move || {
+            let mut con =
+                DuplexConn::connect_to_bus([client_addr], true, Timeout::Infinite).unwrap();
+            assert!(con.send.can_pass_unix_fds());
+
+            let (read, write) = nix::unistd::pipe().unwrap();
+            let mut call = MessageBuilder::new()
+                .call("Hello")
+                .on("/io/killing/spark")
+                .build();
+            call.body.push_param("from the client").unwrap();
+            call.body
+                .push_param(crate::wire::UnixFd::new(write))
+                .unwrap();
+            let serial = con.send.send_message_write_all(&call).unwrap();
+            // the message holds a copy of the write end, which would keep the pipe open
+            drop(call);
+
+            let reply = con.recv.get_next_message(Timeout::Infinite).unwrap();
+            assert_eq!(reply.dynheader.response_serial, Some(serial));
+            let mut read = unsafe { std::fs::File::from_raw_fd(read) };
+            let mut received = String::new();
+            read.read_to_string(&mut received).unwrap();
+            assert_eq!(received, "from the server");
+        });
+
+        let mut con = listener.accept(Timeout::Infinite).unwrap();
+        let call = con.recv.get_next_message(Timeout::Infinite).unwrap();
+        let (text, fd) = call
+            .body
+            .parser()
+            .get2::<&str, crate::wire::UnixFd>()
+            .unwrap();
+        assert_eq!(text, "from the client");
+        {
+            let mut write = unsafe { std::fs::File::from_raw_fd(fd.take_raw_fd().unwrap()) };
+            write.write_all(b"from the server").unwrap();
+        }
+        con.send
+            .send_message_write_all(&call.dynheader.make_response())
+            .unwrap();
+
+        client.join().unwrap();
+
+        let path = match &listener.address().transport {
+            Transport::Unix(UnixAddress::Path(path)) => path.clone(),
+            _ => panic!("expected a path"),
+        };
+        assert!(path.exists());
+        drop(listener);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn accept_times_out() {
+        let addr = DBusAddress {
+            transport: Transport::Unix(UnixAddress::TmpDir(std::env::temp_dir())),
+            guid: None,
+        };
+        let listener = DBusListener::bind(&addr).unwrap();
+        let timeout = Timeout::Duration(std::time::Duration::from_millis(50));
+
+        // nobody connects
+        assert!(matches!(listener.accept(timeout), Err(Error::TimedOut)));
+
+        // a client connects but never authenticates
+        let path = match &listener.address().transport {
+            Transport::Unix(UnixAddress::Path(path)) => path.clone(),
+            _ => panic!("expected a path"),
+        };
+        let mut client = UnixStream::connect(path).unwrap();
+        client.write_all(&[0]).unwrap();
+        assert!(matches!(listener.accept(timeout), Err(Error::TimedOut)));
+    }
+}
@@ -1,16 +1,21 @@
+use super::address::Transport;
+use super::DBusAddress;
 use super::Error;
 use super::Result;
 use super::Timeout;
-use crate::auth;
+use crate::auth::{self, AuthStream};
 use crate::message_builder::MarshalledMessage;
 use crate::wire::errors::UnmarshalError;
 use crate::wire::marshal;
 use crate::wire::unmarshal;
 
+use std::collections::VecDeque;
 use std::io::IoSlice;
 use std::io::IoSliceMut;
+use std::io::{Read, Write};
 use std::time;
 
+use std::net::TcpStream;
 use std::os::unix::io::RawFd;
 use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::os::unix::net::UnixStream;
@@ -19,20 +24,110 @@ use nix::cmsg_space;
 use nix::sys::socket::SockaddrStorage;
 use nix::sys::socket::{
     self, connect, recvmsg, sendmsg, socket, ControlMessage, ControlMessageOwned, MsgFlags,
-    UnixAddr,
 };
 
-/// A lowlevel abstraction over the raw unix socket
+/// The socket a connection is running over. Only unix sockets can be used to pass file descriptors.
+#[derive(Debug)]
+pub(crate) enum Stream {
+    Unix(UnixStream),
+    Tcp(TcpStream),
+}
+
+impl AuthStream for Stream {
+    fn set_timeout(&self, timeout: Timeout) -> std::io::Result<()> {
+        match self {
+            Stream::Unix(s) => s.set_timeout(timeout),
+            Stream::Tcp(s) => s.set_timeout(timeout),
+        }
+    }
+}
+
+impl Stream {
+    fn try_clone(&self) -> std::io::Result<Stream> {
+        match self {
+            Stream::Unix(s) => s.try_clone().map(Stream::Unix),
+            Stream::Tcp(s) => s.try_clone().map(Stream::Tcp),
+        }
+    }
+    fn read_timeout(&self) -> std::io::Result<Option<time::Duration>> {
+        match self {
+            Stream::Unix(s) => s.read_timeout(),
+            Stream::Tcp(s) => s.read_timeout(),
+        }
+    }
+    fn set_read_timeout(&self, timeout: Option<time::Duration>) -> std::io::Result<()> {
+        match self {
+            Stream::Unix(s) => s.set_read_timeout(timeout),
+            Stream::Tcp(s) => s.set_read_timeout(timeout),
+        }
+    }
+    fn write_timeout(&self) -> std::io::Result<Option<time::Duration>> {
+        match self {
+            Stream::Unix(s) => s.write_timeout(),
+            Stream::Tcp(s) => s.write_timeout(),
+        }
+    }
+    fn set_write_timeout(&self, timeout: Option<time::Duration>) -> std::io::Result<()> {
+        match self {
+            Stream::Unix(s) => s.set_write_timeout(timeout),
+            Stream::Tcp(s) => s.set_write_timeout(timeout),
+        }
+    }
+    fn can_pass_fds(&self) -> bool {
+        matches!(self, Stream::Unix(_))
+    }
+}
+
+impl AsRawFd for Stream {
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            Stream::Unix(s) => s.as_raw_fd(),
+            Stream::Tcp(s) => s.as_raw_fd(),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Unix(s) => s.read(buf),
+            Stream::Tcp(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Unix(s) => s.write(buf),
+            Stream::Tcp(s) => s.write(buf),
+        }
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Unix(s) => s.flush(),
+            Stream::Tcp(s) => s.flush(),
+        }
+    }
+}
+
+/// A lowlevel abstraction over the raw socket
 #[derive(Debug)]
 pub struct SendConn {
-    stream: UnixStream,
+    stream: Stream,
     header_buf: Vec<u8>,
+    // whether unix fds were successfully negotiated for this connection
+    unix_fd: bool,
 
     serial_counter: u32,
+
+    // messages queued with queue_message, the first one might be partially sent
+    pending: VecDeque<MarshalledMessage>,
+    pending_progress: Option<SendMessageState>,
 }
 
 pub struct RecvConn {
-    stream: UnixStream,
+    stream: Stream,
 
     msg_buf_in: Vec<u8>,
     cmsgs_in: Vec<ControlMessageOwned>,
@@ -41,6 +136,7 @@ pub struct RecvConn {
 pub struct DuplexConn {
     pub send: SendConn,
     pub recv: RecvConn,
+    guid: String,
 }
 
 impl RecvConn {
@@ -66,7 +162,7 @@ impl RecvConn {
         let iovec = IoSliceMut::new(&mut tmpbuf[..usize::min(bytes_to_read, BUFSIZE)]);
 
         let mut cmsgspace = cmsg_space!([RawFd; 10]);
-        let flags = MsgFlags::empty();
+        let mut flags = MsgFlags::empty();
 
         let old_timeout = self.stream.read_timeout()?;
         match timeout {
@@ -77,7 +173,9 @@ impl RecvConn {
                 self.stream.set_read_timeout(None)?;
             }
             Timeout::Nonblock => {
-                self.stream.set_nonblocking(true)?;
+                // use the flag instead of the O_NONBLOCK of the socket. That is shared with the sending side
+                // which might be used concurrently from another thread.
+                flags |= MsgFlags::MSG_DONTWAIT;
             }
         }
         let msg = recvmsg::<SockaddrStorage>(
@@ -91,7 +189,6 @@ impl RecvConn {
             _ => Error::NixError(e),
         });
 
-        self.stream.set_nonblocking(false)?;
         self.stream.set_read_timeout(old_timeout)?;
 
         let msg = msg?;
@@ -124,9 +221,8 @@ impl RecvConn {
             padding_between_header_and_body
         };
 
-        let bytes_needed = complete_header_size as usize
-            + padding_between_header_and_body
-            + header.body_len as usize;
+        let bytes_needed =
+            complete_header_size + padding_between_header_and_body + header.body_len as usize;
         Ok(bytes_needed)
     }
 
@@ -205,6 +301,25 @@ impl RecvConn {
 
         Ok(msg)
     }
+
+    /// Read all messages that can be read without blocking. Partially received messages stay in the buffer
+    /// and are completed by later calls.
+    ///
+    /// This is meant to be called when the connection was reported readable by a poller like mio. Since those report
+    /// readiness edge triggered, this reads until the socket would block.
+    pub fn try_read_messages(&mut self) -> Result<Vec<MarshalledMessage>> {
+        let mut msgs = Vec::new();
+        loop {
+            match self.get_next_message(Timeout::Nonblock) {
+                Ok(msg) => msgs.push(msg),
+                Err(Error::TimedOut) => break,
+                // hand out what was read before the error, the next call will report it again
+                Err(Error::ConnectionClosed) if !msgs.is_empty() => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msgs)
+    }
 }
 
 impl SendConn {
@@ -215,11 +330,22 @@ impl SendConn {
         serial
     }
 
+    /// Whether this connection can pass unix fds. This is only the case for unix socket transports
+    /// that successfully negotiated fd passing.
+    pub fn can_pass_unix_fds(&self) -> bool {
+        self.unix_fd
+    }
+
     /// send a message over the conn
+    ///
+    /// Messages that contain unix fds are refused with Error::UnixFdNotSupported if the connection can not pass them.
     pub fn send_message<'a>(
         &'a mut self,
         msg: &'a MarshalledMessage,
     ) -> Result<SendMessageContext<'a>> {
+        if !self.unix_fd && !msg.body.raw_fds.is_empty() {
+            return Err(Error::UnixFdNotSupported);
+        }
         let serial = if let Some(serial) = msg.dynheader.serial {
             serial
         } else {
@@ -250,6 +376,56 @@ impl SendConn {
         let ctx = self.send_message(msg)?;
         ctx.write_all().map_err(force_finish_on_error)
     }
+
+    /// Queue a message to be sent by `flush_pending_writes`. Returns the serial of the message to match the response.
+    ///
+    /// Do not use `send_message` while there are pending writes, the queued messages have to be flushed first.
+    pub fn queue_message(&mut self, mut msg: MarshalledMessage) -> Result<u32> {
+        if !self.unix_fd && !msg.body.raw_fds.is_empty() {
+            return Err(Error::UnixFdNotSupported);
+        }
+        let serial = match msg.dynheader.serial {
+            Some(serial) => serial,
+            None => {
+                let serial = self.alloc_serial();
+                msg.dynheader.serial = Some(serial);
+                serial
+            }
+        };
+        self.pending.push_back(msg);
+        Ok(serial)
+    }
+
+    /// Whether there are queued messages that have not been written completely
+    pub fn has_pending_writes(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Write as much of the queued messages as possible without blocking. Returns true if all of them have been written
+    /// and false if the socket would block. Call this again when the connection is reported writable.
+    ///
+    /// Errors other than a full socket leave the connection in an ill defined state.
+    pub fn flush_pending_writes(&mut self) -> Result<bool> {
+        while let Some(msg) = self.pending.pop_front() {
+            let ctx = match self.pending_progress.take() {
+                Some(progress) => SendMessageContext::resume(self, &msg, progress),
+                None => self.send_message(&msg)?,
+            };
+            let (ctx, e) = match ctx.write(Timeout::Nonblock) {
+                Ok(_) => continue,
+                Err(err) => err,
+            };
+            if let Error::TimedOut = e {
+                let progress = ctx.into_progress();
+                self.pending_progress = Some(progress);
+                self.pending.push_front(msg);
+                return Ok(false);
+            }
+            ctx.force_finish();
+            return Err(e);
+        }
+        Ok(true)
+    }
 }
 
 /// only call if you deem the connection doomed by an error returned from writing.
@@ -399,7 +575,7 @@ impl SendMessageContext<'_> {
             IoSlice::new(header_slice_to_send),
             IoSlice::new(body_slice_to_send),
         ];
-        let flags = MsgFlags::empty();
+        let mut flags = MsgFlags::empty();
 
         let old_timeout = self.conn.stream.write_timeout()?;
         match timeout {
@@ -410,7 +586,7 @@ impl SendMessageContext<'_> {
                 self.conn.stream.set_write_timeout(None)?;
             }
             Timeout::Nonblock => {
-                self.conn.stream.set_nonblocking(true)?;
+                flags |= MsgFlags::MSG_DONTWAIT;
             }
         }
 
@@ -426,16 +602,20 @@ impl SendMessageContext<'_> {
         } else {
             vec![]
         };
-        let bytes_sent = sendmsg::<SockaddrStorage>(
-            self.conn.stream.as_raw_fd(),
-            &iov,
-            &[ControlMessage::ScmRights(&raw_fds)],
-            flags,
-            None,
-        );
+        let scm_rights = [ControlMessage::ScmRights(&raw_fds)];
+        let cmsgs: &[ControlMessage] = if raw_fds.is_empty() {
+            &[]
+        } else {
+            &scm_rights
+        };
+        let bytes_sent =
+            sendmsg::<SockaddrStorage>(self.conn.stream.as_raw_fd(), &iov, cmsgs, flags, None)
+                .map_err(|e| match e {
+                    nix::errno::Errno::EAGAIN => Error::TimedOut,
+                    _ => Error::NixError(e),
+                });
 
         self.conn.stream.set_write_timeout(old_timeout)?;
-        self.conn.stream.set_nonblocking(false)?;
 
         let bytes_sent = bytes_sent?;
 
@@ -446,48 +626,138 @@ impl SendMessageContext<'_> {
 }
 
 impl DuplexConn {
-    /// Connect to a unix socket
+    /// Connect to the first of the addresses that can be connected to. The addresses are tried in order
+    /// and the error of the last attempt is returned if none of them worked.
+    ///
+    /// The timeout applies to connecting and authenticating to each of the addresses.
     ///
     /// Remember to send the mandatory hello message before doing anything else with the connection!
     /// You can use the `send_hello` function for this.
-    pub fn connect_to_bus(addr: UnixAddr, with_unix_fd: bool) -> super::Result<DuplexConn> {
-        let sock = socket(
-            socket::AddressFamily::Unix,
-            socket::SockType::Stream,
-            socket::SockFlag::empty(),
-            None,
-        )?;
-
-        connect(sock, &addr)?;
-        let mut stream = unsafe { UnixStream::from_raw_fd(sock) };
-        match auth::do_auth(&mut stream)? {
-            auth::AuthResult::Ok => {}
-            auth::AuthResult::Rejected => return Err(Error::AuthFailed),
+    pub fn connect_to_bus<A: AsRef<[DBusAddress]>>(
+        addrs: A,
+        with_unix_fd: bool,
+        timeout: Timeout,
+    ) -> super::Result<DuplexConn> {
+        let mut last_err = Error::NoAddressFound;
+        for addr in addrs.as_ref() {
+            match Self::connect_to_addr(addr, with_unix_fd, timeout) {
+                Ok(conn) => return Ok(conn),
+                Err(e) => last_err = e,
+            }
         }
+        Err(last_err)
+    }
 
-        if with_unix_fd {
-            match auth::negotiate_unix_fds(&mut stream)? {
-                auth::AuthResult::Ok => {}
-                auth::AuthResult::Rejected => return Err(Error::UnixFdNegotiationFailed),
+    /// Connect to one address
+    ///
+    /// Remember to send the mandatory hello message before doing anything else with the connection!
+    /// You can use the `send_hello` function for this.
+    pub fn connect_to_addr(
+        addr: &DBusAddress,
+        with_unix_fd: bool,
+        timeout: Timeout,
+    ) -> super::Result<DuplexConn> {
+        let start_time = time::Instant::now();
+
+        let mut stream = match &addr.transport {
+            Transport::Unix(unix) => {
+                let addr = super::unix_sockaddr(unix)?;
+                let sock = socket(
+                    socket::AddressFamily::Unix,
+                    socket::SockType::Stream,
+                    socket::SockFlag::empty(),
+                    None,
+                )?;
+
+                if let Err(e) = connect(sock, &addr) {
+                    let _ = nix::unistd::close(sock);
+                    return Err(e.into());
+                }
+                Stream::Unix(unsafe { UnixStream::from_raw_fd(sock) })
+            }
+            Transport::Tcp(tcp) => Stream::Tcp(super::tcp_connect(tcp, timeout)?),
+            Transport::NonceTcp { tcp, noncefile } => {
+                let noncefile = noncefile.as_ref().ok_or_else(|| {
+                    Error::InvalidAddress(format!("{}: noncefile is required", addr))
+                })?;
+                let mut nonce = [0u8; 16];
+                std::fs::File::open(noncefile)?.read_exact(&mut nonce)?;
+
+                let mut stream = super::tcp_connect(tcp, timeout)?;
+                // the nonce has to be the first thing the server sees, even before the auth null byte
+                stream.write_all(&nonce)?;
+                Stream::Tcp(stream)
+            }
+            Transport::Other { .. } => {
+                return Err(Error::AddressTypeNotSupported(addr.to_string()))
+            }
+        };
+
+        let guid = auth::do_auth(&mut stream, super::calc_timeout_left(&start_time, timeout)?)?;
+        if let Some(expected) = &addr.guid {
+            if *expected != guid {
+                return Err(auth::AuthError::GuidMismatch {
+                    expected: expected.clone(),
+                    received: guid,
+                }
+                .into());
             }
         }
 
-        auth::send_begin(&mut stream)?;
+        // fds can only be passed over unix sockets, so there is no point in asking for it on other transports
+        let unix_fd = with_unix_fd
+            && stream.can_pass_fds()
+            && auth::negotiate_unix_fds(
+                &mut stream,
+                super::calc_timeout_left(&start_time, timeout)?,
+            )?;
+
+        auth::send_begin(&mut stream, super::calc_timeout_left(&start_time, timeout)?)?;
+        // the connection sets its own timeouts on each operation from here on
+        stream.set_timeout(Timeout::Infinite)?;
 
+        Self::from_authenticated_stream(stream, unix_fd, guid)
+    }
+
+    /// Build the connection from a stream that finished the authentication
+    pub(crate) fn from_authenticated_stream(
+        stream: Stream,
+        unix_fd: bool,
+        guid: String,
+    ) -> super::Result<DuplexConn> {
         Ok(DuplexConn {
             send: SendConn {
                 stream: stream.try_clone()?,
                 header_buf: Vec::new(),
+                unix_fd,
                 serial_counter: 1,
+                pending: VecDeque::new(),
+                pending_progress: None,
             },
             recv: RecvConn {
                 msg_buf_in: Vec::new(),
                 cmsgs_in: Vec::new(),
                 stream,
             },
+            guid,
         })
     }
 
+    /// The guid of the server, as sent during the authentication
+    pub fn server_guid(&self) -> &str {
+        &self.guid
+    }
+
+    /// See `RecvConn::try_read_messages`
+    pub fn try_read_messages(&mut self) -> Result<Vec<MarshalledMessage>> {
+        self.recv.try_read_messages()
+    }
+
+    /// See `SendConn::flush_pending_writes`
+    pub fn flush_pending_writes(&mut self) -> Result<bool> {
+        self.send.flush_pending_writes()
+    }
+
     /// Sends the obligatory hello message and returns the unique id the daemon assigned this connection
     pub fn send_hello(&mut self, timeout: crate::connection::Timeout) -> super::Result<String> {
         let start_time = time::Instant::now();
@@ -533,3 +803,56 @@ impl AsRawFd for DuplexConn {
         self.recv.stream.as_raw_fd()
     }
 }
+
+/// Registers the socket with a mio poller. The connection is reported readable when messages can be
+/// read with `try_read_messages`.
+#[cfg(feature = "mio")]
+impl mio::event::Source for RecvConn {
+    fn register(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> std::io::Result<()> {
+        mio::unix::SourceFd(&self.stream.as_raw_fd()).register(registry, token, interests)
+    }
+    fn reregister(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> std::io::Result<()> {
+        mio::unix::SourceFd(&self.stream.as_raw_fd()).reregister(registry, token, interests)
+    }
+    fn deregister(&mut self, registry: &mio::Registry) -> std::io::Result<()> {
+        mio::unix::SourceFd(&self.stream.as_raw_fd()).deregister(registry)
+    }
+}
+
+/// Registers the socket with a mio poller. Readable means `try_read_messages` should be called,
+/// writable means `flush_pending_writes` can make progress.
+///
+/// Only register writable interest while there are pending writes, otherwise the poller wakes up all the time.
+#[cfg(feature = "mio")]
+impl mio::event::Source for DuplexConn {
+    // send and recv are dups of the same socket so registering one of them covers both directions
+    fn register(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> std::io::Result<()> {
+        self.recv.register(registry, token, interests)
+    }
+    fn reregister(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> std::io::Result<()> {
+        self.recv.reregister(registry, token, interests)
+    }
+    fn deregister(&mut self, registry: &mio::Registry) -> std::io::Result<()> {
+        self.recv.deregister(registry)
+    }
+}
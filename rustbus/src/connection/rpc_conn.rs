@@ -3,8 +3,13 @@
 
 use super::ll_conn::DuplexConn;
 use super::*;
+use crate::dbus_error::{DBusError, ErrorName};
+use crate::match_rule::MatchRule;
 use crate::message_builder::MarshalledMessage;
+use crate::message_builder::MessageBuilder;
 use crate::message_builder::MessageType;
+use crate::message_builder::{ArgList, RetList};
+use crate::wire::errors::{MarshalError, UnmarshalError};
 use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::time;
@@ -38,6 +43,60 @@ pub struct RpcConn {
     responses: HashMap<u32, MarshalledMessage>,
     conn: DuplexConn,
     filter: MessageFilter,
+    match_rules: Vec<MatchRule>,
+    subscriptions: Vec<Subscription>,
+    next_subscription_id: u64,
+    unique_name: Option<String>,
+    /// The unique owners of the well-known names used as sender in match rules
+    sender_owners: HashMap<String, Option<String>>,
+}
+
+/// Identifies a subscription made with `RpcConn::subscribe` or `RpcConn::subscribe_with_callback`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+/// Called with every signal that matches the rule of the subscription
+pub type SignalCallback = Box<dyn FnMut(&MarshalledMessage) + Sync + Send>;
+
+/// Called with the watched name and the unique name of its new owner
+pub type NameAppearedCallback = Box<dyn FnMut(&str, &str) + Sync + Send>;
+/// Called with the watched name when it lost its owner
+pub type NameVanishedCallback = Box<dyn FnMut(&str) + Sync + Send>;
+
+/// Shared between the NameOwnerChanged subscription of `watch_name` and the initial GetNameOwner
+struct NameWatch {
+    name: String,
+    owner: Option<String>,
+    reported: bool,
+    on_appeared: NameAppearedCallback,
+    on_vanished: NameVanishedCallback,
+}
+
+impl NameWatch {
+    fn set_owner(&mut self, owner: Option<&str>) {
+        if self.reported && self.owner.as_deref() == owner {
+            return;
+        }
+        if self.owner.is_some() || (!self.reported && owner.is_none()) {
+            (self.on_vanished)(&self.name);
+        }
+        if let Some(owner) = owner {
+            (self.on_appeared)(&self.name, owner);
+        }
+        self.owner = owner.map(str::to_owned);
+        self.reported = true;
+    }
+}
+
+enum SignalSink {
+    Queue(VecDeque<MarshalledMessage>),
+    Callback(SignalCallback),
+}
+
+struct Subscription {
+    id: SubscriptionId,
+    rule: MatchRule,
+    sink: SignalSink,
 }
 
 /// Filter out messages you dont want in your RpcConn.
@@ -76,6 +135,155 @@ pub struct RpcConn {
 /// ```
 pub type MessageFilter = Box<dyn Fn(&MarshalledMessage) -> bool + Sync + Send>;
 
+/// Errors of `RpcConn::call`. Failures of the connection itself are kept apart from errors the called peer replied with.
+#[derive(Debug, Error)]
+pub enum CallError {
+    #[error("The call could not be made: {0}")]
+    Connection(Error),
+    #[error("The call was answered with the error {0}")]
+    ErrorReply(DBusError),
+    #[error("The arguments could not be marshalled: {0}")]
+    InvalidArgs(MarshalError),
+    #[error("The reply did not contain the expected values: {0}")]
+    InvalidReply(UnmarshalError),
+}
+
+impl From<Error> for CallError {
+    fn from(e: Error) -> CallError {
+        match e {
+            // calls that are made internally report error replies this way
+            Error::ErrorReply(e) => CallError::ErrorReply(e),
+            e => CallError::Connection(e),
+        }
+    }
+}
+
+impl From<MarshalError> for CallError {
+    fn from(e: MarshalError) -> CallError {
+        CallError::InvalidArgs(e)
+    }
+}
+
+impl From<UnmarshalError> for CallError {
+    fn from(e: UnmarshalError) -> CallError {
+        CallError::InvalidReply(e)
+    }
+}
+
+impl From<CallError> for Error {
+    fn from(e: CallError) -> Error {
+        match e {
+            CallError::Connection(e) => e,
+            CallError::ErrorReply(e) => Error::ErrorReply(e),
+            CallError::InvalidArgs(e) => Error::MarshalError(e),
+            CallError::InvalidReply(e) => Error::UnmarshalError(e),
+        }
+    }
+}
+
+/// The signals of a subscription, turned into `T` by a parse function. Iterating blocks until the next signal arrives,
+/// a timeout is returned as an error. Use `close` to end the subscription, dropping the stream ends it too but waits
+/// at most a second for the bus and ignores errors.
+///
+/// This is what the signal helpers of proxies generated with `#[dbus_proxy]` return.
+pub struct SignalStream<'a, T> {
+    con: &'a mut RpcConn,
+    id: SubscriptionId,
+    timeout: Timeout,
+    parse: fn(&MarshalledMessage) -> std::result::Result<T, UnmarshalError>,
+    closed: bool,
+}
+
+impl<'a, T> SignalStream<'a, T> {
+    /// Subscribe to the signals matching the rule, `timeout` applies to each signal that is waited for
+    pub fn subscribe(
+        con: &'a mut RpcConn,
+        rule: MatchRule,
+        timeout: Timeout,
+        parse: fn(&MarshalledMessage) -> std::result::Result<T, UnmarshalError>,
+    ) -> Result<Self> {
+        let id = con.subscribe(rule, timeout)?;
+        Ok(SignalStream {
+            con,
+            id,
+            timeout,
+            parse,
+            closed: false,
+        })
+    }
+
+    /// Return the next signal if one is there but dont block
+    pub fn try_next(&mut self) -> Option<std::result::Result<T, CallError>> {
+        let msg = self.con.try_get_subscribed_signal(self.id)?;
+        Some((self.parse)(&msg).map_err(CallError::from))
+    }
+
+    /// End the subscription
+    pub fn close(mut self) -> Result<()> {
+        self.closed = true;
+        self.con.unsubscribe(self.id, self.timeout)
+    }
+}
+
+impl<'a, T> Iterator for SignalStream<'a, T> {
+    type Item = std::result::Result<T, CallError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let msg = match self.con.wait_subscribed_signal(self.id, self.timeout) {
+            Ok(msg) => msg,
+            Err(e) => return Some(Err(e.into())),
+        };
+        Some((self.parse)(&msg).map_err(CallError::from))
+    }
+}
+
+impl<'a, T> Drop for SignalStream<'a, T> {
+    fn drop(&mut self) {
+        if !self.closed {
+            let _ = self
+                .con
+                .unsubscribe(self.id, super::drop_timeout(self.timeout));
+        }
+    }
+}
+
+/// Messages only carry the unique name of the sender. If the rule names a well-known name, the sender has to be
+/// its current owner.
+fn rule_matches_received(
+    rule: &MatchRule,
+    msg: &MarshalledMessage,
+    sender_owners: &HashMap<String, Option<String>>,
+) -> bool {
+    match rule
+        .sender
+        .as_deref()
+        .filter(|sender| is_well_known(sender))
+    {
+        Some(sender) => {
+            let owner = sender_owners.get(sender).and_then(Option::as_deref);
+            owner.is_some()
+                && msg.dynheader.sender.as_deref() == owner
+                && rule.matches_ignoring_sender(msg)
+        }
+        None => rule.matches(msg),
+    }
+}
+
+/// The bus sends its messages as org.freedesktop.DBus, so that name needs no resolving
+fn is_well_known(name: &str) -> bool {
+    !name.starts_with(':') && name != "org.freedesktop.DBus"
+}
+
+/// Delivers the NameOwnerChanged signals that keep the owner of a well-known sender up to date
+fn owner_changed_rule(name: &str) -> MatchRule {
+    MatchRule::new()
+        .with_type(MessageType::Signal)
+        .with_sender("org.freedesktop.DBus")
+        .with_interface("org.freedesktop.DBus")
+        .with_member("NameOwnerChanged")
+        .with_arg(0, name)
+}
+
 impl RpcConn {
     pub fn new(conn: DuplexConn) -> Self {
         RpcConn {
@@ -84,6 +292,11 @@ impl RpcConn {
             responses: HashMap::new(),
             conn,
             filter: Box::new(|_| true),
+            match_rules: Vec::new(),
+            subscriptions: Vec::new(),
+            next_subscription_id: 0,
+            unique_name: None,
+            sender_owners: HashMap::new(),
         }
     }
     pub fn conn(&self) -> &DuplexConn {
@@ -108,8 +321,8 @@ impl RpcConn {
         Self::connect_to_path(session_path, timeout)
     }
 
-    pub fn connect_to_path(path: UnixAddr, timeout: Timeout) -> Result<Self> {
-        let con = DuplexConn::connect_to_bus(path, true)?;
+    pub fn connect_to_path<A: AsRef<[DBusAddress]>>(addrs: A, timeout: Timeout) -> Result<Self> {
+        let con = DuplexConn::connect_to_bus(addrs, true, timeout)?;
         let mut con = Self::new(con);
 
         let mut hello = crate::standard_messages::hello();
@@ -118,14 +331,406 @@ impl RpcConn {
             .write(timeout)
             .map_err(super::ll_conn::force_finish_on_error)?;
 
-        con.wait_response(serial, timeout)?;
+        let reply = con.wait_response(serial, timeout)?;
+        con.unique_name = Some(reply.body.parser().get::<String>()?);
         Ok(con)
     }
 
+    /// The unique name the bus assigned to this connection. Only known if the connection was made with
+    /// `connect_to_path` (or `session_conn`/`system_conn`) which send the hello message.
+    pub fn unique_name(&self) -> Option<&str> {
+        self.unique_name.as_deref()
+    }
+
     pub fn set_filter(&mut self, filter: MessageFilter) {
         self.filter = filter;
     }
 
+    /// Send an AddMatch call for the rule to the bus and wait for the reply. Once a rule has been added, received
+    /// signals are only kept if they match one of the added rules (and the filter).
+    ///
+    /// Other messages that arrive while waiting for the reply are put into the queues as usual.
+    ///
+    /// If the rule names a well-known sender, its owner is looked up and followed, because signals only carry the unique
+    /// name of their sender.
+    pub fn add_match(&mut self, rule: MatchRule, timeout: Timeout) -> Result<()> {
+        let start_time = time::Instant::now();
+        self.track_sender(&rule, timeout)?;
+        let call = crate::standard_messages::add_match(&rule.to_string());
+        if let Err(e) = self.call_bus(call, calc_timeout_left(&start_time, timeout)?) {
+            self.untrack_sender(&rule, timeout)?;
+            return Err(e);
+        }
+        self.match_rules.push(rule);
+        Ok(())
+    }
+
+    /// Send a RemoveMatch call for the rule to the bus and wait for the reply. Signals matching only this rule
+    /// are not kept anymore.
+    pub fn remove_match(&mut self, rule: &MatchRule, timeout: Timeout) -> Result<()> {
+        let start_time = time::Instant::now();
+        let call = crate::standard_messages::remove_match(&rule.to_string());
+        self.call_bus(call, timeout)?;
+        if let Some(idx) = self.match_rules.iter().position(|r| r == rule) {
+            self.match_rules.remove(idx);
+        }
+        self.untrack_sender(rule, calc_timeout_left(&start_time, timeout)?)
+    }
+
+    /// The rules that were added with `add_match`
+    pub fn match_rules(&self) -> &[MatchRule] {
+        &self.match_rules
+    }
+
+    /// Subscribe to the signals matching the rule. They are put into a queue for this subscription instead of the
+    /// general signal queue, get them with `try_get_subscribed_signal`/`wait_subscribed_signal`.
+    ///
+    /// A signal that matches multiple subscriptions is delivered to each of them. The message filter does not apply to
+    /// signals that match a subscription. AddMatch is only sent to the bus for the first subscription with this rule.
+    pub fn subscribe(&mut self, rule: MatchRule, timeout: Timeout) -> Result<SubscriptionId> {
+        self.add_subscription(rule, SignalSink::Queue(VecDeque::new()), timeout)
+    }
+
+    /// Like `subscribe` but the signals are passed to the callback while this connection receives messages.
+    pub fn subscribe_with_callback(
+        &mut self,
+        rule: MatchRule,
+        callback: SignalCallback,
+        timeout: Timeout,
+    ) -> Result<SubscriptionId> {
+        self.add_subscription(rule, SignalSink::Callback(callback), timeout)
+    }
+
+    /// Watch a name on the bus, similar to `g_bus_watch_name`. `on_appeared` is called with the unique name of the owner
+    /// when the name gets an owner, `on_vanished` when it loses it. A change of the owner calls both.
+    ///
+    /// The current owner is asked for right away, so one of the callbacks is called before this returns. Changes are
+    /// reported while this connection receives messages. Pass the returned id to `unsubscribe` to stop watching.
+    /// ```rust,no_run
+    /// use rustbus::{connection::Timeout, RpcConn};
+    ///
+    /// let mut con = RpcConn::session_conn(Timeout::Infinite).unwrap();
+    /// let watch = con
+    ///     .watch_name(
+    ///         "org.freedesktop.Notifications",
+    ///         Box::new(|name, owner| println!("{} is owned by {}", name, owner)),
+    ///         Box::new(|name| println!("{} is gone", name)),
+    ///         Timeout::Infinite,
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn watch_name(
+        &mut self,
+        name: &str,
+        on_appeared: NameAppearedCallback,
+        on_vanished: NameVanishedCallback,
+        timeout: Timeout,
+    ) -> std::result::Result<SubscriptionId, CallError> {
+        let start_time = time::Instant::now();
+        let watch = std::sync::Arc::new(std::sync::Mutex::new(NameWatch {
+            name: name.to_owned(),
+            owner: None,
+            reported: false,
+            on_appeared,
+            on_vanished,
+        }));
+
+        // subscribe first, changes that happen while asking for the owner are then reported before the reply arrives
+        let signal_watch = watch.clone();
+        let id = self.subscribe_with_callback(
+            owner_changed_rule(name),
+            Box::new(move |sig| {
+                if let Ok((_, _, new_owner)) = sig.body.parser().get3::<&str, &str, &str>() {
+                    let new_owner = Some(new_owner).filter(|owner| !owner.is_empty());
+                    signal_watch.lock().unwrap().set_owner(new_owner);
+                }
+            }),
+            timeout,
+        )?;
+
+        let owner = self.call::<_, (String,)>(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "GetNameOwner",
+            (name,),
+            calc_timeout_left(&start_time, timeout)?,
+        );
+        let owner = match owner {
+            Ok((owner,)) => Some(owner),
+            Err(CallError::ErrorReply(e)) if e.name == ErrorName::NameHasNoOwner => None,
+            Err(e) => {
+                let _ = self.unsubscribe(id, timeout);
+                return Err(e);
+            }
+        };
+        watch.lock().unwrap().set_owner(owner.as_deref());
+        Ok(id)
+    }
+
+    /// End the subscription. Signals that are still queued for it are dropped. RemoveMatch is sent to the bus
+    /// once the last subscription with this rule is gone. Unknown ids are ignored.
+    pub fn unsubscribe(&mut self, id: SubscriptionId, timeout: Timeout) -> Result<()> {
+        let idx = match self.subscriptions.iter().position(|sub| sub.id == id) {
+            Some(idx) => idx,
+            None => return Ok(()),
+        };
+        let start_time = time::Instant::now();
+        let sub = self.subscriptions.remove(idx);
+        if !self
+            .subscriptions
+            .iter()
+            .any(|other| other.rule == sub.rule)
+        {
+            let call = crate::standard_messages::remove_match(&sub.rule.to_string());
+            self.call_bus(call, timeout)?;
+        }
+        self.untrack_sender(&sub.rule, calc_timeout_left(&start_time, timeout)?)
+    }
+
+    /// Return a signal of the subscription if one is there but dont block. Always None for subscriptions with a callback.
+    pub fn try_get_subscribed_signal(&mut self, id: SubscriptionId) -> Option<MarshalledMessage> {
+        match self.subscriptions.iter_mut().find(|sub| sub.id == id) {
+            Some(Subscription {
+                sink: SignalSink::Queue(queue),
+                ..
+            }) => queue.pop_front(),
+            _ => None,
+        }
+    }
+
+    /// Return a signal of the subscription if one is there or block until it arrives
+    pub fn wait_subscribed_signal(
+        &mut self,
+        id: SubscriptionId,
+        timeout: Timeout,
+    ) -> Result<MarshalledMessage> {
+        let start_time = time::Instant::now();
+        loop {
+            if let Some(msg) = self.try_get_subscribed_signal(id) {
+                return Ok(msg);
+            }
+            self.refill_once(calc_timeout_left(&start_time, timeout)?)?;
+        }
+    }
+
+    fn add_subscription(
+        &mut self,
+        rule: MatchRule,
+        sink: SignalSink,
+        timeout: Timeout,
+    ) -> Result<SubscriptionId> {
+        let start_time = time::Instant::now();
+        self.track_sender(&rule, timeout)?;
+        if !self.subscriptions.iter().any(|sub| sub.rule == rule) {
+            let call = crate::standard_messages::add_match(&rule.to_string());
+            if let Err(e) = self.call_bus(call, calc_timeout_left(&start_time, timeout)?) {
+                self.untrack_sender(&rule, timeout)?;
+                return Err(e);
+            }
+        }
+        let id = SubscriptionId(self.next_subscription_id);
+        self.next_subscription_id += 1;
+        self.subscriptions.push(Subscription { id, rule, sink });
+        Ok(id)
+    }
+
+    fn uses_sender(&self, name: &str) -> bool {
+        self.match_rules
+            .iter()
+            .chain(self.subscriptions.iter().map(|sub| &sub.rule))
+            .any(|rule| rule.sender.as_deref() == Some(name))
+    }
+
+    /// Start following the owner of the well-known sender of the rule, unless another rule already names it.
+    /// Must be called before the rule is added.
+    fn track_sender(&mut self, rule: &MatchRule, timeout: Timeout) -> Result<()> {
+        let name = match rule
+            .sender
+            .as_deref()
+            .filter(|sender| is_well_known(sender))
+        {
+            Some(name) if !self.sender_owners.contains_key(name) => name.to_owned(),
+            _ => return Ok(()),
+        };
+        let start_time = time::Instant::now();
+
+        // listen for changes first, changes that happen while asking for the owner are then applied before the reply arrives
+        let call = crate::standard_messages::add_match(&owner_changed_rule(&name).to_string());
+        self.call_bus(call, timeout)?;
+        self.sender_owners.insert(name.clone(), None);
+
+        let owner = self.call::<_, (String,)>(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "GetNameOwner",
+            (name.as_str(),),
+            calc_timeout_left(&start_time, timeout)?,
+        );
+        let owner = match owner {
+            Ok((owner,)) => Some(owner),
+            Err(CallError::ErrorReply(e)) if e.name == ErrorName::NameHasNoOwner => None,
+            Err(e) => {
+                self.sender_owners.remove(&name);
+                let call =
+                    crate::standard_messages::remove_match(&owner_changed_rule(&name).to_string());
+                let _ = self.call_bus(call, timeout);
+                return Err(e.into());
+            }
+        };
+        self.sender_owners.insert(name, owner);
+        Ok(())
+    }
+
+    /// Stop following the owner of the well-known sender of the rule once no rule names it anymore.
+    /// Must be called after the rule was removed.
+    fn untrack_sender(&mut self, rule: &MatchRule, timeout: Timeout) -> Result<()> {
+        let name = match rule.sender.as_deref() {
+            Some(name) if self.sender_owners.contains_key(name) && !self.uses_sender(name) => name,
+            _ => return Ok(()),
+        };
+        self.sender_owners.remove(name);
+        let call = crate::standard_messages::remove_match(&owner_changed_rule(name).to_string());
+        self.call_bus(call, timeout)
+    }
+
+    /// Applies NameOwnerChanged signals to the owners of the well-known senders. Returns true if the signal was
+    /// one of those.
+    fn update_sender_owner(&mut self, msg: &MarshalledMessage) -> bool {
+        if msg.typ != MessageType::Signal
+            || msg.dynheader.sender.as_deref() != Some("org.freedesktop.DBus")
+            || msg.dynheader.interface.as_deref() != Some("org.freedesktop.DBus")
+            || msg.dynheader.member.as_deref() != Some("NameOwnerChanged")
+        {
+            return false;
+        }
+        let (name, _, new_owner) = match msg.body.parser().get3::<&str, &str, &str>() {
+            Ok(args) => args,
+            Err(_) => return false,
+        };
+        match self.sender_owners.get_mut(name) {
+            Some(owner) => {
+                *owner = Some(new_owner.to_owned()).filter(|owner| !owner.is_empty());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Hand the signal to all subscriptions it matches. Returns it again if there are none.
+    ///
+    /// NameOwnerChanged signals that were only received to follow the owner of a well-known sender are dropped here.
+    fn dispatch_to_subscriptions(&mut self, msg: MarshalledMessage) -> Option<MarshalledMessage> {
+        if msg.typ != MessageType::Signal {
+            return Some(msg);
+        }
+        let owner_changed = self.update_sender_owner(&msg);
+        let sender_owners = &self.sender_owners;
+        let mut matching = self
+            .subscriptions
+            .iter_mut()
+            .filter(|sub| rule_matches_received(&sub.rule, &msg, sender_owners))
+            .peekable();
+        if matching.peek().is_none() {
+            let wanted = self
+                .match_rules
+                .iter()
+                .any(|rule| rule_matches_received(rule, &msg, sender_owners));
+            if owner_changed && !wanted {
+                return None;
+            }
+            return Some(msg);
+        }
+        for sub in matching {
+            match &mut sub.sink {
+                SignalSink::Queue(queue) => queue.push_back(msg.clone()),
+                SignalSink::Callback(callback) => callback(&msg),
+            }
+        }
+        None
+    }
+
+    fn call_bus(&mut self, call: MarshalledMessage, timeout: Timeout) -> Result<()> {
+        self.call_message(call, timeout)?;
+        Ok(())
+    }
+
+    /// Call a method and wait for the reply. The arguments and the values of the reply are given as tuples, e.g. `()` for
+    /// none or `(name,)` for one, see `ArgList` and `RetList`. A reply with more values than expected is an error.
+    /// ```rust,no_run
+    /// use rustbus::{connection::Timeout, RpcConn};
+    ///
+    /// let mut con = RpcConn::session_conn(Timeout::Infinite).unwrap();
+    /// let (owner,): (String,) = con
+    ///     .call(
+    ///         "org.freedesktop.DBus",
+    ///         "/org/freedesktop/DBus",
+    ///         "org.freedesktop.DBus",
+    ///         "GetNameOwner",
+    ///         ("org.freedesktop.DBus",),
+    ///         Timeout::Infinite,
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn call<Args, Ret>(
+        &mut self,
+        destination: &str,
+        path: &str,
+        interface: &str,
+        member: &str,
+        args: Args,
+        timeout: Timeout,
+    ) -> std::result::Result<Ret, CallError>
+    where
+        Args: ArgList,
+        Ret: RetList,
+    {
+        let mut call = MessageBuilder::new()
+            .call(member)
+            .with_interface(interface)
+            .on(path)
+            .at(destination)
+            .build();
+        call.body.push_args(args)?;
+        let reply = self.call_message(call, timeout)?;
+        let ret = reply.body.get_rets()?;
+        Ok(ret)
+    }
+
+    /// Send the call and wait for the reply. Error replies are turned into `CallError::ErrorReply`.
+    ///
+    /// Other messages that arrive while waiting for the reply are put into the queues as usual.
+    pub fn call_message(
+        &mut self,
+        mut call: MarshalledMessage,
+        timeout: Timeout,
+    ) -> std::result::Result<MarshalledMessage, CallError> {
+        let start_time = time::Instant::now();
+        let serial = self
+            .send_message(&mut call)?
+            .write(timeout)
+            .map_err(super::ll_conn::force_finish_on_error)?;
+        let reply = self.wait_response(serial, calc_timeout_left(&start_time, timeout)?)?;
+        match DBusError::from_message(&reply) {
+            Some(error) => Err(CallError::ErrorReply(error)),
+            None => Ok(reply),
+        }
+    }
+
+    fn keep_message(&self, msg: &MarshalledMessage) -> bool {
+        if msg.typ == MessageType::Signal
+            && !self.match_rules.is_empty()
+            && !self
+                .match_rules
+                .iter()
+                .any(|rule| rule_matches_received(rule, msg, &self.sender_owners))
+        {
+            return false;
+        }
+        self.filter.as_ref()(msg)
+    }
+
     /// Return a response if one is there but dont block
     pub fn try_get_response(&mut self, serial: u32) -> Option<MarshalledMessage> {
         self.responses.remove(&serial)
@@ -183,7 +788,11 @@ impl RpcConn {
     }
 
     fn insert_message_or_send_error(&mut self, msg: MarshalledMessage) -> Result<()> {
-        if self.filter.as_ref()(&msg) {
+        let msg = match self.dispatch_to_subscriptions(msg) {
+            Some(msg) => msg,
+            None => return Ok(()),
+        };
+        if self.keep_message(&msg) {
             match msg.typ {
                 MessageType::Call => {
                     self.calls.push_back(msg);
@@ -273,7 +882,11 @@ impl RpcConn {
                 Err(e) => return Err(e),
                 Ok(m) => m,
             };
-            if self.filter.as_ref()(&msg) {
+            let msg = match self.dispatch_to_subscriptions(msg) {
+                Some(msg) => msg,
+                None => continue,
+            };
+            if self.keep_message(&msg) {
                 match msg.typ {
                     MessageType::Call => {
                         self.calls.push_back(msg);
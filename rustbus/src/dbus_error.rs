@@ -0,0 +1,240 @@
+//! The errors a method call can be answered with. The dbus specification and the bus define a set of well-known error
+//! names in `org.freedesktop.DBus.Error`, services can use their own names too.
+//!
+//! ```rust
+//! use rustbus::dbus_error::{DBusError, ErrorName};
+//! use rustbus::MessageBuilder;
+//!
+//! let mut call = MessageBuilder::new()
+//!     .call("Get")
+//!     .with_interface("org.freedesktop.DBus.Properties")
+//!     .on("/io/killing/spark")
+//!     .build();
+//! call.dynheader.serial = Some(1);
+//!
+//! let error = DBusError::new(ErrorName::UnknownProperty, "There is no such property");
+//! let reply = error.to_message(&call.dynheader);
+//! assert_eq!(reply.typ, rustbus::MessageType::Error);
+//! assert_eq!(DBusError::from_message(&reply), Some(error));
+//! ```
+
+use crate::message_builder::{DynamicHeader, MarshalledMessage, MessageType};
+use thiserror::Error;
+
+/// The name of an error. The names defined in the `org.freedesktop.DBus.Error` namespace have their own variants,
+/// everything else is `Custom`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ErrorName {
+    Failed,
+    NoMemory,
+    ServiceUnknown,
+    NameHasNoOwner,
+    NoReply,
+    IoError,
+    BadAddress,
+    NotSupported,
+    LimitsExceeded,
+    AccessDenied,
+    AuthFailed,
+    NoServer,
+    Timeout,
+    NoNetwork,
+    AddressInUse,
+    Disconnected,
+    InvalidArgs,
+    FileNotFound,
+    FileExists,
+    UnknownMethod,
+    UnknownObject,
+    UnknownInterface,
+    UnknownProperty,
+    PropertyReadOnly,
+    TimedOut,
+    MatchRuleNotFound,
+    MatchRuleInvalid,
+    InvalidSignature,
+    InconsistentMessage,
+    InteractiveAuthorizationRequired,
+    /// Any other error name, e.g. `org.freedesktop.Secret.Error.NoSuchObject`
+    Custom(String),
+}
+
+const PREFIX: &str = "org.freedesktop.DBus.Error.";
+
+/// The well-known names without the prefix
+const WELL_KNOWN: &[(&str, ErrorName)] = &[
+    ("Failed", ErrorName::Failed),
+    ("NoMemory", ErrorName::NoMemory),
+    ("ServiceUnknown", ErrorName::ServiceUnknown),
+    ("NameHasNoOwner", ErrorName::NameHasNoOwner),
+    ("NoReply", ErrorName::NoReply),
+    ("IOError", ErrorName::IoError),
+    ("BadAddress", ErrorName::BadAddress),
+    ("NotSupported", ErrorName::NotSupported),
+    ("LimitsExceeded", ErrorName::LimitsExceeded),
+    ("AccessDenied", ErrorName::AccessDenied),
+    ("AuthFailed", ErrorName::AuthFailed),
+    ("NoServer", ErrorName::NoServer),
+    ("Timeout", ErrorName::Timeout),
+    ("NoNetwork", ErrorName::NoNetwork),
+    ("AddressInUse", ErrorName::AddressInUse),
+    ("Disconnected", ErrorName::Disconnected),
+    ("InvalidArgs", ErrorName::InvalidArgs),
+    ("FileNotFound", ErrorName::FileNotFound),
+    ("FileExists", ErrorName::FileExists),
+    ("UnknownMethod", ErrorName::UnknownMethod),
+    ("UnknownObject", ErrorName::UnknownObject),
+    ("UnknownInterface", ErrorName::UnknownInterface),
+    ("UnknownProperty", ErrorName::UnknownProperty),
+    ("PropertyReadOnly", ErrorName::PropertyReadOnly),
+    ("TimedOut", ErrorName::TimedOut),
+    ("MatchRuleNotFound", ErrorName::MatchRuleNotFound),
+    ("MatchRuleInvalid", ErrorName::MatchRuleInvalid),
+    ("InvalidSignature", ErrorName::InvalidSignature),
+    ("InconsistentMessage", ErrorName::InconsistentMessage),
+    (
+        "InteractiveAuthorizationRequired",
+        ErrorName::InteractiveAuthorizationRequired,
+    ),
+];
+
+impl ErrorName {
+    /// The full name as it is sent in the error name header field
+    pub fn as_str(&self) -> std::borrow::Cow<'_, str> {
+        match self {
+            ErrorName::Custom(name) => name.as_str().into(),
+            known => {
+                let short = WELL_KNOWN
+                    .iter()
+                    .find(|(_, name)| name == known)
+                    .map(|(short, _)| *short)
+                    .unwrap();
+                format!("{}{}", PREFIX, short).into()
+            }
+        }
+    }
+}
+
+impl From<&str> for ErrorName {
+    fn from(name: &str) -> Self {
+        name.strip_prefix(PREFIX)
+            .and_then(|short| WELL_KNOWN.iter().find(|(known, _)| *known == short))
+            .map(|(_, known)| known.clone())
+            .unwrap_or_else(|| ErrorName::Custom(name.to_owned()))
+    }
+}
+
+impl From<String> for ErrorName {
+    fn from(name: String) -> Self {
+        match ErrorName::from(name.as_str()) {
+            ErrorName::Custom(_) => ErrorName::Custom(name),
+            known => known,
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// An error as it is sent in an error reply: the name and, by convention, a human readable message as the first argument
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{name}: {}", .message.as_deref().unwrap_or(""))]
+pub struct DBusError {
+    pub name: ErrorName,
+    pub message: Option<String>,
+}
+
+impl DBusError {
+    pub fn new<N: Into<ErrorName>, S: Into<String>>(name: N, message: S) -> Self {
+        DBusError {
+            name: name.into(),
+            message: Some(message.into()),
+        }
+    }
+
+    /// An error without a message
+    pub fn with_name<N: Into<ErrorName>>(name: N) -> Self {
+        DBusError {
+            name: name.into(),
+            message: None,
+        }
+    }
+
+    /// Build the error reply to the call with this header
+    pub fn to_message(&self, call: &DynamicHeader) -> MarshalledMessage {
+        call.make_error_response(self.name.as_str(), self.message.clone())
+    }
+
+    /// Read the error from an error reply. Returns None if the message is not an error or has no error name.
+    pub fn from_message(msg: &MarshalledMessage) -> Option<Self> {
+        if msg.typ != MessageType::Error {
+            return None;
+        }
+        let name = msg.dynheader.error_name.as_deref()?;
+        Some(DBusError {
+            name: name.into(),
+            message: msg.body.parser().get::<String>().ok(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_builder::MessageBuilder;
+
+    #[test]
+    fn error_names() {
+        for (short, name) in WELL_KNOWN {
+            let full = format!("{}{}", PREFIX, short);
+            assert_eq!(name.as_str(), full);
+            assert_eq!(&ErrorName::from(full.as_str()), name);
+        }
+        assert_eq!(
+            ErrorName::from("org.freedesktop.DBus.Error.IOError"),
+            ErrorName::IoError
+        );
+        assert_eq!(
+            ErrorName::from("org.freedesktop.DBus.Error.SomethingNew"),
+            ErrorName::Custom("org.freedesktop.DBus.Error.SomethingNew".to_owned())
+        );
+        assert_eq!(
+            ErrorName::from("io.killing.spark.Error".to_owned()).to_string(),
+            "io.killing.spark.Error"
+        );
+    }
+
+    #[test]
+    fn error_messages() {
+        let mut call = MessageBuilder::new()
+            .call("Echo")
+            .on("/io/killing/spark")
+            .build();
+        call.dynheader.serial = Some(42);
+        call.dynheader.sender = Some(":1.42".to_owned());
+
+        let error = DBusError::new(ErrorName::AccessDenied, "Not for you");
+        let reply = error.to_message(&call.dynheader);
+        assert_eq!(reply.typ, MessageType::Error);
+        assert_eq!(reply.dynheader.response_serial, Some(42));
+        assert_eq!(reply.dynheader.destination.as_deref(), Some(":1.42"));
+        assert_eq!(
+            reply.dynheader.error_name.as_deref(),
+            Some("org.freedesktop.DBus.Error.AccessDenied")
+        );
+        assert_eq!(DBusError::from_message(&reply), Some(error));
+
+        let error = DBusError::with_name("io.killing.spark.Error.Custom");
+        let reply = error.to_message(&call.dynheader);
+        assert_eq!(DBusError::from_message(&reply), Some(error));
+
+        assert_eq!(DBusError::from_message(&call), None);
+        assert_eq!(
+            DBusError::new(ErrorName::Failed, "it broke").to_string(),
+            "org.freedesktop.DBus.Error.Failed: it broke"
+        );
+    }
+}
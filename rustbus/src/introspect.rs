@@ -0,0 +1,854 @@
+//! Introspection data as it is returned by `org.freedesktop.DBus.Introspectable.Introspect`. A `Node` describes the
+//! interfaces of an object and names its children, `to_xml` turns it into the xml format of the specification and
+//! `from_xml` parses it, e.g. to check that a remote object has the methods with the signatures that are expected.
+//!
+//! ```rust
+//! use rustbus::connection::dispatch_conn::PropertyAccess;
+//! use rustbus::introspect::{Interface, Method, Node, Property, Signal};
+//!
+//! let counter = Interface::new("io.killing.spark.Counter")
+//!     .with_method(Method::new("Add").with_in_arg::<u32>("amount").with_out_arg::<u32>("count"))
+//!     .with_signal(Signal::new("Overflowed"))
+//!     .with_property(Property::new::<String>("Label", PropertyAccess::ReadWrite));
+//! let node = Node::new().with_interface(counter).with_child("child");
+//!
+//! let xml = node.to_xml();
+//! assert!(xml.contains(r#"<arg name="amount" type="u" direction="in"/>"#));
+//! assert!(xml.contains(r#"<property name="Label" type="s" access="readwrite"/>"#));
+//! assert!(xml.contains(r#"<node name="child"/>"#));
+//! ```
+
+mod xml;
+
+use crate::connection::dispatch_conn::PropertyAccess;
+use crate::signature;
+use crate::Signature;
+
+use std::fmt::Write;
+use thiserror::Error;
+
+pub const INTROSPECTABLE_INTERFACE: &str = "org.freedesktop.DBus.Introspectable";
+
+const DOCTYPE: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">"#;
+
+/// Errors that occur while parsing introspection data
+#[derive(Debug, Eq, PartialEq, Error)]
+pub enum Error {
+    #[error("Malformed xml at byte {0}: {1}")]
+    Xml(usize, &'static str),
+    #[error("Expected the element <{expected}> but found <{found}>")]
+    UnexpectedElement {
+        expected: &'static str,
+        found: String,
+    },
+    #[error("The element <{element}> is missing the attribute {attribute}")]
+    MissingAttribute {
+        element: &'static str,
+        attribute: &'static str,
+    },
+    #[error("Invalid value {value:?} for the attribute {attribute} of <{element}>")]
+    InvalidAttribute {
+        element: &'static str,
+        attribute: &'static str,
+        value: String,
+    },
+    #[error("Invalid type {0:?}: {1}")]
+    InvalidType(String, signature::Error),
+}
+
+/// An object with its interfaces and the child objects below it
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Node {
+    /// The name relative to the parent node. The node that was introspected has no name.
+    pub name: Option<String>,
+    pub interfaces: Vec<Interface>,
+    pub nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    pub name: String,
+    pub methods: Vec<Method>,
+    pub signals: Vec<Signal>,
+    pub properties: Vec<Property>,
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Method {
+    pub name: String,
+    pub args: Vec<Arg>,
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signal {
+    pub name: String,
+    pub args: Vec<Arg>,
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Property {
+    pub name: String,
+    pub typ: signature::Type,
+    pub access: PropertyAccess,
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+impl Direction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Direction::In => "in",
+            Direction::Out => "out",
+        }
+    }
+}
+
+/// An argument of a method or signal. Signal arguments have no direction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arg {
+    pub name: Option<String>,
+    pub typ: signature::Type,
+    pub direction: Option<Direction>,
+    pub annotations: Vec<Annotation>,
+}
+
+/// Additional information about an element, e.g. `org.freedesktop.DBus.Deprecated`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub name: String,
+    pub value: String,
+}
+
+impl Node {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_interface(mut self, interface: Interface) -> Self {
+        self.interfaces.push(interface);
+        self
+    }
+
+    /// Add an empty child node. Clients introspect it on its own to learn about its interfaces.
+    pub fn with_child<S: Into<String>>(mut self, name: S) -> Self {
+        self.nodes.push(Node {
+            name: Some(name.into()),
+            ..Node::default()
+        });
+        self
+    }
+
+    pub fn interface(&self, name: &str) -> Option<&Interface> {
+        self.interfaces.iter().find(|iface| iface.name == name)
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Node> {
+        self.nodes
+            .iter()
+            .find(|node| node.name.as_deref() == Some(name))
+    }
+
+    /// Parse introspection data, e.g. the reply to Introspect. Elements that are not part of the format, like
+    /// documentation, are ignored.
+    ///
+    /// ```rust
+    /// use rustbus::introspect::{Direction, Node};
+    /// use rustbus::signature::{Base, Type};
+    ///
+    /// let xml = r#"
+    /// <node name="/io/killing/spark">
+    ///   <interface name="io.killing.spark.Counter">
+    ///     <method name="Add">
+    ///       <arg name="amount" type="u"/>
+    ///       <arg name="count" type="u" direction="out"/>
+    ///     </method>
+    ///     <property name="Label" type="s" access="readwrite"/>
+    ///   </interface>
+    ///   <node name="child"/>
+    /// </node>"#;
+    /// let node = Node::from_xml(xml).unwrap();
+    /// let counter = node.interface("io.killing.spark.Counter").unwrap();
+    /// let add = counter.method("Add").unwrap();
+    /// assert_eq!(add.out_args().next().unwrap().typ, Type::Base(Base::Uint32));
+    /// assert_eq!(add.args[1].direction, Some(Direction::Out));
+    /// assert!(node.child("child").is_some());
+    ///
+    /// assert_eq!(Node::from_xml(&node.to_xml()).unwrap(), node);
+    /// ```
+    pub fn from_xml(xml: &str) -> Result<Self, Error> {
+        let root = xml::parse(xml)?;
+        if root.name != "node" {
+            return Err(Error::UnexpectedElement {
+                expected: "node",
+                found: root.name.to_owned(),
+            });
+        }
+        parse_node(&root)
+    }
+
+    /// The xml document with the doctype of the introspection format
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::new();
+        xml.push_str(DOCTYPE);
+        xml.push('\n');
+        self.write_xml(&mut xml, 0);
+        xml
+    }
+
+    fn write_xml(&self, xml: &mut String, depth: usize) {
+        let attrs = match &self.name {
+            Some(name) => vec![("name", name.as_str())],
+            None => vec![],
+        };
+        if self.interfaces.is_empty() && self.nodes.is_empty() {
+            empty_element(xml, depth, "node", &attrs);
+            return;
+        }
+        open_element(xml, depth, "node", &attrs);
+        for interface in &self.interfaces {
+            interface.write_xml(xml, depth + 1);
+        }
+        for node in &self.nodes {
+            node.write_xml(xml, depth + 1);
+        }
+        close_element(xml, depth, "node");
+    }
+}
+
+impl Interface {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Interface {
+            name: name.into(),
+            methods: Vec::new(),
+            signals: Vec::new(),
+            properties: Vec::new(),
+            annotations: Vec::new(),
+        }
+    }
+
+    pub fn with_method(mut self, method: Method) -> Self {
+        self.methods.push(method);
+        self
+    }
+
+    pub fn with_signal(mut self, signal: Signal) -> Self {
+        self.signals.push(signal);
+        self
+    }
+
+    pub fn with_property(mut self, property: Property) -> Self {
+        self.properties.push(property);
+        self
+    }
+
+    pub fn with_annotation<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.annotations.push(Annotation::new(name, value));
+        self
+    }
+
+    pub fn method(&self, name: &str) -> Option<&Method> {
+        self.methods.iter().find(|method| method.name == name)
+    }
+
+    pub fn signal(&self, name: &str) -> Option<&Signal> {
+        self.signals.iter().find(|signal| signal.name == name)
+    }
+
+    pub fn property(&self, name: &str) -> Option<&Property> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+    }
+
+    /// `org.freedesktop.DBus.Introspectable`
+    pub fn introspectable() -> Self {
+        Interface::new(INTROSPECTABLE_INTERFACE)
+            .with_method(Method::new("Introspect").with_out_arg::<String>("xml_data"))
+    }
+
+    /// `org.freedesktop.DBus.Properties`
+    pub fn properties() -> Self {
+        use crate::wire::OwnedVariant;
+        use std::collections::HashMap;
+
+        Interface::new(crate::properties::PROPERTIES_INTERFACE)
+            .with_method(
+                Method::new("Get")
+                    .with_in_arg::<String>("interface_name")
+                    .with_in_arg::<String>("property_name")
+                    .with_out_arg::<OwnedVariant>("value"),
+            )
+            .with_method(
+                Method::new("Set")
+                    .with_in_arg::<String>("interface_name")
+                    .with_in_arg::<String>("property_name")
+                    .with_in_arg::<OwnedVariant>("value"),
+            )
+            .with_method(
+                Method::new("GetAll")
+                    .with_in_arg::<String>("interface_name")
+                    .with_out_arg::<HashMap<String, OwnedVariant>>("props"),
+            )
+            .with_signal(
+                Signal::new("PropertiesChanged")
+                    .with_arg::<String>("interface_name")
+                    .with_arg::<HashMap<String, OwnedVariant>>("changed_properties")
+                    .with_arg::<Vec<String>>("invalidated_properties"),
+            )
+    }
+
+    fn write_xml(&self, xml: &mut String, depth: usize) {
+        let attrs = [("name", self.name.as_str())];
+        if self.methods.is_empty()
+            && self.signals.is_empty()
+            && self.properties.is_empty()
+            && self.annotations.is_empty()
+        {
+            empty_element(xml, depth, "interface", &attrs);
+            return;
+        }
+        open_element(xml, depth, "interface", &attrs);
+        for method in &self.methods {
+            write_member(
+                xml,
+                depth + 1,
+                "method",
+                &method.name,
+                &method.args,
+                &method.annotations,
+            );
+        }
+        for signal in &self.signals {
+            write_member(
+                xml,
+                depth + 1,
+                "signal",
+                &signal.name,
+                &signal.args,
+                &signal.annotations,
+            );
+        }
+        for property in &self.properties {
+            property.write_xml(xml, depth + 1);
+        }
+        write_annotations(xml, depth + 1, &self.annotations);
+        close_element(xml, depth, "interface");
+    }
+}
+
+impl Method {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Method {
+            name: name.into(),
+            args: Vec::new(),
+            annotations: Vec::new(),
+        }
+    }
+
+    /// Add an argument of the call, the type is taken from the `Signature` impl of T
+    pub fn with_in_arg<T: Signature>(mut self, name: &str) -> Self {
+        self.args
+            .push(Arg::new::<T>(name).with_direction(Direction::In));
+        self
+    }
+
+    /// Add a value of the reply, the type is taken from the `Signature` impl of T
+    pub fn with_out_arg<T: Signature>(mut self, name: &str) -> Self {
+        self.args
+            .push(Arg::new::<T>(name).with_direction(Direction::Out));
+        self
+    }
+
+    pub fn with_annotation<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.annotations.push(Annotation::new(name, value));
+        self
+    }
+
+    pub fn in_args(&self) -> impl Iterator<Item = &Arg> {
+        self.args
+            .iter()
+            .filter(|arg| arg.direction != Some(Direction::Out))
+    }
+
+    pub fn out_args(&self) -> impl Iterator<Item = &Arg> {
+        self.args
+            .iter()
+            .filter(|arg| arg.direction == Some(Direction::Out))
+    }
+}
+
+impl Signal {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Signal {
+            name: name.into(),
+            args: Vec::new(),
+            annotations: Vec::new(),
+        }
+    }
+
+    /// Add an argument, the type is taken from the `Signature` impl of T
+    pub fn with_arg<T: Signature>(mut self, name: &str) -> Self {
+        self.args.push(Arg::new::<T>(name));
+        self
+    }
+
+    pub fn with_annotation<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.annotations.push(Annotation::new(name, value));
+        self
+    }
+}
+
+impl Property {
+    /// The type is taken from the `Signature` impl of T
+    pub fn new<T: Signature>(name: &str, access: PropertyAccess) -> Self {
+        Property {
+            name: name.to_owned(),
+            typ: T::signature(),
+            access,
+            annotations: Vec::new(),
+        }
+    }
+
+    pub fn with_annotation<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.annotations.push(Annotation::new(name, value));
+        self
+    }
+
+    fn write_xml(&self, xml: &mut String, depth: usize) {
+        let typ = type_str(&self.typ);
+        let attrs = [
+            ("name", self.name.as_str()),
+            ("type", typ.as_str()),
+            ("access", self.access.as_str()),
+        ];
+        if self.annotations.is_empty() {
+            empty_element(xml, depth, "property", &attrs);
+        } else {
+            open_element(xml, depth, "property", &attrs);
+            write_annotations(xml, depth + 1, &self.annotations);
+            close_element(xml, depth, "property");
+        }
+    }
+}
+
+impl Arg {
+    /// An argument without a direction, the type is taken from the `Signature` impl of T
+    pub fn new<T: Signature>(name: &str) -> Self {
+        Arg {
+            name: Some(name.to_owned()),
+            typ: T::signature(),
+            direction: None,
+            annotations: Vec::new(),
+        }
+    }
+
+    pub fn with_direction(mut self, direction: Direction) -> Self {
+        self.direction = Some(direction);
+        self
+    }
+}
+
+impl Annotation {
+    pub fn new<N: Into<String>, V: Into<String>>(name: N, value: V) -> Self {
+        Annotation {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+}
+
+fn required_attr(
+    element: &xml::Element,
+    name: &'static str,
+    attribute: &'static str,
+) -> Result<String, Error> {
+    element
+        .attr(attribute)
+        .map(str::to_owned)
+        .ok_or(Error::MissingAttribute {
+            element: name,
+            attribute,
+        })
+}
+
+fn parse_type(element: &xml::Element, name: &'static str) -> Result<signature::Type, Error> {
+    let sig = required_attr(element, name, "type")?;
+    let mut types =
+        signature::Type::parse_description(&sig).map_err(|e| Error::InvalidType(sig.clone(), e))?;
+    if types.len() != 1 {
+        return Err(Error::InvalidType(sig, signature::Error::TooManyTypes));
+    }
+    Ok(types.remove(0))
+}
+
+/// The child elements with the name
+fn children<'a, 'e>(
+    element: &'e xml::Element<'a>,
+    name: &'static str,
+) -> impl Iterator<Item = &'e xml::Element<'a>> {
+    element
+        .children
+        .iter()
+        .filter(move |child| child.name == name)
+}
+
+fn parse_node(element: &xml::Element) -> Result<Node, Error> {
+    Ok(Node {
+        name: element.attr("name").map(str::to_owned),
+        interfaces: children(element, "interface")
+            .map(parse_interface)
+            .collect::<Result<_, _>>()?,
+        nodes: children(element, "node")
+            .map(parse_node)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+fn parse_interface(element: &xml::Element) -> Result<Interface, Error> {
+    Ok(Interface {
+        name: required_attr(element, "interface", "name")?,
+        methods: children(element, "method")
+            .map(|method| {
+                Ok(Method {
+                    name: required_attr(method, "method", "name")?,
+                    args: parse_args(method)?,
+                    annotations: parse_annotations(method)?,
+                })
+            })
+            .collect::<Result<_, Error>>()?,
+        signals: children(element, "signal")
+            .map(|signal| {
+                Ok(Signal {
+                    name: required_attr(signal, "signal", "name")?,
+                    args: parse_args(signal)?,
+                    annotations: parse_annotations(signal)?,
+                })
+            })
+            .collect::<Result<_, Error>>()?,
+        properties: children(element, "property")
+            .map(parse_property)
+            .collect::<Result<_, _>>()?,
+        annotations: parse_annotations(element)?,
+    })
+}
+
+fn parse_args(element: &xml::Element) -> Result<Vec<Arg>, Error> {
+    children(element, "arg")
+        .map(|arg| {
+            let direction = match arg.attr("direction") {
+                None => None,
+                Some("in") => Some(Direction::In),
+                Some("out") => Some(Direction::Out),
+                Some(value) => {
+                    return Err(Error::InvalidAttribute {
+                        element: "arg",
+                        attribute: "direction",
+                        value: value.to_owned(),
+                    })
+                }
+            };
+            Ok(Arg {
+                name: arg.attr("name").map(str::to_owned),
+                typ: parse_type(arg, "arg")?,
+                direction,
+                annotations: parse_annotations(arg)?,
+            })
+        })
+        .collect()
+}
+
+fn parse_property(element: &xml::Element) -> Result<Property, Error> {
+    let access = match required_attr(element, "property", "access")?.as_str() {
+        "read" => PropertyAccess::Read,
+        "write" => PropertyAccess::Write,
+        "readwrite" => PropertyAccess::ReadWrite,
+        value => {
+            return Err(Error::InvalidAttribute {
+                element: "property",
+                attribute: "access",
+                value: value.to_owned(),
+            })
+        }
+    };
+    Ok(Property {
+        name: required_attr(element, "property", "name")?,
+        typ: parse_type(element, "property")?,
+        access,
+        annotations: parse_annotations(element)?,
+    })
+}
+
+fn parse_annotations(element: &xml::Element) -> Result<Vec<Annotation>, Error> {
+    children(element, "annotation")
+        .map(|annotation| {
+            Ok(Annotation {
+                name: required_attr(annotation, "annotation", "name")?,
+                value: required_attr(annotation, "annotation", "value")?,
+            })
+        })
+        .collect()
+}
+
+fn type_str(typ: &signature::Type) -> String {
+    let mut sig = String::new();
+    typ.to_str(&mut sig);
+    sig
+}
+
+fn write_member(
+    xml: &mut String,
+    depth: usize,
+    element: &str,
+    name: &str,
+    args: &[Arg],
+    annotations: &[Annotation],
+) {
+    let attrs = [("name", name)];
+    if args.is_empty() && annotations.is_empty() {
+        empty_element(xml, depth, element, &attrs);
+        return;
+    }
+    open_element(xml, depth, element, &attrs);
+    for arg in args {
+        let typ = type_str(&arg.typ);
+        let mut attrs = Vec::new();
+        if let Some(name) = &arg.name {
+            attrs.push(("name", name.as_str()));
+        }
+        attrs.push(("type", typ.as_str()));
+        if let Some(direction) = arg.direction {
+            attrs.push(("direction", direction.as_str()));
+        }
+        if arg.annotations.is_empty() {
+            empty_element(xml, depth + 1, "arg", &attrs);
+        } else {
+            open_element(xml, depth + 1, "arg", &attrs);
+            write_annotations(xml, depth + 2, &arg.annotations);
+            close_element(xml, depth + 1, "arg");
+        }
+    }
+    write_annotations(xml, depth + 1, annotations);
+    close_element(xml, depth, element);
+}
+
+fn write_annotations(xml: &mut String, depth: usize, annotations: &[Annotation]) {
+    for annotation in annotations {
+        empty_element(
+            xml,
+            depth,
+            "annotation",
+            &[
+                ("name", annotation.name.as_str()),
+                ("value", annotation.value.as_str()),
+            ],
+        );
+    }
+}
+
+fn write_start(xml: &mut String, depth: usize, element: &str, attrs: &[(&str, &str)]) {
+    for _ in 0..depth {
+        xml.push_str("  ");
+    }
+    xml.push('<');
+    xml.push_str(element);
+    for (name, value) in attrs {
+        write!(xml, " {}=\"{}\"", name, escape(value)).unwrap();
+    }
+}
+
+fn open_element(xml: &mut String, depth: usize, element: &str, attrs: &[(&str, &str)]) {
+    write_start(xml, depth, element, attrs);
+    xml.push_str(">\n");
+}
+
+fn empty_element(xml: &mut String, depth: usize, element: &str, attrs: &[(&str, &str)]) {
+    write_start(xml, depth, element, attrs);
+    xml.push_str("/>\n");
+}
+
+fn close_element(xml: &mut String, depth: usize, element: &str) {
+    for _ in 0..depth {
+        xml.push_str("  ");
+    }
+    writeln!(xml, "</{}>", element).unwrap();
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signature::{Base, Container, Type};
+
+    const BUS_XML: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+"http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<?xml version="1.0"?>
+<!-- a comment with <node> in it -->
+<node xmlns:doc="http://www.freedesktop.org/dbus/1.0/doc.dtd">
+  <interface name='org.freedesktop.DBus'>
+    <method name="RequestName">
+      <arg direction="in" type="s"/>
+      <arg direction="in" type="u"/>
+      <arg direction="out" type="u"/>
+    </method>
+    <method name="GetConnectionCredentials">
+      <arg direction="in" type="s"/>
+      <arg direction="out" type="a{sv}">
+        <annotation name="org.qtproject.QtDBus.QtTypeName.Out0" value="QVariantMap"/>
+      </arg>
+    </method>
+    <signal name="NameOwnerChanged">
+      <arg type="s"/>
+      <arg type="s"/>
+      <arg type="s"/>
+    </signal>
+    <property name="Features" type="as" access="read">
+      <doc:doc><doc:summary>Text &amp; more</doc:summary></doc:doc>
+      <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
+    </property>
+    <annotation name="org.example.Quoted" value="&quot;a&lt;b&quot; &#x41;&#66;"/>
+  </interface>
+  <node name="org"/>
+  <node name="test"><interface name="org.example.Empty"/></node>
+</node>
+"#;
+
+    #[test]
+    fn parse_bus_introspection() {
+        let node = Node::from_xml(BUS_XML).unwrap();
+        assert_eq!(node.name, None);
+        assert_eq!(node.interfaces.len(), 1);
+        let bus = node.interface("org.freedesktop.DBus").unwrap();
+
+        let request = bus.method("RequestName").unwrap();
+        assert_eq!(request.in_args().count(), 2);
+        assert_eq!(request.out_args().count(), 1);
+        assert_eq!(request.args[0].name, None);
+        assert_eq!(request.args[0].typ, Type::Base(Base::String));
+
+        let credentials = bus.method("GetConnectionCredentials").unwrap();
+        assert_eq!(
+            credentials.args[1].typ,
+            Type::Container(Container::Dict(
+                Base::String,
+                Box::new(Type::Container(Container::Variant))
+            ))
+        );
+        assert_eq!(credentials.args[1].annotations[0].value, "QVariantMap");
+
+        let signal = bus.signal("NameOwnerChanged").unwrap();
+        assert_eq!(signal.args.len(), 3);
+        assert!(signal.args.iter().all(|arg| arg.direction.is_none()));
+
+        let features = bus.property("Features").unwrap();
+        assert_eq!(features.access, PropertyAccess::Read);
+        assert_eq!(
+            features.typ,
+            Type::Container(Container::Array(Box::new(Type::Base(Base::String))))
+        );
+        assert_eq!(features.annotations.len(), 1);
+        assert_eq!(bus.annotations[0].value, r#""a<b" AB"#);
+
+        assert_eq!(node.nodes.len(), 2);
+        assert!(node.child("org").unwrap().interfaces.is_empty());
+        let test = node.child("test").unwrap();
+        assert_eq!(test.interfaces[0], Interface::new("org.example.Empty"));
+
+        assert_eq!(Node::from_xml(&node.to_xml()).unwrap(), node);
+    }
+
+    #[test]
+    fn serialize_and_parse() {
+        let node = Node::new()
+            .with_interface(Interface::introspectable())
+            .with_interface(Interface::properties())
+            .with_interface(
+                Interface::new("io.killing.spark")
+                    .with_property(Property::new::<(u8, String)>("Pair", PropertyAccess::Write))
+                    .with_annotation("io.killing.spark.Note", "<&'\">"),
+            )
+            .with_child("a")
+            .with_child("b");
+        let xml = node.to_xml();
+        assert!(xml.contains(r#"value="&lt;&amp;&apos;&quot;&gt;""#));
+        assert_eq!(Node::from_xml(&xml).unwrap(), node);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Node::from_xml("<interface name=\"a.b\"/>"),
+            Err(Error::UnexpectedElement {
+                expected: "node",
+                found: "interface".to_owned()
+            })
+        );
+        assert_eq!(
+            Node::from_xml("<node><interface/></node>"),
+            Err(Error::MissingAttribute {
+                element: "interface",
+                attribute: "name"
+            })
+        );
+        assert_eq!(
+            Node::from_xml(
+                r#"<node><interface name="a.b"><property name="P" type="s" access="all"/></interface></node>"#
+            ),
+            Err(Error::InvalidAttribute {
+                element: "property",
+                attribute: "access",
+                value: "all".to_owned()
+            })
+        );
+        assert_eq!(
+            Node::from_xml(
+                r#"<node><interface name="a.b"><signal name="S"><arg type="ss"/></signal></interface></node>"#
+            ),
+            Err(Error::InvalidType(
+                "ss".to_owned(),
+                crate::signature::Error::TooManyTypes
+            ))
+        );
+        assert!(matches!(
+            Node::from_xml(
+                r#"<node><interface name="a.b"><signal name="S"><arg type="a{"/></signal></interface></node>"#
+            ),
+            Err(Error::InvalidType(_, _))
+        ));
+        assert!(matches!(
+            Node::from_xml("<node><interface name=\"a.b\"></node>"),
+            Err(Error::Xml(_, _))
+        ));
+        assert!(matches!(Node::from_xml("<node>"), Err(Error::Xml(_, _))));
+        assert!(matches!(
+            Node::from_xml("<node/><node/>"),
+            Err(Error::Xml(_, _))
+        ));
+        assert!(matches!(
+            Node::from_xml(r#"<node name="&bogus;"/>"#),
+            Err(Error::Xml(_, _))
+        ));
+    }
+}
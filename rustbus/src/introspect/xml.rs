@@ -0,0 +1,225 @@
+//! A small xml reader that is just good enough for introspection data. Processing instructions, comments, the doctype
+//! and text content are skipped, entities in attribute values are resolved.
+
+use super::Error;
+
+/// An element with its attributes and child elements
+#[derive(Debug)]
+pub(super) struct Element<'a> {
+    pub name: &'a str,
+    pub attrs: Vec<(&'a str, String)>,
+    pub children: Vec<Element<'a>>,
+}
+
+impl<'a> Element<'a> {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(attr, _)| *attr == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Parse the document into the tree of its elements
+pub(super) fn parse(xml: &str) -> Result<Element<'_>, Error> {
+    let mut reader = Reader { xml, pos: 0 };
+    // elements that are still open, the last one is the innermost
+    let mut open: Vec<Element> = Vec::new();
+    let root = loop {
+        let element = match reader.next_tag()? {
+            Some(Tag::Start(element, false)) => {
+                open.push(element);
+                continue;
+            }
+            Some(Tag::Start(element, true)) => element,
+            Some(Tag::End(name)) => match open.pop() {
+                Some(element) if element.name == name => element,
+                _ => return Err(reader.error("Closing tag does not match the open element")),
+            },
+            None => return Err(reader.error("Unexpected end of the document")),
+        };
+        match open.last_mut() {
+            Some(parent) => parent.children.push(element),
+            None => break element,
+        }
+    };
+    // nothing but comments and the like may follow the root element
+    match reader.next_tag()? {
+        None => Ok(root),
+        Some(_) => Err(reader.error("More than one root element")),
+    }
+}
+
+enum Tag<'a> {
+    /// The element and whether it was empty, like `<node/>`
+    Start(Element<'a>, bool),
+    End(&'a str),
+}
+
+struct Reader<'a> {
+    xml: &'a str,
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn error(&self, msg: &'static str) -> Error {
+        Error::Xml(self.pos, msg)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.xml[self.pos..]
+    }
+
+    fn skip_past(&mut self, end: &str) -> Result<(), Error> {
+        match self.rest().find(end) {
+            Some(idx) => {
+                self.pos += idx + end.len();
+                Ok(())
+            }
+            None => Err(self.error("Unexpected end of the document")),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), Error> {
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            Ok(())
+        } else {
+            Err(self.error("Unexpected character"))
+        }
+    }
+
+    fn name(&mut self) -> Result<&'a str, Error> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/' || c == '>')
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error("Expected a name"));
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    /// The doctype may contain an internal subset in brackets which can contain '>'
+    fn skip_doctype(&mut self) -> Result<(), Error> {
+        let mut depth = 0usize;
+        for (idx, c) in self.rest().char_indices() {
+            match c {
+                '[' => depth += 1,
+                ']' => depth = depth.saturating_sub(1),
+                '>' if depth == 0 => {
+                    self.pos += idx + 1;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        Err(self.error("Unexpected end of the document"))
+    }
+
+    fn next_tag(&mut self) -> Result<Option<Tag<'a>>, Error> {
+        loop {
+            // text content has no meaning in introspection data
+            match self.rest().find('<') {
+                Some(idx) => self.pos += idx,
+                None => {
+                    self.pos = self.xml.len();
+                    return Ok(None);
+                }
+            }
+            let rest = self.rest();
+            if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if rest.starts_with("<![CDATA[") {
+                self.skip_past("]]>")?;
+            } else if rest.starts_with("<!") {
+                self.skip_doctype()?;
+            } else if rest.starts_with("</") {
+                self.pos += 2;
+                let name = self.name()?;
+                self.skip_whitespace();
+                self.expect(">")?;
+                return Ok(Some(Tag::End(name)));
+            } else {
+                self.pos += 1;
+                return self.start_tag().map(Some);
+            }
+        }
+    }
+
+    fn start_tag(&mut self) -> Result<Tag<'a>, Error> {
+        let mut element = Element {
+            name: self.name()?,
+            attrs: Vec::new(),
+            children: Vec::new(),
+        };
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.pos += 2;
+                return Ok(Tag::Start(element, true));
+            }
+            if self.rest().starts_with('>') {
+                self.pos += 1;
+                return Ok(Tag::Start(element, false));
+            }
+            let name = self.name()?;
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+            let quote = match self.rest().chars().next() {
+                Some(quote @ '"') | Some(quote @ '\'') => quote,
+                _ => return Err(self.error("Expected a quoted attribute value")),
+            };
+            self.pos += 1;
+            let len = match self.rest().find(quote) {
+                Some(len) => len,
+                None => return Err(self.error("Unexpected end of the document")),
+            };
+            let value =
+                unescape(&self.rest()[..len]).ok_or_else(|| self.error("Invalid entity"))?;
+            self.pos += len + 1;
+            element.attrs.push((name, value));
+        }
+    }
+}
+
+fn unescape(value: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(idx) = rest.find('&') {
+        unescaped.push_str(&rest[..idx]);
+        rest = &rest[idx + 1..];
+        let end = rest.find(';')?;
+        let entity = &rest[..end];
+        let c = match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = if let Some(hex) = entity.strip_prefix("#x") {
+                    u32::from_str_radix(hex, 16).ok()?
+                } else if let Some(dec) = entity.strip_prefix('#') {
+                    dec.parse().ok()?
+                } else {
+                    return None;
+                };
+                std::char::from_u32(code)?
+            }
+        };
+        unescaped.push(c);
+        rest = &rest[end + 1..];
+    }
+    unescaped.push_str(rest);
+    Some(unescaped)
+}
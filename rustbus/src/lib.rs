@@ -15,7 +15,7 @@
 //!     // To get a connection going you need to connect to a bus.
 //!     // You will likely use either the session or the system bus.
 //!     let session_path = get_session_bus_path()?;
-//!     let mut con = DuplexConn::connect_to_bus(session_path, true)?;
+//!     let mut con = DuplexConn::connect_to_bus(session_path, true, Timeout::Infinite)?;
 //!     // Dont forget to send the obligatory hello message.
 //!     // send_hello wraps the call and parses the response for convenience.
 //!     let unique_name = con.send_hello(Timeout::Infinite)?;
@@ -94,10 +94,16 @@
 //! be faster. The default byteorder is little endian.
 
 pub mod auth;
+pub mod broker;
+pub mod bus;
 pub mod connection;
+pub mod dbus_error;
+pub mod introspect;
+pub mod match_rule;
 pub mod message_builder;
 pub mod params;
 pub mod peer;
+pub mod properties;
 pub mod signature;
 pub mod standard_messages;
 pub mod wire;
@@ -107,7 +113,11 @@ pub use rustbus_derive::*;
 
 // TODO create a rustbus::prelude
 
+// needed to answer calls with errors
+pub use dbus_error::DBusError;
+
 // needed to make own filters in RpcConn
+pub use match_rule::MatchRule;
 pub use message_builder::MessageType;
 
 // needed to create a connection
@@ -0,0 +1,544 @@
+//! Match rules as used by the AddMatch and RemoveMatch methods of the bus to select which messages a connection receives
+//!
+//! Rules can be parsed from the string format of the dbus specification or put together with the builder methods.
+//! The `Display` impl produces the string format again, so a rule can be sent to the bus with `add_match`.
+//!
+//! ```rust
+//! use rustbus::{MatchRule, MessageType};
+//!
+//! let rule = MatchRule::new()
+//!     .with_type(MessageType::Signal)
+//!     .with_interface("org.freedesktop.DBus")
+//!     .with_member("NameOwnerChanged")
+//!     .with_arg(0, "io.killing.spark");
+//! assert_eq!(
+//!     rule.to_string(),
+//!     "type='signal',interface='org.freedesktop.DBus',member='NameOwnerChanged',arg0='io.killing.spark'"
+//! );
+//! assert_eq!(MatchRule::parse(&rule.to_string()).unwrap(), rule);
+//! ```
+
+use crate::message_builder::{MarshalledMessage, MessageType};
+use crate::wire::ObjectPath;
+use thiserror::Error;
+
+/// Arguments can only be matched up to this index
+pub const MAX_ARG_INDEX: u8 = 63;
+
+/// Errors that can occur while parsing a match rule
+#[derive(Debug, Eq, PartialEq, Error)]
+pub enum MatchRuleError {
+    #[error("Malformed match rule: {0}")]
+    Malformed(String),
+    #[error("Unknown key in match rule: {0}")]
+    UnknownKey(String),
+    #[error("Key appears more than once in match rule: {0}")]
+    DuplicateKey(String),
+    #[error("Invalid value for key {key}: {value}")]
+    InvalidValue { key: String, value: String },
+}
+
+/// A match rule. Every condition that is set must be met for a message to match.
+///
+/// The args and arg_paths are kept sorted by their index so equal rules compare equal regardless of the order the
+/// conditions were given in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MatchRule {
+    /// `MessageType::Invalid` can not be written in a rule, it is left out of the string form
+    pub typ: Option<MessageType>,
+    pub sender: Option<String>,
+    pub interface: Option<String>,
+    pub member: Option<String>,
+    pub path: Option<String>,
+    pub path_namespace: Option<String>,
+    pub destination: Option<String>,
+    /// argN='value': the N-th argument must be a string equal to the value
+    pub args: Vec<(u8, String)>,
+    /// argNpath='value': the N-th argument must be a string or object path that is equal to the value or
+    /// one of them is a prefix of the other ending with a '/'
+    pub arg_paths: Vec<(u8, String)>,
+    /// arg0namespace='value': the first argument must be a string that is a bus or interface name in this namespace
+    pub arg0_namespace: Option<String>,
+    pub eavesdrop: Option<bool>,
+}
+
+fn invalid(key: &str, value: &str) -> MatchRuleError {
+    MatchRuleError::InvalidValue {
+        key: key.to_owned(),
+        value: value.to_owned(),
+    }
+}
+
+/// Splits the rule into key/value pairs. Values are quoted with ', inside quotes there are no escapes.
+/// Outside of quotes \' is an escaped '.
+fn split_pairs(rule: &str) -> Result<Vec<(String, String)>, MatchRuleError> {
+    let mut pairs = Vec::new();
+    let mut chars = rule.chars().peekable();
+
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+        loop {
+            match chars.next() {
+                Some('=') => break,
+                Some(c) => key.push(c),
+                None => return Err(MatchRuleError::Malformed(rule.to_owned())),
+            }
+        }
+
+        let mut value = String::new();
+        let mut in_quotes = false;
+        loop {
+            match chars.next() {
+                Some('\'') => in_quotes = !in_quotes,
+                Some('\\') if !in_quotes && chars.peek() == Some(&'\'') => {
+                    chars.next();
+                    value.push('\'');
+                }
+                Some(',') if !in_quotes => break,
+                Some(c) => value.push(c),
+                None if in_quotes => return Err(MatchRuleError::Malformed(rule.to_owned())),
+                None => break,
+            }
+        }
+        pairs.push((key.trim().to_owned(), value));
+    }
+    Ok(pairs)
+}
+
+/// Parse the index out of argN / argNpath keys
+fn parse_arg_key(key: &str) -> Option<(u8, bool)> {
+    let rest = key.strip_prefix("arg")?;
+    let (idx, path) = match rest.strip_suffix("path") {
+        Some(idx) => (idx, true),
+        None => (rest, false),
+    };
+    if idx.is_empty() || !idx.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let idx: u8 = idx.parse().ok()?;
+    if idx > MAX_ARG_INDEX {
+        return None;
+    }
+    Some((idx, path))
+}
+
+fn set_indexed(list: &mut Vec<(u8, String)>, idx: u8, value: String) {
+    match list.binary_search_by_key(&idx, |(i, _)| *i) {
+        Ok(pos) => list[pos].1 = value,
+        Err(pos) => list.insert(pos, (idx, value)),
+    }
+}
+
+impl MatchRule {
+    /// An empty rule that matches every message
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Panics if the type is `MessageType::Invalid`, no message has that type.
+    pub fn with_type(mut self, typ: MessageType) -> Self {
+        assert!(typ != MessageType::Invalid);
+        self.typ = Some(typ);
+        self
+    }
+    pub fn with_sender<S: Into<String>>(mut self, sender: S) -> Self {
+        self.sender = Some(sender.into());
+        self
+    }
+    pub fn with_interface<S: Into<String>>(mut self, interface: S) -> Self {
+        self.interface = Some(interface.into());
+        self
+    }
+    pub fn with_member<S: Into<String>>(mut self, member: S) -> Self {
+        self.member = Some(member.into());
+        self
+    }
+    /// Match one object path exactly. This replaces a path_namespace, they can not be combined.
+    pub fn with_path<S: Into<String>>(mut self, path: S) -> Self {
+        self.path = Some(path.into());
+        self.path_namespace = None;
+        self
+    }
+    /// Match the object path and all paths below it. This replaces a path, they can not be combined.
+    pub fn with_path_namespace<S: Into<String>>(mut self, namespace: S) -> Self {
+        self.path_namespace = Some(namespace.into());
+        self.path = None;
+        self
+    }
+    pub fn with_destination<S: Into<String>>(mut self, destination: S) -> Self {
+        self.destination = Some(destination.into());
+        self
+    }
+    /// The argument at the index must be a string equal to the value. Panics if the index is bigger than `MAX_ARG_INDEX`.
+    pub fn with_arg<S: Into<String>>(mut self, idx: u8, value: S) -> Self {
+        assert!(idx <= MAX_ARG_INDEX);
+        set_indexed(&mut self.args, idx, value.into());
+        self
+    }
+    /// The argument at the index must be a path that is equal to the value or in the same namespace, see `arg_paths`.
+    /// Panics if the index is bigger than `MAX_ARG_INDEX`.
+    pub fn with_arg_path<S: Into<String>>(mut self, idx: u8, value: S) -> Self {
+        assert!(idx <= MAX_ARG_INDEX);
+        set_indexed(&mut self.arg_paths, idx, value.into());
+        self
+    }
+    pub fn with_arg0_namespace<S: Into<String>>(mut self, namespace: S) -> Self {
+        self.arg0_namespace = Some(namespace.into());
+        self
+    }
+    pub fn with_eavesdrop(mut self, eavesdrop: bool) -> Self {
+        self.eavesdrop = Some(eavesdrop);
+        self
+    }
+
+    /// Parse a rule like `type='signal',interface='org.freedesktop.DBus',member='NameOwnerChanged'`
+    pub fn parse(rule: &str) -> Result<Self, MatchRuleError> {
+        let mut parsed = MatchRule::default();
+        let mut seen_keys: Vec<String> = Vec::new();
+
+        for (key, value) in split_pairs(rule)? {
+            if seen_keys.contains(&key) {
+                return Err(MatchRuleError::DuplicateKey(key));
+            }
+            match key.as_str() {
+                "type" => {
+                    parsed.typ = Some(match value.as_str() {
+                        "signal" => MessageType::Signal,
+                        "method_call" => MessageType::Call,
+                        "method_return" => MessageType::Reply,
+                        "error" => MessageType::Error,
+                        _ => return Err(invalid(&key, &value)),
+                    })
+                }
+                "sender" => {
+                    crate::params::validate_busname(&value).map_err(|_| invalid(&key, &value))?;
+                    parsed.sender = Some(value);
+                }
+                "interface" => {
+                    crate::params::validate_interface(&value).map_err(|_| invalid(&key, &value))?;
+                    parsed.interface = Some(value);
+                }
+                "member" => {
+                    crate::params::validate_membername(&value)
+                        .map_err(|_| invalid(&key, &value))?;
+                    parsed.member = Some(value);
+                }
+                "path" => {
+                    crate::params::validate_object_path(&value)
+                        .map_err(|_| invalid(&key, &value))?;
+                    parsed.path = Some(value);
+                }
+                "path_namespace" => {
+                    crate::params::validate_object_path(&value)
+                        .map_err(|_| invalid(&key, &value))?;
+                    parsed.path_namespace = Some(value);
+                }
+                "destination" => {
+                    crate::params::validate_busname(&value).map_err(|_| invalid(&key, &value))?;
+                    parsed.destination = Some(value);
+                }
+                "arg0namespace" => parsed.arg0_namespace = Some(value),
+                "eavesdrop" => {
+                    parsed.eavesdrop = Some(match value.as_str() {
+                        "true" => true,
+                        "false" => false,
+                        _ => return Err(invalid(&key, &value)),
+                    })
+                }
+                _ => {
+                    let (idx, path) = parse_arg_key(&key)
+                        .ok_or_else(|| MatchRuleError::UnknownKey(key.clone()))?;
+                    let list = if path {
+                        &mut parsed.arg_paths
+                    } else {
+                        &mut parsed.args
+                    };
+                    // arg1 and arg01 name the same argument
+                    if list.iter().any(|(i, _)| *i == idx) {
+                        return Err(MatchRuleError::DuplicateKey(key));
+                    }
+                    set_indexed(list, idx, value);
+                }
+            }
+            seen_keys.push(key);
+        }
+
+        if parsed.path.is_some() && parsed.path_namespace.is_some() {
+            return Err(MatchRuleError::Malformed(
+                "path and path_namespace can not be combined".to_owned(),
+            ));
+        }
+        Ok(parsed)
+    }
+
+    /// Check whether the message matches all conditions of this rule
+    pub fn matches(&self, msg: &MarshalledMessage) -> bool {
+        let sender_matches = match &self.sender {
+            Some(sender) => msg.dynheader.sender.as_ref() == Some(sender),
+            None => true,
+        };
+        sender_matches && self.matches_ignoring_sender(msg)
+    }
+
+    /// Check all conditions except the sender. Useful if the sender needs to be resolved first, e.g. if the
+    /// rule names the well-known name and the message carries the unique name of the sender.
+    pub fn matches_ignoring_sender(&self, msg: &MarshalledMessage) -> bool {
+        fn field_matches(expected: &Option<String>, actual: &Option<String>) -> bool {
+            match expected {
+                Some(expected) => actual.as_ref() == Some(expected),
+                None => true,
+            }
+        }
+
+        if let Some(typ) = self.typ {
+            if msg.typ != typ {
+                return false;
+            }
+        }
+        if !field_matches(&self.interface, &msg.dynheader.interface)
+            || !field_matches(&self.member, &msg.dynheader.member)
+            || !field_matches(&self.path, &msg.dynheader.object)
+            || !field_matches(&self.destination, &msg.dynheader.destination)
+        {
+            return false;
+        }
+        if let Some(namespace) = &self.path_namespace {
+            let path = match &msg.dynheader.object {
+                Some(path) => path,
+                None => return false,
+            };
+            let in_namespace = namespace == "/"
+                || path == namespace
+                || path
+                    .strip_prefix(namespace.as_str())
+                    .is_some_and(|rest| rest.starts_with('/'));
+            if !in_namespace {
+                return false;
+            }
+        }
+
+        for (idx, value) in &self.args {
+            match string_arg(msg, *idx, false) {
+                Some(arg) if arg == *value => {}
+                _ => return false,
+            }
+        }
+        for (idx, value) in &self.arg_paths {
+            match string_arg(msg, *idx, true) {
+                Some(arg) if paths_match(&arg, value) => {}
+                _ => return false,
+            }
+        }
+        if let Some(namespace) = &self.arg0_namespace {
+            match string_arg(msg, 0, false) {
+                Some(arg)
+                    if arg == *namespace
+                        || arg
+                            .strip_prefix(namespace.as_str())
+                            .is_some_and(|rest| rest.starts_with('.')) => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Quote a value for the string format. Quotes can not be escaped inside of a quoted value so the quoting is ended,
+/// an escaped quote is put in and the quoting is started again.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+impl std::fmt::Display for MatchRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut pairs: Vec<(String, String)> = Vec::new();
+        let typ = match self.typ {
+            Some(MessageType::Signal) => Some("signal"),
+            Some(MessageType::Call) => Some("method_call"),
+            Some(MessageType::Reply) => Some("method_return"),
+            Some(MessageType::Error) => Some("error"),
+            Some(MessageType::Invalid) | None => None,
+        };
+        if let Some(typ) = typ {
+            pairs.push(("type".to_owned(), typ.to_owned()));
+        }
+        let fields = [
+            ("sender", &self.sender),
+            ("interface", &self.interface),
+            ("member", &self.member),
+            ("path", &self.path),
+            ("path_namespace", &self.path_namespace),
+            ("destination", &self.destination),
+        ];
+        for (key, value) in fields.iter() {
+            if let Some(value) = value {
+                pairs.push((key.to_string(), value.clone()));
+            }
+        }
+        for (idx, value) in &self.args {
+            pairs.push((format!("arg{}", idx), value.clone()));
+        }
+        for (idx, value) in &self.arg_paths {
+            pairs.push((format!("arg{}path", idx), value.clone()));
+        }
+        if let Some(namespace) = &self.arg0_namespace {
+            pairs.push(("arg0namespace".to_owned(), namespace.clone()));
+        }
+        if let Some(eavesdrop) = self.eavesdrop {
+            pairs.push(("eavesdrop".to_owned(), eavesdrop.to_string()));
+        }
+
+        let pairs: Vec<String> = pairs
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, quote(value)))
+            .collect();
+        write!(f, "{}", pairs.join(","))
+    }
+}
+
+impl std::str::FromStr for MatchRule {
+    type Err = MatchRuleError;
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        Self::parse(rule)
+    }
+}
+
+fn paths_match(arg: &str, value: &str) -> bool {
+    arg == value
+        || (value.ends_with('/') && arg.starts_with(value))
+        || (arg.ends_with('/') && value.starts_with(arg))
+}
+
+/// Get the string argument at the index. Object paths are only considered if `allow_path` is set.
+fn string_arg(msg: &MarshalledMessage, idx: u8, allow_path: bool) -> Option<String> {
+    let mut parser = msg.body.parser();
+    for _ in 0..idx {
+        parser.get_param().ok()?;
+    }
+    match parser.get_next_sig()? {
+        "s" => parser.get::<&str>().ok().map(str::to_owned),
+        "o" if allow_path => parser
+            .get::<ObjectPath<&str>>()
+            .ok()
+            .map(|path| path.as_ref().to_owned()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_builder::MessageBuilder;
+
+    #[test]
+    fn parse_rules() {
+        let rule = MatchRule::parse(
+            "type='signal',sender='org.freedesktop.DBus',interface='org.freedesktop.DBus',member='NameOwnerChanged',arg0='io.killing.spark'",
+        )
+        .unwrap();
+        assert_eq!(rule.typ, Some(MessageType::Signal));
+        assert_eq!(rule.sender.as_deref(), Some("org.freedesktop.DBus"));
+        assert_eq!(rule.member.as_deref(), Some("NameOwnerChanged"));
+        assert_eq!(rule.args, vec![(0, "io.killing.spark".to_owned())]);
+
+        // escaped quotes outside of quoted values
+        let rule = MatchRule::parse("arg2='it'\\''s',arg3path='/a/b/'").unwrap();
+        assert_eq!(rule.args, vec![(2, "it's".to_owned())]);
+        assert_eq!(rule.arg_paths, vec![(3, "/a/b/".to_owned())]);
+
+        assert_eq!(MatchRule::parse("").unwrap(), MatchRule::default());
+        assert!(matches!(
+            MatchRule::parse("type='signal',type='error'"),
+            Err(MatchRuleError::DuplicateKey(_))
+        ));
+        assert!(matches!(
+            MatchRule::parse("foo='bar'"),
+            Err(MatchRuleError::UnknownKey(_))
+        ));
+        assert!(matches!(
+            MatchRule::parse("arg64='bar'"),
+            Err(MatchRuleError::UnknownKey(_))
+        ));
+        assert!(matches!(
+            MatchRule::parse("type='nonsense'"),
+            Err(MatchRuleError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            MatchRule::parse("type='invalid'"),
+            Err(MatchRuleError::InvalidValue { .. })
+        ));
+        assert!(MatchRule::parse("member='unterminated").is_err());
+        assert!(MatchRule::parse("path='/a',path_namespace='/a'").is_err());
+    }
+
+    #[test]
+    fn build_and_display() {
+        let rule = MatchRule::new()
+            .with_type(MessageType::Signal)
+            .with_arg(3, "it's")
+            .with_arg(1, "first")
+            .with_arg_path(0, "/a/")
+            .with_path("/a")
+            .with_path_namespace("/b")
+            .with_eavesdrop(true);
+        assert_eq!(rule.path, None);
+        assert_eq!(
+            rule.to_string(),
+            "type='signal',path_namespace='/b',arg1='first',arg3='it'\\''s',arg0path='/a/',eavesdrop='true'"
+        );
+        assert_eq!(rule.to_string().parse::<MatchRule>().unwrap(), rule);
+
+        // the order of the conditions does not matter
+        assert_eq!(
+            MatchRule::parse("arg3='it'\\''s',arg1='first'").unwrap(),
+            MatchRule::new().with_arg(1, "first").with_arg(3, "it's")
+        );
+        assert!(matches!(
+            MatchRule::parse("arg1='a',arg01='b'"),
+            Err(MatchRuleError::DuplicateKey(_))
+        ));
+        assert_eq!(MatchRule::new().to_string(), "");
+
+        // the invalid type has no string form
+        let rule = MatchRule {
+            typ: Some(MessageType::Invalid),
+            ..MatchRule::new().with_member("Member")
+        };
+        assert_eq!(rule.to_string(), "member='Member'");
+    }
+
+    #[test]
+    fn match_messages() {
+        let mut sig = MessageBuilder::new()
+            .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
+            .build();
+        sig.body.push_param("io.killing.spark.Sub").unwrap();
+        sig.body
+            .push_param(ObjectPath::new("/io/killing/spark/sub").unwrap())
+            .unwrap();
+        sig.dynheader.sender = Some(":1.42".to_owned());
+
+        let matches = |rule: &str| MatchRule::parse(rule).unwrap().matches(&sig);
+        assert!(matches(""));
+        assert!(matches("type='signal',interface='io.killing.spark'"));
+        assert!(!matches("type='method_call'"));
+        assert!(matches("sender=':1.42',member='TestSignal'"));
+        assert!(!matches("sender=':1.43'"));
+        assert!(matches("path_namespace='/io/killing'"));
+        assert!(matches("path_namespace='/'"));
+        assert!(!matches("path_namespace='/io/kill'"));
+        assert!(matches("arg0='io.killing.spark.Sub'"));
+        assert!(!matches("arg1='/io/killing/spark/sub'"));
+        assert!(matches("arg1path='/io/killing/'"));
+        assert!(matches("arg0namespace='io.killing'"));
+        assert!(!matches("arg0namespace='io.kill'"));
+        assert!(!matches("arg5='missing'"));
+    }
+}
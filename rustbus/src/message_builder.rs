@@ -38,7 +38,7 @@ impl HeaderFlags {
     }
 
     pub fn is_set(self, flags: u8) -> bool {
-        flags & self.into_raw() == 1
+        flags & self.into_raw() != 0
     }
 
     pub fn set(self, flags: &mut u8) {
@@ -80,7 +80,7 @@ impl DynamicHeader {
         error_msg: Option<String>,
     ) -> crate::message_builder::MarshalledMessage {
         let mut err_resp = crate::message_builder::MarshalledMessage {
-            typ: MessageType::Reply,
+            typ: MessageType::Error,
             dynheader: DynamicHeader {
                 interface: None,
                 member: None,
@@ -211,7 +211,9 @@ impl SignalBuilder {
 /// The body accepts everything that implements the Marshal trait (e.g. all basic types, strings, slices, Hashmaps,.....)
 /// And you can of course write an Marshal impl for your own datastructures. See the doc on the Marshal trait what you have
 /// to look out for when doing this though.
-#[derive(Debug)]
+///
+/// Clones share the unix fds of the original, see the doc of UnixFd.
+#[derive(Debug, Clone)]
 pub struct MarshalledMessage {
     pub body: MarshalledMessageBody,
 
@@ -289,7 +291,7 @@ impl MarshalledMessage {
 }
 /// The body accepts everything that implements the Marshal trait (e.g. all basic types, strings, slices, Hashmaps,.....)
 /// And you can of course write an Marshal impl for your own datastrcutures
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MarshalledMessageBody {
     pub(crate) buf: Vec<u8>,
 
@@ -408,7 +410,7 @@ impl MarshalledMessageBody {
         }
         Ok(())
     }
-    fn create_ctx(&mut self) -> MarshalContext {
+    fn create_ctx(&mut self) -> MarshalContext<'_, '_> {
         MarshalContext {
             buf: &mut self.buf,
             fds: &mut self.raw_fds,
@@ -506,6 +508,11 @@ impl MarshalledMessageBody {
         })
     }
 
+    /// Append each element of the argument list as its own param. If any of them fails the body is reset.
+    pub fn push_args<A: ArgList>(&mut self, args: A) -> Result<(), MarshalError> {
+        self.push_mult_helper(move |msg: &mut Self| args.push_args(msg))
+    }
+
     /// Append any number of things that have the same type that is Marshal to the message body
     pub fn push_params<P: Marshal>(&mut self, params: &[P]) -> Result<(), MarshalError> {
         for p in params {
@@ -539,9 +546,14 @@ impl MarshalledMessageBody {
     }
     /// Create a parser to retrieve parameters from the body.
     #[inline]
-    pub fn parser(&self) -> MessageBodyParser {
+    pub fn parser(&self) -> MessageBodyParser<'_> {
         MessageBodyParser::new(self)
     }
+
+    /// Read all values of the body as a list, see `RetList`.
+    pub fn get_rets<R: RetList>(&self) -> Result<R, UnmarshalError> {
+        R::get_rets(self)
+    }
 }
 
 #[test]
@@ -780,7 +792,10 @@ impl<'fds, 'body: 'fds> MessageBodyParser<'body> {
     /// This checks if there are params left in the message and if the type you requested fits the signature of the message.
     pub fn get<T: Unmarshal<'body, 'fds>>(&mut self) -> Result<T, UnmarshalError> {
         if let Some(expected_sig) = self.get_next_sig() {
-            if !T::has_sig(expected_sig) {
+            let is_last = self.sig_idx + expected_sig.len() == self.body.sig.len();
+            let sig_fits =
+                T::has_sig(expected_sig) || (is_last && T::has_partial_sig(expected_sig));
+            if !sig_fits {
                 return Err(UnmarshalError::WrongSignature);
             }
 
@@ -896,7 +911,7 @@ impl<'fds, 'body: 'fds> MessageBodyParser<'body> {
 
     /// Get the next (old_style) param.
     /// This checks if there are params left in the message and if the type you requested fits the signature of the message.
-    pub fn get_param(&mut self) -> Result<crate::params::Param, UnmarshalError> {
+    pub fn get_param(&mut self) -> Result<crate::params::Param<'_, '_>, UnmarshalError> {
         if let Some(sig_str) = self.get_next_sig() {
             let mut ctx = UnmarshalContext {
                 byteorder: self.body.byteorder,
@@ -921,6 +936,83 @@ impl<'fds, 'body: 'fds> MessageBodyParser<'body> {
     }
 }
 
+/// A list of arguments for a method call. Each element is pushed as its own param, so `()` is a call without arguments
+/// and `(a, b)` is a call with the two arguments a and b. Use `(a,)` for a single argument.
+///
+/// Note that `push_param` marshals a tuple as one struct instead.
+pub trait ArgList {
+    fn push_args(self, body: &mut MarshalledMessageBody) -> Result<(), MarshalError>;
+}
+
+impl ArgList for () {
+    fn push_args(self, _body: &mut MarshalledMessageBody) -> Result<(), MarshalError> {
+        Ok(())
+    }
+}
+
+macro_rules! impl_arg_list {
+    ($($arg:ident),+) => {
+        impl<$($arg: Marshal),+> ArgList for ($($arg,)+) {
+            #[allow(non_snake_case)]
+            fn push_args(self, body: &mut MarshalledMessageBody) -> Result<(), MarshalError> {
+                let ($($arg,)+) = self;
+                $(body.push_param($arg)?;)+
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_arg_list!(A1);
+impl_arg_list!(A1, A2);
+impl_arg_list!(A1, A2, A3);
+impl_arg_list!(A1, A2, A3, A4);
+impl_arg_list!(A1, A2, A3, A4, A5);
+impl_arg_list!(A1, A2, A3, A4, A5, A6);
+impl_arg_list!(A1, A2, A3, A4, A5, A6, A7);
+impl_arg_list!(A1, A2, A3, A4, A5, A6, A7, A8);
+
+/// The values of a reply, the counterpart of `ArgList`. Each element is read as its own value, so `()` is a reply
+/// without values and `(a, b)` is a reply with the two values a and b. Use `(a,)` for a single value.
+///
+/// Bodies that contain more values than the list are rejected with `UnmarshalError::NotAllBytesUsed`.
+pub trait RetList: Sized {
+    fn get_rets(body: &MarshalledMessageBody) -> Result<Self, UnmarshalError>;
+}
+
+impl RetList for () {
+    fn get_rets(body: &MarshalledMessageBody) -> Result<Self, UnmarshalError> {
+        if body.parser().get_next_sig().is_some() {
+            return Err(UnmarshalError::NotAllBytesUsed);
+        }
+        Ok(())
+    }
+}
+
+macro_rules! impl_ret_list {
+    ($($ret:ident),+) => {
+        impl<$($ret: for<'a> Unmarshal<'a, 'a>),+> RetList for ($($ret,)+) {
+            fn get_rets(body: &MarshalledMessageBody) -> Result<Self, UnmarshalError> {
+                let mut parser = body.parser();
+                let rets = ($(parser.get::<$ret>()?,)+);
+                if parser.get_next_sig().is_some() {
+                    return Err(UnmarshalError::NotAllBytesUsed);
+                }
+                Ok(rets)
+            }
+        }
+    };
+}
+
+impl_ret_list!(R1);
+impl_ret_list!(R1, R2);
+impl_ret_list!(R1, R2, R3);
+impl_ret_list!(R1, R2, R3, R4);
+impl_ret_list!(R1, R2, R3, R4, R5);
+impl_ret_list!(R1, R2, R3, R4, R5, R6);
+impl_ret_list!(R1, R2, R3, R4, R5, R6, R7);
+impl_ret_list!(R1, R2, R3, R4, R5, R6, R7, R8);
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -960,4 +1052,52 @@ mod tests {
         assert!(parser.get::<(u32, i32, &str)>().is_ok());
         assert!(parser.get2::<(u32, i32, &str), (u32, i32, &str)>().is_ok());
     }
+
+    #[test]
+    fn push_args() {
+        let mut sig = super::MessageBuilder::new()
+            .signal("io.killingspark", "Signal", "/io/killingspark/Signaler")
+            .build();
+
+        sig.body.push_args(()).unwrap();
+        assert_eq!(sig.get_sig(), "");
+        sig.body.push_args((100u32,)).unwrap();
+        sig.body.push_args((200i32, "ABCDEFGH")).unwrap();
+        // a tuple inside the list is still a struct
+        sig.body.push_args(((1u8, 2u8),)).unwrap();
+        assert_eq!(sig.get_sig(), "uis(yy)");
+    }
+
+    #[test]
+    fn get_rets() {
+        use crate::wire::errors::UnmarshalError;
+
+        let mut sig = super::MessageBuilder::new()
+            .signal("io.killingspark", "Signal", "/io/killingspark/Signaler")
+            .build();
+        assert_eq!(sig.body.get_rets::<()>(), Ok(()));
+
+        sig.body.push_param3(100u32, 200i32, "ABCDEFGH").unwrap();
+        assert_eq!(
+            sig.body.get_rets::<(u32, i32, String)>(),
+            Ok((100, 200, "ABCDEFGH".to_owned()))
+        );
+        // the values are not read as one struct
+        assert_eq!(
+            sig.body.get_rets::<((u32, i32, String),)>(),
+            Err(UnmarshalError::WrongSignature)
+        );
+        assert_eq!(
+            sig.body.get_rets::<(u32, i32)>(),
+            Err(UnmarshalError::NotAllBytesUsed)
+        );
+        assert_eq!(
+            sig.body.get_rets::<()>(),
+            Err(UnmarshalError::NotAllBytesUsed)
+        );
+        assert_eq!(
+            sig.body.get_rets::<(u32, i32, String, u8)>(),
+            Err(UnmarshalError::EndOfMessage)
+        );
+    }
 }
@@ -193,6 +193,7 @@ impl<'e, 'a: 'e> Container<'a, 'e> {
 
         Ok(Container::Dict(dict))
     }
+    #[allow(clippy::mutable_key_type)]
     pub fn make_dict_ref(
         key_sig: &str,
         val_sig: &str,
@@ -220,6 +221,7 @@ impl<'e, 'a: 'e> Container<'a, 'e> {
         Self::make_dict_ref_with_sig(key_sig, value_sig, map)
     }
 
+    #[allow(clippy::mutable_key_type)]
     pub fn make_dict_ref_with_sig(
         key_sig: signature::Base,
         value_sig: signature::Type,
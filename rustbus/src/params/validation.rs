@@ -240,6 +240,7 @@ pub fn validate_array<'a, 'e>(array: &[Param<'a, 'e>], sig: &signature::Type) ->
     Ok(())
 }
 
+#[allow(clippy::mutable_key_type)]
 pub fn validate_dict(
     dict: &params::DictMap,
     key_sig: signature::Base,
@@ -0,0 +1,318 @@
+//! Client side of `org.freedesktop.DBus.Properties`: typed calls with `PropertiesProxy` and a `PropertyCache` that is
+//! kept current by the PropertiesChanged signals of the object.
+//!
+//! ```rust,no_run
+//! use rustbus::properties::{PropertiesProxy, PropertyCache};
+//! use rustbus::{connection::Timeout, RpcConn};
+//!
+//! fn main() -> Result<(), rustbus::connection::rpc_conn::CallError> {
+//!     let mut con = RpcConn::session_conn(Timeout::Infinite)?;
+//!
+//!     let mut proxy = PropertiesProxy::new(
+//!         &mut con,
+//!         "org.freedesktop.secrets",
+//!         "/org/freedesktop/secrets/collection/login",
+//!         "org.freedesktop.Secret.Collection",
+//!     );
+//!     let label: String = proxy.get("Label")?;
+//!     proxy.set("Label", "Login")?;
+//!
+//!     let mut cache = PropertyCache::new(
+//!         &mut con,
+//!         "org.freedesktop.secrets",
+//!         "/org/freedesktop/secrets/collection/login",
+//!         "org.freedesktop.Secret.Collection",
+//!         Timeout::Infinite,
+//!     )?;
+//!     cache.on_change(Box::new(|name| println!("{} changed", name)));
+//!     loop {
+//!         // signals are processed while the connection receives messages
+//!         let _call = con.wait_call(Timeout::Infinite)?;
+//!         cache.update(&mut con)?;
+//!         let label: Option<String> = cache.get("Label").transpose()?;
+//!     }
+//! }
+//! ```
+
+use crate::connection::rpc_conn::{CallError, RpcConn, SubscriptionId};
+use crate::connection::Timeout;
+use crate::dbus_error::ErrorName;
+use crate::match_rule::MatchRule;
+use crate::message_builder::MessageType;
+use crate::wire::errors::UnmarshalError;
+use crate::wire::marshal::traits::Variant;
+use crate::wire::OwnedVariant;
+use crate::{Marshal, Unmarshal};
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+pub const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+type Result<T> = std::result::Result<T, CallError>;
+
+/// Calls the methods of the properties interface of one interface of an object over an RpcConn. Messages that arrive
+/// while waiting for the replies are put into the queues of the RpcConn as usual.
+pub struct PropertiesProxy<'a> {
+    con: &'a mut RpcConn,
+    destination: String,
+    path: String,
+    interface: String,
+    timeout: Timeout,
+}
+
+impl<'a> PropertiesProxy<'a> {
+    /// Wait for the replies without a timeout
+    pub fn new(con: &'a mut RpcConn, destination: &str, path: &str, interface: &str) -> Self {
+        Self::with_timeout(con, destination, path, interface, Timeout::Infinite)
+    }
+
+    /// Wait at most `timeout` for each reply
+    pub fn with_timeout(
+        con: &'a mut RpcConn,
+        destination: &str,
+        path: &str,
+        interface: &str,
+        timeout: Timeout,
+    ) -> Self {
+        PropertiesProxy {
+            con,
+            destination: destination.to_owned(),
+            path: path.to_owned(),
+            interface: interface.to_owned(),
+            timeout,
+        }
+    }
+
+    /// Get the value of the property. Fails with `CallError::InvalidReply` if the value is not of type T.
+    pub fn get<T: for<'r> Unmarshal<'r, 'r>>(&mut self, name: &str) -> Result<T> {
+        let value = self.get_variant(name)?;
+        let value = value.get()?;
+        Ok(value)
+    }
+
+    /// Get the value of the property whatever type it has
+    pub fn get_variant(&mut self, name: &str) -> Result<OwnedVariant> {
+        let (value,) = self.con.call(
+            &self.destination,
+            &self.path,
+            PROPERTIES_INTERFACE,
+            "Get",
+            (self.interface.as_str(), name),
+            self.timeout,
+        )?;
+        Ok(value)
+    }
+
+    pub fn set<T: Marshal>(&mut self, name: &str, value: T) -> Result<()> {
+        self.con.call(
+            &self.destination,
+            &self.path,
+            PROPERTIES_INTERFACE,
+            "Set",
+            (self.interface.as_str(), name, Variant(value)),
+            self.timeout,
+        )
+    }
+
+    /// The values of all readable properties of the interface
+    pub fn get_all(&mut self) -> Result<HashMap<String, OwnedVariant>> {
+        let (values,) = self.con.call(
+            &self.destination,
+            &self.path,
+            PROPERTIES_INTERFACE,
+            "GetAll",
+            (self.interface.as_str(),),
+            self.timeout,
+        )?;
+        Ok(values)
+    }
+}
+
+/// Called with the name of a property when its value changed or it was invalidated
+pub type PropertyChangedCallback = Box<dyn FnMut(&str) + Sync + Send>;
+
+struct CacheState {
+    values: HashMap<String, OwnedVariant>,
+    invalidated: HashSet<String>,
+}
+
+/// Kept apart from the CacheState so the callback runs without the values being locked and can read the cache.
+type CallbackSlot = Arc<Mutex<Option<PropertyChangedCallback>>>;
+
+fn notify_changed(callback: &CallbackSlot, names: &[String]) {
+    if names.is_empty() {
+        return;
+    }
+    if let Some(callback) = &mut *callback.lock().unwrap() {
+        for name in names {
+            callback(name);
+        }
+    }
+}
+
+/// A local copy of the properties of one interface of an object. It fetches all properties when it is created and
+/// subscribes to the PropertiesChanged signals of the object to keep the copy current.
+///
+/// The signals are processed while the RpcConn receives messages. Properties that were invalidated by the object are
+/// missing from the cache until `update` fetched them again. The cache does not hold on to the connection, so
+/// multiple caches can share one. Use `close` to end the subscription.
+pub struct PropertyCache {
+    destination: String,
+    path: String,
+    interface: String,
+    timeout: Timeout,
+    subscription: SubscriptionId,
+    state: Arc<Mutex<CacheState>>,
+    callback: CallbackSlot,
+}
+
+impl PropertyCache {
+    /// The timeout is used for all calls made by the cache
+    pub fn new(
+        con: &mut RpcConn,
+        destination: &str,
+        path: &str,
+        interface: &str,
+        timeout: Timeout,
+    ) -> Result<Self> {
+        let state = Arc::new(Mutex::new(CacheState {
+            values: HashMap::new(),
+            invalidated: HashSet::new(),
+        }));
+        let callback: CallbackSlot = Arc::new(Mutex::new(None));
+
+        // subscribe before fetching the values, so no change is missed
+        let rule = MatchRule::new()
+            .with_type(MessageType::Signal)
+            .with_sender(destination)
+            .with_path(path)
+            .with_interface(PROPERTIES_INTERFACE)
+            .with_member("PropertiesChanged")
+            .with_arg(0, interface);
+        let signal_state = state.clone();
+        let signal_callback = callback.clone();
+        let subscription = con.subscribe_with_callback(
+            rule,
+            Box::new(move |sig| {
+                let mut parser = sig.body.parser();
+                if let Ok((_, changed, invalidated)) =
+                    parser.get3::<&str, HashMap<String, OwnedVariant>, Vec<String>>()
+                {
+                    let mut names = Vec::with_capacity(changed.len() + invalidated.len());
+                    {
+                        let mut state = signal_state.lock().unwrap();
+                        for (name, value) in changed {
+                            state.invalidated.remove(&name);
+                            state.values.insert(name.clone(), value);
+                            names.push(name);
+                        }
+                        for name in invalidated {
+                            state.values.remove(&name);
+                            state.invalidated.insert(name.clone());
+                            names.push(name);
+                        }
+                    }
+                    notify_changed(&signal_callback, &names);
+                }
+            }),
+            timeout,
+        )?;
+
+        let cache = PropertyCache {
+            destination: destination.to_owned(),
+            path: path.to_owned(),
+            interface: interface.to_owned(),
+            timeout,
+            subscription,
+            state,
+            callback,
+        };
+        let values = match cache.proxy(con).get_all() {
+            Ok(values) => values,
+            Err(e) => {
+                let _ = cache.close(con);
+                return Err(e);
+            }
+        };
+        // the reply is newer than the signals that arrived before it
+        let mut state = cache.state.lock().unwrap();
+        state.values = values;
+        state.invalidated.clear();
+        drop(state);
+        Ok(cache)
+    }
+
+    fn proxy<'a>(&self, con: &'a mut RpcConn) -> PropertiesProxy<'a> {
+        PropertiesProxy::with_timeout(
+            con,
+            &self.destination,
+            &self.path,
+            &self.interface,
+            self.timeout,
+        )
+    }
+
+    /// Get the cached value of the property. None if the property is unknown or was invalidated.
+    pub fn get<T: for<'r> Unmarshal<'r, 'r>>(
+        &self,
+        name: &str,
+    ) -> Option<std::result::Result<T, UnmarshalError>> {
+        let state = self.state.lock().unwrap();
+        state.values.get(name).map(|value| value.get::<T>())
+    }
+
+    pub fn get_variant(&self, name: &str) -> Option<OwnedVariant> {
+        self.state.lock().unwrap().values.get(name).cloned()
+    }
+
+    /// A copy of all cached values
+    pub fn values(&self) -> HashMap<String, OwnedVariant> {
+        self.state.lock().unwrap().values.clone()
+    }
+
+    /// Call the callback whenever a property changes. Replaces the previous callback.
+    pub fn on_change(&mut self, callback: PropertyChangedCallback) {
+        *self.callback.lock().unwrap() = Some(callback);
+    }
+
+    /// Fetch the values of the invalidated properties again. Properties that are gone are dropped from the cache.
+    pub fn update(&mut self, con: &mut RpcConn) -> Result<()> {
+        let invalidated = std::mem::take(&mut self.state.lock().unwrap().invalidated);
+        let mut invalidated = invalidated.into_iter();
+        while let Some(name) = invalidated.next() {
+            match self.proxy(con).get_variant(&name) {
+                Ok(value) => {
+                    let mut state = self.state.lock().unwrap();
+                    // a newer value may have arrived while waiting for the reply
+                    if !state.values.contains_key(&name) {
+                        state.invalidated.remove(&name);
+                        state.values.insert(name.clone(), value);
+                        drop(state);
+                        notify_changed(&self.callback, &[name]);
+                    }
+                }
+                Err(CallError::ErrorReply(e)) if e.name == ErrorName::UnknownProperty => {}
+                Err(e) => {
+                    // try again with the next update
+                    let mut state = self.state.lock().unwrap();
+                    state.invalidated.insert(name);
+                    state.invalidated.extend(invalidated);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the property on the object. The cache is updated once the PropertiesChanged signal arrives.
+    pub fn set<T: Marshal>(&self, con: &mut RpcConn, name: &str, value: T) -> Result<()> {
+        self.proxy(con).set(name, value)
+    }
+
+    /// End the subscription to the PropertiesChanged signals
+    pub fn close(self, con: &mut RpcConn) -> Result<()> {
+        con.unsubscribe(self.subscription, self.timeout)?;
+        Ok(())
+    }
+}
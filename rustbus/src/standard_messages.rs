@@ -1,5 +1,6 @@
 //! Some standard messages that are often needed
 
+use crate::dbus_error::{DBusError, ErrorName};
 use crate::message_builder::DynamicHeader;
 use crate::message_builder::MarshalledMessage;
 use crate::message_builder::MessageBuilder;
@@ -38,6 +39,10 @@ pub const DBUS_REQUEST_NAME_REPLY_IN_QUEUE: u32 = 2;
 pub const DBUS_REQUEST_NAME_REPLY_EXISTS: u32 = 3;
 pub const DBUS_REQUEST_NAME_REPLY_ALREADY_OWNER: u32 = 4;
 
+pub const DBUS_RELEASE_NAME_REPLY_RELEASED: u32 = 1;
+pub const DBUS_RELEASE_NAME_REPLY_NON_EXISTENT: u32 = 2;
+pub const DBUS_RELEASE_NAME_REPLY_NOT_OWNER: u32 = 3;
+
 fn make_standard_msg(name: &str) -> MarshalledMessage {
     MessageBuilder::new()
         .call(name)
@@ -81,10 +86,7 @@ pub fn unknown_method(call: &DynamicHeader) -> MarshalledMessage {
         call.member.clone().unwrap_or_else(|| "".to_owned()),
         call.object.clone().unwrap_or_else(|| "".to_owned()),
     );
-    call.make_error_response(
-        "org.freedesktop.DBus.Error.UnknownMethod".to_owned(),
-        Some(text),
-    )
+    DBusError::new(ErrorName::UnknownMethod, text).to_message(call)
 }
 
 /// Error message to tell the caller that this method uses a different interface than what the caller provided as parameters
@@ -101,8 +103,5 @@ pub fn invalid_args(call: &DynamicHeader, sig: Option<&str>) -> MarshalledMessag
         }
     );
 
-    call.make_error_response(
-        "org.freedesktop.DBus.Error.InvalidArgs".to_owned(),
-        Some(text),
-    )
+    DBusError::new(ErrorName::InvalidArgs, text).to_message(call)
 }
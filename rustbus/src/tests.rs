@@ -5,13 +5,21 @@ use crate::wire::unmarshal::unmarshal_dynamic_header;
 use crate::wire::unmarshal::unmarshal_header;
 use crate::wire::unmarshal::unmarshal_next_message;
 
+#[cfg(feature = "tokio")]
+mod async_conn;
+mod broker;
+mod bus_proxy;
 mod dbus_send;
 mod fdpassing;
+#[cfg(feature = "mio")]
+mod mio;
+mod tcp;
 mod verify_marshalling;
 mod verify_padding;
 
 // this tests the happy path
 #[test]
+#[allow(clippy::vec_init_then_push)]
 fn test_marshal_unmarshal() {
     let mut params: Vec<Param> = Vec::new();
 
@@ -0,0 +1,74 @@
+use crate::connection::async_conn::AsyncRpcConn;
+use crate::message_builder::{MessageBuilder, MessageType};
+use std::io::{Read, Write};
+use std::os::unix::io::FromRawFd;
+
+const TEST_STRING: &str = "This will be sent over the fd\n";
+
+async fn wait_test_signal(con: &mut AsyncRpcConn) -> crate::message_builder::MarshalledMessage {
+    loop {
+        let signal = con.wait_signal().await.unwrap();
+        if signal.dynheader.interface.as_deref() == Some("io.killing.spark")
+            && signal.dynheader.member.as_deref() == Some("TestSignal")
+        {
+            break signal;
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_async_fd_passing() {
+    let mut con1 = AsyncRpcConn::system_conn().await.unwrap();
+    let mut con2 = AsyncRpcConn::system_conn().await.unwrap();
+
+    let serial = con2
+        .send_message(&crate::standard_messages::add_match("type='signal'"))
+        .await
+        .unwrap();
+    let reply = con2.wait_response(serial).await.unwrap();
+    assert_eq!(reply.typ, MessageType::Reply);
+
+    let (read, write) = nix::unistd::pipe().unwrap();
+    let mut readfile = unsafe { std::fs::File::from_raw_fd(read) };
+    let mut sig = MessageBuilder::new()
+        .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
+        .build();
+    sig.body
+        .push_param(crate::wire::UnixFd::new(write))
+        .unwrap();
+    con1.send_message(&sig).await.unwrap();
+    drop(sig);
+
+    let sig = wait_test_signal(&mut con2).await;
+    let fd = sig.body.parser().get::<crate::wire::UnixFd>().unwrap();
+    let mut writefile = unsafe { std::fs::File::from_raw_fd(fd.take_raw_fd().unwrap()) };
+    writefile.write_all(TEST_STRING.as_bytes()).unwrap();
+    drop(writefile);
+
+    let mut received = String::new();
+    readfile.read_to_string(&mut received).unwrap();
+    assert_eq!(received, TEST_STRING);
+}
+
+#[tokio::test]
+async fn test_async_large_message() {
+    let mut con1 = AsyncRpcConn::system_conn().await.unwrap();
+    let mut con2 = AsyncRpcConn::system_conn().await.unwrap();
+
+    let serial = con2
+        .send_message(&crate::standard_messages::add_match("type='signal'"))
+        .await
+        .unwrap();
+    con2.wait_response(serial).await.unwrap();
+
+    // big enough to need multiple writes and reads
+    let payload = "a".repeat(4 * 1024 * 1024);
+    let mut sig = MessageBuilder::new()
+        .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
+        .build();
+    sig.body.push_param(payload.as_str()).unwrap();
+
+    let (sent, received) = tokio::join!(con1.send_message(&sig), wait_test_signal(&mut con2));
+    sent.unwrap();
+    assert_eq!(received.body.parser().get::<&str>().unwrap(), payload);
+}
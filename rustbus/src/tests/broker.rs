@@ -0,0 +1,911 @@
+use crate::broker::{Broker, BrokerHandle};
+use crate::connection::address::{Transport, UnixAddress};
+use crate::connection::Timeout;
+use crate::dbus_error::ErrorName;
+use crate::message_builder::{MarshalledMessage, MessageBuilder, MessageType};
+use crate::standard_messages;
+use crate::MatchRule;
+use crate::RpcConn;
+
+fn spawn_broker() -> BrokerHandle {
+    Broker::bind_tmpdir().unwrap().spawn()
+}
+
+fn call(con: &mut RpcConn, mut msg: MarshalledMessage) -> MarshalledMessage {
+    let serial = con.send_message(&mut msg).unwrap().write_all().unwrap();
+    con.wait_response(serial, Timeout::Infinite).unwrap()
+}
+
+fn call_bus(con: &mut RpcConn, member: &str, arg: &str) -> MarshalledMessage {
+    let mut msg = MessageBuilder::new()
+        .call(member)
+        .with_interface("org.freedesktop.DBus")
+        .on("/org/freedesktop/DBus")
+        .at("org.freedesktop.DBus")
+        .build();
+    msg.body.push_param(arg).unwrap();
+    call(con, msg)
+}
+
+/// Skips other signals, e.g. the NameAcquired for the unique name that every client gets after the Hello
+fn wait_signal(con: &mut RpcConn, member: &str) -> MarshalledMessage {
+    loop {
+        let sig = con.wait_signal(Timeout::Infinite).unwrap();
+        if sig.dynheader.member.as_deref() == Some(member) {
+            return sig;
+        }
+    }
+}
+
+#[test]
+fn names_and_routing() {
+    let broker = spawn_broker();
+    let addr = [broker.address().clone()];
+
+    let mut con1 = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    let mut con2 = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+
+    // replies of the bus are addressed to the unique name of the caller
+    let unique1 = call_bus(&mut con1, "GetNameOwner", "org.freedesktop.DBus")
+        .dynheader
+        .destination
+        .unwrap();
+    assert!(unique1.starts_with(":1."));
+
+    call(
+        &mut con2,
+        standard_messages::add_match(
+            "type='signal',sender='org.freedesktop.DBus',member='NameOwnerChanged',arg0='io.killing.spark'",
+        ),
+    );
+    let reply = call(
+        &mut con1,
+        standard_messages::request_name("io.killing.spark", 0),
+    );
+    assert_eq!(
+        reply.body.parser().get::<u32>().unwrap(),
+        standard_messages::DBUS_REQUEST_NAME_REPLY_PRIMARY_OWNER
+    );
+    let reply = call(
+        &mut con2,
+        standard_messages::request_name(
+            "io.killing.spark",
+            standard_messages::DBUS_NAME_FLAG_DO_NOT_QUEUE,
+        ),
+    );
+    assert_eq!(
+        reply.body.parser().get::<u32>().unwrap(),
+        standard_messages::DBUS_REQUEST_NAME_REPLY_EXISTS
+    );
+
+    let sig = wait_signal(&mut con2, "NameOwnerChanged");
+    assert_eq!(
+        sig.body.parser().get3::<&str, &str, &str>().unwrap(),
+        ("io.killing.spark", "", unique1.as_str())
+    );
+
+    let owner = call_bus(&mut con2, "GetNameOwner", "io.killing.spark");
+    assert_eq!(owner.body.parser().get::<&str>().unwrap(), unique1);
+    let missing = call_bus(&mut con2, "GetNameOwner", "io.killing.missing");
+    assert_eq!(missing.typ, MessageType::Error);
+    assert_eq!(
+        missing.dynheader.error_name.as_deref(),
+        Some("org.freedesktop.DBus.Error.NameHasNoOwner")
+    );
+    let names = call(&mut con2, standard_messages::list_names());
+    let names = names.body.parser().get::<Vec<String>>().unwrap();
+    assert!(names.contains(&"io.killing.spark".to_owned()));
+    assert!(names.contains(&unique1));
+
+    // unicast call via the well-known name
+    let mut msg = MessageBuilder::new()
+        .call("Echo")
+        .with_interface("io.killing.spark")
+        .on("/io/killing/spark")
+        .at("io.killing.spark")
+        .build();
+    msg.body.push_param("hello from con2").unwrap();
+    let serial = con2.send_message(&mut msg).unwrap().write_all().unwrap();
+    let received = con1.wait_call(Timeout::Infinite).unwrap();
+    assert_ne!(received.dynheader.sender.as_deref(), Some(unique1.as_str()));
+    let mut reply = received.dynheader.make_response();
+    reply.body.push_param("hello from con1").unwrap();
+    con1.send_message(&mut reply).unwrap().write_all().unwrap();
+    let reply = con2.wait_response(serial, Timeout::Infinite).unwrap();
+    assert_eq!(reply.dynheader.sender.as_deref(), Some(unique1.as_str()));
+    assert_eq!(
+        reply.body.parser().get::<&str>().unwrap(),
+        "hello from con1"
+    );
+
+    // signals are broadcast to the clients with a matching rule, the sender can be given as the well-known name
+    call(
+        &mut con2,
+        standard_messages::add_match("type='signal',sender='io.killing.spark'"),
+    );
+    let mut sig = MessageBuilder::new()
+        .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
+        .build();
+    con1.send_message(&mut sig).unwrap().write_all().unwrap();
+    let sig = wait_signal(&mut con2, "TestSignal");
+    assert_eq!(sig.dynheader.object.as_deref(), Some("/io/killing/spark"));
+
+    // calls to unknown names are answered by the bus
+    let msg = MessageBuilder::new()
+        .call("Echo")
+        .on("/io/killing/spark")
+        .at("io.killing.missing")
+        .build();
+    let reply = call(&mut con2, msg);
+    assert_eq!(
+        reply.dynheader.error_name.as_deref(),
+        Some("org.freedesktop.DBus.Error.ServiceUnknown")
+    );
+
+    // the name is released once the owner disconnects
+    drop(con1);
+    let sig = wait_signal(&mut con2, "NameOwnerChanged");
+    assert_eq!(
+        sig.body.parser().get3::<&str, &str, &str>().unwrap(),
+        ("io.killing.spark", unique1.as_str(), "")
+    );
+}
+
+#[test]
+fn stalled_client_does_not_block_others() {
+    let broker = spawn_broker();
+    let addr = [broker.address().clone()];
+    let path = match &addr[0].transport {
+        Transport::Unix(UnixAddress::Path(path)) => path.clone(),
+        _ => panic!("expected a path"),
+    };
+
+    // connects but never authenticates
+    let _stalled = std::os::unix::net::UnixStream::connect(path).unwrap();
+
+    let timeout = Timeout::Duration(std::time::Duration::from_secs(2));
+    let mut con = RpcConn::connect_to_path(&addr, timeout).unwrap();
+    call_bus(&mut con, "GetNameOwner", "org.freedesktop.DBus");
+
+    // stopping does not wait for the stalled client either
+    let start = std::time::Instant::now();
+    broker.stop().unwrap();
+    assert!(start.elapsed() < std::time::Duration::from_secs(2));
+}
+
+#[test]
+fn rpc_conn_match_rules() {
+    let broker = spawn_broker();
+    let addr = [broker.address().clone()];
+
+    let mut con1 = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    let mut con2 = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    let unique2 = call_bus(&mut con2, "GetNameOwner", "org.freedesktop.DBus")
+        .dynheader
+        .destination
+        .unwrap();
+    // the NameAcquired for the unique name was received before the reply
+    assert!(con2.try_get_signal().is_some());
+
+    let rule = MatchRule::new()
+        .with_type(MessageType::Signal)
+        .with_member("TestSignal");
+    con2.add_match(rule.clone(), Timeout::Infinite).unwrap();
+    assert_eq!(con2.match_rules(), std::slice::from_ref(&rule));
+
+    // unicast signals are delivered by the bus regardless of the rules but dropped locally
+    let mut other = MessageBuilder::new()
+        .signal("io.killing.spark", "OtherSignal", "/io/killing/spark")
+        .to(unique2.clone())
+        .build();
+    con1.send_message(&mut other).unwrap().write_all().unwrap();
+    let mut sig = MessageBuilder::new()
+        .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
+        .build();
+    con1.send_message(&mut sig).unwrap().write_all().unwrap();
+
+    let sig = con2.wait_signal(Timeout::Infinite).unwrap();
+    assert_eq!(sig.dynheader.member.as_deref(), Some("TestSignal"));
+    assert!(con2.try_get_signal().is_none());
+
+    con2.remove_match(&rule, Timeout::Infinite).unwrap();
+    assert!(con2.match_rules().is_empty());
+    match con2.remove_match(&rule, Timeout::Infinite) {
+        Err(crate::connection::Error::ErrorReply(e)) => {
+            assert_eq!(e.name, ErrorName::MatchRuleNotFound)
+        }
+        other => panic!("Expected an error reply, got: {:?}", other),
+    }
+}
+
+#[test]
+fn bus_proxy_match_rules() {
+    use crate::bus::BusProxy;
+
+    let broker = spawn_broker();
+    let addr = [broker.address().clone()];
+
+    let mut con1 = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    let mut con2 = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+
+    let rule = MatchRule::new()
+        .with_type(MessageType::Signal)
+        .with_member("TestSignal");
+    let proxy_rule = MatchRule::new()
+        .with_type(MessageType::Signal)
+        .with_member("ProxySignal");
+    con2.add_match(rule.clone(), Timeout::Infinite).unwrap();
+    BusProxy::new(&mut con2).add_match(&proxy_rule).unwrap();
+    assert_eq!(con2.match_rules(), &[rule.clone(), proxy_rule.clone()][..]);
+
+    // the rule added through the proxy keeps its signals
+    let mut sig = MessageBuilder::new()
+        .signal("io.killing.spark", "ProxySignal", "/io/killing/spark")
+        .build();
+    con1.send_message(&mut sig).unwrap().write_all().unwrap();
+    wait_signal(&mut con2, "ProxySignal");
+
+    BusProxy::new(&mut con2).remove_match(&proxy_rule).unwrap();
+    assert_eq!(con2.match_rules(), std::slice::from_ref(&rule));
+    match BusProxy::new(&mut con2).remove_match(&proxy_rule) {
+        Err(crate::connection::rpc_conn::CallError::ErrorReply(e)) => {
+            assert_eq!(e.name, ErrorName::MatchRuleNotFound)
+        }
+        other => panic!("Expected an error reply, got: {:?}", other),
+    }
+}
+
+#[test]
+fn rpc_conn_subscriptions() {
+    let broker = spawn_broker();
+    let addr = [broker.address().clone()];
+
+    let mut con1 = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    let mut con2 = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+
+    let rule = MatchRule::new()
+        .with_type(MessageType::Signal)
+        .with_interface("io.killing.spark");
+    let sub1 = con2.subscribe(rule.clone(), Timeout::Infinite).unwrap();
+    let sub2 = con2.subscribe(rule.clone(), Timeout::Infinite).unwrap();
+    let received = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let received_cb = received.clone();
+    let sub3 = con2
+        .subscribe_with_callback(
+            rule.clone().with_member("TestSignal"),
+            Box::new(move |sig| {
+                received_cb
+                    .lock()
+                    .unwrap()
+                    .push(sig.dynheader.member.clone().unwrap())
+            }),
+            Timeout::Infinite,
+        )
+        .unwrap();
+    while con2.try_get_signal().is_some() {}
+
+    for member in ["TestSignal", "OtherSignal"].iter() {
+        let mut sig = MessageBuilder::new()
+            .signal("io.killing.spark", *member, "/io/killing/spark")
+            .build();
+        con1.send_message(&mut sig).unwrap().write_all().unwrap();
+    }
+
+    // every subscription gets its own copy and the general queue stays empty
+    for sub in [sub1, sub2].iter() {
+        for member in ["TestSignal", "OtherSignal"].iter() {
+            let sig = con2
+                .wait_subscribed_signal(*sub, Timeout::Infinite)
+                .unwrap();
+            assert_eq!(sig.dynheader.member.as_deref(), Some(*member));
+        }
+    }
+    assert_eq!(*received.lock().unwrap(), vec!["TestSignal".to_owned()]);
+    assert!(con2.try_get_signal().is_none());
+    assert!(con2.try_get_subscribed_signal(sub3).is_none());
+
+    // the match rule is only removed from the bus with the last subscription for it
+    con2.unsubscribe(sub1, Timeout::Infinite).unwrap();
+    // removing it by hand succeeds, so the rule is still known to the bus. Add it back for sub2.
+    let reply = call(
+        &mut con2,
+        standard_messages::remove_match(&rule.to_string()),
+    );
+    assert_eq!(reply.typ, MessageType::Reply);
+    call(&mut con2, standard_messages::add_match(&rule.to_string()));
+    con2.unsubscribe(sub2, Timeout::Infinite).unwrap();
+    let reply = call(
+        &mut con2,
+        standard_messages::remove_match(&rule.to_string()),
+    );
+    assert_eq!(
+        reply.dynheader.error_name.as_deref(),
+        Some("org.freedesktop.DBus.Error.MatchRuleNotFound")
+    );
+    con2.unsubscribe(sub3, Timeout::Infinite).unwrap();
+}
+
+#[test]
+fn rpc_conn_well_known_sender() {
+    let broker = spawn_broker();
+    let addr = [broker.address().clone()];
+
+    let mut owner = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    let mut other = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    let mut con = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    call(
+        &mut owner,
+        standard_messages::request_name("io.killing.spark.Sender", 0),
+    );
+
+    let rule = MatchRule::new()
+        .with_type(MessageType::Signal)
+        .with_sender("io.killing.spark.Sender")
+        .with_member("TestSignal");
+    let sub = con.subscribe(rule, Timeout::Infinite).unwrap();
+    while con.try_get_signal().is_some() {}
+
+    let send_signal = |con: &mut RpcConn, path: &str| {
+        let mut sig = MessageBuilder::new()
+            .signal("io.killing.spark", "TestSignal", path)
+            .build();
+        con.send_message(&mut sig).unwrap().write_all().unwrap();
+        // the bus has routed the signal once the reply is here
+        call_bus(con, "GetNameOwner", "org.freedesktop.DBus");
+    };
+
+    // only signals of the current owner match
+    send_signal(&mut other, "/other");
+    send_signal(&mut owner, "/owner");
+    let sig = con.wait_subscribed_signal(sub, Timeout::Infinite).unwrap();
+    assert_eq!(sig.dynheader.object.as_deref(), Some("/owner"));
+    call_bus(&mut con, "GetNameOwner", "org.freedesktop.DBus");
+    assert!(con.try_get_subscribed_signal(sub).is_none());
+
+    // the owner changes
+    call(
+        &mut owner,
+        standard_messages::release_name("io.killing.spark.Sender"),
+    );
+    call(
+        &mut other,
+        standard_messages::request_name("io.killing.spark.Sender", 0),
+    );
+    send_signal(&mut owner, "/owner");
+    send_signal(&mut other, "/other");
+    let sig = con.wait_subscribed_signal(sub, Timeout::Infinite).unwrap();
+    assert_eq!(sig.dynheader.object.as_deref(), Some("/other"));
+    call_bus(&mut con, "GetNameOwner", "org.freedesktop.DBus");
+    assert!(con.try_get_subscribed_signal(sub).is_none());
+    // the NameOwnerChanged signals used to follow the owner are not handed out
+    assert!(con.try_get_signal().is_none());
+
+    con.unsubscribe(sub, Timeout::Infinite).unwrap();
+}
+
+#[test]
+fn rpc_conn_call() {
+    use crate::connection::rpc_conn::CallError;
+
+    let broker = spawn_broker();
+    let mut con = RpcConn::connect_to_path([broker.address().clone()], Timeout::Infinite).unwrap();
+
+    let get_name_owner = |con: &mut RpcConn, name: &str| -> Result<String, CallError> {
+        let (owner,) = con.call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "GetNameOwner",
+            (name,),
+            Timeout::Infinite,
+        )?;
+        Ok(owner)
+    };
+    assert_eq!(
+        get_name_owner(&mut con, "org.freedesktop.DBus").unwrap(),
+        "org.freedesktop.DBus"
+    );
+    match get_name_owner(&mut con, "io.killing.missing") {
+        Err(CallError::ErrorReply(e)) => {
+            assert_eq!(e.name, ErrorName::NameHasNoOwner);
+            assert!(e.message.unwrap().contains("io.killing.missing"));
+        }
+        other => panic!("Expected an error reply, got: {:?}", other),
+    }
+
+    let wrong_type: Result<(u32,), CallError> = con.call(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        "NameHasOwner",
+        ("org.freedesktop.DBus",),
+        Timeout::Infinite,
+    );
+    assert!(matches!(wrong_type, Err(CallError::InvalidReply(_))));
+    let too_many: Result<(bool, u32), CallError> = con.call(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        "NameHasOwner",
+        ("org.freedesktop.DBus",),
+        Timeout::Infinite,
+    );
+    assert!(matches!(too_many, Err(CallError::InvalidReply(_))));
+    let too_few: Result<(), CallError> = con.call(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        "NameHasOwner",
+        ("org.freedesktop.DBus",),
+        Timeout::Infinite,
+    );
+    assert!(matches!(too_few, Err(CallError::InvalidReply(_))));
+
+    // each element of the tuple is its own argument
+    let (reply,): (u32,) = con
+        .call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "RequestName",
+            ("io.killing.spark", 0u32),
+            Timeout::Infinite,
+        )
+        .unwrap();
+    assert_eq!(
+        reply,
+        standard_messages::DBUS_REQUEST_NAME_REPLY_PRIMARY_OWNER
+    );
+    let (names,): (Vec<String>,) = con
+        .call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "ListNames",
+            (),
+            Timeout::Infinite,
+        )
+        .unwrap();
+    assert!(names.iter().any(|name| name == "io.killing.spark"));
+
+    // methods without return values
+    con.call::<_, ()>(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        "AddMatch",
+        ("type='signal',member='Nothing'",),
+        Timeout::Infinite,
+    )
+    .unwrap();
+}
+
+#[test]
+fn name_owner() {
+    use crate::bus::{NameFlags, NameOwner, NameStatus, ReleaseNameReply};
+
+    let broker = spawn_broker();
+    let addr = [broker.address().clone()];
+
+    let con1 = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    let mut con2 = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+
+    let flags = NameFlags::default().allow_replacement();
+    let mut owner1 =
+        NameOwner::request(con1, "io.killing.spark", flags, Timeout::Infinite).unwrap();
+    assert_eq!(owner1.status(), NameStatus::Owner);
+    assert_eq!(owner1.queue_position().unwrap(), Some(0));
+    let changes = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let changes_cb = changes.clone();
+    owner1.on_change(Box::new(move |status| {
+        changes_cb.lock().unwrap().push(status)
+    }));
+
+    let flags = NameFlags::default().replace_existing();
+    let owner2 =
+        NameOwner::request(&mut con2, "io.killing.spark", flags, Timeout::Infinite).unwrap();
+    assert_eq!(owner2.status(), NameStatus::Owner);
+
+    // the NameLost is processed with the next message the connection receives
+    assert_eq!(owner1.queue_position().unwrap(), Some(1));
+    assert_eq!(owner1.status(), NameStatus::InQueue);
+    assert_eq!(*changes.lock().unwrap(), vec![NameStatus::InQueue]);
+
+    // dropping the second owner releases the name and the first one gets it back
+    drop(owner2);
+    assert_eq!(owner1.queue_position().unwrap(), Some(0));
+    assert_eq!(owner1.status(), NameStatus::Owner);
+    assert_eq!(
+        *changes.lock().unwrap(),
+        vec![NameStatus::InQueue, NameStatus::Owner]
+    );
+
+    assert_eq!(owner1.release().unwrap(), ReleaseNameReply::Released);
+    assert!(!call_bus(&mut con2, "NameHasOwner", "io.killing.spark")
+        .body
+        .parser()
+        .get::<bool>()
+        .unwrap());
+}
+
+#[test]
+fn rpc_conn_watch_name() {
+    let broker = spawn_broker();
+    let addr = [broker.address().clone()];
+
+    let mut con1 = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    let mut con2 = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    let unique1 = con1.unique_name().unwrap().to_owned();
+
+    let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let appeared = events.clone();
+    let vanished = events.clone();
+    let watch = con2
+        .watch_name(
+            "io.killing.spark",
+            Box::new(move |name, owner| {
+                appeared
+                    .lock()
+                    .unwrap()
+                    .push(format!("appeared {} {}", name, owner))
+            }),
+            Box::new(move |name| vanished.lock().unwrap().push(format!("vanished {}", name))),
+            Timeout::Infinite,
+        )
+        .unwrap();
+    // the name has no owner yet, which is reported right away
+    assert_eq!(*events.lock().unwrap(), vec!["vanished io.killing.spark"]);
+
+    call(
+        &mut con1,
+        standard_messages::request_name("io.killing.spark", 0),
+    );
+    // the signals are processed while waiting for the reply to some call
+    call(&mut con2, standard_messages::list_names());
+    assert_eq!(
+        events.lock().unwrap().last().unwrap(),
+        &format!("appeared io.killing.spark {}", unique1)
+    );
+
+    // a watch started while the name is owned reports the owner right away
+    let owner = std::sync::Arc::new(std::sync::Mutex::new(None));
+    let owner_cb = owner.clone();
+    let second_watch = con2
+        .watch_name(
+            "io.killing.spark",
+            Box::new(move |_, owner| *owner_cb.lock().unwrap() = Some(owner.to_owned())),
+            Box::new(|_| panic!("The watch should have ended")),
+            Timeout::Infinite,
+        )
+        .unwrap();
+    assert_eq!(owner.lock().unwrap().as_deref(), Some(unique1.as_str()));
+    con2.unsubscribe(second_watch, Timeout::Infinite).unwrap();
+
+    call(
+        &mut con1,
+        standard_messages::release_name("io.killing.spark"),
+    );
+    call(&mut con2, standard_messages::list_names());
+    assert_eq!(
+        events.lock().unwrap().last().unwrap(),
+        "vanished io.killing.spark"
+    );
+    assert_eq!(events.lock().unwrap().len(), 3);
+    con2.unsubscribe(watch, Timeout::Infinite).unwrap();
+}
+
+/// Runs a DispatchConn with the properties Count (read), Label (readwrite) and Secret (write) on /counter. Calling
+/// Increment changes Count, calling Relabel changes and invalidates Label. /plain has a handler that answers every call
+/// with its member name. Returns the unique name of the service.
+fn spawn_counter_service(broker: &BrokerHandle) -> String {
+    use crate::connection::dispatch_conn::{DispatchConn, HandleEnvironment, Matches, Property};
+    use crate::introspect::{Interface, Method};
+    use crate::DBusError;
+
+    struct Counter {
+        count: u32,
+        label: String,
+    }
+
+    broker
+        .spawn_service(&[], |conn| {
+            let handler = Box::new(
+                |counter: &mut Counter,
+                 _: Matches,
+                 msg: &MarshalledMessage,
+                 env: &mut HandleEnvironment<Counter, ()>| {
+                    if msg.dynheader.member.as_deref() == Some("Relabel") {
+                        counter.label = "relabeled".to_owned();
+                        env.properties_changed(
+                            "/counter",
+                            "io.killing.spark.Counter",
+                            &[],
+                            &["Label"],
+                        );
+                    } else {
+                        counter.count += 1;
+                        env.properties_changed(
+                            "/counter",
+                            "io.killing.spark.Counter",
+                            &["Count"],
+                            &[],
+                        );
+                    }
+                    Ok(None)
+                },
+            );
+            let mut service = DispatchConn::new(
+                conn,
+                Counter {
+                    count: 0,
+                    label: "clicks".to_owned(),
+                },
+                Box::new(|_, _, _, _| Ok(None)),
+            );
+            service.add_handler("/counter", handler);
+            service.add_handler(
+                "/plain",
+                Box::new(|_, _, msg, _| {
+                    let mut reply = msg.dynheader.make_response();
+                    reply
+                        .body
+                        .push_param(msg.dynheader.member.as_deref().unwrap_or_default())
+                        .unwrap();
+                    Ok(Some(reply))
+                }),
+            );
+            service.add_interface(
+                "/counter",
+                Interface::new("io.killing.spark.Counter")
+                    .with_method(Method::new("Increment"))
+                    .with_method(Method::new("Relabel"))
+                    .with_annotation("org.freedesktop.DBus.Deprecated", "false"),
+            );
+            service.add_property(
+                "/counter",
+                "io.killing.spark.Counter",
+                "Count",
+                Property::read_only(|counter: &mut Counter, _| Ok(counter.count)),
+            );
+            service.add_property(
+                "/counter",
+                "io.killing.spark.Counter",
+                "Label",
+                Property::read_write(
+                    |counter: &mut Counter, _| Ok(counter.label.clone()),
+                    |counter: &mut Counter, _, label: String| {
+                        if label.is_empty() {
+                            return Err(DBusError::new(ErrorName::InvalidArgs, "Empty label"));
+                        }
+                        counter.label = label;
+                        Ok(())
+                    },
+                ),
+            );
+            service.add_property(
+                "/counter",
+                "io.killing.spark.Counter",
+                "Secret",
+                Property::write_only(|_: &mut Counter, _, _: u64| Ok(())),
+            );
+            service
+        })
+        .unwrap()
+}
+
+#[test]
+fn dispatch_conn_properties() {
+    use crate::connection::rpc_conn::CallError;
+    use crate::wire::unmarshal::traits::Variant;
+    use std::collections::HashMap;
+
+    let broker = spawn_broker();
+    let addr = [broker.address().clone()];
+    let service_name = spawn_counter_service(&broker);
+
+    let mut con = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    let properties_call = |member: &str| {
+        MessageBuilder::new()
+            .call(member)
+            .with_interface("org.freedesktop.DBus.Properties")
+            .on("/counter")
+            .at(service_name.clone())
+            .build()
+    };
+    let get = |con: &mut RpcConn, name: &str| {
+        let mut call = properties_call("Get");
+        call.body
+            .push_param2("io.killing.spark.Counter", name)
+            .unwrap();
+        con.call_message(call, Timeout::Infinite)
+    };
+    let set = |con: &mut RpcConn, name: &str, value: &str| {
+        let mut call = properties_call("Set");
+        call.body
+            .push_param2("io.killing.spark.Counter", name)
+            .unwrap();
+        call.body.push_variant(value).unwrap();
+        con.call_message(call, Timeout::Infinite)
+    };
+    let error_name = |result: Result<MarshalledMessage, CallError>| match result {
+        Err(CallError::ErrorReply(e)) => e.name,
+        other => panic!("Expected an error reply, got: {:?}", other),
+    };
+
+    let reply = get(&mut con, "Count").unwrap();
+    assert_eq!(reply.get_sig(), "v");
+    let value = reply.body.parser().get::<Variant>().unwrap();
+    assert_eq!(value.get::<u32>().unwrap(), 0);
+
+    set(&mut con, "Label", "presses").unwrap();
+    let reply = get(&mut con, "Label").unwrap();
+    let value = reply.body.parser().get::<Variant>().unwrap();
+    assert_eq!(value.get::<&str>().unwrap(), "presses");
+
+    assert_eq!(
+        error_name(get(&mut con, "Missing")),
+        ErrorName::UnknownProperty
+    );
+    assert_eq!(
+        error_name(set(&mut con, "Count", "1")),
+        ErrorName::PropertyReadOnly
+    );
+    assert_eq!(
+        error_name(set(&mut con, "Label", "")),
+        ErrorName::InvalidArgs
+    );
+    assert_eq!(
+        error_name(set(&mut con, "Secret", "not a u64")),
+        ErrorName::InvalidArgs
+    );
+    assert_eq!(error_name(get(&mut con, "Secret")), ErrorName::AccessDenied);
+
+    let mut call = properties_call("GetAll");
+    call.body.push_param("io.killing.spark.Counter").unwrap();
+    let reply = con.call_message(call, Timeout::Infinite).unwrap();
+    assert_eq!(reply.get_sig(), "a{sv}");
+    let values = reply.body.parser().get::<HashMap<&str, Variant>>().unwrap();
+    assert_eq!(values.len(), 2);
+    assert_eq!(values["Count"].get::<u32>().unwrap(), 0);
+    assert_eq!(values["Label"].get::<&str>().unwrap(), "presses");
+
+    // calls the handler, which reports the change
+    con.add_match(
+        MatchRule::new()
+            .with_type(MessageType::Signal)
+            .with_member("PropertiesChanged"),
+        Timeout::Infinite,
+    )
+    .unwrap();
+    let call = MessageBuilder::new()
+        .call("Increment")
+        .with_interface("io.killing.spark.Counter")
+        .on("/counter")
+        .at(service_name.clone())
+        .build();
+    con.call_message(call, Timeout::Infinite).unwrap();
+    let sig = wait_signal(&mut con, "PropertiesChanged");
+    assert_eq!(sig.dynheader.object.as_deref(), Some("/counter"));
+    let (interface, changed, invalidated) = sig
+        .body
+        .parser()
+        .get3::<&str, HashMap<&str, Variant>, Vec<&str>>()
+        .unwrap();
+    assert_eq!(interface, "io.killing.spark.Counter");
+    assert_eq!(changed["Count"].get::<u32>().unwrap(), 1);
+    assert!(invalidated.is_empty());
+}
+
+#[test]
+fn properties_proxy_and_cache() {
+    use crate::connection::rpc_conn::CallError;
+    use crate::properties::{PropertiesProxy, PropertyCache};
+
+    let broker = spawn_broker();
+    let addr = [broker.address().clone()];
+    let service_name = spawn_counter_service(&broker);
+    let mut con = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+
+    let mut proxy = PropertiesProxy::new(
+        &mut con,
+        &service_name,
+        "/counter",
+        "io.killing.spark.Counter",
+    );
+    assert_eq!(proxy.get::<u32>("Count").unwrap(), 0);
+    assert!(matches!(
+        proxy.get::<String>("Count"),
+        Err(CallError::InvalidReply(_))
+    ));
+    proxy.set("Label", "presses").unwrap();
+    assert_eq!(proxy.get::<String>("Label").unwrap(), "presses");
+    assert_eq!(
+        proxy.get_variant("Label").unwrap().get::<&str>().unwrap(),
+        "presses"
+    );
+    let values = proxy.get_all().unwrap();
+    assert_eq!(values.len(), 2);
+    assert_eq!(values["Count"].get::<u32>().unwrap(), 0);
+
+    let mut cache = PropertyCache::new(
+        &mut con,
+        &service_name,
+        "/counter",
+        "io.killing.spark.Counter",
+        Timeout::Infinite,
+    )
+    .unwrap();
+    assert_eq!(cache.values(), values);
+    let changes = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let changes_cb = changes.clone();
+    cache.on_change(Box::new(move |name| {
+        changes_cb.lock().unwrap().push(name.to_owned())
+    }));
+
+    let call = |member: &str| {
+        MessageBuilder::new()
+            .call(member)
+            .with_interface("io.killing.spark.Counter")
+            .on("/counter")
+            .at(service_name.clone())
+            .build()
+    };
+    con.call_message(call("Increment"), Timeout::Infinite)
+        .unwrap();
+    con.call_message(call("Relabel"), Timeout::Infinite)
+        .unwrap();
+    // the signals are sent after the replies
+    while changes.lock().unwrap().len() < 2 {
+        con.refill_once(Timeout::Infinite).unwrap();
+    }
+    assert_eq!(cache.get::<u32>("Count").unwrap().unwrap(), 1);
+    assert!(cache.get::<String>("Label").is_none());
+    assert_eq!(*changes.lock().unwrap(), vec!["Count", "Label"]);
+
+    cache.update(&mut con).unwrap();
+    assert_eq!(cache.get::<String>("Label").unwrap().unwrap(), "relabeled");
+    assert_eq!(*changes.lock().unwrap(), vec!["Count", "Label", "Label"]);
+
+    cache.close(&mut con).unwrap();
+}
+
+#[test]
+fn dispatch_conn_introspect() {
+    let broker = spawn_broker();
+    let addr = [broker.address().clone()];
+    let service_name = spawn_counter_service(&broker);
+
+    let mut con = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    let mut introspect = |path: &str| {
+        let call = MessageBuilder::new()
+            .call("Introspect")
+            .with_interface("org.freedesktop.DBus.Introspectable")
+            .on(path)
+            .at(service_name.clone())
+            .build();
+        let reply = con.call_message(call, Timeout::Infinite).unwrap();
+        reply.body.parser().get::<String>().unwrap()
+    };
+
+    let root = introspect("/");
+    assert!(root.starts_with("<!DOCTYPE node"));
+    assert!(root.contains(r#"<node name="counter"/>"#));
+    assert!(root.contains(r#"<interface name="org.freedesktop.DBus.Introspectable">"#));
+    assert!(!root.contains("io.killing.spark.Counter"));
+
+    let counter = introspect("/counter");
+    assert!(!counter.contains("<node name="));
+    assert!(counter.contains(r#"<interface name="io.killing.spark.Counter">"#));
+    assert!(counter.contains(r#"<method name="Increment"/>"#));
+    assert!(
+        counter.contains(r#"<annotation name="org.freedesktop.DBus.Deprecated" value="false"/>"#)
+    );
+    // the registered properties are merged into the declared interface
+    assert!(counter.contains(r#"<property name="Count" type="u" access="read"/>"#));
+    assert!(counter.contains(r#"<property name="Label" type="s" access="readwrite"/>"#));
+    assert!(counter.contains(r#"<property name="Secret" type="t" access="write"/>"#));
+    assert!(counter.contains(r#"<interface name="org.freedesktop.DBus.Properties">"#));
+
+    // objects without declared interfaces or properties are introspected by their handler
+    assert_eq!(introspect("/plain"), "Introspect");
+}
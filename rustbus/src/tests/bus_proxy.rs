@@ -0,0 +1,84 @@
+use crate::bus::{BusProxy, NameFlags, ReleaseNameReply, RequestNameReply};
+use crate::connection::rpc_conn::{CallError, RpcConn};
+use crate::connection::Timeout;
+use crate::dbus_error::ErrorName;
+use crate::match_rule::MatchRule;
+
+#[test]
+fn bus_proxy() {
+    let mut con1 = RpcConn::system_conn(Timeout::Infinite).unwrap();
+    let mut con2 = RpcConn::system_conn(Timeout::Infinite).unwrap();
+    let name = format!("io.killing.spark.busproxy{}", std::process::id());
+
+    let mut bus1 = BusProxy::new(&mut con1);
+    assert_eq!(
+        bus1.request_name(&name, NameFlags::default()).unwrap(),
+        RequestNameReply::PrimaryOwner
+    );
+    assert_eq!(
+        bus1.request_name(&name, NameFlags::default()).unwrap(),
+        RequestNameReply::AlreadyOwner
+    );
+    let unique1 = bus1.get_name_owner(&name).unwrap();
+    assert!(bus1.name_has_owner(&name).unwrap());
+    assert!(bus1.list_names().unwrap().contains(&name));
+
+    let uid = bus1.get_connection_unix_user(&unique1).unwrap();
+    assert_eq!(uid, nix::unistd::getuid().as_raw());
+    let pid = bus1.get_connection_unix_process_id(&unique1).unwrap();
+    assert_eq!(pid, std::process::id());
+    let creds = bus1.get_connection_credentials(&name).unwrap();
+    assert_eq!(creds.unix_user_id, Some(uid));
+    assert_eq!(creds.process_id, Some(pid));
+    assert_eq!(bus1.get_id().unwrap().len(), 32);
+    assert!(bus1
+        .interfaces()
+        .unwrap()
+        .contains(&"org.freedesktop.DBus.Monitoring".to_owned()));
+    bus1.features().unwrap();
+    bus1.list_activatable_names().unwrap();
+
+    let rule = MatchRule::new().with_member("NothingWillEverBeCalledThis");
+    bus1.add_match(&rule).unwrap();
+    bus1.remove_match(&rule).unwrap();
+    match bus1.remove_match(&rule) {
+        Err(CallError::ErrorReply(e)) => assert_eq!(e.name, ErrorName::MatchRuleNotFound),
+        other => panic!("Expected an error reply, got: {:?}", other),
+    }
+
+    let mut bus2 = BusProxy::new(&mut con2);
+    assert_eq!(
+        bus2.request_name(&name, NameFlags::default().do_not_queue())
+            .unwrap(),
+        RequestNameReply::Exists
+    );
+    assert_eq!(
+        bus2.request_name(&name, NameFlags::default()).unwrap(),
+        RequestNameReply::InQueue
+    );
+    let queued = bus2.list_queued_owners(&name).unwrap();
+    assert_eq!(queued.len(), 2);
+    assert_eq!(queued[0], unique1);
+    assert_eq!(
+        bus2.release_name(&name).unwrap(),
+        ReleaseNameReply::Released
+    );
+    assert_eq!(
+        bus2.release_name(&name).unwrap(),
+        ReleaseNameReply::NotOwner
+    );
+    match bus2.get_name_owner("io.killing.spark.nobody") {
+        Err(CallError::ErrorReply(e)) => assert_eq!(e.name, ErrorName::NameHasNoOwner),
+        other => panic!("Expected an error reply, got: {:?}", other),
+    }
+
+    let mut bus1 = BusProxy::new(&mut con1);
+    assert_eq!(
+        bus1.release_name(&name).unwrap(),
+        ReleaseNameReply::Released
+    );
+    assert_eq!(
+        bus1.release_name(&name).unwrap(),
+        ReleaseNameReply::NonExistent
+    );
+}
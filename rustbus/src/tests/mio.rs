@@ -0,0 +1,70 @@
+use crate::connection::get_system_bus_path;
+use crate::connection::ll_conn::DuplexConn;
+use crate::connection::Timeout;
+use crate::message_builder::{MessageBuilder, MessageType};
+
+use mio::{Events, Interest, Poll, Token};
+
+#[test]
+fn test_mio_poll_loop() {
+    let mut con =
+        DuplexConn::connect_to_bus(get_system_bus_path().unwrap(), true, Timeout::Infinite)
+            .unwrap();
+
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+    const CON: Token = Token(0);
+
+    // queue everything at once, including a signal big enough to not fit into the socket buffer
+    let hello_serial = con
+        .send
+        .queue_message(crate::standard_messages::hello())
+        .unwrap();
+    con.send
+        .queue_message(crate::standard_messages::add_match("type='signal'"))
+        .unwrap();
+    let payload = "a".repeat(4 * 1024 * 1024);
+    let mut sig = MessageBuilder::new()
+        .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
+        .build();
+    sig.body.push_param(payload.as_str()).unwrap();
+    con.send.queue_message(sig).unwrap();
+    assert!(con.send.has_pending_writes());
+
+    poll.registry()
+        .register(&mut con, CON, Interest::READABLE | Interest::WRITABLE)
+        .unwrap();
+
+    let mut got_hello = false;
+    let mut writable_registered = true;
+    'outer: loop {
+        poll.poll(&mut events, Some(std::time::Duration::from_secs(10)))
+            .unwrap();
+        assert!(!events.is_empty(), "poll timed out");
+
+        for event in events.iter() {
+            assert_eq!(event.token(), CON);
+            if event.is_writable() && con.flush_pending_writes().unwrap() && writable_registered {
+                // all written, stop listening for writability
+                poll.registry()
+                    .reregister(&mut con, CON, Interest::READABLE)
+                    .unwrap();
+                writable_registered = false;
+            }
+            if event.is_readable() {
+                for msg in con.try_read_messages().unwrap() {
+                    if msg.dynheader.response_serial == Some(hello_serial) {
+                        assert_eq!(msg.typ, MessageType::Reply);
+                        got_hello = true;
+                    }
+                    if msg.dynheader.member.as_deref() == Some("TestSignal") {
+                        assert_eq!(msg.body.parser().get::<&str>().unwrap(), payload);
+                        break 'outer;
+                    }
+                }
+            }
+        }
+    }
+    assert!(got_hello);
+    assert!(!con.send.has_pending_writes());
+}
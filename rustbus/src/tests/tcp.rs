@@ -0,0 +1,118 @@
+use crate::connection::address::{DBusAddress, TcpAddress, Transport};
+use crate::connection::ll_conn::DuplexConn;
+use crate::connection::{Error, Timeout};
+use crate::message_builder::MessageBuilder;
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+fn read_line(stream: &mut TcpStream) -> String {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    while !line.ends_with(b"\r\n") {
+        stream.read_exact(&mut byte).unwrap();
+        line.push(byte[0]);
+    }
+    line.truncate(line.len() - 2);
+    String::from_utf8(line).unwrap()
+}
+
+/// Accepts one client, does the server side of the auth and then echos one message back to the client
+fn fake_server(listener: TcpListener, nonce: Option<[u8; 16]>) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        if let Some(nonce) = nonce {
+            let mut received = [0u8; 16];
+            stream.read_exact(&mut received).unwrap();
+            assert_eq!(received, nonce);
+        }
+        let mut null = [1u8; 1];
+        stream.read_exact(&mut null).unwrap();
+        assert_eq!(null[0], 0);
+        assert!(read_line(&mut stream).starts_with("AUTH "));
+        stream
+            .write_all(b"OK 0123456789abcdef0123456789abcdef\r\n")
+            .unwrap();
+        // no unix fds are negotiated over tcp
+        assert_eq!(read_line(&mut stream), "BEGIN");
+
+        let mut header = [0u8; 16];
+        stream.read_exact(&mut header).unwrap();
+        let body_len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let fields_len =
+            u32::from_le_bytes([header[12], header[13], header[14], header[15]]) as usize;
+        let header_len = 16 + fields_len;
+        let padding = (8 - (header_len % 8)) % 8;
+        let mut rest = vec![0u8; fields_len + padding + body_len];
+        stream.read_exact(&mut rest).unwrap();
+
+        stream.write_all(&header).unwrap();
+        stream.write_all(&rest).unwrap();
+    })
+}
+
+fn echo_signal(con: &mut DuplexConn) {
+    let mut sig = MessageBuilder::new()
+        .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
+        .build();
+    sig.body.push_param("over tcp").unwrap();
+    con.send.send_message_write_all(&sig).unwrap();
+
+    let echo = con.recv.get_next_message(Timeout::Infinite).unwrap();
+    assert_eq!(echo.dynheader.member.as_deref(), Some("TestSignal"));
+    assert_eq!(echo.body.parser().get::<&str>().unwrap(), "over tcp");
+}
+
+#[test]
+fn test_tcp_transport() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = fake_server(listener, None);
+
+    let addr = DBusAddress::parse(&format!("tcp:host=127.0.0.1,port={}", port)).unwrap();
+    let mut con = DuplexConn::connect_to_bus([addr], true, Timeout::Infinite).unwrap();
+    assert!(!con.send.can_pass_unix_fds());
+
+    // fds are refused before anything is written to the connection
+    let mut sig = MessageBuilder::new()
+        .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
+        .build();
+    sig.body
+        .push_param(crate::wire::UnixFd::new(nix::unistd::dup(0).unwrap()))
+        .unwrap();
+    assert!(matches!(
+        con.send.send_message(&sig),
+        Err(Error::UnixFdNotSupported)
+    ));
+
+    echo_signal(&mut con);
+    server.join().unwrap();
+}
+
+#[test]
+fn test_nonce_tcp_transport() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let nonce = *b"0123456789ABCDEF";
+    let server = fake_server(listener, Some(nonce));
+
+    let noncefile = std::env::temp_dir().join(format!("rustbus-test-nonce-{}", port));
+    std::fs::write(&noncefile, nonce).unwrap();
+
+    let addr = DBusAddress {
+        transport: Transport::NonceTcp {
+            tcp: TcpAddress {
+                host: Some("127.0.0.1".into()),
+                port: Some(port),
+                ..Default::default()
+            },
+            noncefile: Some(noncefile.clone()),
+        },
+        guid: None,
+    };
+    let mut con = DuplexConn::connect_to_bus([addr], false, Timeout::Infinite).unwrap();
+    std::fs::remove_file(noncefile).unwrap();
+
+    echo_signal(&mut con);
+    server.join().unwrap();
+}
@@ -8,6 +8,7 @@ pub mod validate_raw;
 pub mod variant_macros;
 
 mod wrapper_types;
+pub use wrapper_types::owned_variant::{OwnedVariant, VariantMap};
 pub use wrapper_types::unixfd::UnixFd;
 pub use wrapper_types::ObjectPath;
 pub use wrapper_types::SignatureWrapper;
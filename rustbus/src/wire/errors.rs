@@ -88,4 +88,13 @@ pub enum UnmarshalError {
     /// When unmarshalling a Variant and there is not matching variant in the enum that had the unmarshal impl derived
     #[error("When unmarshalling a Variant and there is not matching variant in the enum that had the unmarshal impl derived")]
     NoMatchingVariantFound,
+    /// A dict that is unmarshalled into a struct with `#[rustbus(dict)]` is missing a key the struct requires
+    #[error("The dict is missing the required key {0}")]
+    MissingDictKey(String),
+    /// A dict that is unmarshalled into a struct with `#[rustbus(dict, deny_unknown_keys)]` contains an unknown key
+    #[error("The dict contains the unknown key {0}")]
+    UnknownDictKey(String),
+    /// A value that is unmarshalled into an enum with `#[rustbus(repr = "...")]` matches none of its variants
+    #[error("The value {0} matches no variant of the enum")]
+    UnknownEnumValue(String),
 }
@@ -105,6 +105,12 @@ fn marshal_header(
     if let Some(obj) = &msg.dynheader.object {
         marshal_header_field(byteorder, &HeaderField::Path(obj.clone()), buf)?;
     }
+    if let Some(name) = &msg.dynheader.error_name {
+        marshal_header_field(byteorder, &HeaderField::ErrorName(name.clone()), buf)?;
+    }
+    if let Some(sender) = &msg.dynheader.sender {
+        marshal_header_field(byteorder, &HeaderField::Sender(sender.clone()), buf)?;
+    }
     if !msg.body.raw_fds.is_empty() {
         marshal_header_field(
             byteorder,
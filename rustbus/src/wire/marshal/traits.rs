@@ -3,6 +3,7 @@ use crate::wire::marshal::MarshalContext;
 
 mod base;
 mod container;
+#[allow(unused_imports)]
 pub use base::*;
 pub use container::*;
 
@@ -63,11 +64,11 @@ pub use container::*;
 /// # Implementing for your own structs
 /// There are some rules you need to follow, or the messages will be malformed:
 /// 1. Structs need to be aligned to 8 bytes. Use `ctx.align_to(8);` to do that. If your type is marshalled as a primitive type
-///     you still need to align to that types alignment.
+///    you still need to align to that types alignment.
 /// 1. If you write your own dict type, you need to align every key-value pair at 8 bytes like a struct
 /// 1. The signature needs to be correct, or the message will be malformed
 /// 1. The alignment must report the correct number. This does not need to be a constant like in the example, but it needs to be consistent with the type
-///     the signature() function returns. If you are not sure, just use Self::signature().get_alignment().
+///    the signature() function returns. If you are not sure, just use Self::signature().get_alignment().
 pub trait Marshal: Signature {
     fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), crate::wire::errors::MarshalError>;
     fn marshal_as_variant(
@@ -88,7 +89,7 @@ pub trait Marshal: Signature {
 
 /// `SignatureBuffer` is used to store static or dynamic signatures and avoid allocations if possible.
 /// It is a wrapper around Cow.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SignatureBuffer(Cow<'static, str>);
 
 impl SignatureBuffer {
@@ -235,6 +236,15 @@ pub trait Signature {
         Self::sig_str(&mut s_buf);
         sig == s_buf.as_str()
     }
+
+    /// Check if this type accepts the signature with some trailing values left out. The `MessageBodyParser` only asks
+    /// this for the last value of a message body, because only there the missing values can not be confused with the
+    /// values that follow. Inside of containers the full signature is always required.
+    ///
+    /// The default impl returns false. Structs derived with `#[rustbus(default)]` fields accept signatures without them.
+    fn has_partial_sig(_sig: &str) -> bool {
+        false
+    }
 }
 
 impl<S: Signature> Signature for &S {
@@ -250,6 +260,9 @@ impl<S: Signature> Signature for &S {
     fn has_sig(sig: &str) -> bool {
         S::has_sig(sig)
     }
+    fn has_partial_sig(sig: &str) -> bool {
+        S::has_partial_sig(sig)
+    }
 }
 
 impl<P: Marshal> Marshal for &P {
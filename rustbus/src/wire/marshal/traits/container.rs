@@ -313,7 +313,8 @@ impl<E: Marshal> Marshal for &[E] {
     }
 }
 
-pub struct Variant<T: Marshal + Signature>(T);
+/// Marshals the wrapped value as a variant, e.g. to pass it as an argument of type `v`
+pub struct Variant<T: Marshal + Signature>(pub T);
 
 impl<T: Marshal + Signature> Signature for Variant<T> {
     #[inline]
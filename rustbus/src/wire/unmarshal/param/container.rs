@@ -45,6 +45,7 @@ pub fn unmarshal_variant<'a, 'e>(
     ))
 }
 
+#[allow(clippy::mutable_key_type)]
 pub fn unmarshal_container<'a, 'e>(
     typ: &signature::Container,
     ctx: &mut UnmarshalContext,
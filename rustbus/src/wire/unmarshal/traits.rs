@@ -7,6 +7,7 @@ use crate::wire::unmarshal::UnmarshalContext;
 // these contain the implementations
 mod base;
 mod container;
+#[allow(unused_imports)]
 pub use base::*;
 pub use container::*;
 
@@ -134,7 +135,6 @@ pub use container::*;
 ///     }
 /// }
 /// ```
-
 pub trait Unmarshal<'buf, 'fds>: Sized + Signature {
     fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self>;
 }
@@ -170,9 +170,9 @@ mod test {
         // annotate the receiver with a type &str to unmarshal a &str
         "ABCD".marshal(ctx).unwrap();
         let _s: &str = unmarshal(&mut UnmarshalContext {
-            buf: &ctx.buf,
+            buf: ctx.buf,
             byteorder: ctx.byteorder,
-            fds: &ctx.fds,
+            fds: ctx.fds,
             offset: 0,
         })
         .unwrap()
@@ -182,9 +182,9 @@ mod test {
         ctx.buf.clear();
         true.marshal(ctx).unwrap();
         let _b: bool = unmarshal(&mut UnmarshalContext {
-            buf: &ctx.buf,
+            buf: ctx.buf,
             byteorder: ctx.byteorder,
-            fds: &ctx.fds,
+            fds: ctx.fds,
             offset: 0,
         })
         .unwrap()
@@ -194,9 +194,9 @@ mod test {
         ctx.buf.clear();
         0i32.marshal(ctx).unwrap();
         let _i = unmarshal::<i32>(&mut UnmarshalContext {
-            buf: &ctx.buf,
+            buf: ctx.buf,
             byteorder: ctx.byteorder,
-            fds: &ctx.fds,
+            fds: ctx.fds,
             offset: 0,
         })
         .unwrap()
@@ -207,9 +207,9 @@ mod test {
         fn x(_arg: (i32, i32, &str)) {}
         (0, 0, "ABCD").marshal(ctx).unwrap();
         let arg = unmarshal(&mut UnmarshalContext {
-            buf: &ctx.buf,
+            buf: ctx.buf,
             byteorder: ctx.byteorder,
-            fds: &ctx.fds,
+            fds: ctx.fds,
             offset: 0,
         })
         .unwrap()
@@ -369,6 +369,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::mutable_key_type)]
     fn test_variant() {
         use crate::message_builder::MarshalledMessageBody;
         use crate::params::{Array, Base, Container, Dict, Param, Variant as ParamVariant};
@@ -481,10 +482,7 @@ mod test {
             SignatureWrapper::new("sy").unwrap(),
             parser.get::<Variant>().unwrap().get().unwrap()
         );
-        assert_eq!(
-            true,
-            parser.get::<Variant>().unwrap().get::<bool>().unwrap()
-        );
+        assert!(parser.get::<Variant>().unwrap().get::<bool>().unwrap());
 
         // check Array of variants
         let var_vec: Vec<Variant> = parser.get().unwrap();
@@ -500,7 +498,7 @@ mod test {
             SignatureWrapper::new("sy").unwrap(),
             var_vec[8].get().unwrap()
         );
-        assert_eq!(true, var_vec[9].get::<bool>().unwrap());
+        assert!(var_vec[9].get::<bool>().unwrap());
 
         // check Dict of {String, variants}
         let var_map: HashMap<String, Variant> = parser.get().unwrap();
@@ -516,6 +514,6 @@ mod test {
             SignatureWrapper::new("sy").unwrap(),
             var_map["8"].get().unwrap()
         );
-        assert_eq!(true, var_map["9"].get::<bool>().unwrap());
+        assert!(var_map["9"].get::<bool>().unwrap());
     }
 }
@@ -1,5 +1,6 @@
 use std::convert::TryFrom;
 
+pub mod owned_variant;
 pub mod unixfd;
 
 #[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
@@ -36,7 +37,7 @@ impl TryFrom<String> for ObjectPath<String> {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 /// Wraps a String or a &str or whatever implements AsRef<str> and checks at creation, that it is a valid Signature
 pub struct SignatureWrapper<S: AsRef<str>>(S);
 impl<S: AsRef<str>> SignatureWrapper<S> {
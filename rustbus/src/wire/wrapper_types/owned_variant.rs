@@ -0,0 +1,224 @@
+//! An owned version of the Variant unmarshal type
+
+use crate::signature;
+use crate::wire::errors::{MarshalError, UnmarshalError};
+use crate::wire::marshal::traits::SignatureBuffer;
+use crate::wire::marshal::MarshalContext;
+use crate::wire::unmarshal::traits::Variant;
+use crate::wire::unmarshal::UnmarshalContext;
+use crate::wire::UnixFd;
+use crate::{ByteOrder, Marshal, Signature, Unmarshal};
+
+/// A variant that does not borrow from the message it was unmarshalled from, so it can be kept around, e.g. in a
+/// map of property values. It is used like the `Variant` it was made from and can be marshalled again.
+///
+/// The value is kept in its marshalled form. Getting it does the unmarshalling then.
+/// ```rust
+/// use rustbus::wire::OwnedVariant;
+/// use rustbus::MessageBuilder;
+/// use std::collections::HashMap;
+///
+/// let mut msg = MessageBuilder::new()
+///     .signal("io.killing.spark", "Values", "/io/killing/spark")
+///     .build();
+/// msg.body.push_variant(42u32).unwrap();
+/// msg.body.push_variant("Hello").unwrap();
+///
+/// let mut parser = msg.body.parser();
+/// let (number, text) = parser.get2::<OwnedVariant, OwnedVariant>().unwrap();
+/// drop(msg);
+/// assert_eq!(number.get::<u32>().unwrap(), 42);
+/// assert_eq!(text.get::<&str>().unwrap(), "Hello");
+/// assert!(text.get::<u32>().is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct OwnedVariant {
+    sig: signature::Type,
+    byteorder: ByteOrder,
+    // the value starts at this offset. The bytes before it are kept so the value has the same alignment as in the
+    // message, which keeps the padding inside the value valid.
+    offset: usize,
+    buf: Vec<u8>,
+    fds: Vec<UnixFd>,
+}
+
+/// A dict of type `a{sv}`, which is commonly used to pass a set of named values of any type, e.g. the properties
+/// returned by GetAll
+pub type VariantMap = std::collections::HashMap<String, OwnedVariant>;
+
+impl OwnedVariant {
+    /// Get the [`Type`] of the value contained by the variant.
+    ///
+    /// [`Type`]: /rustbus/signature/enum.Type.html
+    pub fn get_value_sig(&self) -> &signature::Type {
+        &self.sig
+    }
+
+    /// Unmarshal the variant's value, like [`Variant::get()`]. Borrowed types like `&str` borrow from the OwnedVariant.
+    ///
+    /// [`Variant::get()`]: /rustbus/wire/unmarshal/traits/struct.Variant.html#method.get
+    pub fn get<'a, T: Unmarshal<'a, 'a>>(&'a self) -> Result<T, UnmarshalError> {
+        if self.sig != T::signature() {
+            return Err(UnmarshalError::WrongSignature);
+        }
+        let mut ctx = UnmarshalContext {
+            byteorder: self.byteorder,
+            offset: self.offset,
+            buf: &self.buf,
+            fds: &self.fds,
+        };
+        T::unmarshal(&mut ctx).map(|r| r.1)
+    }
+
+    fn to_param(&self) -> crate::params::Param<'static, 'static> {
+        let mut ctx = UnmarshalContext {
+            byteorder: self.byteorder,
+            offset: self.offset,
+            buf: &self.buf,
+            fds: &self.fds,
+        };
+        crate::wire::unmarshal::container::unmarshal_with_sig(&self.sig, &mut ctx)
+            .expect("The value was validated when the variant was unmarshalled")
+            .1
+    }
+}
+
+impl From<&Variant<'_, '_>> for OwnedVariant {
+    fn from(variant: &Variant<'_, '_>) -> Self {
+        // the variants buf ends with the value
+        let start = variant.offset - variant.offset % 8;
+        OwnedVariant {
+            sig: variant.sig.clone(),
+            byteorder: variant.byteorder,
+            offset: variant.offset - start,
+            buf: variant.buf[start..].to_vec(),
+            fds: variant.fds.to_vec(),
+        }
+    }
+}
+
+impl PartialEq for OwnedVariant {
+    fn eq(&self, other: &Self) -> bool {
+        if self.sig != other.sig {
+            return false;
+        }
+        if self.byteorder == other.byteorder
+            && self.offset == other.offset
+            && self.fds.is_empty()
+            && other.fds.is_empty()
+        {
+            return self.buf[self.offset..] == other.buf[other.offset..];
+        }
+        self.to_param() == other.to_param()
+    }
+}
+
+impl Signature for OwnedVariant {
+    fn signature() -> signature::Type {
+        signature::Type::Container(signature::Container::Variant)
+    }
+    fn alignment() -> usize {
+        1
+    }
+    #[inline]
+    fn sig_str(s_buf: &mut SignatureBuffer) {
+        s_buf.push_static("v");
+    }
+    fn has_sig(sig: &str) -> bool {
+        sig.starts_with('v')
+    }
+}
+
+impl Marshal for OwnedVariant {
+    fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
+        let mut sig = SignatureBuffer::new();
+        self.sig.to_str(sig.to_string_mut());
+        crate::wire::util::write_signature(&sig, ctx.buf);
+
+        ctx.align_to(self.sig.get_alignment());
+        if self.byteorder == ctx.byteorder
+            && self.fds.is_empty()
+            && ctx.buf.len() % 8 == self.offset
+        {
+            // same layout, the bytes can be copied as they are
+            ctx.buf.extend_from_slice(&self.buf[self.offset..]);
+            Ok(())
+        } else {
+            crate::wire::marshal::container::marshal_param(&self.to_param(), ctx)
+        }
+    }
+}
+
+impl<'buf, 'fds> Unmarshal<'buf, 'fds> for OwnedVariant {
+    fn unmarshal(
+        ctx: &mut UnmarshalContext<'fds, 'buf>,
+    ) -> crate::wire::unmarshal::UnmarshalResult<Self> {
+        let (bytes, variant) = Variant::unmarshal(ctx)?;
+        Ok((bytes, OwnedVariant::from(&variant)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OwnedVariant;
+    use crate::message_builder::MarshalledMessageBody;
+    use crate::wire::UnixFd;
+    use std::collections::HashMap;
+
+    #[test]
+    fn owned_variant_roundtrip() {
+        let mut values = HashMap::new();
+        values.insert("byte".to_owned(), 1u8);
+
+        let mut body = MarshalledMessageBody::new();
+        body.push_param(1u8).unwrap();
+        body.push_variant(vec![1u64, 2, 3]).unwrap();
+        body.push_variant(("text", 42i32)).unwrap();
+        body.push_variant(&values).unwrap();
+
+        let mut parser = body.parser();
+        parser.get::<u8>().unwrap();
+        let array = parser.get::<OwnedVariant>().unwrap();
+        let strct = parser.get::<OwnedVariant>().unwrap();
+        let dict = parser.get::<OwnedVariant>().unwrap();
+        assert_eq!(array.get::<Vec<u64>>().unwrap(), vec![1, 2, 3]);
+        assert_eq!(strct.get::<(&str, i32)>().unwrap(), ("text", 42));
+        assert_eq!(dict.get::<HashMap<String, u8>>().unwrap(), values);
+
+        // pushing the variants at other alignments than they had before needs the values to be marshalled again
+        for padding in 0..8 {
+            let mut body = MarshalledMessageBody::new();
+            for _ in 0..padding {
+                body.push_param(0u8).unwrap();
+            }
+            body.push_param3(&array, &strct, &dict).unwrap();
+            let mut parser = body.parser();
+            for _ in 0..padding {
+                parser.get::<u8>().unwrap();
+            }
+            let (array2, strct2, dict2) = parser
+                .get3::<OwnedVariant, OwnedVariant, OwnedVariant>()
+                .unwrap();
+            assert_eq!(array2.get::<Vec<u64>>().unwrap(), vec![1, 2, 3]);
+            assert_eq!(array2, array);
+            assert_eq!(strct2, strct);
+            assert_eq!(dict2, dict);
+        }
+    }
+
+    #[test]
+    fn owned_variant_fds() {
+        let mut body = MarshalledMessageBody::new();
+        body.push_param(1u8).unwrap();
+        body.push_variant(UnixFd::new(nix::unistd::dup(1).unwrap()))
+            .unwrap();
+        let fd = body.parser().get2::<u8, OwnedVariant>().unwrap().1;
+        drop(body);
+
+        let mut body = MarshalledMessageBody::new();
+        body.push_param(&fd).unwrap();
+        assert_eq!(body.get_fds().len(), 1);
+        let fd2 = body.parser().get::<OwnedVariant>().unwrap();
+        assert!(fd2.get::<UnixFd>().unwrap().get_raw_fd().is_some());
+    }
+}
@@ -47,11 +47,7 @@ impl UnixFdInner {
                 std::sync::atomic::Ordering::SeqCst,
             );
             //  If swapped_fd == fd then we did a sucessful swap and we actually took the value
-            if let Ok(taken_fd) = swapped_fd {
-                Some(taken_fd as i32)
-            } else {
-                None
-            }
+            swapped_fd.ok()
         }
     }
 
@@ -88,9 +84,9 @@ impl UnixFdInner {
 ///
 /// ## UnixFds and messages
 /// 1. When a UnixFd is **marshalled** rustbus will dup() the FD so that the message and the original UnixFd do not depend on each others lifetime. You are free to use
-/// or close the original one.
+///    or close the original one.
 /// 1. When a UnixFd is **unmarshalled** rustbus will **NOT** dup() the FD. This means if you call take_raw_fd(), it is gone from the message too! If you do not want this,
-/// you have to call dup() and then get_raw_fd() or take_raw_fd()
+///    you have to call dup() and then get_raw_fd() or take_raw_fd()
 #[derive(Clone, Debug)]
 pub struct UnixFd(Arc<UnixFdInner>);
 impl UnixFd {
@@ -0,0 +1,696 @@
+//! Writing the bindings of one interface
+
+use crate::names::{camel_case, ident, snake_case};
+use crate::types::{GeneratedStruct, Types};
+use crate::Error;
+use rustbus::connection::dispatch_conn::PropertyAccess;
+use rustbus::introspect::{self, Interface, Node};
+
+/// Names of locals in the generated functions, arguments with these names get renamed
+const LOCALS: &[&str] = &["call", "reply", "parser", "object", "msg", "value", "error"];
+
+const MESSAGE: &str = "::rustbus::message_builder::MarshalledMessage";
+const MARSHAL_ERROR: &str = "::rustbus::wire::errors::MarshalError";
+const UNMARSHAL_ERROR: &str = "::rustbus::wire::errors::UnmarshalError";
+const CALL_ERROR: &str = "::rustbus::connection::rpc_conn::CallError";
+const DBUS_ERROR: &str = "::rustbus::DBusError";
+const DISPATCH: &str = "::rustbus::connection::dispatch_conn";
+
+/// Collects the lines of the generated code with the right indentation
+#[derive(Default)]
+pub struct Code {
+    buf: String,
+    indent: usize,
+}
+
+impl Code {
+    pub fn line(&mut self, line: &str) {
+        if !line.is_empty() {
+            for _ in 0..self.indent {
+                self.buf.push_str("    ");
+            }
+            self.buf.push_str(line);
+        }
+        self.buf.push('\n');
+    }
+
+    /// A line that opens a block, the following lines are indented
+    pub fn open(&mut self, line: &str) {
+        self.line(line);
+        self.indent += 1;
+    }
+
+    pub fn close(&mut self, line: &str) {
+        self.indent -= 1;
+        self.line(line);
+    }
+
+    pub fn into_string(self) -> String {
+        self.buf
+    }
+}
+
+struct Arg {
+    ident: String,
+    owned: String,
+    borrowed: String,
+}
+
+struct Method {
+    member: String,
+    fn_name: String,
+    ins: Vec<Arg>,
+    outs: Vec<Arg>,
+}
+
+struct Property {
+    member: String,
+    getter: String,
+    setter: String,
+    owned: String,
+    borrowed: String,
+    access: PropertyAccess,
+}
+
+struct Signal {
+    member: String,
+    struct_name: String,
+    fields: Vec<Arg>,
+}
+
+fn args<'a>(
+    args: impl Iterator<Item = &'a introspect::Arg>,
+    types: &mut Types,
+    context: &str,
+) -> Result<Vec<Arg>, Error> {
+    let mut result: Vec<Arg> = Vec::new();
+    for (idx, arg) in args.enumerate() {
+        let name = match &arg.name {
+            Some(name) => snake_case(name),
+            None => format!("arg{}", idx),
+        };
+        let mut taken: Vec<&str> = LOCALS.to_vec();
+        taken.extend(result.iter().map(|arg| arg.ident.as_str()));
+        let context = format!("{}{}", context, camel_case(&name));
+        result.push(Arg {
+            ident: ident(name, &taken),
+            owned: types.owned(&arg.typ, &context)?,
+            borrowed: types.borrowed(&arg.typ, &context)?,
+        });
+    }
+    Ok(result)
+}
+
+/// Everything that is generated for one interface
+pub struct Bindings {
+    interface: Interface,
+    module: String,
+    trait_name: String,
+    proxy_name: String,
+    methods: Vec<Method>,
+    properties: Vec<Property>,
+    signals: Vec<Signal>,
+    structs: Vec<GeneratedStruct>,
+}
+
+impl Bindings {
+    pub fn new(interface: &Interface, module: String) -> Result<Self, Error> {
+        let base_name = camel_case(interface.name.rsplit('.').next().unwrap_or_default());
+        let trait_name = base_name.clone();
+        let proxy_name = format!("{}Proxy", base_name);
+        let mut types = Types::default();
+
+        // the functions of the proxy and the trait share their names
+        let mut fn_names: Vec<String> = vec![
+            "new".to_owned(),
+            "with_timeout".to_owned(),
+            "properties".to_owned(),
+        ];
+        let mut take_fn_name = |name: String| {
+            let taken: Vec<&str> = fn_names.iter().map(String::as_str).collect();
+            let name = ident(name, &taken);
+            fn_names.push(name.clone());
+            name
+        };
+
+        let mut methods = Vec::new();
+        for method in &interface.methods {
+            let context = camel_case(&method.name);
+            methods.push(Method {
+                member: method.name.clone(),
+                fn_name: take_fn_name(snake_case(&method.name)),
+                ins: args(method.in_args(), &mut types, &context)?,
+                outs: args(method.out_args(), &mut types, &context)?,
+            });
+        }
+
+        let mut properties = Vec::new();
+        for property in &interface.properties {
+            let snake = snake_case(&property.name);
+            let mut getter = snake.clone();
+            let mut setter = format!("set_{}", snake);
+            // a method with the same name takes precedence
+            if methods
+                .iter()
+                .any(|m| m.fn_name == getter || m.fn_name == setter)
+            {
+                getter = format!("{}_property", snake);
+                setter = format!("set_{}_property", snake);
+            }
+            let context = camel_case(&property.name);
+            properties.push(Property {
+                member: property.name.clone(),
+                getter: take_fn_name(getter),
+                setter: take_fn_name(setter),
+                owned: types.owned(&property.typ, &context)?,
+                borrowed: types.borrowed(&property.typ, &context)?,
+                access: property.access,
+            });
+        }
+
+        let mut signals = Vec::new();
+        for signal in &interface.signals {
+            let struct_name = camel_case(&signal.name);
+            signals.push(Signal {
+                member: signal.name.clone(),
+                fields: args(signal.args.iter(), &mut types, &struct_name)?,
+                struct_name,
+            });
+        }
+
+        Ok(Bindings {
+            interface: interface.clone(),
+            module,
+            trait_name,
+            proxy_name,
+            methods,
+            properties,
+            signals,
+            structs: types.structs,
+        })
+    }
+
+    pub fn write(&self, code: &mut Code) {
+        code.line(&format!("/// Bindings for `{}`", self.interface.name));
+        code.open(&format!("pub mod {} {{", self.module));
+        code.line("#![allow(clippy::too_many_arguments, clippy::type_complexity)]");
+        code.line("");
+        code.line(&format!(
+            "pub const INTERFACE: &str = {:?};",
+            self.interface.name
+        ));
+        code.line("");
+        self.write_introspection(code);
+        for generated in &self.structs {
+            code.line("");
+            write_struct(code, generated);
+        }
+        code.line("");
+        self.write_proxy(code);
+        for signal in &self.signals {
+            code.line("");
+            self.write_signal(code, signal);
+        }
+        code.line("");
+        self.write_trait(code);
+        code.line("");
+        self.write_handle(code);
+        code.line("");
+        self.write_register(code);
+        code.close("}");
+    }
+
+    fn write_introspection(&self, code: &mut Code) {
+        let xml = Node::new().with_interface(self.interface.clone()).to_xml();
+        let mut hashes = "#".to_owned();
+        while xml.contains(&format!("\"{}", hashes)) {
+            hashes.push('#');
+        }
+        code.line(&format!(
+            "const INTROSPECTION: &str = r{}\"{}\"{};",
+            hashes,
+            xml.trim_end(),
+            hashes
+        ));
+        code.line("");
+        code.line("/// The description of the interface, as it is reported by Introspect");
+        code.open("pub fn interface() -> ::rustbus::introspect::Interface {");
+        code.line("::rustbus::introspect::Node::from_xml(INTROSPECTION)");
+        code.line(
+            "    .expect(\"The introspection data was valid when the bindings were generated\")",
+        );
+        code.line("    .interfaces");
+        code.line("    .remove(0)");
+        code.close("}");
+    }
+
+    fn write_proxy(&self, code: &mut Code) {
+        let proxy = &self.proxy_name;
+        code.line(
+            "/// Calls the methods of the interface on a remote object over an RpcConn. Messages that arrive while waiting",
+        );
+        code.line("/// for the replies are put into the queues of the RpcConn as usual.");
+        code.open(&format!("pub struct {}<'a> {{", proxy));
+        code.line("con: &'a mut ::rustbus::RpcConn,");
+        code.line("destination: String,");
+        code.line("path: String,");
+        code.line("timeout: ::rustbus::connection::Timeout,");
+        code.close("}");
+        code.line("");
+
+        code.open(&format!("impl<'a> {}<'a> {{", proxy));
+        code.line("/// Wait for the replies without a timeout");
+        code.open(
+            "pub fn new(con: &'a mut ::rustbus::RpcConn, destination: &str, path: &str) -> Self {",
+        );
+        code.line(
+            "Self::with_timeout(con, destination, path, ::rustbus::connection::Timeout::Infinite)",
+        );
+        code.close("}");
+        code.line("");
+        code.line("/// Wait at most `timeout` for each reply");
+        code.open("pub fn with_timeout(");
+        code.line("con: &'a mut ::rustbus::RpcConn,");
+        code.line("destination: &str,");
+        code.line("path: &str,");
+        code.line("timeout: ::rustbus::connection::Timeout,");
+        code.close(") -> Self {");
+        code.indent += 1;
+        code.open(&format!("{} {{", proxy));
+        code.line("con,");
+        code.line("destination: destination.to_owned(),");
+        code.line("path: path.to_owned(),");
+        code.line("timeout,");
+        code.close("}");
+        code.close("}");
+
+        for method in &self.methods {
+            code.line("");
+            write_proxy_method(code, method);
+        }
+        if !self.properties.is_empty() {
+            code.line("");
+            code.open("fn properties(&mut self) -> ::rustbus::properties::PropertiesProxy<'_> {");
+            code.line("::rustbus::properties::PropertiesProxy::with_timeout(");
+            code.line("    self.con,");
+            code.line("    &self.destination,");
+            code.line("    &self.path,");
+            code.line("    INTERFACE,");
+            code.line("    self.timeout,");
+            code.line(")");
+            code.close("}");
+        }
+        for property in &self.properties {
+            let properties_proxy = "self.properties()";
+            if property.access.readable() {
+                code.line("");
+                code.open(&format!(
+                    "pub fn {}(&mut self) -> Result<{}, {}> {{",
+                    property.getter, property.owned, CALL_ERROR
+                ));
+                code.line(&format!("{}.get({:?})", properties_proxy, property.member));
+                code.close("}");
+            }
+            if property.access.writable() {
+                code.line("");
+                code.open(&format!(
+                    "pub fn {}(&mut self, value: {}) -> Result<(), {}> {{",
+                    property.setter, property.borrowed, CALL_ERROR
+                ));
+                code.line(&format!(
+                    "{}.set({:?}, value)",
+                    properties_proxy, property.member
+                ));
+                code.close("}");
+            }
+        }
+        code.close("}");
+    }
+
+    fn write_signal(&self, code: &mut Code, signal: &Signal) {
+        let name = &signal.struct_name;
+        code.line(&format!("/// The signal `{}`", signal.member));
+        code.line("#[derive(Debug, Clone)]");
+        if signal.fields.is_empty() {
+            code.line(&format!("pub struct {} {{}}", name));
+        } else {
+            code.open(&format!("pub struct {} {{", name));
+            for field in &signal.fields {
+                code.line(&format!("pub {}: {},", field.ident, field.owned));
+            }
+            code.close("}");
+        }
+        code.line("");
+
+        code.open(&format!("impl {} {{", name));
+        code.line(&format!(
+            "pub const MEMBER: &'static str = {:?};",
+            signal.member
+        ));
+        code.line("");
+        code.line("/// Matches the signal sent by any object");
+        code.open("pub fn match_rule() -> ::rustbus::MatchRule {");
+        code.line("::rustbus::MatchRule::new()");
+        code.line("    .with_type(::rustbus::MessageType::Signal)");
+        code.line("    .with_interface(INTERFACE)");
+        code.line("    .with_member(Self::MEMBER)");
+        code.close("}");
+        code.line("");
+
+        code.line("/// None if the message is not this signal");
+        code.open(&format!(
+            "pub fn from_message(msg: &{}) -> Option<Result<Self, {}>> {{",
+            MESSAGE, UNMARSHAL_ERROR
+        ));
+        code.open("if msg.typ != ::rustbus::MessageType::Signal");
+        code.line("|| msg.dynheader.interface.as_deref() != Some(INTERFACE)");
+        code.line("|| msg.dynheader.member.as_deref() != Some(Self::MEMBER)");
+        code.close("{");
+        code.indent += 1;
+        code.line("return None;");
+        code.close("}");
+        if signal.fields.is_empty() {
+            code.line(&format!("Some(Ok({} {{}}))", name));
+        } else {
+            code.line("Some(Self::parse(msg))");
+        }
+        code.close("}");
+        code.line("");
+
+        if !signal.fields.is_empty() {
+            code.open(&format!(
+                "fn parse(msg: &{}) -> Result<Self, {}> {{",
+                MESSAGE, UNMARSHAL_ERROR
+            ));
+            code.line("let mut parser = msg.body.parser();");
+            code.open(&format!("Ok({} {{", name));
+            for field in &signal.fields {
+                code.line(&format!("{}: parser.get()?,", field.ident));
+            }
+            code.close("})");
+            code.close("}");
+            code.line("");
+        }
+
+        code.line("/// Build the signal as it is sent by the object at `path`");
+        code.open(&format!(
+            "pub fn to_message(&self, path: &str) -> Result<{}, {}> {{",
+            MESSAGE, MARSHAL_ERROR
+        ));
+        let build =
+            "::rustbus::MessageBuilder::new().signal(INTERFACE, Self::MEMBER, path).build()";
+        if signal.fields.is_empty() {
+            code.line(&format!("Ok({})", build));
+        } else {
+            code.line(&format!("let mut msg = {};", build));
+            for field in &signal.fields {
+                // values that are passed by value to the proxy are Copy
+                let borrow = if field.borrowed == field.owned {
+                    ""
+                } else {
+                    "&"
+                };
+                code.line(&format!(
+                    "msg.body.push_param({}self.{})?;",
+                    borrow, field.ident
+                ));
+            }
+            code.line("Ok(msg)");
+        }
+        code.close("}");
+        code.close("}");
+    }
+
+    fn write_trait(&self, code: &mut Code) {
+        code.line("/// The methods and properties of the interface for objects served by a DispatchConn, see `register`");
+        code.open(&format!("pub trait {} {{", self.trait_name));
+        let mut first = true;
+        let mut separate = |code: &mut Code| {
+            if !first {
+                code.line("");
+            }
+            first = false;
+        };
+        for method in &self.methods {
+            separate(code);
+            let mut params = vec!["&mut self".to_owned()];
+            params.extend(
+                method
+                    .ins
+                    .iter()
+                    .map(|arg| format!("{}: {}", arg.ident, arg.owned)),
+            );
+            code.line(&format!(
+                "fn {}({}) -> Result<{}, {}>;",
+                method.fn_name,
+                params.join(", "),
+                out_type(&method.outs),
+                DBUS_ERROR
+            ));
+        }
+        for property in &self.properties {
+            if property.access.readable() {
+                separate(code);
+                code.line(&format!(
+                    "fn {}(&mut self) -> Result<{}, {}>;",
+                    property.getter, property.owned, DBUS_ERROR
+                ));
+            }
+            if property.access.writable() {
+                separate(code);
+                code.line(&format!(
+                    "fn {}(&mut self, value: {}) -> Result<(), {}>;",
+                    property.setter, property.owned, DBUS_ERROR
+                ));
+            }
+        }
+        code.close("}");
+    }
+
+    fn write_handle(&self, code: &mut Code) {
+        let trait_name = &self.trait_name;
+        let object = if self.methods.is_empty() {
+            "_object"
+        } else {
+            "object"
+        };
+        code.line("/// Answer a call to a method of the interface. None if the message is not a call to this interface.");
+        code.open(&format!(
+            "pub fn handle<T: {}>({}: &mut T, msg: &{}) -> Option<Result<{}, {}>> {{",
+            trait_name, object, MESSAGE, MESSAGE, MARSHAL_ERROR
+        ));
+        code.open("if msg.typ != ::rustbus::MessageType::Call");
+        code.line("|| msg.dynheader.interface.as_deref() != Some(INTERFACE)");
+        code.close("{");
+        code.indent += 1;
+        code.line("return None;");
+        code.close("}");
+        let unknown_method = "Ok(::rustbus::standard_messages::unknown_method(&msg.dynheader))";
+        if self.methods.is_empty() {
+            code.line(&format!("Some({})", unknown_method));
+        } else {
+            code.open("Some(match msg.dynheader.member.as_deref() {");
+            for method in &self.methods {
+                code.line(&format!(
+                    "Some({:?}) => call_{}(object, msg),",
+                    method.member, method.fn_name
+                ));
+            }
+            code.line(&format!("_ => {},", unknown_method));
+            code.close("})");
+        }
+        code.close("}");
+
+        for method in &self.methods {
+            code.line("");
+            self.write_call(code, method);
+        }
+        if self.methods.iter().any(|method| !method.ins.is_empty()) {
+            code.line("");
+            code.open(&format!(
+                "fn invalid_args(msg: &{}) -> {} {{",
+                MESSAGE, MESSAGE
+            ));
+            code.open(&format!("{}::new(", DBUS_ERROR));
+            code.line("::rustbus::dbus_error::ErrorName::InvalidArgs,");
+            code.line("format!(\"Invalid arguments for {:?}\", msg.dynheader.member),");
+            code.close(")");
+            code.line(".to_message(&msg.dynheader)");
+            code.close("}");
+        }
+    }
+
+    fn write_call(&self, code: &mut Code, method: &Method) {
+        code.open(&format!(
+            "fn call_{}<T: {}>(object: &mut T, msg: &{}) -> Result<{}, {}> {{",
+            method.fn_name, self.trait_name, MESSAGE, MESSAGE, MARSHAL_ERROR
+        ));
+        if !method.ins.is_empty() {
+            code.line("let mut parser = msg.body.parser();");
+        }
+        for arg in &method.ins {
+            code.open(&format!("let {} = match parser.get() {{", arg.ident));
+            code.line("Ok(value) => value,");
+            code.line("Err(_) => return Ok(invalid_args(msg)),");
+            code.close("};");
+        }
+        let ins: Vec<&str> = method.ins.iter().map(|arg| arg.ident.as_str()).collect();
+        code.open(&format!(
+            "match object.{}({}) {{",
+            method.fn_name,
+            ins.join(", ")
+        ));
+        let outs: Vec<&str> = method.outs.iter().map(|arg| arg.ident.as_str()).collect();
+        match outs.len() {
+            0 => code.line("Ok(()) => Ok(msg.dynheader.make_response()),"),
+            _ => {
+                if outs.len() == 1 {
+                    code.open(&format!("Ok({}) => {{", outs[0]));
+                } else {
+                    code.open(&format!("Ok(({})) => {{", outs.join(", ")));
+                }
+                code.line("let mut reply = msg.dynheader.make_response();");
+                for out in &outs {
+                    code.line(&format!("reply.body.push_param({})?;", out));
+                }
+                code.line("Ok(reply)");
+                code.close("}");
+            }
+        }
+        code.line("Err(error) => Ok(error.to_message(&msg.dynheader)),");
+        code.close("}");
+        code.close("}");
+    }
+
+    fn write_register(&self, code: &mut Code) {
+        code.line("/// Register the object at `path` with the DispatchConn: the interface for Introspect, the properties and a");
+        code.line("/// handler for the method calls. Objects with more than one interface need a handler of their own that calls the");
+        code.line("/// `handle` functions of the interfaces.");
+        code.line("pub fn register<T, E>(con: &mut ::rustbus::DispatchConn<T, E>, path: &str)");
+        code.line("where");
+        code.line(&format!("    T: {} + 'static,", self.trait_name));
+        code.line("    E: ::std::fmt::Debug + 'static,");
+        code.open("{");
+        code.line("con.add_interface(path, interface());");
+        for property in &self.properties {
+            let getter = format!("|object: &mut T, _| object.{}()", property.getter);
+            let setter = format!(
+                "|object: &mut T, _, value: {}| object.{}(value)",
+                property.owned, property.setter
+            );
+            let constructor = match property.access {
+                PropertyAccess::Read => format!("read_only({})", getter),
+                PropertyAccess::Write => format!("write_only({})", setter),
+                PropertyAccess::ReadWrite => format!("read_write({}, {})", getter, setter),
+            };
+            code.open("con.add_property(");
+            code.line("path,");
+            code.line("INTERFACE,");
+            code.line(&format!("{:?},", property.member));
+            code.line(&format!("{}::Property::{},", DISPATCH, constructor));
+            code.close(");");
+        }
+        code.line("con.add_handler(path, Box::new(dispatch::<T, E>));");
+        code.close("}");
+        code.line("");
+
+        code.open("fn dispatch<T, E>(");
+        code.line("object: &mut T,");
+        code.line(&format!("_: {}::Matches,", DISPATCH));
+        code.line(&format!("msg: &{},", MESSAGE));
+        code.line(&format!("_: &mut {}::HandleEnvironment<T, E>,", DISPATCH));
+        code.close(&format!(") -> {}::HandleResult<E>", DISPATCH));
+        code.line("where");
+        code.line(&format!("    T: {},", self.trait_name));
+        code.line("    E: ::std::fmt::Debug,");
+        code.open("{");
+        code.open("match handle(object, msg) {");
+        code.line(&format!(
+            "Some(reply) => reply.map(Some).map_err({}::HandleError::Marshal),",
+            DISPATCH
+        ));
+        code.line(
+            "None => Ok(Some(::rustbus::standard_messages::unknown_method(&msg.dynheader))),",
+        );
+        code.close("}");
+        code.close("}");
+    }
+}
+
+fn out_type(outs: &[Arg]) -> String {
+    match outs.len() {
+        0 => "()".to_owned(),
+        1 => outs[0].owned.clone(),
+        _ => format!(
+            "({})",
+            outs.iter()
+                .map(|arg| arg.owned.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// The expression of a tuple with the values, as taken by `ArgList`
+fn tuple(values: &[&str]) -> String {
+    match values {
+        [] => "()".to_owned(),
+        [value] => format!("({},)", value),
+        _ => format!("({})", values.join(", ")),
+    }
+}
+
+fn write_proxy_method(code: &mut Code, method: &Method) {
+    let mut params = vec!["&mut self".to_owned()];
+    params.extend(
+        method
+            .ins
+            .iter()
+            .map(|arg| format!("{}: {}", arg.ident, arg.borrowed)),
+    );
+    code.open(&format!(
+        "pub fn {}({}) -> Result<{}, {}> {{",
+        method.fn_name,
+        params.join(", "),
+        out_type(&method.outs),
+        CALL_ERROR
+    ));
+    let args: Vec<&str> = method.ins.iter().map(|arg| arg.ident.as_str()).collect();
+    let outs: Vec<&str> = method.outs.iter().map(|arg| arg.ident.as_str()).collect();
+    // a single value is returned as it is, other return types are already the tuple of the values
+    let single_out = if outs.len() == 1 {
+        code.line(&format!("let ({},) = self.con.call(", outs[0]));
+        true
+    } else {
+        code.line("self.con.call(");
+        false
+    };
+    code.line("    &self.destination,");
+    code.line("    &self.path,");
+    code.line("    INTERFACE,");
+    code.line(&format!("    {:?},", method.member));
+    code.line(&format!("    {},", tuple(&args)));
+    code.line("    self.timeout,");
+    if single_out {
+        code.line(")?;");
+        code.line(&format!("Ok({})", outs[0]));
+    } else {
+        code.line(")");
+    }
+    code.close("}");
+}
+
+fn write_struct(code: &mut Code, generated: &GeneratedStruct) {
+    code.line(&format!(
+        "/// The struct `{}`",
+        crate::types::signature_str(&generated.typ)
+    ));
+    code.line(
+        "#[derive(Debug, Clone, ::rustbus::Marshal, ::rustbus::Unmarshal, ::rustbus::Signature)]",
+    );
+    code.open(&format!("pub struct {} {{", generated.name));
+    for (idx, field) in generated.fields.iter().enumerate() {
+        code.line(&format!("pub field{}: {},", idx, field));
+    }
+    code.close("}");
+}
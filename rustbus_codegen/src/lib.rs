@@ -0,0 +1,87 @@
+//! Generate rust bindings for dbus interfaces from their introspection data. For each interface there is a module with
+//!
+//! 1. a proxy that calls the methods and reads/writes the properties of a remote object over an `RpcConn`
+//! 1. a struct for each signal, that can be parsed from and turned into a message
+//! 1. a trait with the methods and properties, and a `register` function that serves an implementation with a `DispatchConn`
+//!
+//! The types of the arguments are derived from their signatures. `a{sv}` becomes a `VariantMap`, structs with more fields
+//! than the tuples of rustbus support are generated as structs with derived impls. The standard interfaces
+//! `org.freedesktop.DBus.{Introspectable, Properties, Peer}` are skipped, rustbus has its own support for them.
+//!
+//! The bindings can be generated with the `rustbus-codegen` binary, or from a build script:
+//!
+//! ```rust,no_run
+//! // in build.rs
+//! let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+//! println!("cargo:rerun-if-changed=counter.xml");
+//! rustbus_codegen::generate_file("counter.xml", out_dir.join("counter.rs")).unwrap();
+//! ```
+//!
+//! and included with `include!(concat!(env!("OUT_DIR"), "/counter.rs"));`
+
+mod generate;
+mod names;
+mod types;
+
+use generate::{Bindings, Code};
+use rustbus::introspect::{Interface, Node};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Could not parse the introspection data: {0}")]
+    Introspect(#[from] rustbus::introspect::Error),
+    #[error("The type {0} is not supported: {1}")]
+    UnsupportedType(String, &'static str),
+    #[error("Io error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Interfaces that rustbus implements itself
+const STANDARD_INTERFACES: &[&str] = &[
+    "org.freedesktop.DBus.Introspectable",
+    "org.freedesktop.DBus.Properties",
+    "org.freedesktop.DBus.Peer",
+];
+
+fn collect_interfaces<'a>(node: &'a Node, interfaces: &mut Vec<&'a Interface>) {
+    for interface in &node.interfaces {
+        let known = interfaces.iter().any(|known| known.name == interface.name);
+        if !known && !STANDARD_INTERFACES.contains(&interface.name.as_str()) {
+            interfaces.push(interface);
+        }
+    }
+    for child in &node.nodes {
+        collect_interfaces(child, interfaces);
+    }
+}
+
+/// Generate the bindings for all interfaces of the node and its children
+pub fn generate(xml: &str) -> Result<String, Error> {
+    let node = Node::from_xml(xml)?;
+    let mut interfaces = Vec::new();
+    collect_interfaces(&node, &mut interfaces);
+
+    let mut code = Code::default();
+    code.line("// Generated by rustbus-codegen from introspection data. Regenerate instead of editing it.");
+    let mut modules: Vec<String> = Vec::new();
+    for interface in interfaces {
+        let last_part = interface.name.rsplit('.').next().unwrap_or_default();
+        let taken: Vec<&str> = modules.iter().map(String::as_str).collect();
+        let module = names::ident(names::snake_case(last_part), &taken);
+        let bindings = Bindings::new(interface, module.clone())?;
+        modules.push(module);
+        code.line("");
+        bindings.write(&mut code);
+    }
+    Ok(code.into_string())
+}
+
+/// Generate the bindings for the introspection data in the input file and write them to the output file
+pub fn generate_file<P: AsRef<Path>, Q: AsRef<Path>>(input: P, output: Q) -> Result<(), Error> {
+    let xml = std::fs::read_to_string(input)?;
+    let code = generate(&xml)?;
+    std::fs::write(output, code)?;
+    Ok(())
+}
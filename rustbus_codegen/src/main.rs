@@ -0,0 +1,57 @@
+//! Generate rust bindings from dbus introspection data. Reads the xml from the input file or stdin and writes the
+//! bindings to the output file or stdout.
+
+use std::io::{Read, Write};
+
+const USAGE: &str = "Usage: rustbus-codegen [INPUT] [-o OUTPUT]
+
+Generates rust bindings for the interfaces in the introspection xml.
+Reads from stdin if no INPUT or - is given, writes to stdout if no OUTPUT is given.";
+
+fn run() -> Result<(), String> {
+    let mut input = None;
+    let mut output = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            "-o" | "--output" => {
+                output = Some(args.next().ok_or("-o needs the output file")?);
+            }
+            _ if input.is_none() => input = Some(arg),
+            _ => return Err(format!("Unexpected argument {}\n\n{}", arg, USAGE)),
+        }
+    }
+
+    let xml = match input.as_deref() {
+        None | Some("-") => {
+            let mut xml = String::new();
+            std::io::stdin()
+                .read_to_string(&mut xml)
+                .map_err(|e| format!("Could not read stdin: {}", e))?;
+            xml
+        }
+        Some(path) => {
+            std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?
+        }
+    };
+    let code = rustbus_codegen::generate(&xml).map_err(|e| e.to_string())?;
+    match output {
+        Some(path) => {
+            std::fs::write(&path, code).map_err(|e| format!("Could not write {}: {}", path, e))
+        }
+        None => std::io::stdout()
+            .write_all(code.as_bytes())
+            .map_err(|e| format!("Could not write to stdout: {}", e)),
+    }
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
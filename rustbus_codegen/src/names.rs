@@ -0,0 +1,85 @@
+//! Turning dbus names into rust identifiers
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "Self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Append an underscore to keywords and names that are already taken, and prefix names that start with a digit
+pub fn ident(name: String, taken: &[&str]) -> String {
+    let mut name = if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    };
+    while KEYWORDS.contains(&name.as_str()) || taken.contains(&name.as_str()) {
+        name.push('_');
+    }
+    name
+}
+
+/// `GetConnectionUnixProcessID` becomes `get_connection_unix_process_id`
+pub fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let mut snake = String::new();
+    for (idx, c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() && idx > 0 {
+            let prev = chars[idx - 1];
+            let next_is_lower = matches!(chars.get(idx + 1), Some(c) if c.is_ascii_lowercase());
+            if prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_is_lower)
+            {
+                snake.push('_');
+            }
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    if snake.is_empty() {
+        snake.push('_');
+    }
+    snake
+}
+
+/// `network_manager` and `NetworkManager` both become `NetworkManager`
+pub fn camel_case(name: &str) -> String {
+    let mut camel = String::new();
+    for part in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            camel.push(first.to_ascii_uppercase());
+            camel.extend(chars);
+        }
+    }
+    if camel.starts_with(|c: char| c.is_ascii_digit()) || camel.is_empty() {
+        camel.insert(0, '_');
+    }
+    camel
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(
+            snake_case("GetConnectionUnixProcessID"),
+            "get_connection_unix_process_id"
+        );
+        assert_eq!(snake_case("ListUnits"), "list_units");
+        assert_eq!(snake_case("xml_data"), "xml_data");
+        assert_eq!(snake_case("dns-servers"), "dns_servers");
+        assert_eq!(camel_case("network_manager"), "NetworkManager");
+        assert_eq!(camel_case("Manager"), "Manager");
+        assert_eq!(ident("type".to_owned(), &[]), "type_");
+        assert_eq!(ident("new".to_owned(), &["new"]), "new_");
+        assert_eq!(ident("1st".to_owned(), &[]), "_1st");
+    }
+}
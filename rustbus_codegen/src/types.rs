@@ -0,0 +1,134 @@
+//! Mapping dbus types to rust types
+
+use crate::names::camel_case;
+use crate::Error;
+use rustbus::signature::{Base, Container, Type};
+
+/// A struct with more fields than the tuples rustbus supports, it is generated as a struct with derived impls
+pub struct GeneratedStruct {
+    pub name: String,
+    pub typ: Type,
+    pub fields: Vec<String>,
+}
+
+/// Keeps track of the structs that need to be generated for the types of one interface
+#[derive(Default)]
+pub struct Types {
+    pub structs: Vec<GeneratedStruct>,
+}
+
+/// Tuples up to this length implement Marshal and Unmarshal
+const MAX_TUPLE_LEN: usize = 4;
+
+fn base_type(base: Base) -> &'static str {
+    match base {
+        Base::Byte => "u8",
+        Base::Boolean => "bool",
+        Base::Int16 => "i16",
+        Base::Uint16 => "u16",
+        Base::Int32 => "i32",
+        Base::Uint32 => "u32",
+        Base::Int64 => "i64",
+        Base::Uint64 => "u64",
+        Base::Double => "f64",
+        Base::String => "String",
+        Base::ObjectPath => "::rustbus::wire::ObjectPath<String>",
+        Base::Signature => "::rustbus::wire::SignatureWrapper<String>",
+        Base::UnixFd => "::rustbus::wire::UnixFd",
+    }
+}
+
+pub fn signature_str(typ: &Type) -> String {
+    let mut sig = String::new();
+    typ.to_str(&mut sig);
+    sig
+}
+
+impl Types {
+    /// The owned type that values of the dbus type are unmarshalled to. `context` names structs that need to be generated.
+    pub fn owned(&mut self, typ: &Type, context: &str) -> Result<String, Error> {
+        Ok(match typ {
+            Type::Base(base) => base_type(*base).to_owned(),
+            Type::Container(Container::Array(elem)) => {
+                format!("Vec<{}>", self.owned(elem, context)?)
+            }
+            Type::Container(Container::Dict(Base::String, value))
+                if **value == Type::Container(Container::Variant) =>
+            {
+                "::rustbus::wire::VariantMap".to_owned()
+            }
+            Type::Container(Container::Dict(Base::Double, _)) => {
+                return Err(Error::UnsupportedType(
+                    signature_str(typ),
+                    "doubles can not be used as keys of a HashMap",
+                ))
+            }
+            Type::Container(Container::Dict(key, value)) => format!(
+                "::std::collections::HashMap<{}, {}>",
+                base_type(*key),
+                self.owned(value, context)?
+            ),
+            Type::Container(Container::Variant) => "::rustbus::wire::OwnedVariant".to_owned(),
+            Type::Container(Container::Struct(fields)) => {
+                let fields = fields.as_ref();
+                if fields.len() <= MAX_TUPLE_LEN {
+                    let fields = fields
+                        .iter()
+                        .map(|field| self.owned(field, context))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if fields.len() == 1 {
+                        format!("({},)", fields[0])
+                    } else {
+                        format!("({})", fields.join(", "))
+                    }
+                } else {
+                    self.generated_struct(typ, fields, context)?
+                }
+            }
+        })
+    }
+
+    /// The type that is taken as argument when values of the dbus type are sent
+    pub fn borrowed(&mut self, typ: &Type, context: &str) -> Result<String, Error> {
+        Ok(match typ {
+            Type::Base(Base::String) => "&str".to_owned(),
+            Type::Base(Base::ObjectPath)
+            | Type::Base(Base::Signature)
+            | Type::Base(Base::UnixFd) => {
+                format!("&{}", self.owned(typ, context)?)
+            }
+            Type::Base(_) => self.owned(typ, context)?,
+            Type::Container(Container::Array(elem)) => format!("&[{}]", self.owned(elem, context)?),
+            Type::Container(_) => format!("&{}", self.owned(typ, context)?),
+        })
+    }
+
+    fn generated_struct(
+        &mut self,
+        typ: &Type,
+        fields: &[Type],
+        context: &str,
+    ) -> Result<String, Error> {
+        if let Some(existing) = self.structs.iter().find(|s| s.typ == *typ) {
+            return Ok(existing.name.clone());
+        }
+        let fields = fields
+            .iter()
+            .enumerate()
+            .map(|(idx, field)| self.owned(field, &format!("{}Field{}", context, idx)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let base_name = camel_case(context);
+        let mut name = base_name.clone();
+        let mut suffix = 1;
+        while self.structs.iter().any(|s| s.name == name) {
+            suffix += 1;
+            name = format!("{}{}", base_name, suffix);
+        }
+        self.structs.push(GeneratedStruct {
+            name: name.clone(),
+            typ: typ.clone(),
+            fields,
+        });
+        Ok(name)
+    }
+}
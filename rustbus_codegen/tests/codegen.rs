@@ -0,0 +1,176 @@
+//! The bindings for counter.xml are checked in, so they are compiled (and linted) with the tests
+
+use rustbus::broker::Broker;
+use rustbus::connection::Timeout;
+use rustbus::wire::{ObjectPath, OwnedVariant, VariantMap};
+use rustbus::{DBusError, DispatchConn, MessageBuilder, RpcConn};
+
+#[allow(dead_code)]
+mod generated {
+    include!("generated/counter.rs");
+}
+use generated::{counter, item};
+
+#[test]
+fn generated_code_is_current() {
+    let xml = include_str!("counter.xml");
+    let code = rustbus_codegen::generate(xml).unwrap();
+    assert!(
+        code == include_str!("generated/counter.rs"),
+        "Regenerate with: cargo run -p rustbus_codegen -- tests/counter.xml -o tests/generated/counter.rs"
+    );
+}
+
+struct Counter {
+    count: u32,
+    label: String,
+    step: u32,
+}
+
+impl counter::Counter for Counter {
+    fn add(&mut self, amount: u32) -> Result<u32, DBusError> {
+        self.count += amount * self.step;
+        Ok(self.count)
+    }
+
+    fn reset(&mut self) -> Result<(), DBusError> {
+        self.count = 0;
+        Ok(())
+    }
+
+    fn describe(&mut self) -> Result<(String, u32), DBusError> {
+        Ok((self.label.clone(), self.count))
+    }
+
+    fn summary(
+        &mut self,
+        options: VariantMap,
+        paths: Vec<ObjectPath<String>>,
+    ) -> Result<counter::SummarySummary, DBusError> {
+        let verbose = match options.get("verbose") {
+            Some(verbose) => verbose.get::<bool>().map_err(|_| {
+                DBusError::new(
+                    rustbus::dbus_error::ErrorName::InvalidArgs,
+                    "verbose must be a bool",
+                )
+            })?,
+            None => false,
+        };
+        Ok(counter::SummarySummary {
+            field0: self.label.clone(),
+            field1: self.count,
+            field2: paths
+                .iter()
+                .map(|path| path.as_ref())
+                .collect::<Vec<_>>()
+                .join(","),
+            field3: "summary".to_owned(),
+            field4: verbose,
+        })
+    }
+
+    fn count(&mut self) -> Result<u32, DBusError> {
+        Ok(self.count)
+    }
+
+    fn label(&mut self) -> Result<String, DBusError> {
+        Ok(self.label.clone())
+    }
+
+    fn set_label(&mut self, value: String) -> Result<(), DBusError> {
+        self.label = value;
+        Ok(())
+    }
+
+    fn set_step(&mut self, value: u32) -> Result<(), DBusError> {
+        self.step = value;
+        Ok(())
+    }
+}
+
+#[test]
+fn generated_proxy_and_server() {
+    let broker = Broker::bind_tmpdir().unwrap().spawn();
+    let addr = [broker.address().clone()];
+
+    let service_name = broker
+        .spawn_service(&[], |conn| {
+            let counter = Counter {
+                count: 0,
+                label: "clicks".to_owned(),
+                step: 1,
+            };
+            let mut service: DispatchConn<Counter, ()> =
+                DispatchConn::new(conn, counter, Box::new(|_, _, _, _| Ok(None)));
+            counter::register(&mut service, "/counter");
+            service
+        })
+        .unwrap();
+
+    let mut con = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    let mut proxy = counter::CounterProxy::new(&mut con, &service_name, "/counter");
+    assert_eq!(proxy.add(2).unwrap(), 2);
+    proxy.set_step(10).unwrap();
+    assert_eq!(proxy.add(1).unwrap(), 12);
+    assert_eq!(proxy.count().unwrap(), 12);
+    proxy.set_label("taps").unwrap();
+    assert_eq!(proxy.describe().unwrap(), ("taps".to_owned(), 12));
+
+    let mut options = VariantMap::new();
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "Options", "/")
+        .build();
+    msg.body.push_variant(true).unwrap();
+    options.insert(
+        "verbose".to_owned(),
+        msg.body.parser().get::<OwnedVariant>().unwrap(),
+    );
+    let paths = [
+        ObjectPath::new("/a".to_owned()).unwrap(),
+        ObjectPath::new("/b".to_owned()).unwrap(),
+    ];
+    let summary = proxy.summary(&options, &paths).unwrap();
+    assert_eq!(summary.field0, "taps");
+    assert_eq!(summary.field2, "/a,/b");
+    assert!(summary.field4);
+
+    proxy.reset().unwrap();
+    assert_eq!(proxy.count().unwrap(), 0);
+
+    // the registered interface is reported by Introspect
+    let call = MessageBuilder::new()
+        .call("Introspect")
+        .with_interface("org.freedesktop.DBus.Introspectable")
+        .on("/counter")
+        .at(service_name.clone())
+        .build();
+    let reply = con.call_message(call, Timeout::Infinite).unwrap();
+    let node =
+        rustbus::introspect::Node::from_xml(&reply.body.parser().get::<String>().unwrap()).unwrap();
+    assert_eq!(
+        node.interface(counter::INTERFACE),
+        Some(&counter::interface())
+    );
+
+    // the item interface is not served, calls are rejected
+    let mut item = item::ItemProxy::new(&mut con, &service_name, "/counter");
+    assert!(item.rename("other").is_err());
+}
+
+#[test]
+fn generated_signals() {
+    let signal = counter::Overflowed {
+        count: 3,
+        arg1: vec![(1, 2)],
+    };
+    let msg = signal.to_message("/counter").unwrap();
+    let parsed = counter::Overflowed::from_message(&msg).unwrap().unwrap();
+    assert_eq!(parsed.count, 3);
+    assert_eq!(parsed.arg1, vec![(1, 2)]);
+    assert!(counter::Cleared::from_message(&msg).is_none());
+
+    let msg = counter::Cleared {}.to_message("/counter").unwrap();
+    assert!(counter::Cleared::from_message(&msg).unwrap().is_ok());
+    let rule = counter::Cleared::match_rule();
+    assert_eq!(rule.member.as_deref(), Some("Cleared"));
+}
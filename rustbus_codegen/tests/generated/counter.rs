@@ -0,0 +1,536 @@
+// Generated by rustbus-codegen from introspection data. Regenerate instead of editing it.
+
+/// Bindings for `io.killing.spark.Counter`
+pub mod counter {
+    #![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+    pub const INTERFACE: &str = "io.killing.spark.Counter";
+
+    const INTROSPECTION: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+  <interface name="io.killing.spark.Counter">
+    <method name="Add">
+      <arg name="amount" type="u" direction="in"/>
+      <arg name="count" type="u" direction="out"/>
+    </method>
+    <method name="Reset"/>
+    <method name="Describe">
+      <arg name="label" type="s" direction="out"/>
+      <arg name="count" type="u" direction="out"/>
+    </method>
+    <method name="Summary">
+      <arg name="options" type="a{sv}" direction="in"/>
+      <arg name="paths" type="ao" direction="in"/>
+      <arg name="summary" type="(sussb)" direction="out"/>
+    </method>
+    <signal name="Overflowed">
+      <arg name="count" type="u"/>
+      <arg type="a(ii)"/>
+    </signal>
+    <signal name="Cleared"/>
+    <property name="Count" type="u" access="read"/>
+    <property name="Label" type="s" access="readwrite"/>
+    <property name="Step" type="u" access="write"/>
+  </interface>
+</node>"#;
+
+    /// The description of the interface, as it is reported by Introspect
+    pub fn interface() -> ::rustbus::introspect::Interface {
+        ::rustbus::introspect::Node::from_xml(INTROSPECTION)
+            .expect("The introspection data was valid when the bindings were generated")
+            .interfaces
+            .remove(0)
+    }
+
+    /// The struct `(sussb)`
+    #[derive(Debug, Clone, ::rustbus::Marshal, ::rustbus::Unmarshal, ::rustbus::Signature)]
+    pub struct SummarySummary {
+        pub field0: String,
+        pub field1: u32,
+        pub field2: String,
+        pub field3: String,
+        pub field4: bool,
+    }
+
+    /// Calls the methods of the interface on a remote object over an RpcConn. Messages that arrive while waiting
+    /// for the replies are put into the queues of the RpcConn as usual.
+    pub struct CounterProxy<'a> {
+        con: &'a mut ::rustbus::RpcConn,
+        destination: String,
+        path: String,
+        timeout: ::rustbus::connection::Timeout,
+    }
+
+    impl<'a> CounterProxy<'a> {
+        /// Wait for the replies without a timeout
+        pub fn new(con: &'a mut ::rustbus::RpcConn, destination: &str, path: &str) -> Self {
+            Self::with_timeout(con, destination, path, ::rustbus::connection::Timeout::Infinite)
+        }
+
+        /// Wait at most `timeout` for each reply
+        pub fn with_timeout(
+            con: &'a mut ::rustbus::RpcConn,
+            destination: &str,
+            path: &str,
+            timeout: ::rustbus::connection::Timeout,
+        ) -> Self {
+            CounterProxy {
+                con,
+                destination: destination.to_owned(),
+                path: path.to_owned(),
+                timeout,
+            }
+        }
+
+        pub fn add(&mut self, amount: u32) -> Result<u32, ::rustbus::connection::rpc_conn::CallError> {
+            let (count,) = self.con.call(
+                &self.destination,
+                &self.path,
+                INTERFACE,
+                "Add",
+                (amount,),
+                self.timeout,
+            )?;
+            Ok(count)
+        }
+
+        pub fn reset(&mut self) -> Result<(), ::rustbus::connection::rpc_conn::CallError> {
+            self.con.call(
+                &self.destination,
+                &self.path,
+                INTERFACE,
+                "Reset",
+                (),
+                self.timeout,
+            )
+        }
+
+        pub fn describe(&mut self) -> Result<(String, u32), ::rustbus::connection::rpc_conn::CallError> {
+            self.con.call(
+                &self.destination,
+                &self.path,
+                INTERFACE,
+                "Describe",
+                (),
+                self.timeout,
+            )
+        }
+
+        pub fn summary(&mut self, options: &::rustbus::wire::VariantMap, paths: &[::rustbus::wire::ObjectPath<String>]) -> Result<SummarySummary, ::rustbus::connection::rpc_conn::CallError> {
+            let (summary,) = self.con.call(
+                &self.destination,
+                &self.path,
+                INTERFACE,
+                "Summary",
+                (options, paths),
+                self.timeout,
+            )?;
+            Ok(summary)
+        }
+
+        fn properties(&mut self) -> ::rustbus::properties::PropertiesProxy<'_> {
+            ::rustbus::properties::PropertiesProxy::with_timeout(
+                self.con,
+                &self.destination,
+                &self.path,
+                INTERFACE,
+                self.timeout,
+            )
+        }
+
+        pub fn count(&mut self) -> Result<u32, ::rustbus::connection::rpc_conn::CallError> {
+            self.properties().get("Count")
+        }
+
+        pub fn label(&mut self) -> Result<String, ::rustbus::connection::rpc_conn::CallError> {
+            self.properties().get("Label")
+        }
+
+        pub fn set_label(&mut self, value: &str) -> Result<(), ::rustbus::connection::rpc_conn::CallError> {
+            self.properties().set("Label", value)
+        }
+
+        pub fn set_step(&mut self, value: u32) -> Result<(), ::rustbus::connection::rpc_conn::CallError> {
+            self.properties().set("Step", value)
+        }
+    }
+
+    /// The signal `Overflowed`
+    #[derive(Debug, Clone)]
+    pub struct Overflowed {
+        pub count: u32,
+        pub arg1: Vec<(i32, i32)>,
+    }
+
+    impl Overflowed {
+        pub const MEMBER: &'static str = "Overflowed";
+
+        /// Matches the signal sent by any object
+        pub fn match_rule() -> ::rustbus::MatchRule {
+            ::rustbus::MatchRule::new()
+                .with_type(::rustbus::MessageType::Signal)
+                .with_interface(INTERFACE)
+                .with_member(Self::MEMBER)
+        }
+
+        /// None if the message is not this signal
+        pub fn from_message(msg: &::rustbus::message_builder::MarshalledMessage) -> Option<Result<Self, ::rustbus::wire::errors::UnmarshalError>> {
+            if msg.typ != ::rustbus::MessageType::Signal
+                || msg.dynheader.interface.as_deref() != Some(INTERFACE)
+                || msg.dynheader.member.as_deref() != Some(Self::MEMBER)
+            {
+                return None;
+            }
+            Some(Self::parse(msg))
+        }
+
+        fn parse(msg: &::rustbus::message_builder::MarshalledMessage) -> Result<Self, ::rustbus::wire::errors::UnmarshalError> {
+            let mut parser = msg.body.parser();
+            Ok(Overflowed {
+                count: parser.get()?,
+                arg1: parser.get()?,
+            })
+        }
+
+        /// Build the signal as it is sent by the object at `path`
+        pub fn to_message(&self, path: &str) -> Result<::rustbus::message_builder::MarshalledMessage, ::rustbus::wire::errors::MarshalError> {
+            let mut msg = ::rustbus::MessageBuilder::new().signal(INTERFACE, Self::MEMBER, path).build();
+            msg.body.push_param(self.count)?;
+            msg.body.push_param(&self.arg1)?;
+            Ok(msg)
+        }
+    }
+
+    /// The signal `Cleared`
+    #[derive(Debug, Clone)]
+    pub struct Cleared {}
+
+    impl Cleared {
+        pub const MEMBER: &'static str = "Cleared";
+
+        /// Matches the signal sent by any object
+        pub fn match_rule() -> ::rustbus::MatchRule {
+            ::rustbus::MatchRule::new()
+                .with_type(::rustbus::MessageType::Signal)
+                .with_interface(INTERFACE)
+                .with_member(Self::MEMBER)
+        }
+
+        /// None if the message is not this signal
+        pub fn from_message(msg: &::rustbus::message_builder::MarshalledMessage) -> Option<Result<Self, ::rustbus::wire::errors::UnmarshalError>> {
+            if msg.typ != ::rustbus::MessageType::Signal
+                || msg.dynheader.interface.as_deref() != Some(INTERFACE)
+                || msg.dynheader.member.as_deref() != Some(Self::MEMBER)
+            {
+                return None;
+            }
+            Some(Ok(Cleared {}))
+        }
+
+        /// Build the signal as it is sent by the object at `path`
+        pub fn to_message(&self, path: &str) -> Result<::rustbus::message_builder::MarshalledMessage, ::rustbus::wire::errors::MarshalError> {
+            Ok(::rustbus::MessageBuilder::new().signal(INTERFACE, Self::MEMBER, path).build())
+        }
+    }
+
+    /// The methods and properties of the interface for objects served by a DispatchConn, see `register`
+    pub trait Counter {
+        fn add(&mut self, amount: u32) -> Result<u32, ::rustbus::DBusError>;
+
+        fn reset(&mut self) -> Result<(), ::rustbus::DBusError>;
+
+        fn describe(&mut self) -> Result<(String, u32), ::rustbus::DBusError>;
+
+        fn summary(&mut self, options: ::rustbus::wire::VariantMap, paths: Vec<::rustbus::wire::ObjectPath<String>>) -> Result<SummarySummary, ::rustbus::DBusError>;
+
+        fn count(&mut self) -> Result<u32, ::rustbus::DBusError>;
+
+        fn label(&mut self) -> Result<String, ::rustbus::DBusError>;
+
+        fn set_label(&mut self, value: String) -> Result<(), ::rustbus::DBusError>;
+
+        fn set_step(&mut self, value: u32) -> Result<(), ::rustbus::DBusError>;
+    }
+
+    /// Answer a call to a method of the interface. None if the message is not a call to this interface.
+    pub fn handle<T: Counter>(object: &mut T, msg: &::rustbus::message_builder::MarshalledMessage) -> Option<Result<::rustbus::message_builder::MarshalledMessage, ::rustbus::wire::errors::MarshalError>> {
+        if msg.typ != ::rustbus::MessageType::Call
+            || msg.dynheader.interface.as_deref() != Some(INTERFACE)
+        {
+            return None;
+        }
+        Some(match msg.dynheader.member.as_deref() {
+            Some("Add") => call_add(object, msg),
+            Some("Reset") => call_reset(object, msg),
+            Some("Describe") => call_describe(object, msg),
+            Some("Summary") => call_summary(object, msg),
+            _ => Ok(::rustbus::standard_messages::unknown_method(&msg.dynheader)),
+        })
+    }
+
+    fn call_add<T: Counter>(object: &mut T, msg: &::rustbus::message_builder::MarshalledMessage) -> Result<::rustbus::message_builder::MarshalledMessage, ::rustbus::wire::errors::MarshalError> {
+        let mut parser = msg.body.parser();
+        let amount = match parser.get() {
+            Ok(value) => value,
+            Err(_) => return Ok(invalid_args(msg)),
+        };
+        match object.add(amount) {
+            Ok(count) => {
+                let mut reply = msg.dynheader.make_response();
+                reply.body.push_param(count)?;
+                Ok(reply)
+            }
+            Err(error) => Ok(error.to_message(&msg.dynheader)),
+        }
+    }
+
+    fn call_reset<T: Counter>(object: &mut T, msg: &::rustbus::message_builder::MarshalledMessage) -> Result<::rustbus::message_builder::MarshalledMessage, ::rustbus::wire::errors::MarshalError> {
+        match object.reset() {
+            Ok(()) => Ok(msg.dynheader.make_response()),
+            Err(error) => Ok(error.to_message(&msg.dynheader)),
+        }
+    }
+
+    fn call_describe<T: Counter>(object: &mut T, msg: &::rustbus::message_builder::MarshalledMessage) -> Result<::rustbus::message_builder::MarshalledMessage, ::rustbus::wire::errors::MarshalError> {
+        match object.describe() {
+            Ok((label, count)) => {
+                let mut reply = msg.dynheader.make_response();
+                reply.body.push_param(label)?;
+                reply.body.push_param(count)?;
+                Ok(reply)
+            }
+            Err(error) => Ok(error.to_message(&msg.dynheader)),
+        }
+    }
+
+    fn call_summary<T: Counter>(object: &mut T, msg: &::rustbus::message_builder::MarshalledMessage) -> Result<::rustbus::message_builder::MarshalledMessage, ::rustbus::wire::errors::MarshalError> {
+        let mut parser = msg.body.parser();
+        let options = match parser.get() {
+            Ok(value) => value,
+            Err(_) => return Ok(invalid_args(msg)),
+        };
+        let paths = match parser.get() {
+            Ok(value) => value,
+            Err(_) => return Ok(invalid_args(msg)),
+        };
+        match object.summary(options, paths) {
+            Ok(summary) => {
+                let mut reply = msg.dynheader.make_response();
+                reply.body.push_param(summary)?;
+                Ok(reply)
+            }
+            Err(error) => Ok(error.to_message(&msg.dynheader)),
+        }
+    }
+
+    fn invalid_args(msg: &::rustbus::message_builder::MarshalledMessage) -> ::rustbus::message_builder::MarshalledMessage {
+        ::rustbus::DBusError::new(
+            ::rustbus::dbus_error::ErrorName::InvalidArgs,
+            format!("Invalid arguments for {:?}", msg.dynheader.member),
+        )
+        .to_message(&msg.dynheader)
+    }
+
+    /// Register the object at `path` with the DispatchConn: the interface for Introspect, the properties and a
+    /// handler for the method calls. Objects with more than one interface need a handler of their own that calls the
+    /// `handle` functions of the interfaces.
+    pub fn register<T, E>(con: &mut ::rustbus::DispatchConn<T, E>, path: &str)
+    where
+        T: Counter + 'static,
+        E: ::std::fmt::Debug + 'static,
+    {
+        con.add_interface(path, interface());
+        con.add_property(
+            path,
+            INTERFACE,
+            "Count",
+            ::rustbus::connection::dispatch_conn::Property::read_only(|object: &mut T, _| object.count()),
+        );
+        con.add_property(
+            path,
+            INTERFACE,
+            "Label",
+            ::rustbus::connection::dispatch_conn::Property::read_write(|object: &mut T, _| object.label(), |object: &mut T, _, value: String| object.set_label(value)),
+        );
+        con.add_property(
+            path,
+            INTERFACE,
+            "Step",
+            ::rustbus::connection::dispatch_conn::Property::write_only(|object: &mut T, _, value: u32| object.set_step(value)),
+        );
+        con.add_handler(path, Box::new(dispatch::<T, E>));
+    }
+
+    fn dispatch<T, E>(
+        object: &mut T,
+        _: ::rustbus::connection::dispatch_conn::Matches,
+        msg: &::rustbus::message_builder::MarshalledMessage,
+        _: &mut ::rustbus::connection::dispatch_conn::HandleEnvironment<T, E>,
+    ) -> ::rustbus::connection::dispatch_conn::HandleResult<E>
+    where
+        T: Counter,
+        E: ::std::fmt::Debug,
+    {
+        match handle(object, msg) {
+            Some(reply) => reply.map(Some).map_err(::rustbus::connection::dispatch_conn::HandleError::Marshal),
+            None => Ok(Some(::rustbus::standard_messages::unknown_method(&msg.dynheader))),
+        }
+    }
+}
+
+/// Bindings for `io.killing.spark.Item`
+pub mod item {
+    #![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+    pub const INTERFACE: &str = "io.killing.spark.Item";
+
+    const INTROSPECTION: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+  <interface name="io.killing.spark.Item">
+    <method name="Rename">
+      <arg name="type" type="s"/>
+    </method>
+    <property name="Name" type="s" access="read"/>
+  </interface>
+</node>"#;
+
+    /// The description of the interface, as it is reported by Introspect
+    pub fn interface() -> ::rustbus::introspect::Interface {
+        ::rustbus::introspect::Node::from_xml(INTROSPECTION)
+            .expect("The introspection data was valid when the bindings were generated")
+            .interfaces
+            .remove(0)
+    }
+
+    /// Calls the methods of the interface on a remote object over an RpcConn. Messages that arrive while waiting
+    /// for the replies are put into the queues of the RpcConn as usual.
+    pub struct ItemProxy<'a> {
+        con: &'a mut ::rustbus::RpcConn,
+        destination: String,
+        path: String,
+        timeout: ::rustbus::connection::Timeout,
+    }
+
+    impl<'a> ItemProxy<'a> {
+        /// Wait for the replies without a timeout
+        pub fn new(con: &'a mut ::rustbus::RpcConn, destination: &str, path: &str) -> Self {
+            Self::with_timeout(con, destination, path, ::rustbus::connection::Timeout::Infinite)
+        }
+
+        /// Wait at most `timeout` for each reply
+        pub fn with_timeout(
+            con: &'a mut ::rustbus::RpcConn,
+            destination: &str,
+            path: &str,
+            timeout: ::rustbus::connection::Timeout,
+        ) -> Self {
+            ItemProxy {
+                con,
+                destination: destination.to_owned(),
+                path: path.to_owned(),
+                timeout,
+            }
+        }
+
+        pub fn rename(&mut self, type_: &str) -> Result<(), ::rustbus::connection::rpc_conn::CallError> {
+            self.con.call(
+                &self.destination,
+                &self.path,
+                INTERFACE,
+                "Rename",
+                (type_,),
+                self.timeout,
+            )
+        }
+
+        fn properties(&mut self) -> ::rustbus::properties::PropertiesProxy<'_> {
+            ::rustbus::properties::PropertiesProxy::with_timeout(
+                self.con,
+                &self.destination,
+                &self.path,
+                INTERFACE,
+                self.timeout,
+            )
+        }
+
+        pub fn name(&mut self) -> Result<String, ::rustbus::connection::rpc_conn::CallError> {
+            self.properties().get("Name")
+        }
+    }
+
+    /// The methods and properties of the interface for objects served by a DispatchConn, see `register`
+    pub trait Item {
+        fn rename(&mut self, type_: String) -> Result<(), ::rustbus::DBusError>;
+
+        fn name(&mut self) -> Result<String, ::rustbus::DBusError>;
+    }
+
+    /// Answer a call to a method of the interface. None if the message is not a call to this interface.
+    pub fn handle<T: Item>(object: &mut T, msg: &::rustbus::message_builder::MarshalledMessage) -> Option<Result<::rustbus::message_builder::MarshalledMessage, ::rustbus::wire::errors::MarshalError>> {
+        if msg.typ != ::rustbus::MessageType::Call
+            || msg.dynheader.interface.as_deref() != Some(INTERFACE)
+        {
+            return None;
+        }
+        Some(match msg.dynheader.member.as_deref() {
+            Some("Rename") => call_rename(object, msg),
+            _ => Ok(::rustbus::standard_messages::unknown_method(&msg.dynheader)),
+        })
+    }
+
+    fn call_rename<T: Item>(object: &mut T, msg: &::rustbus::message_builder::MarshalledMessage) -> Result<::rustbus::message_builder::MarshalledMessage, ::rustbus::wire::errors::MarshalError> {
+        let mut parser = msg.body.parser();
+        let type_ = match parser.get() {
+            Ok(value) => value,
+            Err(_) => return Ok(invalid_args(msg)),
+        };
+        match object.rename(type_) {
+            Ok(()) => Ok(msg.dynheader.make_response()),
+            Err(error) => Ok(error.to_message(&msg.dynheader)),
+        }
+    }
+
+    fn invalid_args(msg: &::rustbus::message_builder::MarshalledMessage) -> ::rustbus::message_builder::MarshalledMessage {
+        ::rustbus::DBusError::new(
+            ::rustbus::dbus_error::ErrorName::InvalidArgs,
+            format!("Invalid arguments for {:?}", msg.dynheader.member),
+        )
+        .to_message(&msg.dynheader)
+    }
+
+    /// Register the object at `path` with the DispatchConn: the interface for Introspect, the properties and a
+    /// handler for the method calls. Objects with more than one interface need a handler of their own that calls the
+    /// `handle` functions of the interfaces.
+    pub fn register<T, E>(con: &mut ::rustbus::DispatchConn<T, E>, path: &str)
+    where
+        T: Item + 'static,
+        E: ::std::fmt::Debug + 'static,
+    {
+        con.add_interface(path, interface());
+        con.add_property(
+            path,
+            INTERFACE,
+            "Name",
+            ::rustbus::connection::dispatch_conn::Property::read_only(|object: &mut T, _| object.name()),
+        );
+        con.add_handler(path, Box::new(dispatch::<T, E>));
+    }
+
+    fn dispatch<T, E>(
+        object: &mut T,
+        _: ::rustbus::connection::dispatch_conn::Matches,
+        msg: &::rustbus::message_builder::MarshalledMessage,
+        _: &mut ::rustbus::connection::dispatch_conn::HandleEnvironment<T, E>,
+    ) -> ::rustbus::connection::dispatch_conn::HandleResult<E>
+    where
+        T: Item,
+        E: ::std::fmt::Debug,
+    {
+        match handle(object, msg) {
+            Some(reply) => reply.map(Some).map_err(::rustbus::connection::dispatch_conn::HandleError::Marshal),
+            None => Ok(Some(::rustbus::standard_messages::unknown_method(&msg.dynheader))),
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::ext::IdentExt;
+
+use crate::structs::Field;
+
+/// A struct that is put on the wire as an `a{sv}` dict, see `#[rustbus(dict)]`
+pub struct Dict<'a> {
+    entries: Vec<Entry<'a>>,
+    deny_unknown_keys: bool,
+}
+
+struct Entry<'a> {
+    member: syn::Member,
+    key: String,
+    /// The type of the value in the variant, the `T` of `Option<T>` fields
+    value_ty: &'a syn::Type,
+    /// `Option<T>` fields are left out if they are `None` and `None` if the key is missing
+    optional: bool,
+    /// The field is `Default::default()` if the key is missing
+    default: bool,
+}
+
+impl<'a> Dict<'a> {
+    pub fn new(fields: Vec<Field<'a>>, deny_unknown_keys: bool) -> syn::Result<Self> {
+        let mut entries: Vec<Entry> = Vec::new();
+        for field in fields {
+            let attrs = &field.attrs;
+            if attrs.with.is_some() || attrs.signature.is_some() || attrs.as_variant {
+                return Err(syn::Error::new_spanned(
+                    field.field,
+                    "The values of #[rustbus(dict)] structs are always variants, `with`, `signature` and `as_variant` are not supported",
+                ));
+            }
+            let key = match (&attrs.rename, &field.member) {
+                (Some(rename), _) => rename.value(),
+                (None, syn::Member::Named(ident)) => ident.unraw().to_string(),
+                (None, syn::Member::Unnamed(_)) => {
+                    return Err(syn::Error::new_spanned(
+                        field.field,
+                        "The fields of #[rustbus(dict)] structs need a name or a #[rustbus(rename = \"...\")]",
+                    ))
+                }
+            };
+            if entries.iter().any(|entry| entry.key == key) {
+                return Err(syn::Error::new_spanned(
+                    field.field,
+                    format!("The key {} is used twice", key),
+                ));
+            }
+            let option_value = option_value(field.ty);
+            if option_value.is_some() && attrs.default {
+                return Err(syn::Error::new_spanned(
+                    field.field,
+                    "Option fields are already None if the key is missing, `default` is not needed",
+                ));
+            }
+            entries.push(Entry {
+                member: field.member,
+                key,
+                value_ty: option_value.unwrap_or(field.ty),
+                optional: option_value.is_some(),
+                default: attrs.default,
+            });
+        }
+        Ok(Dict {
+            entries,
+            deny_unknown_keys,
+        })
+    }
+}
+
+/// The type of the value if this is an `Option`
+fn option_value(typ: &syn::Type) -> Option<&syn::Type> {
+    let segment = match typ {
+        syn::Type::Path(path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) if args.args.len() == 1 => {
+            match args.args.first()? {
+                syn::GenericArgument::Type(value) => Some(value),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+pub fn dict_marshal(dict: &Dict) -> TokenStream {
+    let entries = dict.entries.iter().map(|entry| {
+        let member = &entry.member;
+        let key = &entry.key;
+        let marshal = quote! {
+            ctx.align_to(8);
+            ::rustbus::Marshal::marshal(&#key, ctx)?;
+            ::rustbus::Marshal::marshal_as_variant(value, ctx)?;
+        };
+        if entry.optional {
+            quote! {
+                if let Some(value) = &self.#member {
+                    #marshal
+                }
+            }
+        } else {
+            quote! {
+                let value = &self.#member;
+                #marshal
+            }
+        }
+    });
+
+    quote! {
+            ctx.align_to(4);
+            let size_pos = ctx.buf.len();
+            ctx.buf.extend_from_slice(&[0; 4]);
+            ctx.align_to(8);
+
+            let size_before = ctx.buf.len();
+            #(
+                #entries
+            )*
+            let size_of_content = ctx.buf.len() - size_before;
+            ::rustbus::wire::util::insert_u32(
+                ctx.byteorder,
+                size_of_content as u32,
+                &mut ctx.buf[size_pos..size_pos + 4],
+            );
+            Ok(())
+    }
+}
+
+pub fn dict_unmarshal(dict: &Dict) -> TokenStream {
+    let members = dict.entries.iter().map(|entry| &entry.member);
+    let values = dict.entries.iter().map(|entry| {
+        let key = &entry.key;
+        let value_ty = entry.value_ty;
+        let get = quote! { value.get::<#value_ty>()? };
+        if entry.optional {
+            quote! {
+                match entries.remove(#key) {
+                    Some(value) => Some(#get),
+                    None => None,
+                }
+            }
+        } else if entry.default {
+            quote! {
+                match entries.remove(#key) {
+                    Some(value) => #get,
+                    None => ::std::default::Default::default(),
+                }
+            }
+        } else {
+            quote! {
+                match entries.remove(#key) {
+                    Some(value) => #get,
+                    None => return Err(::rustbus::wire::errors::UnmarshalError::MissingDictKey(#key.to_owned())),
+                }
+            }
+        }
+    });
+    let check_unknown = if dict.deny_unknown_keys {
+        quote! {
+            if let Some(key) = entries.keys().next() {
+                return Err(::rustbus::wire::errors::UnmarshalError::UnknownDictKey(key.to_string()));
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    quote! {
+            let start_offset = ctx.offset;
+            let (_, mut entries) = <::std::collections::HashMap<&str, ::rustbus::wire::unmarshal::traits::Variant> as ::rustbus::Unmarshal>::unmarshal(ctx)?;
+
+            let this = Self{
+                #(
+                    #members: #values,
+                )*
+            };
+            #check_unknown
+            let total_bytes = ctx.offset - start_offset;
+            Ok((total_bytes, this))
+    }
+}
+
+pub fn dict_sig() -> TokenStream {
+    quote! {
+        ::rustbus::signature::Type::Container(::rustbus::signature::Container::Dict(
+            ::rustbus::signature::Base::String,
+            Box::new(::rustbus::signature::Type::Container(::rustbus::signature::Container::Variant)),
+        ))
+    }
+}
+
+pub fn dict_has_sig() -> TokenStream {
+    quote! {
+        sig == "a{sv}"
+    }
+}
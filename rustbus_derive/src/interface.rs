@@ -0,0 +1,371 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+
+pub fn make_interface_impl(
+    args: &[syn::NestedMeta],
+    mut item: syn::ItemImpl,
+) -> syn::Result<TokenStream> {
+    let interface = interface_name(args)?;
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(syn::Error::new_spanned(
+            path,
+            "dbus_interface can only be used on inherent impl blocks",
+        ));
+    }
+
+    let mut methods: Vec<ExportedMethod> = Vec::new();
+    for impl_item in &mut item.items {
+        if let syn::ImplItem::Method(method) = impl_item {
+            let attrs = take_method_attrs(&mut method.attrs)?;
+            // associated functions like constructors can not be called on an object
+            if attrs.skip || method.sig.receiver().is_none() {
+                continue;
+            }
+            let exported = ExportedMethod::new(&method.sig, attrs.name)?;
+            if methods.iter().any(|other| other.member == exported.member) {
+                return Err(syn::Error::new_spanned(
+                    &method.sig.ident,
+                    format!("The method {} is exported twice", exported.member),
+                ));
+            }
+            methods.push(exported);
+        }
+    }
+
+    let (impl_gen, _, clause_gen) = item.generics.split_for_impl();
+    let self_ty = &item.self_ty;
+    let introspect_methods = methods.iter().map(ExportedMethod::introspect);
+    let members = methods.iter().map(|method| &method.member);
+    let call_fns: Vec<_> = methods
+        .iter()
+        .map(|method| method.call_fn_ident())
+        .collect();
+    let call_fn_defs = methods.iter().map(|method| {
+        let call_fn = method.call_fn_ident();
+        let body = method.call_body();
+        quote! {
+            fn #call_fn #impl_gen (
+                object: &mut #self_ty,
+                msg: &::rustbus::message_builder::MarshalledMessage,
+            ) -> Result<::rustbus::message_builder::MarshalledMessage, ::rustbus::wire::errors::MarshalError>
+            #clause_gen
+            {
+                #body
+            }
+        }
+    });
+
+    Ok(quote! {
+        #item
+
+        const _: () = {
+            impl #impl_gen ::rustbus::connection::dispatch_conn::DBusInterface for #self_ty #clause_gen {
+                const INTERFACE: &'static str = #interface;
+
+                fn introspect() -> ::rustbus::introspect::Interface {
+                    ::rustbus::introspect::Interface::new(Self::INTERFACE)
+                        #(.with_method(#introspect_methods))*
+                }
+
+                fn handle_call(
+                    &mut self,
+                    msg: &::rustbus::message_builder::MarshalledMessage,
+                ) -> Option<Result<::rustbus::message_builder::MarshalledMessage, ::rustbus::wire::errors::MarshalError>> {
+                    if msg.typ != ::rustbus::MessageType::Call
+                        || msg.dynheader.interface.as_deref() != Some(Self::INTERFACE)
+                    {
+                        return None;
+                    }
+                    Some(match msg.dynheader.member.as_deref() {
+                        #(Some(#members) => #call_fns(self, msg),)*
+                        _ => Ok(::rustbus::standard_messages::unknown_method(&msg.dynheader)),
+                    })
+                }
+            }
+
+            #(#call_fn_defs)*
+        };
+    })
+}
+
+fn interface_name(args: &[syn::NestedMeta]) -> syn::Result<String> {
+    let mut name = None;
+    for arg in args {
+        match arg {
+            syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                path,
+                lit: syn::Lit::Str(lit),
+                ..
+            })) if path.is_ident("name") => name = Some(lit.value()),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    arg,
+                    "Expected the name of the interface: #[dbus_interface(name = \"...\")]",
+                ))
+            }
+        }
+    }
+    name.ok_or_else(|| {
+        syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "Expected the name of the interface: #[dbus_interface(name = \"...\")]",
+        )
+    })
+}
+
+#[derive(Default)]
+struct MethodAttrs {
+    name: Option<String>,
+    skip: bool,
+}
+
+/// Remove the `#[dbus_method(...)]` attributes, the compiler does not know about them
+fn take_method_attrs(attrs: &mut Vec<syn::Attribute>) -> syn::Result<MethodAttrs> {
+    let mut method_attrs = MethodAttrs::default();
+    let mut result = Ok(());
+    attrs.retain(|attr| {
+        if !attr.path.is_ident("dbus_method") {
+            return true;
+        }
+        if let Err(err) = parse_method_attr(attr, &mut method_attrs) {
+            result = Err(err);
+        }
+        false
+    });
+    result.map(|_| method_attrs)
+}
+
+fn parse_method_attr(attr: &syn::Attribute, method_attrs: &mut MethodAttrs) -> syn::Result<()> {
+    let list = match attr.parse_meta()? {
+        syn::Meta::List(list) => list,
+        meta => {
+            return Err(syn::Error::new_spanned(
+                meta,
+                "Expected #[dbus_method(name = \"...\")] or #[dbus_method(skip)]",
+            ))
+        }
+    };
+    for nested in &list.nested {
+        match nested {
+            syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                path,
+                lit: syn::Lit::Str(lit),
+                ..
+            })) if path.is_ident("name") => method_attrs.name = Some(lit.value()),
+            syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("skip") => {
+                method_attrs.skip = true
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    nested,
+                    "Expected #[dbus_method(name = \"...\")] or #[dbus_method(skip)]",
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+struct ExportedMethod {
+    ident: syn::Ident,
+    member: String,
+    /// Name and type of the arguments
+    args: Vec<(String, syn::Type)>,
+    outputs: Vec<syn::Type>,
+    /// Whether the method returns a Result whose error is turned into an error reply
+    fallible: bool,
+}
+
+impl ExportedMethod {
+    fn new(sig: &syn::Signature, name: Option<String>) -> syn::Result<Self> {
+        if sig.asyncness.is_some() {
+            return Err(syn::Error::new_spanned(
+                sig.asyncness,
+                "async methods can not be exported",
+            ));
+        }
+        if !sig.generics.params.is_empty() {
+            return Err(syn::Error::new_spanned(
+                &sig.generics,
+                "generic methods can not be exported",
+            ));
+        }
+
+        let mut args = Vec::new();
+        for (idx, input) in sig.inputs.iter().enumerate() {
+            match input {
+                syn::FnArg::Receiver(receiver) if receiver.reference.is_some() => {}
+                syn::FnArg::Receiver(_) => {
+                    return Err(syn::Error::new_spanned(
+                        input,
+                        "Exported methods take &self or &mut self",
+                    ))
+                }
+                syn::FnArg::Typed(arg) => {
+                    let name = match &*arg.pat {
+                        syn::Pat::Ident(pat) if pat.ident != "self" => {
+                            pat.ident.unraw().to_string()
+                        }
+                        syn::Pat::Ident(_) => {
+                            return Err(syn::Error::new_spanned(
+                                input,
+                                "Exported methods take &self or &mut self",
+                            ))
+                        }
+                        _ => format!("arg{}", idx - 1),
+                    };
+                    args.push((name, (*arg.ty).clone()));
+                }
+            }
+        }
+
+        let (fallible, output) = match &sig.output {
+            syn::ReturnType::Default => (false, None),
+            syn::ReturnType::Type(_, typ) => match result_value(typ) {
+                Some(value) => (true, Some(value)),
+                None => (false, Some(&**typ)),
+            },
+        };
+        // a tuple is returned as multiple values, the unit type as none
+        let outputs = match output {
+            None => Vec::new(),
+            Some(syn::Type::Tuple(tuple)) => tuple.elems.iter().cloned().collect(),
+            Some(typ) => vec![typ.clone()],
+        };
+
+        Ok(ExportedMethod {
+            ident: sig.ident.clone(),
+            member: name.unwrap_or_else(|| camel_case(&sig.ident.unraw().to_string())),
+            args,
+            outputs,
+            fallible,
+        })
+    }
+
+    fn call_fn_ident(&self) -> syn::Ident {
+        format_ident!("call_{}", self.ident.unraw())
+    }
+
+    fn introspect(&self) -> TokenStream {
+        let member = &self.member;
+        let arg_names = self.args.iter().map(|(name, _)| name);
+        let arg_types = self.args.iter().map(|(_, typ)| typ);
+        let method = quote! {
+            ::rustbus::introspect::Method::new(#member)
+                #(.with_in_arg::<#arg_types>(#arg_names))*
+        };
+        if self.outputs.is_empty() {
+            return method;
+        }
+        // the values of the reply have no names
+        let outputs = &self.outputs;
+        quote! {
+            {
+                let mut method = #method;
+                #(method.args.push(::rustbus::introspect::Arg {
+                    name: None,
+                    typ: <#outputs as ::rustbus::Signature>::signature(),
+                    direction: Some(::rustbus::introspect::Direction::Out),
+                    annotations: Vec::new(),
+                });)*
+                method
+            }
+        }
+    }
+
+    fn call_body(&self) -> TokenStream {
+        let ident = &self.ident;
+        let arg_types: Vec<_> = self.args.iter().map(|(_, typ)| typ).collect();
+        let arg_vars: Vec<_> = (0..self.args.len())
+            .map(|idx| format_ident!("arg{}", idx))
+            .collect();
+        let out_vars: Vec<_> = (0..self.outputs.len())
+            .map(|idx| format_ident!("value{}", idx))
+            .collect();
+
+        let parse_args = if self.args.is_empty() {
+            quote! {
+                if !msg.get_sig().is_empty() {
+                    return Ok(::rustbus::standard_messages::invalid_args(&msg.dynheader, Some("")));
+                }
+            }
+        } else {
+            quote! {
+                let mut expected = String::new();
+                #(<#arg_types as ::rustbus::Signature>::signature().to_str(&mut expected);)*
+                if msg.get_sig() != expected {
+                    return Ok(::rustbus::standard_messages::invalid_args(&msg.dynheader, Some(&expected)));
+                }
+                let mut parser = msg.body.parser();
+                #(let #arg_vars: #arg_types = match parser.get() {
+                    Ok(value) => value,
+                    Err(_) => return Ok(::rustbus::standard_messages::invalid_args(&msg.dynheader, Some(&expected))),
+                };)*
+            }
+        };
+
+        let values = match out_vars.len() {
+            1 => quote! { #(#out_vars)* },
+            _ => quote! { (#(#out_vars),*) },
+        };
+        let call = quote! { object.#ident(#(#arg_vars),*) };
+        let call = if self.fallible {
+            quote! {
+                let #values = match #call {
+                    Ok(values) => values,
+                    Err(error) => return Ok(::rustbus::DBusError::from(error).to_message(&msg.dynheader)),
+                };
+            }
+        } else {
+            quote! { let #values = #call; }
+        };
+
+        let reply = if out_vars.is_empty() {
+            quote! { Ok(msg.dynheader.make_response()) }
+        } else {
+            quote! {
+                let mut reply = msg.dynheader.make_response();
+                #(reply.body.push_param(#out_vars)?;)*
+                Ok(reply)
+            }
+        };
+
+        quote! {
+            #parse_args
+            #call
+            #reply
+        }
+    }
+}
+
+/// The type of the value if this is a `Result`
+pub fn result_value(typ: &syn::Type) -> Option<&syn::Type> {
+    let segment = match typ {
+        syn::Type::Path(path) => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Result" {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first()? {
+            syn::GenericArgument::Type(value) => Some(value),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// `add_item` becomes `AddItem`
+pub fn camel_case(name: &str) -> String {
+    let mut camel = String::new();
+    for part in name.split('_') {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            camel.push(first.to_ascii_uppercase());
+            camel.extend(chars);
+        }
+    }
+    camel
+}
@@ -1,45 +1,148 @@
+mod dicts;
+mod interface;
+mod proxy;
+mod reprs;
 mod structs;
 mod variants;
 
-#[proc_macro_derive(Marshal)]
+/// Derive `Marshal` for structs and enums. Structs are marshalled as dbus structs with their fields in declaration
+/// order, the fields can be configured with `#[rustbus(...)]` for all three derives:
+/// * `with = "module"` (un-)marshals the field with `module::marshal(&T, ctx)`, `module::unmarshal(ctx)` and
+///   `module::signature()` instead of the traits of its type
+/// * `signature = "..."` overrides the signature of the field, e.g. for `with` modules without a signature function
+/// * `default` marks trailing fields that older peers do not send, they are `Default::default()` if the message
+///   ends before them. Fields can only be missing if the struct is the last value of the message, inside of
+///   containers or before other values the full signature is required.
+/// * `as_variant` marshals the field as a variant containing its value
+///
+/// With `#[rustbus(dict)]` on the struct it is marshalled as an `a{sv}` dict instead, like the option arguments of
+/// most newer APIs. Each field is an entry keyed by its name, or by `#[rustbus(rename = "...")]`, with its value in a
+/// variant. `Option` fields are left out if they are `None` and are `None` if the key is missing, `default` fields are
+/// `Default::default()` if the key is missing and all other keys are required. Unknown keys are ignored unless the
+/// struct has `#[rustbus(dict, deny_unknown_keys)]`.
+///
+/// Enums are marshalled as variants holding the fields of the variant, chosen by their signature. Enums without
+/// fields can instead be marshalled as an integer with `#[rustbus(repr = "u32")]` (or any other integer type) or as a
+/// string with `#[rustbus(repr = "string")]`. The values are the discriminants or `#[rustbus(value = 1)]` for integers
+/// and the names of the variants or `#[rustbus(rename = "...")]` for strings. Unknown values are an
+/// `UnmarshalError::UnknownEnumValue`, unless one variant like `Unknown(u32)` is marked with `#[rustbus(other)]` to
+/// hold them.
+#[proc_macro_derive(Marshal, attributes(rustbus))]
 pub fn derive_marshal(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
 
-    match ast.data {
+    match &ast.data {
         syn::Data::Struct(data) => {
-            structs::make_struct_marshal_impl(&ast.ident, &ast.generics, &data.fields).into()
+            structs::make_struct_marshal_impl(&ast.ident, &ast.generics, &ast.attrs, &data.fields)
         }
         syn::Data::Enum(data) => {
-            variants::make_variant_marshal_impl(&ast.ident, &ast.generics, &data.variants).into()
+            reprs::parse_enum(&ast.attrs, &data.variants).and_then(|repr| match repr {
+                Some(repr) => Ok(reprs::make_repr_marshal_impl(
+                    &ast.ident,
+                    &ast.generics,
+                    &repr,
+                )),
+                None => {
+                    variants::make_variant_marshal_impl(&ast.ident, &ast.generics, &data.variants)
+                }
+            })
         }
-        _ => unimplemented!("Nothing but structs can be derived on right now"),
+        syn::Data::Union(data) => Err(union_error(data)),
     }
+    .unwrap_or_else(|err| err.to_compile_error())
+    .into()
 }
-#[proc_macro_derive(Unmarshal)]
+#[proc_macro_derive(Unmarshal, attributes(rustbus))]
 pub fn derive_unmarshal(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
 
-    match ast.data {
+    match &ast.data {
         syn::Data::Struct(data) => {
-            structs::make_struct_unmarshal_impl(&ast.ident, &ast.generics, &data.fields).into()
+            structs::make_struct_unmarshal_impl(&ast.ident, &ast.generics, &ast.attrs, &data.fields)
         }
         syn::Data::Enum(data) => {
-            variants::make_variant_unmarshal_impl(&ast.ident, &ast.generics, &data.variants).into()
+            reprs::parse_enum(&ast.attrs, &data.variants).and_then(|repr| match repr {
+                Some(repr) => Ok(reprs::make_repr_unmarshal_impl(
+                    &ast.ident,
+                    &ast.generics,
+                    &repr,
+                )),
+                None => {
+                    variants::make_variant_unmarshal_impl(&ast.ident, &ast.generics, &data.variants)
+                }
+            })
         }
-        _ => unimplemented!("Nothing but structs can be derived on right now"),
+        syn::Data::Union(data) => Err(union_error(data)),
     }
+    .unwrap_or_else(|err| err.to_compile_error())
+    .into()
 }
-#[proc_macro_derive(Signature)]
+#[proc_macro_derive(Signature, attributes(rustbus))]
 pub fn derive_signature(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
 
-    match ast.data {
+    match &ast.data {
         syn::Data::Struct(data) => {
-            structs::make_struct_signature_impl(&ast.ident, &ast.generics, &data.fields).into()
+            structs::make_struct_signature_impl(&ast.ident, &ast.generics, &ast.attrs, &data.fields)
         }
-        syn::Data::Enum(_data) => {
-            variants::make_variant_signature_imp(&ast.ident, &ast.generics).into()
+        syn::Data::Enum(data) => {
+            reprs::parse_enum(&ast.attrs, &data.variants).map(|repr| match repr {
+                Some(repr) => reprs::make_repr_signature_impl(&ast.ident, &ast.generics, &repr),
+                None => variants::make_variant_signature_imp(&ast.ident, &ast.generics),
+            })
         }
-        _ => unimplemented!("Nothing but structs can be derived on right now"),
+        syn::Data::Union(data) => Err(union_error(data)),
     }
+    .unwrap_or_else(|err| err.to_compile_error())
+    .into()
+}
+
+fn union_error(data: &syn::DataUnion) -> syn::Error {
+    syn::Error::new_spanned(
+        data.union_token,
+        "Nothing but structs and enums can be derived on right now",
+    )
+}
+
+/// Implement `rustbus::connection::dispatch_conn::DBusInterface` for the type of an impl block. Every method with a
+/// `&self` or `&mut self` receiver becomes a method of the interface, named like the function in CamelCase. The
+/// arguments are unmarshalled, calls with a different signature are answered with an InvalidArgs error. A tuple is
+/// returned as multiple values, the error of a `Result` is turned into an error reply with `DBusError::from`.
+///
+/// Methods can be renamed with `#[dbus_method(name = "...")]` and left out with `#[dbus_method(skip)]`.
+#[proc_macro_attribute]
+pub fn dbus_interface(
+    args: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let args = syn::parse_macro_input!(args as syn::AttributeArgs);
+    let item = syn::parse_macro_input!(input as syn::ItemImpl);
+
+    interface::make_interface_impl(&args, item)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Generate a proxy for the interface described by a trait: `#[dbus_proxy(interface = "...")]` on `trait Name`
+/// generates `NameProxy`, which wraps a `&mut RpcConn` and calls the methods on a remote object. The trait itself is
+/// not emitted. Methods are named like the functions in CamelCase, their return type is the type of the reply, written
+/// with or without the `Result`. Replies are unmarshalled into owned types.
+///
+/// `default_path` and `default_service` add the constants `DEFAULT_PATH`/`DEFAULT_SERVICE` and, if both are given,
+/// `NameProxy::with_defaults(con)`. Members of the trait can be annotated with:
+/// * `#[dbus_proxy(name = "...")]` to use a different name on the bus
+/// * `#[dbus_proxy(property)]` on `fn name(&self) -> T` or `fn set_name(&self, value: T)` to get/set a property
+/// * `#[dbus_proxy(signal)]` on `fn name(&self, args...)` to add `receive_name`, which subscribes to the signal and
+///   returns a `SignalStream` of its values
+#[proc_macro_attribute]
+pub fn dbus_proxy(
+    args: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let args = syn::parse_macro_input!(args as syn::AttributeArgs);
+    let item = syn::parse_macro_input!(input as syn::ItemTrait);
+
+    proxy::make_proxy(&args, item)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
 }
@@ -0,0 +1,371 @@
+use crate::interface::{camel_case, result_value};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+
+#[derive(Default)]
+struct ProxyArgs {
+    interface: Option<String>,
+    default_path: Option<String>,
+    default_service: Option<String>,
+}
+
+pub fn make_proxy(args: &[syn::NestedMeta], item: syn::ItemTrait) -> syn::Result<TokenStream> {
+    let proxy_args = parse_proxy_args(args)?;
+    let interface = proxy_args.interface.ok_or_else(|| {
+        syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "Expected the name of the interface: #[dbus_proxy(interface = \"...\")]",
+        )
+    })?;
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &item.generics,
+            "dbus_proxy can not be used on generic traits",
+        ));
+    }
+
+    let mut members = Vec::new();
+    for trait_item in &item.items {
+        match trait_item {
+            syn::TraitItem::Method(method) => members.push(ProxyMember::new(method)?),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    trait_item,
+                    "dbus_proxy traits can only contain methods",
+                ))
+            }
+        }
+    }
+
+    let vis = &item.vis;
+    let docs = item.attrs.iter().filter(|attr| attr.path.is_ident("doc"));
+    let proxy = format_ident!("{}Proxy", item.ident);
+
+    let mut defaults = Vec::new();
+    if let Some(service) = &proxy_args.default_service {
+        defaults.push(quote! { pub const DEFAULT_SERVICE: &'static str = #service; });
+    }
+    if let Some(path) = &proxy_args.default_path {
+        defaults.push(quote! { pub const DEFAULT_PATH: &'static str = #path; });
+    }
+    if proxy_args.default_service.is_some() && proxy_args.default_path.is_some() {
+        defaults.push(quote! {
+            /// The object at `DEFAULT_PATH` of `DEFAULT_SERVICE`, waiting for the replies without a timeout
+            pub fn with_defaults(con: &'a mut ::rustbus::RpcConn) -> Self {
+                Self::new(con, Self::DEFAULT_SERVICE, Self::DEFAULT_PATH)
+            }
+        });
+    }
+    if members
+        .iter()
+        .any(|member| matches!(member.kind, MemberKind::Getter | MemberKind::Setter))
+    {
+        defaults.push(quote! {
+            fn properties(&mut self) -> ::rustbus::properties::PropertiesProxy<'_> {
+                ::rustbus::properties::PropertiesProxy::with_timeout(
+                    self.con,
+                    &self.destination,
+                    &self.path,
+                    Self::INTERFACE,
+                    self.timeout,
+                )
+            }
+        });
+    }
+    let fns = members.iter().map(ProxyMember::proxy_fn);
+
+    Ok(quote! {
+        #(#docs)*
+        #vis struct #proxy<'a> {
+            con: &'a mut ::rustbus::RpcConn,
+            destination: String,
+            path: String,
+            timeout: ::rustbus::connection::Timeout,
+        }
+
+        impl<'a> #proxy<'a> {
+            pub const INTERFACE: &'static str = #interface;
+
+            /// Wait for the replies without a timeout
+            pub fn new(con: &'a mut ::rustbus::RpcConn, destination: &str, path: &str) -> Self {
+                Self::with_timeout(con, destination, path, ::rustbus::connection::Timeout::Infinite)
+            }
+
+            /// Wait at most `timeout` for each reply
+            pub fn with_timeout(
+                con: &'a mut ::rustbus::RpcConn,
+                destination: &str,
+                path: &str,
+                timeout: ::rustbus::connection::Timeout,
+            ) -> Self {
+                #proxy {
+                    con,
+                    destination: destination.to_owned(),
+                    path: path.to_owned(),
+                    timeout,
+                }
+            }
+
+            #(#defaults)*
+
+            #(#fns)*
+        }
+    })
+}
+
+fn parse_proxy_args(args: &[syn::NestedMeta]) -> syn::Result<ProxyArgs> {
+    let mut proxy_args = ProxyArgs::default();
+    for arg in args {
+        let (path, value) = match arg {
+            syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                path,
+                lit: syn::Lit::Str(lit),
+                ..
+            })) => (path, lit.value()),
+            _ => return Err(syn::Error::new_spanned(arg, PROXY_USAGE)),
+        };
+        if path.is_ident("interface") {
+            proxy_args.interface = Some(value);
+        } else if path.is_ident("default_path") {
+            proxy_args.default_path = Some(value);
+        } else if path.is_ident("default_service") {
+            proxy_args.default_service = Some(value);
+        } else {
+            return Err(syn::Error::new_spanned(arg, PROXY_USAGE));
+        }
+    }
+    Ok(proxy_args)
+}
+
+const PROXY_USAGE: &str =
+    "Expected #[dbus_proxy(interface = \"...\", default_path = \"...\", default_service = \"...\")]";
+
+const MEMBER_USAGE: &str =
+    "Expected #[dbus_proxy(name = \"...\")], #[dbus_proxy(property)] or #[dbus_proxy(signal)]";
+
+enum MemberKind {
+    Method,
+    Getter,
+    Setter,
+    Signal,
+}
+
+struct ProxyMember {
+    kind: MemberKind,
+    ident: syn::Ident,
+    docs: Vec<syn::Attribute>,
+    /// The name of the method, property or signal
+    name: String,
+    /// Name and type of the arguments
+    args: Vec<(syn::Ident, syn::Type)>,
+    outputs: Vec<syn::Type>,
+}
+
+impl ProxyMember {
+    fn new(method: &syn::TraitItemMethod) -> syn::Result<Self> {
+        let sig = &method.sig;
+        let mut name = None;
+        let mut kind = MemberKind::Method;
+        for attr in &method.attrs {
+            if !attr.path.is_ident("dbus_proxy") {
+                continue;
+            }
+            let list = match attr.parse_meta()? {
+                syn::Meta::List(list) => list,
+                meta => return Err(syn::Error::new_spanned(meta, MEMBER_USAGE)),
+            };
+            for nested in &list.nested {
+                match nested {
+                    syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                        path,
+                        lit: syn::Lit::Str(lit),
+                        ..
+                    })) if path.is_ident("name") => name = Some(lit.value()),
+                    syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("property") => {
+                        kind = MemberKind::Getter
+                    }
+                    syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("signal") => {
+                        kind = MemberKind::Signal
+                    }
+                    _ => return Err(syn::Error::new_spanned(nested, MEMBER_USAGE)),
+                }
+            }
+        }
+
+        if !sig.generics.params.is_empty() || sig.asyncness.is_some() {
+            return Err(syn::Error::new_spanned(
+                sig,
+                "Proxy methods can not be generic or async",
+            ));
+        }
+        if sig.receiver().is_none() {
+            return Err(syn::Error::new_spanned(
+                sig,
+                "Proxy methods need a self receiver",
+            ));
+        }
+        let mut args = Vec::new();
+        for (idx, input) in sig.inputs.iter().enumerate() {
+            if let syn::FnArg::Typed(arg) = input {
+                let ident = match &*arg.pat {
+                    syn::Pat::Ident(pat) => pat.ident.clone(),
+                    _ => format_ident!("arg{}", idx - 1),
+                };
+                args.push((ident, (*arg.ty).clone()));
+            }
+        }
+        // the reply type may be written with or without the Result
+        let outputs = match &sig.output {
+            syn::ReturnType::Default => Vec::new(),
+            syn::ReturnType::Type(_, typ) => match result_value(typ).unwrap_or(typ) {
+                syn::Type::Tuple(tuple) => tuple.elems.iter().cloned().collect(),
+                typ => vec![typ.clone()],
+            },
+        };
+
+        let fn_name = sig.ident.unraw().to_string();
+        if let MemberKind::Getter = kind {
+            if args.is_empty() && outputs.len() == 1 {
+                // a getter
+            } else if args.len() == 1 && outputs.is_empty() && fn_name.starts_with("set_") {
+                kind = MemberKind::Setter;
+            } else {
+                return Err(syn::Error::new_spanned(
+                    sig,
+                    "Properties are read by `fn name(&self) -> T` and written by `fn set_name(&self, value: T)`",
+                ));
+            }
+        }
+        if let MemberKind::Signal = kind {
+            if !outputs.is_empty() {
+                return Err(syn::Error::new_spanned(
+                    &sig.output,
+                    "The values of a signal are declared as the arguments",
+                ));
+            }
+        }
+        let name = name.unwrap_or_else(|| match kind {
+            MemberKind::Setter => camel_case(&fn_name["set_".len()..]),
+            _ => camel_case(&fn_name),
+        });
+
+        Ok(ProxyMember {
+            kind,
+            ident: sig.ident.clone(),
+            docs: method
+                .attrs
+                .iter()
+                .filter(|attr| attr.path.is_ident("doc"))
+                .cloned()
+                .collect(),
+            name,
+            args,
+            outputs,
+        })
+    }
+
+    fn proxy_fn(&self) -> TokenStream {
+        let docs = &self.docs;
+        let ident = &self.ident;
+        let name = &self.name;
+        let arg_idents: Vec<_> = self.args.iter().map(|(ident, _)| ident).collect();
+        let arg_types: Vec<_> = self.args.iter().map(|(_, typ)| typ).collect();
+        let output = match self.outputs.len() {
+            1 => {
+                let output = &self.outputs[0];
+                quote! { #output }
+            }
+            _ => {
+                let outputs = &self.outputs;
+                quote! { (#(#outputs),*) }
+            }
+        };
+
+        match self.kind {
+            MemberKind::Method => {
+                let call = quote! {
+                    self.con.call(
+                        &self.destination,
+                        &self.path,
+                        Self::INTERFACE,
+                        #name,
+                        (#(#arg_idents,)*),
+                        self.timeout,
+                    )
+                };
+                // a single value is returned as it is, other outputs are already the tuple of the values
+                let body = if self.outputs.len() == 1 {
+                    quote! {
+                        let (value,) = #call?;
+                        Ok(value)
+                    }
+                } else {
+                    call
+                };
+                quote! {
+                    #(#docs)*
+                    pub fn #ident(&mut self, #(#arg_idents: #arg_types),*) -> Result<#output, ::rustbus::connection::rpc_conn::CallError> {
+                        #body
+                    }
+                }
+            }
+            MemberKind::Getter => quote! {
+                #(#docs)*
+                pub fn #ident(&mut self) -> Result<#output, ::rustbus::connection::rpc_conn::CallError> {
+                    self.properties().get(#name)
+                }
+            },
+            MemberKind::Setter => quote! {
+                #(#docs)*
+                pub fn #ident(&mut self, #(#arg_idents: #arg_types),*) -> Result<(), ::rustbus::connection::rpc_conn::CallError> {
+                    self.properties().set(#name, #(#arg_idents),*)
+                }
+            },
+            MemberKind::Signal => {
+                let receive = format_ident!("receive_{}", ident.unraw());
+                let values = match arg_types.len() {
+                    1 => quote! { #(#arg_types)* },
+                    _ => quote! { (#(#arg_types),*) },
+                };
+                let docs = if docs.is_empty() {
+                    let doc = format!(
+                        "The `{}` signals sent by the object, until the stream is dropped",
+                        name
+                    );
+                    vec![syn::parse_quote! { #[doc = #doc] }]
+                } else {
+                    docs.clone()
+                };
+                let parse = match arg_types.len() {
+                    0 => quote! { Ok(()) },
+                    1 => quote! { msg.body.parser().get() },
+                    _ => quote! {
+                        let mut parser = msg.body.parser();
+                        Ok((#({ let value: #arg_types = parser.get()?; value }),*))
+                    },
+                };
+                quote! {
+                    #(#docs)*
+                    pub fn #receive(&mut self) -> Result<::rustbus::connection::rpc_conn::SignalStream<'_, #values>, ::rustbus::connection::rpc_conn::CallError> {
+                        let rule = ::rustbus::MatchRule::new()
+                            .with_type(::rustbus::MessageType::Signal)
+                            .with_sender(self.destination.clone())
+                            .with_path(self.path.clone())
+                            .with_interface(Self::INTERFACE)
+                            .with_member(#name);
+                        let stream = ::rustbus::connection::rpc_conn::SignalStream::subscribe(
+                            self.con,
+                            rule,
+                            self.timeout,
+                            |msg: &::rustbus::message_builder::MarshalledMessage| -> Result<#values, ::rustbus::wire::errors::UnmarshalError> {
+                                #parse
+                            },
+                        )?;
+                        Ok(stream)
+                    }
+                }
+            }
+        }
+    }
+}
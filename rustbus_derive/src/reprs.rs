@@ -0,0 +1,382 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::{punctuated::Punctuated, token::Comma, Variant};
+
+/// A fieldless enum that is put on the wire as an integer or a string, see `#[rustbus(repr = "...")]`
+pub struct Repr {
+    kind: ReprKind,
+    values: Vec<(syn::Ident, Value)>,
+    /// The variant with one field that holds unknown values
+    other: Option<syn::Ident>,
+}
+
+enum ReprKind {
+    /// The name of the integer type
+    Int(syn::Ident),
+    String,
+}
+
+enum Value {
+    Int(i128),
+    String(String),
+}
+
+impl Value {
+    fn to_tokens(&self) -> TokenStream {
+        match self {
+            // negative literals are written as a negation so they can be used as patterns
+            Value::Int(value) if *value < 0 => {
+                let value = proc_macro2::Literal::u128_unsuffixed(value.unsigned_abs());
+                quote! { -#value }
+            }
+            Value::Int(value) => {
+                let value = proc_macro2::Literal::u128_unsuffixed(*value as u128);
+                quote! { #value }
+            }
+            Value::String(value) => quote! { #value },
+        }
+    }
+}
+
+const INT_REPRS: &[&str] = &["u8", "i16", "u16", "i32", "u32", "i64", "u64"];
+
+/// Parse the `#[rustbus(...)]` attributes of an enum. Returns `None` for enums that are marshalled as variants, which
+/// do not support any attributes.
+pub fn parse_enum(
+    attrs: &[syn::Attribute],
+    variants: &Punctuated<Variant, Comma>,
+) -> syn::Result<Option<Repr>> {
+    let kind = match parse_repr_attr(attrs)? {
+        Some(kind) => kind,
+        None => {
+            let variant_attrs = variants.iter().flat_map(|variant| {
+                let field_attrs = variant.fields.iter().flat_map(|field| field.attrs.iter());
+                variant.attrs.iter().chain(field_attrs)
+            });
+            for attr in variant_attrs {
+                if attr.path.is_ident("rustbus") {
+                    return Err(syn::Error::new_spanned(
+                        attr,
+                        "#[rustbus(...)] attributes are only supported on the fields of structs and the variants of enums with #[rustbus(repr = \"...\")]",
+                    ));
+                }
+            }
+            return Ok(None);
+        }
+    };
+
+    let mut repr = Repr {
+        kind,
+        values: Vec::new(),
+        other: None,
+    };
+    // the discriminant the compiler assigns, used if the variant has no value
+    let mut discriminant = -1;
+    for variant in variants {
+        let attrs = parse_variant_attrs(&variant.attrs)?;
+        if let Some((_, expr)) = &variant.discriminant {
+            discriminant = int_expr(expr)?;
+        } else {
+            discriminant += 1;
+        }
+
+        if attrs.other {
+            if repr.other.is_some() {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "Only one variant can be #[rustbus(other)]",
+                ));
+            }
+            if !matches!(&variant.fields, syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1)
+                || attrs.value.is_some()
+                || attrs.rename.is_some()
+            {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "The #[rustbus(other)] variant holds the unknown values in its only field, like `Unknown(u32)`",
+                ));
+            }
+            repr.other = Some(variant.ident.clone());
+            continue;
+        }
+        if !variant.fields.is_empty() {
+            return Err(syn::Error::new_spanned(
+                &variant.fields,
+                "Enums with #[rustbus(repr = \"...\")] can only have fields in the #[rustbus(other)] variant",
+            ));
+        }
+
+        let value = match &repr.kind {
+            ReprKind::Int(_) => {
+                if let Some(rename) = &attrs.rename {
+                    return Err(syn::Error::new_spanned(
+                        rename,
+                        "`rename` is only supported with #[rustbus(repr = \"string\")], use `value` instead",
+                    ));
+                }
+                match &attrs.value {
+                    Some(value) => Value::Int(value.base10_parse()?),
+                    None => Value::Int(discriminant),
+                }
+            }
+            ReprKind::String => {
+                if let Some(value) = &attrs.value {
+                    return Err(syn::Error::new_spanned(
+                        value,
+                        "`value` is only supported with integer reprs, use `rename` instead",
+                    ));
+                }
+                match &attrs.rename {
+                    Some(rename) => Value::String(rename.value()),
+                    None => Value::String(variant.ident.unraw().to_string()),
+                }
+            }
+        };
+        let used = repr.values.iter().any(|(_, other)| match (other, &value) {
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            _ => false,
+        });
+        if used {
+            return Err(syn::Error::new_spanned(
+                variant,
+                "Another variant already has the same value",
+            ));
+        }
+        repr.values.push((variant.ident.clone(), value));
+    }
+    Ok(Some(repr))
+}
+
+fn parse_repr_attr(attrs: &[syn::Attribute]) -> syn::Result<Option<ReprKind>> {
+    let mut kind = None;
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("rustbus")) {
+        let list = match attr.parse_meta()? {
+            syn::Meta::List(list) => list,
+            meta => return Err(syn::Error::new_spanned(meta, REPR_USAGE)),
+        };
+        for nested in &list.nested {
+            match nested {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Str(lit),
+                    ..
+                })) if path.is_ident("repr") => {
+                    if kind.is_some() {
+                        return Err(syn::Error::new_spanned(nested, "Duplicate `repr`"));
+                    }
+                    let repr = lit.value();
+                    kind = Some(if repr == "string" {
+                        ReprKind::String
+                    } else if INT_REPRS.contains(&repr.as_str()) {
+                        ReprKind::Int(syn::Ident::new(&repr, lit.span()))
+                    } else {
+                        return Err(syn::Error::new_spanned(
+                            lit,
+                            "Expected one of u8, i16, u16, i32, u32, i64, u64 or string",
+                        ));
+                    });
+                }
+                _ => return Err(syn::Error::new_spanned(nested, REPR_USAGE)),
+            }
+        }
+    }
+    Ok(kind)
+}
+
+const REPR_USAGE: &str = "Expected #[rustbus(repr = \"u32\")] or #[rustbus(repr = \"string\")]";
+
+#[derive(Default)]
+struct VariantAttrs {
+    value: Option<syn::LitInt>,
+    rename: Option<syn::LitStr>,
+    other: bool,
+}
+
+fn parse_variant_attrs(attrs: &[syn::Attribute]) -> syn::Result<VariantAttrs> {
+    let mut variant_attrs = VariantAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("rustbus")) {
+        let list = match attr.parse_meta()? {
+            syn::Meta::List(list) => list,
+            meta => return Err(syn::Error::new_spanned(meta, VARIANT_USAGE)),
+        };
+        for nested in &list.nested {
+            match nested {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Int(lit),
+                    ..
+                })) if path.is_ident("value") => {
+                    if variant_attrs.value.is_some() {
+                        return Err(syn::Error::new_spanned(nested, "Duplicate `value`"));
+                    }
+                    variant_attrs.value = Some(lit.clone());
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Str(lit),
+                    ..
+                })) if path.is_ident("rename") => {
+                    if variant_attrs.rename.is_some() {
+                        return Err(syn::Error::new_spanned(nested, "Duplicate `rename`"));
+                    }
+                    variant_attrs.rename = Some(lit.clone());
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("other") => {
+                    variant_attrs.other = true;
+                }
+                _ => return Err(syn::Error::new_spanned(nested, VARIANT_USAGE)),
+            }
+        }
+    }
+    Ok(variant_attrs)
+}
+
+const VARIANT_USAGE: &str =
+    "Expected #[rustbus(value = 1)], #[rustbus(rename = \"...\")] or #[rustbus(other)]";
+
+/// The value of an explicit discriminant like `A = 3` or `B = -1`
+fn int_expr(expr: &syn::Expr) -> syn::Result<i128> {
+    match expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(lit),
+            ..
+        }) => lit.base10_parse(),
+        syn::Expr::Unary(syn::ExprUnary {
+            op: syn::UnOp::Neg(_),
+            expr,
+            ..
+        }) => int_expr(expr).map(|value| -value),
+        syn::Expr::Group(group) => int_expr(&group.expr),
+        _ => Err(syn::Error::new_spanned(
+            expr,
+            "Only integer literals are supported as discriminants, use #[rustbus(value = ...)] instead",
+        )),
+    }
+}
+
+pub fn make_repr_signature_impl(
+    ident: &syn::Ident,
+    generics: &syn::Generics,
+    repr: &Repr,
+) -> TokenStream {
+    let (impl_gen, typ_gen, clause_gen) = generics.split_for_impl();
+    let typ = match &repr.kind {
+        ReprKind::Int(typ) => quote! { #typ },
+        ReprKind::String => quote! { ::std::string::String },
+    };
+
+    quote! {
+        impl #impl_gen ::rustbus::Signature for #ident #typ_gen #clause_gen {
+            #[inline]
+            fn signature() -> ::rustbus::signature::Type {
+                <#typ as ::rustbus::Signature>::signature()
+            }
+            fn alignment() -> usize {
+                <#typ as ::rustbus::Signature>::alignment()
+            }
+            fn has_sig(sig: &str) -> bool {
+                <#typ as ::rustbus::Signature>::has_sig(sig)
+            }
+        }
+    }
+}
+
+pub fn make_repr_marshal_impl(
+    ident: &syn::Ident,
+    generics: &syn::Generics,
+    repr: &Repr,
+) -> TokenStream {
+    let (impl_gen, typ_gen, clause_gen) = generics.split_for_impl();
+    let names = repr.values.iter().map(|(name, _)| name);
+    let values = repr.values.iter().map(|(_, value)| match &repr.kind {
+        ReprKind::Int(typ) => {
+            let value = value.to_tokens();
+            quote! { <#typ as ::rustbus::Marshal>::marshal(&#value, ctx) }
+        }
+        ReprKind::String => {
+            let value = value.to_tokens();
+            quote! { <&str as ::rustbus::Marshal>::marshal(&#value, ctx) }
+        }
+    });
+    let other = repr.other.as_ref().map(|other| match &repr.kind {
+        ReprKind::Int(typ) => quote! {
+            #ident::#other(value) => <#typ as ::rustbus::Marshal>::marshal(value, ctx),
+        },
+        ReprKind::String => quote! {
+            #ident::#other(value) => <&str as ::rustbus::Marshal>::marshal(&::std::convert::AsRef::<str>::as_ref(value), ctx),
+        },
+    });
+
+    quote! {
+        impl #impl_gen ::rustbus::Marshal for #ident #typ_gen #clause_gen {
+            #[inline]
+            fn marshal(&self, ctx: &mut ::rustbus::wire::marshal::MarshalContext<'_,'_>) -> Result<(), ::rustbus::wire::errors::MarshalError> {
+                match self {
+                    #(
+                        #ident::#names => #values,
+                    )*
+                    #other
+                }
+            }
+        }
+    }
+}
+
+pub fn make_repr_unmarshal_impl(
+    ident: &syn::Ident,
+    generics: &syn::Generics,
+    repr: &Repr,
+) -> TokenStream {
+    let names = repr.values.iter().map(|(name, _)| name);
+    let values = repr.values.iter().map(|(_, value)| value.to_tokens());
+    let typ = match &repr.kind {
+        ReprKind::Int(typ) => quote! { #typ },
+        ReprKind::String => quote! { &'__internal_buf str },
+    };
+    let unknown = match &repr.other {
+        Some(other) => quote! { value => #ident::#other(::std::convert::From::from(value)), },
+        None => quote! {
+            _ => return Err(::rustbus::wire::errors::UnmarshalError::UnknownEnumValue(value.to_string())),
+        },
+    };
+
+    let mut bufdef = syn::LifetimeDef {
+        attrs: Vec::new(),
+        lifetime: syn::Lifetime::new("'__internal_buf", proc_macro2::Span::call_site()),
+        colon_token: None,
+        bounds: syn::punctuated::Punctuated::new(),
+    };
+
+    let mut new_generics = generics.clone();
+    for lt in new_generics.lifetimes_mut() {
+        bufdef.bounds.push(lt.lifetime.clone());
+        lt.bounds.push(bufdef.lifetime.clone());
+    }
+
+    let typ_generics = new_generics.clone();
+    let (_, typ_gen, _) = typ_generics.split_for_impl();
+
+    new_generics
+        .params
+        .insert(0, syn::GenericParam::Lifetime(bufdef));
+
+    let (impl_gen, _, clause_gen) = new_generics.split_for_impl();
+
+    quote! {
+        impl #impl_gen ::rustbus::Unmarshal<'__internal_buf, '_> for #ident #typ_gen #clause_gen {
+            #[inline]
+            fn unmarshal(ctx: &mut ::rustbus::wire::unmarshal::UnmarshalContext<'_,'__internal_buf>) -> Result<(usize,Self), ::rustbus::wire::errors::UnmarshalError> {
+                let (bytes, value) = <#typ as ::rustbus::Unmarshal>::unmarshal(ctx)?;
+                let this = match value {
+                    #(
+                        #values => #ident::#names,
+                    )*
+                    #unknown
+                };
+                Ok((bytes, this))
+            }
+        }
+    }
+}
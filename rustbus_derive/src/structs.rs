@@ -1,29 +1,39 @@
 use proc_macro2::TokenStream;
 use quote::{quote, ToTokens};
 
+use crate::dicts;
+
 pub fn make_struct_marshal_impl(
     ident: &syn::Ident,
     generics: &syn::Generics,
+    attrs: &[syn::Attribute],
     fields: &syn::Fields,
-) -> TokenStream {
+) -> syn::Result<TokenStream> {
     let (impl_gen, typ_gen, clause_gen) = generics.split_for_impl();
-    let marshal = struct_field_marshal(fields);
+    let marshal = match parse_struct(attrs, fields)? {
+        Layout::Struct(fields) => struct_field_marshal(&fields),
+        Layout::Dict(dict) => dicts::dict_marshal(&dict),
+    };
 
-    quote! {
+    Ok(quote! {
         impl #impl_gen ::rustbus::Marshal for #ident #typ_gen #clause_gen {
             #[inline]
             fn marshal(&self, ctx: &mut ::rustbus::wire::marshal::MarshalContext<'_,'_>) -> Result<(), ::rustbus::wire::errors::MarshalError> {
                 #marshal
             }
         }
-    }
+    })
 }
 pub fn make_struct_unmarshal_impl(
     ident: &syn::Ident,
     generics: &syn::Generics,
+    attrs: &[syn::Attribute],
     fields: &syn::Fields,
-) -> TokenStream {
-    let marshal = struct_field_unmarshal(fields);
+) -> syn::Result<TokenStream> {
+    let marshal = match parse_struct(attrs, fields)? {
+        Layout::Struct(fields) => struct_field_unmarshal(&fields),
+        Layout::Dict(dict) => dicts::dict_unmarshal(&dict),
+    };
 
     let mut bufdef = syn::LifetimeDef {
         attrs: Vec::new(),
@@ -47,59 +57,328 @@ pub fn make_struct_unmarshal_impl(
 
     let (impl_gen, _, clause_gen) = new_generics.split_for_impl();
 
-    quote! {
+    Ok(quote! {
         impl #impl_gen ::rustbus::Unmarshal<'__internal_buf, '_> for #ident #typ_gen #clause_gen {
             #[inline]
             fn unmarshal(ctx: &mut ::rustbus::wire::unmarshal::UnmarshalContext<'_,'__internal_buf>) -> Result<(usize,Self), ::rustbus::wire::errors::UnmarshalError> {
                 #marshal
             }
         }
-    }
+    })
 }
 pub fn make_struct_signature_impl(
     ident: &syn::Ident,
     generics: &syn::Generics,
+    attrs: &[syn::Attribute],
     fields: &syn::Fields,
-) -> TokenStream {
+) -> syn::Result<TokenStream> {
     let (impl_gen, typ_gen, clause_gen) = generics.split_for_impl();
-    let signature = struct_field_sigs(fields);
-    let has_sig = struct_field_has_sigs(fields);
+    let (signature, alignment, has_sig, has_partial_sig) = match parse_struct(attrs, fields)? {
+        Layout::Struct(fields) if fields.is_empty() => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "Signature can not be derived for empty structs",
+            ))
+        }
+        Layout::Struct(fields) => {
+            let has_partial_sig = if fields.iter().any(|field| field.attrs.default) {
+                let check = struct_field_has_sigs(&fields, true);
+                quote! {
+                    fn has_partial_sig(sig: &str) -> bool {
+                        #check
+                    }
+                }
+            } else {
+                quote! {}
+            };
+            (
+                struct_field_sigs(&fields),
+                8usize,
+                struct_field_has_sigs(&fields, false),
+                has_partial_sig,
+            )
+        }
+        Layout::Dict(_) => (dicts::dict_sig(), 4, dicts::dict_has_sig(), quote! {}),
+    };
 
-    quote! {
+    Ok(quote! {
         impl #impl_gen ::rustbus::Signature for #ident #typ_gen #clause_gen {
             #[inline]
             fn signature() -> ::rustbus::signature::Type {
                 #signature
             }
             fn alignment() -> usize {
-                8
+                #alignment
             }
             fn has_sig(sig: &str) -> bool {
                 #has_sig
             }
+            #has_partial_sig
+        }
+    })
+}
+
+/// The options of a field, set with `#[rustbus(...)]`
+#[derive(Default)]
+pub struct FieldAttrs {
+    /// A module with `marshal`, `unmarshal` and `signature` functions that are used instead of the traits
+    pub with: Option<syn::Path>,
+    /// The signature of the field instead of the one of its type
+    pub signature: Option<syn::LitStr>,
+    /// Trailing fields that may be missing, they are `Default::default()` then
+    pub default: bool,
+    /// Marshal the field as a variant containing its value
+    pub as_variant: bool,
+    /// The key of the field in dict structs instead of its name
+    pub rename: Option<syn::LitStr>,
+}
+
+pub struct Field<'a> {
+    pub field: &'a syn::Field,
+    pub member: syn::Member,
+    pub ty: &'a syn::Type,
+    pub attrs: FieldAttrs,
+}
+
+/// How the struct is put on the wire, chosen with the `#[rustbus(...)]` attributes of the struct itself
+enum Layout<'a> {
+    /// A dbus struct with one value per field
+    Struct(Vec<Field<'a>>),
+    /// An `a{sv}` dict with one entry per field
+    Dict(dicts::Dict<'a>),
+}
+
+/// The options of the struct itself, set with `#[rustbus(...)]`
+#[derive(Default)]
+struct ContainerAttrs {
+    dict: bool,
+    deny_unknown_keys: bool,
+}
+
+fn parse_struct<'a>(attrs: &[syn::Attribute], fields: &'a syn::Fields) -> syn::Result<Layout<'a>> {
+    let container = parse_container_attrs(attrs)?;
+    let fields = parse_fields(fields)?;
+    if container.dict {
+        return dicts::Dict::new(fields, container.deny_unknown_keys).map(Layout::Dict);
+    }
+    for (idx, field) in fields.iter().enumerate() {
+        if let Some(rename) = &field.attrs.rename {
+            return Err(syn::Error::new_spanned(
+                rename,
+                "`rename` is only supported in #[rustbus(dict)] structs",
+            ));
+        }
+        let after_default = idx > 0 && fields[idx - 1].attrs.default;
+        if after_default && !field.attrs.default {
+            return Err(syn::Error::new_spanned(
+                field.field,
+                "Only trailing fields can be #[rustbus(default)], this field follows one",
+            ));
+        }
+    }
+    Ok(Layout::Struct(fields))
+}
+
+fn parse_container_attrs(attrs: &[syn::Attribute]) -> syn::Result<ContainerAttrs> {
+    let mut container = ContainerAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("rustbus")) {
+        let list = match attr.parse_meta()? {
+            syn::Meta::List(list) => list,
+            meta => return Err(syn::Error::new_spanned(meta, CONTAINER_USAGE)),
+        };
+        for nested in &list.nested {
+            match nested {
+                syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("dict") => {
+                    container.dict = true;
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(path))
+                    if path.is_ident("deny_unknown_keys") =>
+                {
+                    container.deny_unknown_keys = true;
+                }
+                _ => return Err(syn::Error::new_spanned(nested, CONTAINER_USAGE)),
+            }
+        }
+        if container.deny_unknown_keys && !container.dict {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "`deny_unknown_keys` is only supported in #[rustbus(dict)] structs",
+            ));
+        }
+    }
+    Ok(container)
+}
+
+const CONTAINER_USAGE: &str = "Expected #[rustbus(dict)] or #[rustbus(dict, deny_unknown_keys)]";
+
+fn parse_fields(fields: &syn::Fields) -> syn::Result<Vec<Field<'_>>> {
+    let mut parsed = Vec::new();
+    for (idx, field) in fields.iter().enumerate() {
+        let member = match &field.ident {
+            Some(ident) => syn::Member::Named(ident.clone()),
+            None => syn::Member::Unnamed(syn::Index::from(idx)),
+        };
+        parsed.push(Field {
+            field,
+            member,
+            ty: &field.ty,
+            attrs: parse_field_attrs(&field.attrs)?,
+        });
+    }
+    Ok(parsed)
+}
+
+fn parse_field_attrs(attrs: &[syn::Attribute]) -> syn::Result<FieldAttrs> {
+    let mut field_attrs = FieldAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("rustbus")) {
+        let list = match attr.parse_meta()? {
+            syn::Meta::List(list) => list,
+            meta => return Err(syn::Error::new_spanned(meta, FIELD_USAGE)),
+        };
+        for nested in &list.nested {
+            match nested {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Str(lit),
+                    ..
+                })) if path.is_ident("with") => {
+                    if field_attrs.with.is_some() {
+                        return Err(syn::Error::new_spanned(nested, "Duplicate `with`"));
+                    }
+                    field_attrs.with = Some(lit.parse()?);
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Str(lit),
+                    ..
+                })) if path.is_ident("signature") => {
+                    if field_attrs.signature.is_some() {
+                        return Err(syn::Error::new_spanned(nested, "Duplicate `signature`"));
+                    }
+                    if !is_single_type(&lit.value()) {
+                        return Err(syn::Error::new_spanned(
+                            lit,
+                            "The signature has to be exactly one complete type",
+                        ));
+                    }
+                    field_attrs.signature = Some(lit.clone());
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Str(lit),
+                    ..
+                })) if path.is_ident("rename") => {
+                    if field_attrs.rename.is_some() {
+                        return Err(syn::Error::new_spanned(nested, "Duplicate `rename`"));
+                    }
+                    field_attrs.rename = Some(lit.clone());
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("default") => {
+                    field_attrs.default = true;
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("as_variant") => {
+                    field_attrs.as_variant = true;
+                }
+                _ => return Err(syn::Error::new_spanned(nested, FIELD_USAGE)),
+            }
+        }
+        if field_attrs.as_variant && (field_attrs.with.is_some() || field_attrs.signature.is_some())
+        {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "`as_variant` can not be combined with `with` or `signature`",
+            ));
+        }
+    }
+    Ok(field_attrs)
+}
+
+const FIELD_USAGE: &str =
+    "Expected #[rustbus(with = \"module\")], #[rustbus(signature = \"...\")], #[rustbus(default)], #[rustbus(as_variant)] or #[rustbus(rename = \"...\")]";
+
+const BASIC_TYPES: &[u8] = b"ybnqiuxtdhsog";
+
+/// Check the signature in the macro so mistakes are reported at compile time
+fn is_single_type(sig: &str) -> bool {
+    matches!(parse_type(sig.as_bytes()), Some(rest) if rest.is_empty())
+}
+
+/// Parse one complete type from the start of the signature, returns the rest
+fn parse_type(sig: &[u8]) -> Option<&[u8]> {
+    let (first, rest) = sig.split_first()?;
+    match first {
+        b'v' => Some(rest),
+        b'a' => match rest.split_first()? {
+            (b'{', rest) => {
+                let (key, rest) = rest.split_first()?;
+                if !BASIC_TYPES.contains(key) {
+                    return None;
+                }
+                parse_type(rest)?.strip_prefix(b"}")
+            }
+            _ => parse_type(rest),
+        },
+        b'(' => {
+            let mut rest = parse_type(rest)?;
+            while !rest.starts_with(b")") {
+                rest = parse_type(rest)?;
+            }
+            Some(&rest[1..])
         }
+        typ if BASIC_TYPES.contains(typ) => Some(rest),
+        _ => None,
     }
 }
 
-fn struct_field_marshal(fields: &syn::Fields) -> TokenStream {
-    let field_names = fields
-        .iter()
-        .map(|field| field.ident.as_ref().unwrap().to_token_stream());
+fn struct_field_marshal(fields: &[Field]) -> TokenStream {
+    let marshal = fields.iter().map(|field| {
+        let member = &field.member;
+        if let Some(with) = &field.attrs.with {
+            quote! { #with::marshal(&self.#member, ctx)?; }
+        } else if field.attrs.as_variant {
+            quote! { ::rustbus::Marshal::marshal_as_variant(&self.#member, ctx)?; }
+        } else {
+            quote! { ::rustbus::Marshal::marshal(&self.#member, ctx)?; }
+        }
+    });
 
     quote! {
             ctx.align_to(8);
             #(
-                self.#field_names.marshal(ctx)?;
+                #marshal
             )*
             Ok(())
     }
 }
-fn struct_field_unmarshal(fields: &syn::Fields) -> TokenStream {
-    let field_names = fields
-        .iter()
-        .map(|field| field.ident.as_ref().unwrap().to_token_stream());
-
-    let field_types = fields.iter().map(|field| field.ty.to_token_stream());
+fn struct_field_unmarshal(fields: &[Field]) -> TokenStream {
+    let field_names = fields.iter().map(|field| &field.member);
+    let values = fields.iter().map(|field| {
+        let field_type = field.ty.to_token_stream();
+        let value = if let Some(with) = &field.attrs.with {
+            quote! { #with::unmarshal(ctx)?.1 }
+        } else if field.attrs.as_variant {
+            quote! {
+                <::rustbus::wire::unmarshal::traits::Variant as ::rustbus::Unmarshal>::unmarshal(ctx)?
+                    .1
+                    .get::<#field_type>()?
+            }
+        } else {
+            quote! { <#field_type as ::rustbus::Unmarshal>::unmarshal(ctx)?.1 }
+        };
+        if field.attrs.default {
+            // a struct with missing fields is only accepted as the last value of the body (see has_partial_sig),
+            // so if the body ends here the field is missing
+            quote! {
+                if ctx.offset >= ctx.buf.len() {
+                    ::std::default::Default::default()
+                } else {
+                    #value
+                }
+            }
+        } else {
+            value
+        }
+    });
 
     quote! {
             let start_offset = ctx.offset;
@@ -107,27 +386,36 @@ fn struct_field_unmarshal(fields: &syn::Fields) -> TokenStream {
 
             let this = Self{
                 #(
-                    #field_names: <#field_types as ::rustbus::Unmarshal>::unmarshal(ctx)?.1,
+                    #field_names: #values,
                 )*
             };
             let total_bytes = ctx.offset - start_offset;
             Ok((total_bytes, this))
     }
 }
-fn struct_field_sigs(fields: &syn::Fields) -> TokenStream {
-    let field_types = fields
-        .iter()
-        .map(|field| field.ty.to_token_stream())
-        .collect::<Vec<_>>();
-    if field_types.is_empty() {
-        panic!("Signature can not be derived for empty structs!")
-    }
+fn struct_field_sigs(fields: &[Field]) -> TokenStream {
+    let sigs = fields.iter().map(|field| {
+        let field_type = field.ty.to_token_stream();
+        if let Some(signature) = &field.attrs.signature {
+            quote! {
+                ::rustbus::signature::Type::parse_description(#signature)
+                    .expect("The signature was checked by the derive")
+                    .remove(0)
+            }
+        } else if let Some(with) = &field.attrs.with {
+            quote! { #with::signature() }
+        } else if field.attrs.as_variant {
+            quote! { ::rustbus::signature::Type::Container(::rustbus::signature::Container::Variant) }
+        } else {
+            quote! { <#field_type as ::rustbus::Signature>::signature() }
+        }
+    });
 
     quote! {
             let mut sigs = vec![];
 
             #(
-                sigs.push(<#field_types as rustbus::Signature>::signature());
+                sigs.push(#sigs);
             )*
 
             ::rustbus::signature::Type::Container(::rustbus::signature::Container::Struct(
@@ -135,25 +423,44 @@ fn struct_field_sigs(fields: &syn::Fields) -> TokenStream {
             ))
     }
 }
-fn struct_field_has_sigs(fields: &syn::Fields) -> TokenStream {
-    let field_types = fields
-        .iter()
-        .map(|field| field.ty.to_token_stream())
-        .collect::<Vec<_>>();
-    if field_types.is_empty() {
-        panic!("Signature can not be derived for empty structs!")
-    }
+/// Check the signature against the fields. If `partial` is true, the signature may end before the trailing default fields.
+fn struct_field_has_sigs(fields: &[Field], partial: bool) -> TokenStream {
+    let checks = fields.iter().map(|field| {
+        let field_type = field.ty.to_token_stream();
+        let check = if let Some(signature) = &field.attrs.signature {
+            quote! { part == #signature }
+        } else if let Some(with) = &field.attrs.with {
+            quote! {{
+                let mut expected = String::new();
+                #with::signature().to_str(&mut expected);
+                part == expected
+            }}
+        } else if field.attrs.as_variant {
+            quote! { part == "v" }
+        } else {
+            quote! { <#field_type as ::rustbus::Signature>::has_sig(part) }
+        };
+        let missing = if partial && field.attrs.default {
+            quote! { None => return true, }
+        } else {
+            quote! {}
+        };
+        quote! {
+            match iter.next() {
+                Some(part) if #check => {}
+                #missing
+                _ => return false,
+            }
+        }
+    });
 
     quote! {
-        if sig.starts_with('(') {
+        if sig.starts_with('(') && sig.ends_with(')') {
             let mut iter = ::rustbus::signature::SignatureIter::new(&sig[1..sig.len() - 1]);
-            let mut accu = true;
-
             #(
-                accu &= <#field_types as rustbus::Signature>::has_sig(iter.next().unwrap());
+                #checks
             )*
-
-            accu
+            iter.next().is_none()
         } else {
             false
         }
@@ -25,16 +25,14 @@ pub fn make_variant_marshal_impl(
     ident: &syn::Ident,
     generics: &syn::Generics,
     variant: &Punctuated<Variant, Comma>,
-) -> TokenStream {
+) -> syn::Result<TokenStream> {
     let (impl_gen, typ_gen, clause_gen) = generics.split_for_impl();
-    let marshal = variant
-        .iter()
-        .fold(Default::default(), |mut tokens: TokenStream, variant| {
-            tokens.extend(variant_marshal(ident.clone(), variant));
-            tokens
-        });
+    let mut marshal = TokenStream::new();
+    for variant in variant {
+        marshal.extend(variant_marshal(ident.clone(), variant)?);
+    }
 
-    quote! {
+    Ok(quote! {
         impl #impl_gen ::rustbus::Marshal for #ident #typ_gen #clause_gen {
             #[inline]
             fn marshal(&self, ctx: &mut ::rustbus::wire::marshal::MarshalContext<'_,'_>) -> Result<(), ::rustbus::wire::errors::MarshalError> {
@@ -43,10 +41,10 @@ pub fn make_variant_marshal_impl(
                 }
             }
         }
-    }
+    })
 }
 
-fn variant_marshal(enum_name: syn::Ident, variant: &syn::Variant) -> TokenStream {
+fn variant_marshal(enum_name: syn::Ident, variant: &syn::Variant) -> syn::Result<TokenStream> {
     let name = variant.ident.clone();
     let field_types = variant
         .fields
@@ -62,7 +60,7 @@ fn variant_marshal(enum_name: syn::Ident, variant: &syn::Variant) -> TokenStream
                 .map(|field| field.ident.as_ref().unwrap().to_token_stream());
             let field_names2 = field_names1.clone();
 
-            quote! {
+            Ok(quote! {
                 #enum_name::#name{ #( #field_names1, )* } => {
                     // marshal signature
                     let pos = ctx.buf.len();
@@ -90,7 +88,7 @@ fn variant_marshal(enum_name: syn::Ident, variant: &syn::Variant) -> TokenStream
                     )*
                     Ok(())
                 },
-            }
+            })
         } else if variant.fields.iter().next().unwrap().ident.is_none() && variant.fields.len() > 1
         {
             // Unnamed fields
@@ -100,7 +98,7 @@ fn variant_marshal(enum_name: syn::Ident, variant: &syn::Variant) -> TokenStream
 
             let field_names2 = field_names1.clone();
 
-            quote! {
+            Ok(quote! {
                 #enum_name::#name( #( #field_names1, )* ) => {
                     // marshal signature
                     let pos = ctx.buf.len();
@@ -127,12 +125,12 @@ fn variant_marshal(enum_name: syn::Ident, variant: &syn::Variant) -> TokenStream
                     )*
                     Ok(())
                 },
-            }
+            })
         } else {
             // One unnamed field
             let mut field_types = field_types;
             let ty = field_types.next().unwrap();
-            quote! {
+            Ok(quote! {
                 #enum_name::#name( val ) => {
                     let mut sig_str = ::rustbus::wire::marshal::traits::SignatureBuffer::new();
                     <#ty as ::rustbus::Signature>::sig_str(&mut sig_str);
@@ -141,10 +139,13 @@ fn variant_marshal(enum_name: syn::Ident, variant: &syn::Variant) -> TokenStream
                     val.marshal(ctx)?;
                     Ok(())
                 },
-            }
+            })
         }
     } else {
-        panic!("Variants with no fields are not supported yet")
+        Err(syn::Error::new_spanned(
+            variant,
+            "Variants with no fields are only supported in enums with #[rustbus(repr = \"...\")]",
+        ))
     }
 }
 
@@ -152,13 +153,11 @@ pub fn make_variant_unmarshal_impl(
     ident: &syn::Ident,
     generics: &syn::Generics,
     variant: &Punctuated<Variant, Comma>,
-) -> TokenStream {
-    let marshal = variant
-        .iter()
-        .fold(Default::default(), |mut tokens: TokenStream, variant| {
-            tokens.extend(variant_unmarshal(ident.clone(), variant));
-            tokens
-        });
+) -> syn::Result<TokenStream> {
+    let mut marshal = TokenStream::new();
+    for variant in variant {
+        marshal.extend(variant_unmarshal(ident.clone(), variant)?);
+    }
 
     let mut bufdef = syn::LifetimeDef {
         attrs: Vec::new(),
@@ -182,7 +181,7 @@ pub fn make_variant_unmarshal_impl(
 
     let (impl_gen, _, clause_gen) = new_generics.split_for_impl();
 
-    quote! {
+    Ok(quote! {
         impl #impl_gen ::rustbus::Unmarshal<'__internal_buf, '_> for #ident #typ_gen #clause_gen {
             #[inline]
             fn unmarshal(ctx: &mut ::rustbus::wire::unmarshal::UnmarshalContext<'_,'__internal_buf>) -> Result<(usize,Self), ::rustbus::wire::errors::UnmarshalError> {
@@ -194,10 +193,10 @@ pub fn make_variant_unmarshal_impl(
                 Err(::rustbus::wire::errors::UnmarshalError::NoMatchingVariantFound)
             }
         }
-    }
+    })
 }
 
-fn variant_unmarshal(enum_name: syn::Ident, variant: &syn::Variant) -> TokenStream {
+fn variant_unmarshal(enum_name: syn::Ident, variant: &syn::Variant) -> syn::Result<TokenStream> {
     let name = variant.ident.clone();
     let field_types1 = variant
         .fields
@@ -214,7 +213,7 @@ fn variant_unmarshal(enum_name: syn::Ident, variant: &syn::Variant) -> TokenStre
                 .iter()
                 .map(|field| field.ident.as_ref().unwrap().to_token_stream());
 
-            quote! {
+            Ok(quote! {
                 let mut expected_sig = "(".to_owned();
                 let mut sig_str = ::rustbus::wire::marshal::traits::SignatureBuffer::new();
                 #(
@@ -233,10 +232,10 @@ fn variant_unmarshal(enum_name: syn::Ident, variant: &syn::Variant) -> TokenStre
                     let total_bytes = ctx.offset - start_offset;
                     return Ok((total_bytes, this));
                 }
-            }
+            })
         } else if variant.fields.iter().next().unwrap().ident.is_none() && variant.fields.len() > 1
         {
-            quote! {
+            Ok(quote! {
                 let mut expected_sig = "(".to_owned();
                 let mut sig_str = ::rustbus::wire::marshal::traits::SignatureBuffer::new();
                 #(
@@ -255,12 +254,12 @@ fn variant_unmarshal(enum_name: syn::Ident, variant: &syn::Variant) -> TokenStre
                     let total_bytes = ctx.offset - start_offset;
                     return Ok((total_bytes, this));
                 }
-            }
+            })
         } else {
             // One unnamed field
             let mut field_types = field_types1;
             let ty = field_types.next().unwrap();
-            quote! {
+            Ok(quote! {
                 let mut sig_str = ::rustbus::wire::marshal::traits::SignatureBuffer::new();
                 <#ty as ::rustbus::Signature>::sig_str(&mut sig_str);
 
@@ -271,9 +270,12 @@ fn variant_unmarshal(enum_name: syn::Ident, variant: &syn::Variant) -> TokenStre
                     let total_bytes = ctx.offset - start_offset;
                     return Ok((total_bytes, this));
                 }
-            }
+            })
         }
     } else {
-        panic!("Variants with no fields are not supported yet")
+        Err(syn::Error::new_spanned(
+            variant,
+            "Variants with no fields are only supported in enums with #[rustbus(repr = \"...\")]",
+        ))
     }
 }
@@ -102,7 +102,7 @@ pub fn test_enum_derive() {
     }
 
     let v1 = Variant1::A("ABCD".into());
-    let v2 = Variant1::B("ABCD".into(), "EFGH".into());
+    let v2 = Variant1::B("ABCD", "EFGH".into());
     let v3 = Variant1::C {
         c1: "ABCD".into(),
         c2: "EFGH".into(),
@@ -163,3 +163,667 @@ pub fn test_enum_derive() {
         err
     );
 }
+
+#[test]
+fn test_dbus_interface() {
+    use rustbus::connection::dispatch_conn::DBusInterface;
+    use rustbus::dbus_error::ErrorName;
+    use rustbus::introspect::Direction;
+    use rustbus::message_builder::{MarshalledMessage, MessageType};
+    use rustbus::{dbus_interface, DBusError, MessageBuilder};
+
+    struct Store {
+        items: Vec<String>,
+    }
+
+    #[dbus_interface(name = "io.killing.spark.Store")]
+    impl Store {
+        fn new() -> Self {
+            Store { items: Vec::new() }
+        }
+
+        fn add_item(&mut self, item: &str, count: u32) -> u32 {
+            for _ in 0..count {
+                self.items.push(item.to_owned());
+            }
+            self.items.len() as u32
+        }
+
+        fn get(&self, idx: u32) -> Result<(String, u32), DBusError> {
+            match self.items.get(idx as usize) {
+                Some(item) => Ok((item.clone(), idx)),
+                None => Err(DBusError::new(ErrorName::InvalidArgs, "No such item")),
+            }
+        }
+
+        #[dbus_method(name = "Clear")]
+        fn remove_all(&mut self) {
+            self.items.clear();
+        }
+
+        #[dbus_method(skip)]
+        fn len(&self) -> usize {
+            self.items.len()
+        }
+    }
+
+    fn call(member: &str) -> MarshalledMessage {
+        MessageBuilder::new()
+            .call(member)
+            .with_interface("io.killing.spark.Store")
+            .on("/store")
+            .build()
+    }
+
+    let interface = Store::introspect();
+    assert_eq!(interface.name, "io.killing.spark.Store");
+    let names: Vec<_> = interface.methods.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, ["AddItem", "Get", "Clear"]);
+    let get = interface.method("Get").unwrap();
+    assert_eq!(get.args[0].name.as_deref(), Some("idx"));
+    assert_eq!(get.args[0].direction, Some(Direction::In));
+    assert_eq!(get.out_args().count(), 2);
+
+    let mut store = Store::new();
+    let mut msg = call("AddItem");
+    msg.body.push_param("apple").unwrap();
+    msg.body.push_param(2u32).unwrap();
+    let reply = store.handle_call(&msg).unwrap().unwrap();
+    assert_eq!(reply.typ, MessageType::Reply);
+    assert_eq!(reply.body.parser().get::<u32>().unwrap(), 2);
+    assert_eq!(store.len(), 2);
+
+    let mut msg = call("Get");
+    msg.body.push_param(1u32).unwrap();
+    let reply = store.handle_call(&msg).unwrap().unwrap();
+    assert_eq!(
+        reply.body.parser().get2::<String, u32>().unwrap(),
+        ("apple".to_owned(), 1)
+    );
+
+    // the error of the method is sent as the reply
+    let mut msg = call("Get");
+    msg.body.push_param(5u32).unwrap();
+    let reply = store.handle_call(&msg).unwrap().unwrap();
+    assert_eq!(reply.typ, MessageType::Error);
+    assert_eq!(
+        reply.dynheader.error_name.as_deref(),
+        Some("org.freedesktop.DBus.Error.InvalidArgs")
+    );
+
+    // wrong and missing arguments
+    let mut msg = call("AddItem");
+    msg.body.push_param("apple").unwrap();
+    let reply = store.handle_call(&msg).unwrap().unwrap();
+    assert_eq!(reply.typ, MessageType::Error);
+    assert_eq!(
+        reply.dynheader.error_name.as_deref(),
+        Some("org.freedesktop.DBus.Error.InvalidArgs")
+    );
+    let mut msg = call("Clear");
+    msg.body.push_param(1u32).unwrap();
+    let reply = store.handle_call(&msg).unwrap().unwrap();
+    assert_eq!(reply.typ, MessageType::Error);
+    assert_eq!(store.len(), 2);
+
+    let reply = store.handle_call(&call("Clear")).unwrap().unwrap();
+    assert_eq!(reply.typ, MessageType::Reply);
+    assert_eq!(store.len(), 0);
+
+    // skipped methods are unknown and other interfaces are not handled at all
+    let reply = store.handle_call(&call("Len")).unwrap().unwrap();
+    assert_eq!(
+        reply.dynheader.error_name.as_deref(),
+        Some("org.freedesktop.DBus.Error.UnknownMethod")
+    );
+    let msg = MessageBuilder::new()
+        .call("Ping")
+        .with_interface("org.freedesktop.DBus.Peer")
+        .on("/store")
+        .build();
+    assert!(store.handle_call(&msg).is_none());
+}
+
+#[test]
+fn test_dbus_interface_dispatch() {
+    use rustbus::broker::Broker;
+    use rustbus::connection::Timeout;
+    use rustbus::introspect::Node;
+    use rustbus::message_builder::MessageType;
+    use rustbus::{dbus_interface, DispatchConn, MessageBuilder, RpcConn};
+
+    struct Greeter {
+        greeting: String,
+    }
+
+    #[dbus_interface(name = "io.killing.spark.Greeter")]
+    impl Greeter {
+        fn greet(&self, name: String) -> String {
+            format!("{}, {}!", self.greeting, name)
+        }
+
+        fn set_greeting(&mut self, greeting: String) {
+            self.greeting = greeting;
+        }
+    }
+
+    let broker = Broker::bind_tmpdir().unwrap().spawn();
+    let addr = [broker.address().clone()];
+
+    let service_name = broker
+        .spawn_service(&[], |conn| {
+            let mut service: DispatchConn<(), ()> =
+                DispatchConn::new(conn, (), Box::new(|_, _, _, _| Ok(None)));
+            service.add_object(
+                "/greeter",
+                Greeter {
+                    greeting: "Hello".to_owned(),
+                },
+            );
+            service
+        })
+        .unwrap();
+
+    let mut con = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    let mut call = |member: &str, interface: &str, arg: Option<&str>| {
+        let mut msg = MessageBuilder::new()
+            .call(member)
+            .with_interface(interface)
+            .on("/greeter")
+            .at(service_name.clone())
+            .build();
+        if let Some(arg) = arg {
+            msg.body.push_param(arg).unwrap();
+        }
+        let serial = con.send_message(&mut msg).unwrap().write_all().unwrap();
+        con.wait_response(serial, Timeout::Infinite).unwrap()
+    };
+
+    let reply = call("Greet", "io.killing.spark.Greeter", Some("World"));
+    assert_eq!(reply.body.parser().get::<&str>().unwrap(), "Hello, World!");
+    call("SetGreeting", "io.killing.spark.Greeter", Some("Hi"));
+    let reply = call("Greet", "io.killing.spark.Greeter", Some("World"));
+    assert_eq!(reply.body.parser().get::<&str>().unwrap(), "Hi, World!");
+    let reply = call("Greet", "io.killing.spark.Greeter", None);
+    assert_eq!(reply.typ, MessageType::Error);
+    let reply = call("Greet", "io.killing.spark.Other", Some("World"));
+    assert_eq!(
+        reply.dynheader.error_name.as_deref(),
+        Some("org.freedesktop.DBus.Error.UnknownMethod")
+    );
+
+    let reply = call("Introspect", "org.freedesktop.DBus.Introspectable", None);
+    let node = Node::from_xml(reply.body.parser().get::<&str>().unwrap()).unwrap();
+    let greeter = node.interface("io.killing.spark.Greeter").unwrap();
+    let greet = greeter.method("Greet").unwrap();
+    assert_eq!(
+        greet.in_args().next().unwrap().name.as_deref(),
+        Some("name")
+    );
+    assert!(greeter.method("SetGreeting").is_some());
+}
+
+#[test]
+fn test_dbus_proxy() {
+    use rustbus::broker::Broker;
+    use rustbus::connection::dispatch_conn::Property;
+    use rustbus::connection::rpc_conn::CallError;
+    use rustbus::connection::Timeout;
+    use rustbus::{dbus_interface, dbus_proxy, DBusError, DispatchConn, MessageBuilder, RpcConn};
+
+    struct Calculator;
+
+    #[dbus_interface(name = "io.killing.spark.Calculator")]
+    impl Calculator {
+        fn add(&self, a: i32, b: i32) -> i32 {
+            a + b
+        }
+
+        fn div_mod(&self, a: u32, b: u32) -> Result<(u32, u32), DBusError> {
+            if b == 0 {
+                return Err(DBusError::new(
+                    rustbus::dbus_error::ErrorName::InvalidArgs,
+                    "Division by zero",
+                ));
+            }
+            Ok((a / b, a % b))
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    /// Calls the calculator
+    #[dbus_proxy(
+        interface = "io.killing.spark.Calculator",
+        default_path = "/calculator",
+        default_service = "io.killing.spark.Calculator"
+    )]
+    trait Calculator {
+        fn add(&self, a: i32, b: i32) -> i32;
+
+        fn div_mod(&self, a: u32, b: u32) -> Result<(u32, u32), CallError>;
+
+        #[dbus_proxy(name = "Reset")]
+        fn clear(&self);
+
+        #[dbus_proxy(property)]
+        fn precision(&self) -> u8;
+
+        #[dbus_proxy(property)]
+        fn set_precision(&self, value: u8);
+
+        #[dbus_proxy(signal)]
+        fn overflowed(&self, value: i32, message: String);
+
+        #[dbus_proxy(signal)]
+        fn cleared(&self);
+    }
+
+    let broker = Broker::bind_tmpdir().unwrap().spawn();
+    let addr = [broker.address().clone()];
+
+    broker
+        .spawn_service(&["io.killing.spark.Calculator"], |conn| {
+            let mut service: DispatchConn<u8, ()> =
+                DispatchConn::new(conn, 2, Box::new(|_, _, _, _| Ok(None)));
+            service.add_object("/calculator", Calculator);
+            service.add_property(
+                "/calculator",
+                "io.killing.spark.Calculator",
+                "Precision",
+                Property::read_write(
+                    |precision: &mut u8, _| Ok(*precision),
+                    |precision, _, value| {
+                        *precision = value;
+                        Ok(())
+                    },
+                ),
+            );
+            service
+        })
+        .unwrap();
+
+    let mut con = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    let mut proxy = CalculatorProxy::with_defaults(&mut con);
+    assert_eq!(CalculatorProxy::DEFAULT_PATH, "/calculator");
+    assert_eq!(proxy.add(2, 3).unwrap(), 5);
+    assert_eq!(proxy.div_mod(7, 2).unwrap(), (3, 1));
+    match proxy.div_mod(7, 0) {
+        Err(CallError::ErrorReply(error)) => {
+            assert_eq!(error.message.as_deref(), Some("Division by zero"))
+        }
+        other => panic!("Expected an error reply: {:?}", other),
+    }
+    proxy.clear().unwrap();
+    assert_eq!(proxy.precision().unwrap(), 2);
+    proxy.set_precision(5).unwrap();
+    assert_eq!(proxy.precision().unwrap(), 5);
+
+    // signals are received from the object the proxy points to
+    let mut emitter = RpcConn::connect_to_path(&addr, Timeout::Infinite).unwrap();
+    let emitter_name = emitter.unique_name().unwrap().to_owned();
+    let mut proxy = CalculatorProxy::new(&mut con, &emitter_name, "/calculator");
+    let mut overflows = proxy.receive_overflowed().unwrap();
+    let mut other_path = MessageBuilder::new()
+        .signal("io.killing.spark.Calculator", "Overflowed", "/other")
+        .build();
+    other_path.body.push_param2(1i32, "other").unwrap();
+    let mut signal = MessageBuilder::new()
+        .signal("io.killing.spark.Calculator", "Overflowed", "/calculator")
+        .build();
+    signal.body.push_param2(i32::MAX, "too big").unwrap();
+    for msg in [&mut other_path, &mut signal].iter_mut() {
+        emitter.send_message(msg).unwrap().write_all().unwrap();
+    }
+    let (value, message) = overflows.next().unwrap().unwrap();
+    assert_eq!(value, i32::MAX);
+    assert_eq!(message, "too big");
+    assert!(overflows.try_next().is_none());
+    overflows.close().unwrap();
+
+    let mut cleared = proxy.receive_cleared().unwrap();
+    let mut signal = MessageBuilder::new()
+        .signal("io.killing.spark.Calculator", "Cleared", "/calculator")
+        .build();
+    emitter
+        .send_message(&mut signal)
+        .unwrap()
+        .write_all()
+        .unwrap();
+    cleared.next().unwrap().unwrap();
+}
+
+#[test]
+fn test_field_attributes() {
+    use rustbus::wire::errors::UnmarshalError;
+    use rustbus::{Marshal, MessageBuilder, Signature, Unmarshal};
+    use std::time::Duration;
+
+    mod millis {
+        use rustbus::wire::errors::MarshalError;
+        use rustbus::wire::marshal::MarshalContext;
+        use rustbus::wire::unmarshal::{UnmarshalContext, UnmarshalResult};
+        use rustbus::{Marshal, Signature, Unmarshal};
+        use std::time::Duration;
+
+        pub fn marshal(value: &Duration, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
+            (value.as_millis() as u64).marshal(ctx)
+        }
+
+        pub fn unmarshal(ctx: &mut UnmarshalContext) -> UnmarshalResult<Duration> {
+            let (bytes, millis) = u64::unmarshal(ctx)?;
+            Ok((bytes, Duration::from_millis(millis)))
+        }
+
+        pub fn signature() -> rustbus::signature::Type {
+            u64::signature()
+        }
+    }
+
+    mod as_text {
+        use rustbus::wire::errors::{MarshalError, UnmarshalError};
+        use rustbus::wire::marshal::MarshalContext;
+        use rustbus::wire::unmarshal::{UnmarshalContext, UnmarshalResult};
+        use rustbus::{Marshal, Unmarshal};
+
+        pub fn marshal(value: &u32, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
+            value.to_string().as_str().marshal(ctx)
+        }
+
+        pub fn unmarshal(ctx: &mut UnmarshalContext) -> UnmarshalResult<u32> {
+            let (bytes, text) = <&str>::unmarshal(ctx)?;
+            let value = text.parse().map_err(|_| UnmarshalError::WrongSignature)?;
+            Ok((bytes, value))
+        }
+    }
+
+    #[derive(Marshal, Unmarshal, Signature, Debug, PartialEq)]
+    struct Settings {
+        #[rustbus(with = "millis")]
+        timeout: Duration,
+        #[rustbus(with = "as_text", signature = "s")]
+        level: u32,
+        #[rustbus(as_variant)]
+        extra: (u8, String),
+        #[rustbus(default)]
+        name: String,
+        #[rustbus(default)]
+        tags: Vec<String>,
+    }
+
+    /// What older peers send
+    #[derive(Marshal, Signature)]
+    struct OldSettings(
+        #[rustbus(with = "millis")] Duration,
+        #[rustbus(with = "as_text", signature = "s")] u32,
+        #[rustbus(as_variant)] (u8, String),
+    );
+
+    let mut sig = String::new();
+    Settings::signature().to_str(&mut sig);
+    assert_eq!(sig, "(tsvsas)");
+    assert!(Settings::has_sig("(tsvsas)"));
+    assert!(!Settings::has_sig("(tsvs)"));
+    assert!(!Settings::has_sig("(tsvsasu)"));
+    assert!(!Settings::has_sig("(usvsas)"));
+    // the default fields may only be missing at the end of the body
+    assert!(Settings::has_partial_sig("(tsvsas)"));
+    assert!(Settings::has_partial_sig("(tsvs)"));
+    assert!(Settings::has_partial_sig("(tsv)"));
+    assert!(!Settings::has_partial_sig("(ts)"));
+
+    let settings = Settings {
+        timeout: Duration::from_millis(1500),
+        level: 3,
+        extra: (1, "one".to_owned()),
+        name: "main".to_owned(),
+        tags: vec!["a".to_owned()],
+    };
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "Settings", "/")
+        .build();
+    msg.body.push_param(&settings).unwrap();
+    assert_eq!(msg.get_sig(), "(tsvsas)");
+    assert_eq!(msg.body.parser().get::<Settings>().unwrap(), settings);
+    // the level is sent as text and the extra value in a variant
+    let (millis, level, extra) = msg
+        .body
+        .parser()
+        .get::<(
+            u64,
+            String,
+            rustbus::wire::unmarshal::traits::Variant,
+            String,
+        )>()
+        .map(|(millis, level, extra, _)| (millis, level, extra.get::<(u8, String)>().unwrap()))
+        .unwrap();
+    assert_eq!((millis, level.as_str()), (1500, "3"));
+    assert_eq!(extra, (1, "one".to_owned()));
+
+    let old = OldSettings(Duration::from_secs(2), 7, (2, "two".to_owned()));
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "Settings", "/")
+        .build();
+    msg.body.push_param(&old).unwrap();
+    assert_eq!(msg.get_sig(), "(tsv)");
+    let settings = msg.body.parser().get::<Settings>().unwrap();
+    assert_eq!(settings.timeout, Duration::from_secs(2));
+    assert_eq!(settings.level, 7);
+    assert_eq!(settings.name, "");
+    assert!(settings.tags.is_empty());
+
+    // missing fields can not be told apart from the values that follow or the next element of an array
+    msg.body.push_param(5u32).unwrap();
+    assert_eq!(msg.get_sig(), "(tsv)u");
+    assert_eq!(
+        msg.body.parser().get::<Settings>(),
+        Err(UnmarshalError::WrongSignature)
+    );
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "Settings", "/")
+        .build();
+    msg.body.push_param(&[&old, &old][..]).unwrap();
+    assert_eq!(msg.get_sig(), "a(tsv)");
+    assert_eq!(
+        msg.body.parser().get::<Vec<Settings>>(),
+        Err(UnmarshalError::WrongSignature)
+    );
+}
+
+#[test]
+fn test_dict_derive() {
+    use rustbus::wire::errors::UnmarshalError;
+    use rustbus::{Marshal, MessageBuilder, Signature, Unmarshal};
+    use std::collections::HashMap;
+
+    #[derive(Marshal, Unmarshal, Signature, Debug, PartialEq, Default)]
+    #[rustbus(dict)]
+    struct Options {
+        #[rustbus(rename = "handle-token")]
+        handle_token: String,
+        modal: Option<bool>,
+        #[rustbus(default)]
+        filters: Vec<String>,
+    }
+
+    #[derive(Marshal, Unmarshal, Signature, Debug, PartialEq)]
+    #[rustbus(dict, deny_unknown_keys)]
+    struct StrictOptions<'a> {
+        modal: Option<bool>,
+        name: Option<&'a str>,
+    }
+
+    let mut sig = String::new();
+    Options::signature().to_str(&mut sig);
+    assert_eq!(sig, "a{sv}");
+    assert!(Options::has_sig("a{sv}"));
+    assert!(!Options::has_sig("a{ss}"));
+
+    let options = Options {
+        handle_token: "token".to_owned(),
+        modal: Some(true),
+        filters: vec!["*.rs".to_owned()],
+    };
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "Options", "/")
+        .build();
+    msg.body.push_param(&options).unwrap();
+    msg.body.push_param(42u32).unwrap();
+    assert_eq!(msg.get_sig(), "a{sv}u");
+    let (parsed, after) = msg.body.parser().get2::<Options, u32>().unwrap();
+    assert_eq!(parsed, options);
+    assert_eq!(after, 42);
+
+    // None fields are left out
+    let options = Options {
+        handle_token: "token".to_owned(),
+        ..Default::default()
+    };
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "Options", "/")
+        .build();
+    msg.body.push_param(&options).unwrap();
+    let map: HashMap<String, rustbus::wire::unmarshal::traits::Variant> =
+        msg.body.parser().get().unwrap();
+    let mut keys: Vec<_> = map.keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    assert_eq!(keys, ["filters", "handle-token"]);
+    assert_eq!(msg.body.parser().get::<Options>().unwrap(), options);
+
+    // a newer peer that sends a key the structs do not know
+    #[derive(Marshal, Signature)]
+    #[rustbus(dict)]
+    struct NewerOptions {
+        #[rustbus(rename = "handle-token")]
+        handle_token: Option<String>,
+        modal: bool,
+        unknown: u32,
+    }
+
+    let newer = |handle_token: Option<&str>| {
+        let mut msg = MessageBuilder::new()
+            .signal("io.killing.spark", "Options", "/")
+            .build();
+        msg.body
+            .push_param(NewerOptions {
+                handle_token: handle_token.map(str::to_owned),
+                modal: false,
+                unknown: 1,
+            })
+            .unwrap();
+        msg
+    };
+    let msg = newer(Some("token"));
+    let parsed = msg.body.parser().get::<Options>().unwrap();
+    assert_eq!(parsed.handle_token, "token");
+    assert_eq!(parsed.modal, Some(false));
+    assert!(parsed.filters.is_empty());
+
+    let msg = newer(None);
+    assert_eq!(
+        msg.body.parser().get::<Options>(),
+        Err(UnmarshalError::MissingDictKey("handle-token".to_owned()))
+    );
+    assert_eq!(
+        msg.body.parser().get::<StrictOptions>(),
+        Err(UnmarshalError::UnknownDictKey("unknown".to_owned()))
+    );
+}
+
+#[test]
+fn test_repr_enum_derive() {
+    use rustbus::wire::errors::UnmarshalError;
+    use rustbus::{Marshal, MessageBuilder, Signature, Unmarshal};
+
+    #[derive(Marshal, Unmarshal, Signature, Debug, PartialEq)]
+    #[rustbus(repr = "u32")]
+    enum State {
+        Inactive,
+        Activating,
+        #[rustbus(value = 10)]
+        Active,
+        Failed = 20,
+    }
+
+    #[derive(Marshal, Unmarshal, Signature, Debug, PartialEq)]
+    #[rustbus(repr = "i16")]
+    #[repr(i16)]
+    enum Level {
+        Low = -1,
+        High = 1,
+        #[rustbus(other)]
+        Unknown(i16),
+    }
+
+    #[derive(Marshal, Unmarshal, Signature, Debug, PartialEq)]
+    #[rustbus(repr = "string")]
+    enum Mode<'a> {
+        #[rustbus(rename = "read-only")]
+        ReadOnly,
+        ReadWrite,
+        #[rustbus(other)]
+        Other(&'a str),
+    }
+
+    #[derive(Marshal, Unmarshal, Signature, Debug, PartialEq)]
+    #[rustbus(repr = "string")]
+    enum Color {
+        Red,
+    }
+
+    let mut sig = String::new();
+    State::signature().to_str(&mut sig);
+    Level::signature().to_str(&mut sig);
+    Mode::signature().to_str(&mut sig);
+    assert_eq!(sig, "uns");
+
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "Enums", "/")
+        .build();
+    msg.body.push_param(State::Activating).unwrap();
+    msg.body.push_param(State::Active).unwrap();
+    msg.body.push_param(State::Failed).unwrap();
+    msg.body.push_param(Level::Low).unwrap();
+    msg.body.push_param(Level::Unknown(5)).unwrap();
+    msg.body.push_param(Mode::ReadOnly).unwrap();
+    msg.body.push_param(Mode::ReadWrite).unwrap();
+    msg.body.push_param(Mode::Other("append")).unwrap();
+    assert_eq!(msg.get_sig(), "uuunnsss");
+
+    let mut parser = msg.body.parser();
+    assert_eq!(
+        (
+            parser.get::<u32>().unwrap(),
+            parser.get::<u32>().unwrap(),
+            parser.get::<u32>().unwrap(),
+            parser.get::<i16>().unwrap(),
+            parser.get::<i16>().unwrap(),
+            parser.get::<&str>().unwrap(),
+            parser.get::<&str>().unwrap(),
+            parser.get::<&str>().unwrap(),
+        ),
+        (1, 10, 20, -1, 5, "read-only", "ReadWrite", "append")
+    );
+
+    let mut parser = msg.body.parser();
+    assert_eq!(parser.get::<State>().unwrap(), State::Activating);
+    assert_eq!(parser.get::<State>().unwrap(), State::Active);
+    assert_eq!(parser.get::<State>().unwrap(), State::Failed);
+    assert_eq!(parser.get::<Level>().unwrap(), Level::Low);
+    assert_eq!(parser.get::<Level>().unwrap(), Level::Unknown(5));
+    assert_eq!(parser.get::<Mode>().unwrap(), Mode::ReadOnly);
+    assert_eq!(parser.get::<Mode>().unwrap(), Mode::ReadWrite);
+    assert_eq!(
+        parser.get::<Color>(),
+        Err(UnmarshalError::UnknownEnumValue("append".to_owned()))
+    );
+
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "Enums", "/")
+        .build();
+    msg.body.push_param(2u32).unwrap();
+    assert_eq!(
+        msg.body.parser().get::<State>(),
+        Err(UnmarshalError::UnknownEnumValue("2".to_owned()))
+    );
+}